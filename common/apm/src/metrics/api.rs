@@ -10,6 +10,7 @@ use lazy_static::lazy_static;
 make_auto_flush_static_metric! {
     pub label_enum RequestKind {
         eth_sendRawTransaction,
+        eth_sendPrivateRawTransaction,
         eth_getTransactionByHash,
         eth_getBlockByNumber,
         eth_blockNumber,
@@ -43,6 +44,8 @@ make_auto_flush_static_metric! {
         eth_getUncleByBlockNumberAndIndex,
         eth_getUncleCountByBlockHash,
         eth_getUncleCountByBlockNumber,
+        axon_getTransactionStatus,
+        axon_getDoubleSignEvidences,
     }
 
     pub label_enum Request_Result {
@@ -1,7 +1,7 @@
 use crate::metrics::{
     auto_flush_from, exponential_buckets, make_auto_flush_static_metric, register_counter_vec,
     register_histogram, register_histogram_vec, register_int_counter, register_int_gauge,
-    CounterVec, Histogram, HistogramVec, IntCounter, IntGauge,
+    register_int_gauge_vec, CounterVec, Histogram, HistogramVec, IntCounter, IntGauge, IntGaugeVec,
 };
 
 use lazy_static::lazy_static;
@@ -115,3 +115,58 @@ lazy_static! {
     )
     .unwrap();
 }
+
+lazy_static! {
+    pub static ref VALIDATOR_MISSED_BLOCKS_GAUGE_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "axon_consensus_validator_missed_blocks",
+        "The recent blocks which the validator does not sign",
+        &["validator"]
+    )
+    .unwrap();
+    pub static ref VALIDATOR_PROPOSED_BLOCKS_GAUGE_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "axon_consensus_validator_proposed_blocks",
+        "The recent blocks proposed by the validator",
+        &["validator"]
+    )
+    .unwrap();
+    pub static ref VALIDATOR_EXPECTED_PROPOSALS_GAUGE_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "axon_consensus_validator_expected_proposals",
+        "The recent rounds in which the validator is elected to propose",
+        &["validator"]
+    )
+    .unwrap();
+    pub static ref VALIDATOR_VIEW_CHANGES_GAUGE_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "axon_consensus_validator_view_changes",
+        "The recent view changes caused by the validator",
+        &["validator"]
+    )
+    .unwrap();
+}
+
+pub fn on_validator_liveness(
+    validator: &str,
+    missed_blocks: u64,
+    proposed_blocks: u64,
+    expected_proposals: u64,
+    view_changes: u64,
+) {
+    VALIDATOR_MISSED_BLOCKS_GAUGE_VEC
+        .with_label_values(&[validator])
+        .set(missed_blocks as i64);
+    VALIDATOR_PROPOSED_BLOCKS_GAUGE_VEC
+        .with_label_values(&[validator])
+        .set(proposed_blocks as i64);
+    VALIDATOR_EXPECTED_PROPOSALS_GAUGE_VEC
+        .with_label_values(&[validator])
+        .set(expected_proposals as i64);
+    VALIDATOR_VIEW_CHANGES_GAUGE_VEC
+        .with_label_values(&[validator])
+        .set(view_changes as i64);
+}
+
+pub fn remove_validator_liveness(validator: &str) {
+    let _ = VALIDATOR_MISSED_BLOCKS_GAUGE_VEC.remove_label_values(&[validator]);
+    let _ = VALIDATOR_PROPOSED_BLOCKS_GAUGE_VEC.remove_label_values(&[validator]);
+    let _ = VALIDATOR_EXPECTED_PROPOSALS_GAUGE_VEC.remove_label_values(&[validator]);
+    let _ = VALIDATOR_VIEW_CHANGES_GAUGE_VEC.remove_label_values(&[validator]);
+}
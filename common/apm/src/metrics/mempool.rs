@@ -10,6 +10,10 @@ make_auto_flush_static_metric! {
         insert_tx_from_p2p,
         package,
         current_size,
+        relay_push_tx,
+        relay_skip_tx,
+        ensure_hit_tx,
+        ensure_pull_tx,
     }
 
     pub label_enum MempoolOpResult {
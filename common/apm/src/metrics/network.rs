@@ -54,6 +54,18 @@ lazy_static! {
         &["direction", "url"]
     )
     .expect("network message size");
+    pub static ref NETWORK_COMPRESSED_BYTES_COUNT_VEC: CounterVec = register_counter_vec!(
+        "axon_network_compressed_bytes_total",
+        "Accumulated network message bytes on the wire",
+        &["direction", "codec"]
+    )
+    .expect("network compressed bytes total");
+    pub static ref NETWORK_UNCOMPRESSED_BYTES_COUNT_VEC: CounterVec = register_counter_vec!(
+        "axon_network_uncompressed_bytes_total",
+        "Accumulated network message bytes before compression",
+        &["direction", "codec"]
+    )
+    .expect("network uncompressed bytes total");
     pub static ref NETWORK_RPC_RESULT_COUNT_VEC: CounterVec = register_counter_vec!(
         "axon_network_rpc_result_total",
         "Total number of network rpc result",
@@ -144,6 +156,28 @@ lazy_static! {
         "Total number of connected consensus peers"
     )
     .expect("network connected consenss peers");
+    pub static ref NETWORK_CONSENSUS_MESH_MISSING_LINKS: IntGauge = register_int_gauge!(
+        "axon_network_consensus_mesh_missing_links",
+        "Number of consensus peers without a direct mesh session"
+    )
+    .expect("network consensus mesh missing links");
+    pub static ref NETWORK_CONSENSUS_MESH_RTT_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "axon_network_consensus_mesh_rtt_ms",
+        "Ping round trip time of the consensus mesh sessions",
+        &["peer_id"]
+    )
+    .expect("network consensus mesh rtt");
+    pub static ref NETWORK_CONSENSUS_MESH_QUEUE_LEN: IntGauge = register_int_gauge!(
+        "axon_network_consensus_mesh_queue_len",
+        "Number of messages waiting in the consensus mesh queue"
+    )
+    .expect("network consensus mesh queue len");
+    pub static ref NETWORK_REJECTED_SESSION_COUNT_VEC: CounterVec = register_counter_vec!(
+        "axon_network_rejected_session_total",
+        "Total number of sessions rejected after handshake",
+        &["reason"]
+    )
+    .expect("network rejected sessions");
 }
 
 fn on_network_message(direction: &str, target: &str, url: &str, inc: f64) {
@@ -12,18 +12,24 @@ pub const DEFAULT_BROADCAST_TXS_INTERVAL: u64 = 200; // milliseconds
 pub const DEFAULT_OVERLORD_GAP: usize = 5;
 pub const DEFAULT_SYNC_TXS_CHUNK_SIZE: usize = 5000;
 pub const DEFAULT_CACHE_SIZE: usize = 100;
+pub const DEFAULT_TX_TTL: u64 = 3 * 60 * 60; // seconds
+pub const DEFAULT_DROPPED_TX_CACHE_SIZE: usize = 10_000;
+pub const DEFAULT_PRIVATE_TX_TIMEOUT_GAP: u64 = 5; // blocks
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ConfigApi {
-    pub http_listening_address: Option<SocketAddr>,
-    pub ws_listening_address:   Option<SocketAddr>,
-    pub maxconn:                u32,
-    pub max_payload_size:       u32,
-    pub enable_dump_profile:    Option<bool>,
+    pub http_listening_address:  Option<SocketAddr>,
+    pub ws_listening_address:    Option<SocketAddr>,
+    /// The address of the admin RPC of the peer store. It has no
+    /// authentication, so only a loopback address is accepted.
+    pub admin_listening_address: Option<SocketAddr>,
+    pub maxconn:                 u32,
+    pub max_payload_size:        u32,
+    pub enable_dump_profile:     Option<bool>,
     #[serde(default)]
-    pub client_version:         String,
+    pub client_version:          String,
     #[serde(default = "default_gas_cap")]
-    pub gas_cap:                u64,
+    pub gas_cap:                 u64,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -35,6 +41,11 @@ pub struct ConfigGraphQLTLS {
 #[derive(Clone, Debug, Deserialize)]
 pub struct ConfigNetwork {
     pub bootstraps:                 Option<Vec<ConfigNetworkBootstrap>>,
+    /// The signed DNS trees of the bootstraps,
+    /// `enrtree://<base32 public key>@<domain>`.
+    pub dns_trees:                  Option<Vec<String>>,
+    /// The seconds between the resolving of the DNS bootstraps and trees.
+    pub dns_seed_interval:          Option<u64>,
     pub allowlist:                  Option<Vec<String>>,
     pub allowlist_only:             Option<bool>,
     pub trust_interval_duration:    Option<u64>,
@@ -45,6 +56,11 @@ pub struct ConfigNetwork {
     pub same_ip_conn_limit:         Option<usize>,
     pub inbound_conn_limit:         Option<usize>,
     pub listening_address:          MultiAddr,
+    /// The addresses advertised to the other nodes instead of the ones
+    /// discovered by port mapping and observed address voting.
+    pub announce_addresses:         Option<Vec<MultiAddr>>,
+    /// Map the listening port on the gateway by UPnP or NAT-PMP.
+    pub nat_port_mapping:           Option<bool>,
     pub rpc_timeout:                Option<u64>,
     pub selfcheck_interval:         Option<u64>,
     pub send_buffer_size:           Option<usize>,
@@ -53,6 +69,16 @@ pub struct ConfigNetwork {
     pub max_frame_length:           Option<usize>,
     pub max_wait_streams:           Option<usize>,
     pub ping_interval:              Option<u64>,
+    pub consensus_queue_size:       Option<usize>,
+    /// The message limits of every peer on the endpoints, replacing the
+    /// default ones of the same endpoints.
+    pub rate_limits:                Option<Vec<ConfigRateLimit>>,
+    /// The security of the connections, `secio`, `noise`, or `mixed` which
+    /// accepts both during the transition.
+    pub transport_security:         Option<String>,
+    /// The zstd dictionary shared by the nodes of the chain, trained on the
+    /// block and transaction payloads.
+    pub zstd_dict_path:             Option<PathBuf>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -60,6 +86,15 @@ pub struct ConfigNetworkBootstrap {
     pub multi_address: MultiAddr,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfigRateLimit {
+    pub endpoint: String,
+    /// The messages per second on average.
+    pub rate:     u32,
+    /// The messages at once.
+    pub burst:    u32,
+}
+
 fn default_overlord_gap() -> usize {
     DEFAULT_OVERLORD_GAP
 }
@@ -71,9 +106,26 @@ fn default_sync_txs_chunk_size() -> usize {
 #[derive(Clone, Debug, Deserialize)]
 pub struct ConfigConsensus {
     #[serde(default = "default_overlord_gap")]
-    pub overlord_gap:        usize,
+    pub overlord_gap:               usize,
     #[serde(default = "default_sync_txs_chunk_size")]
-    pub sync_txs_chunk_size: usize,
+    pub sync_txs_chunk_size:        usize,
+    /// Download the state of the trusted checkpoint block instead of
+    /// executing all the blocks up to it when the node is new.
+    #[serde(default)]
+    pub snapshot_sync:              Option<ConfigSnapshotSync>,
+    /// The block from which the proposer executes the proposal before voting
+    /// and commits to the post-execution roots in it. It changes the hash of
+    /// the blocks, so all the nodes of a chain must use the same value.
+    #[serde(default)]
+    pub speculative_execution_from: Option<u64>,
+}
+
+/// The checkpoint block of snapshot sync. The downloaded state is verified
+/// only against its hash, so it must come from a trusted source.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfigSnapshotSync {
+    pub number: u64,
+    pub hash:   H256,
 }
 
 fn default_broadcast_txs_size() -> usize {
@@ -84,6 +136,18 @@ fn default_broadcast_txs_interval() -> u64 {
     DEFAULT_BROADCAST_TXS_INTERVAL
 }
 
+fn default_tx_ttl() -> u64 {
+    DEFAULT_TX_TTL
+}
+
+fn default_dropped_tx_cache_size() -> usize {
+    DEFAULT_DROPPED_TX_CACHE_SIZE
+}
+
+fn default_private_tx_timeout_gap() -> u64 {
+    DEFAULT_PRIVATE_TX_TIMEOUT_GAP
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct ConfigMempool {
     pub pool_size:   u64,
@@ -93,6 +157,17 @@ pub struct ConfigMempool {
     pub broadcast_txs_size:     usize,
     #[serde(default = "default_broadcast_txs_interval")]
     pub broadcast_txs_interval: u64,
+    /// The max seconds a tx can stay in the mempool, zero means unlimited.
+    #[serde(default = "default_tx_ttl")]
+    pub tx_ttl:                 u64,
+    #[serde(default = "default_dropped_tx_cache_size")]
+    pub dropped_tx_cache_size:  usize,
+    /// The blocks a private tx can stay in the mempool before it is dropped.
+    #[serde(default = "default_private_tx_timeout_gap")]
+    pub private_tx_timeout_gap: u64,
+    /// The threads to verify tx signatures, zero means the number of CPUs.
+    #[serde(default)]
+    pub verify_pool_size:       usize,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -296,6 +371,12 @@ impl Config {
         path_state.push("consensus_wal");
         path_state
     }
+
+    pub fn data_path_for_peer_store(&self) -> PathBuf {
+        let mut path_state = self.data_path.clone();
+        path_state.push("peer_store");
+        path_state
+    }
 }
 
 fn default_gas_cap() -> u64 {
@@ -0,0 +1,41 @@
+use alloc::vec::Vec;
+
+use blst::min_pk::{AggregatePublicKey, PublicKey, Signature};
+use blst::BLST_ERROR;
+
+use crate::types::Hash;
+use crate::{AggregateVerifier, LightClientError, LightClientResult};
+
+/// The domain separation tag of the BLS signatures of the nodes, whose public
+/// keys are in G1 and signatures are in G2.
+pub const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// The verifier with the same BLS scheme as the crypto of the consensus.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlstVerifier;
+
+impl AggregateVerifier for BlstVerifier {
+    fn verify_aggregated(
+        &self,
+        hash: &Hash,
+        signature: &[u8],
+        pub_keys: &[&[u8]],
+    ) -> LightClientResult<()> {
+        let pub_keys = pub_keys
+            .iter()
+            .map(|key| PublicKey::key_validate(key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| LightClientError::InvalidPublicKey)?;
+        let pub_keys = pub_keys.iter().collect::<Vec<_>>();
+        let aggregate_key = AggregatePublicKey::aggregate(&pub_keys, false)
+            .map_err(|_| LightClientError::InvalidPublicKey)?
+            .to_public_key();
+
+        let signature = Signature::sig_validate(signature, true)
+            .map_err(|_| LightClientError::InvalidSignature)?;
+        match signature.verify(false, hash, DST, &[], &aggregate_key, false) {
+            BLST_ERROR::BLST_SUCCESS => Ok(()),
+            _ => Err(LightClientError::InvalidSignature),
+        }
+    }
+}
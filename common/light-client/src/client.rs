@@ -0,0 +1,164 @@
+use alloc::collections::VecDeque;
+
+use crate::types::{Proof, ValidatorSet};
+use crate::verify::verify_proof;
+use crate::{AggregateVerifier, LightClientError, LightClientResult};
+
+/// A light client which follows the validator sets of the epochs. The set of
+/// the next epoch is taken from the metadata update of the chain, and must
+/// begin where the set of the latest epoch ends. The sets of the epochs
+/// before the latest verified block are dropped.
+pub struct LightClient<V> {
+    verifier:      V,
+    sets:          VecDeque<ValidatorSet>,
+    latest_number: Option<u64>,
+}
+
+impl<V: AggregateVerifier> LightClient<V> {
+    pub fn new(verifier: V, set: ValidatorSet) -> Self {
+        let mut sets = VecDeque::new();
+        sets.push_back(set);
+
+        LightClient {
+            verifier,
+            sets,
+            latest_number: None,
+        }
+    }
+
+    /// The number of the highest block whose proof is verified.
+    pub fn latest_number(&self) -> Option<u64> {
+        self.latest_number
+    }
+
+    pub fn validator_sets(&self) -> impl Iterator<Item = &ValidatorSet> {
+        self.sets.iter()
+    }
+
+    /// Add the validator set of the epoch after the latest one. Adding the
+    /// latest set again is a no-op.
+    pub fn update_validator_set(&mut self, set: ValidatorSet) -> LightClientResult<()> {
+        let last = self.sets.back().expect("there is always a validator set");
+        if last == &set {
+            return Ok(());
+        }
+
+        if set.epoch != last.epoch + 1 || set.start != last.end || set.end <= set.start {
+            return Err(LightClientError::DiscontinuousValidatorSet {
+                epoch:      set.epoch,
+                start:      set.start,
+                end:        set.end,
+                last_epoch: last.epoch,
+                last_end:   last.end,
+            });
+        }
+
+        self.sets.push_back(set);
+        Ok(())
+    }
+
+    pub fn validator_set(&self, number: u64) -> Option<&ValidatorSet> {
+        self.sets.iter().find(|set| set.contains(number))
+    }
+
+    /// Verify the proof of a block with the validator set of its epoch. The
+    /// block hash of the proof must be checked against the block by the
+    /// caller.
+    pub fn verify(&mut self, proof: &Proof) -> LightClientResult<()> {
+        let set = self
+            .validator_set(proof.number)
+            .ok_or(LightClientError::UnknownValidatorSet(proof.number))?;
+        verify_proof(&self.verifier, set, proof)?;
+
+        if self.latest_number.map_or(true, |n| proof.number > n) {
+            self.latest_number = Some(proof.number);
+            // The latest set is always kept for the following updates.
+            while self.sets.len() > 1 && self.sets[0].end <= proof.number {
+                self.sets.pop_front();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::types::{Hash, Validator};
+
+    /// The verifier which accepts the signature made of the hash.
+    struct MockVerifier;
+
+    impl AggregateVerifier for MockVerifier {
+        fn verify_aggregated(
+            &self,
+            hash: &Hash,
+            signature: &[u8],
+            _pub_keys: &[&[u8]],
+        ) -> LightClientResult<()> {
+            if signature == hash {
+                Ok(())
+            } else {
+                Err(LightClientError::InvalidSignature)
+            }
+        }
+    }
+
+    fn mock_set(epoch: u64, start: u64, end: u64, key: u8) -> ValidatorSet {
+        let validators = vec![Validator {
+            pub_key:     vec![key; 33],
+            bls_pub_key: vec![key; 48],
+            vote_weight: 1,
+        }];
+        ValidatorSet::new(epoch, start, end, validators)
+    }
+
+    fn mock_proof(number: u64) -> Proof {
+        let mut proof = Proof {
+            number,
+            bitmap: vec![0x80],
+            ..Default::default()
+        };
+        proof.signature = crate::vote_hash(&proof).to_vec();
+        proof
+    }
+
+    #[test]
+    fn test_epoch_transition() {
+        let mut client = LightClient::new(MockVerifier, mock_set(0, 0, 10, 0));
+        client.verify(&mock_proof(5)).unwrap();
+        assert_eq!(
+            client.verify(&mock_proof(10)),
+            Err(LightClientError::UnknownValidatorSet(10))
+        );
+
+        // The set must follow the latest one.
+        assert!(client.update_validator_set(mock_set(1, 11, 20, 1)).is_err());
+        assert!(client.update_validator_set(mock_set(2, 10, 20, 1)).is_err());
+        client.update_validator_set(mock_set(1, 10, 20, 1)).unwrap();
+        client.update_validator_set(mock_set(1, 10, 20, 1)).unwrap();
+        assert_eq!(client.validator_sets().count(), 2);
+
+        // The blocks of the previous epoch are still verified before the
+        // client moves on.
+        client.verify(&mock_proof(9)).unwrap();
+        client.verify(&mock_proof(10)).unwrap();
+        assert_eq!(client.latest_number(), Some(10));
+        assert_eq!(client.validator_sets().count(), 1);
+        assert_eq!(
+            client.verify(&mock_proof(9)),
+            Err(LightClientError::UnknownValidatorSet(9))
+        );
+
+        let mut forged = mock_proof(11);
+        forged.round = 1;
+        assert_eq!(
+            client.verify(&forged),
+            Err(LightClientError::InvalidSignature)
+        );
+        assert_eq!(client.latest_number(), Some(10));
+    }
+}
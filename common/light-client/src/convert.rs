@@ -0,0 +1,145 @@
+use common_merkle::TrieMerkle;
+use core_metadata::metadata_abi::MetadataContractCalls;
+use ethers_core::abi::AbiDecode;
+use protocol::types::{
+    self, is_speculative_execution, receipt_leaf, Block, Metadata, Proposal, Public,
+    SignedTransaction, TxResp, UnverifiedTransaction, H160,
+};
+
+use crate::types::{Proof, Validator, ValidatorSet};
+use crate::{AggregateVerifier, LightClient, LightClientError, LightClientResult};
+
+impl From<&Metadata> for ValidatorSet {
+    fn from(metadata: &Metadata) -> Self {
+        let validators = metadata
+            .verifier_list
+            .iter()
+            .map(|v| Validator {
+                pub_key:     v.pub_key.as_bytes().to_vec(),
+                bls_pub_key: v.bls_pub_key.as_bytes().to_vec(),
+                vote_weight: v.vote_weight,
+            })
+            .collect();
+
+        ValidatorSet::new(
+            metadata.epoch,
+            metadata.version.start,
+            metadata.version.end,
+            validators,
+        )
+    }
+}
+
+impl From<&types::Proof> for Proof {
+    fn from(proof: &types::Proof) -> Self {
+        Proof {
+            number:     proof.number,
+            round:      proof.round,
+            block_hash: proof.block_hash.0,
+            signature:  proof.signature.to_vec(),
+            bitmap:     proof.bitmap.to_vec(),
+        }
+    }
+}
+
+impl<V: AggregateVerifier> LightClient<V> {
+    /// Verify the proof of a block, which is carried by the header of the next
+    /// block, as the `verify_proof` of the consensus does.
+    pub fn verify_block(&mut self, block: &Block, proof: &types::Proof) -> LightClientResult<()> {
+        // The genesis block has no proof.
+        if block.header.number == 0 {
+            return Ok(());
+        }
+
+        if block.header.number != proof.number {
+            return Err(LightClientError::HeightMismatch {
+                proof: proof.number,
+                block: block.header.number,
+            });
+        }
+
+        if Proposal::from(block).hash() != proof.block_hash {
+            return Err(LightClientError::HashMismatch);
+        }
+
+        self.verify(&proof.into())
+    }
+
+    /// Add the validator set of the next epoch, which is appended to the
+    /// metadata contract by a transaction of the block. The block is verified
+    /// with the current validator sets first, so that the new set is proven
+    /// by the set it follows. The receipt proof is the proof of the receipts
+    /// root that the transaction succeeded, which is only committed from the
+    /// speculative execution height.
+    pub fn update_metadata(
+        &mut self,
+        metadata_address: H160,
+        block: &Block,
+        proof: &types::Proof,
+        tx: &UnverifiedTransaction,
+        receipt_proof: Vec<Vec<u8>>,
+    ) -> LightClientResult<()> {
+        let number = block.header.number;
+        // The genesis block is trusted without a proof, its metadata is the
+        // one the client starts with.
+        if number == 0 || !is_speculative_execution(number) {
+            return Err(LightClientError::InvalidMetadataUpdate(number));
+        }
+
+        self.verify_block(block, proof)?;
+
+        let index = block
+            .tx_hashes
+            .iter()
+            .position(|hash| hash == &tx.get_hash())
+            .ok_or(LightClientError::InvalidMetadataUpdate(number))?;
+        // The metadata contract returns nothing on success.
+        let leaf = receipt_leaf(number, &TxResp::default());
+        if TrieMerkle::verify_proof_by_index(&block.header.receipts_root, index, receipt_proof)
+            != Some(rlp::encode(&leaf).to_vec())
+        {
+            return Err(LightClientError::InvalidMetadataUpdate(number));
+        }
+
+        let metadata = self
+            .decode_metadata_update(metadata_address, number, tx)
+            .ok_or(LightClientError::InvalidMetadataUpdate(number))?;
+        self.update_validator_set((&metadata).into())
+    }
+
+    /// Decode the metadata appended by a transaction to the metadata contract,
+    /// which only accepts the metadata from a validator of the block.
+    fn decode_metadata_update(
+        &self,
+        metadata_address: H160,
+        number: u64,
+        tx: &UnverifiedTransaction,
+    ) -> Option<Metadata> {
+        if tx.unsigned.to() != Some(metadata_address) {
+            return None;
+        }
+
+        let metadata = decode_append_metadata(tx.unsigned.data())?;
+        let sender = compress_public(&SignedTransaction::try_from(tx.clone()).ok()?.public?);
+        self.validator_set(number)?
+            .validators()
+            .iter()
+            .any(|v| v.pub_key == sender)
+            .then_some(metadata)
+    }
+}
+
+/// The compressed secp256k1 public key, which the validators are known by.
+fn compress_public(public: &Public) -> Vec<u8> {
+    let mut key = Vec::with_capacity(33);
+    key.push(0x02 | (public[63] & 1));
+    key.extend_from_slice(&public[..32]);
+    key
+}
+
+pub(crate) fn decode_append_metadata(data: &[u8]) -> Option<Metadata> {
+    match MetadataContractCalls::decode(data).ok()? {
+        MetadataContractCalls::AppendMetadata(call) => Some(call.metadata.into()),
+        _ => None,
+    }
+}
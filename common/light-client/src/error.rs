@@ -0,0 +1,48 @@
+use derive_more::Display;
+
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum LightClientError {
+    #[display(fmt = "No validator set of block {}", _0)]
+    UnknownValidatorSet(u64),
+
+    #[display(
+        fmt = "Validator set of epoch {} [{}, {}) does not follow epoch {} ending at {}",
+        epoch,
+        start,
+        end,
+        last_epoch,
+        last_end
+    )]
+    DiscontinuousValidatorSet {
+        epoch:      u64,
+        start:      u64,
+        end:        u64,
+        last_epoch: u64,
+        last_end:   u64,
+    },
+
+    #[display(fmt = "Block {} does not append the metadata of the next epoch", _0)]
+    InvalidMetadataUpdate(u64),
+
+    #[display(fmt = "Proof number {} mismatches block number {}", proof, block)]
+    HeightMismatch { proof: u64, block: u64 },
+
+    #[display(fmt = "Proof block hash mismatches the block")]
+    HashMismatch,
+
+    #[display(
+        fmt = "Signed weight {} is not more than 2/3 of total weight {}",
+        signed,
+        total
+    )]
+    InsufficientWeight { signed: u64, total: u64 },
+
+    #[display(fmt = "Invalid BLS public key")]
+    InvalidPublicKey,
+
+    #[display(fmt = "Invalid aggregated signature")]
+    InvalidSignature,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LightClientError {}
@@ -0,0 +1,46 @@
+//! The verification of the block proofs of Axon for light clients. A proof is
+//! the aggregated BLS signature of the precommit votes of a block and the
+//! bitmap of the signers, which is checked against the verifier list of the
+//! metadata of the block in the same way as the consensus of the nodes does.
+//!
+//! The library is `no_std` with `alloc` when the default features are turned
+//! off, and the aggregated signature is checked by an `AggregateVerifier`,
+//! so that it can be used where blst is unavailable, such as in a contract.
+//! The `blst` feature provides the verifier of the scheme of the nodes, and
+//! the `protocol` feature verifies the blocks and metadata of the protocol.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "blst")]
+mod bls;
+mod client;
+#[cfg(feature = "protocol")]
+mod convert;
+mod error;
+#[cfg(all(test, feature = "blst", feature = "protocol"))]
+mod tests;
+mod types;
+mod verify;
+
+#[cfg(feature = "blst")]
+pub use bls::{BlstVerifier, DST};
+pub use client::LightClient;
+pub use error::LightClientError;
+pub use types::{Hash, Proof, Validator, ValidatorSet};
+pub use verify::{extract_signers, verify_proof, verify_weight, vote_hash, PRECOMMIT};
+
+pub type LightClientResult<T> = Result<T, LightClientError>;
+
+/// The verifier of the aggregated BLS signature of a message hash.
+pub trait AggregateVerifier {
+    /// Verify the signature aggregated from the signatures of the given
+    /// compressed public keys on the hash.
+    fn verify_aggregated(
+        &self,
+        hash: &Hash,
+        signature: &[u8],
+        pub_keys: &[&[u8]],
+    ) -> LightClientResult<()>;
+}
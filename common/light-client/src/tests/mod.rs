@@ -0,0 +1,443 @@
+//! The vectors are made with the keys of the four nodes of `devtools/chain`.
+//! The metadata of the chain is decoded from the `appendMetadata` call of its
+//! genesis, and the metadata update transaction is signed out of the protocol
+//! code, so that the encoding, the hash and the sender recovery of it are
+//! checked against an independent implementation. The proofs are signed by
+//! the BLS crypto of the nodes, so that the verifier is checked against the
+//! scheme used by the consensus.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use common_crypto::{BlsPrivateKey, BlsPublicKey, BlsSignature, HashValue, PrivateKey, Signature};
+use common_merkle::TrieMerkle;
+use overlord::types::{Vote, VoteType};
+use protocol::codec::hex_decode;
+use protocol::lazy::SPECULATIVE_EXECUTION_FROM;
+use protocol::types::{
+    self, receipt_leaf, Block, Bytes, ExitReason, ExitRevert, Hasher, Hex, Metadata,
+    MetadataVersion, Proposal, RichBlock, SignatureComponents, SignedTransaction, TxResp,
+    UnsignedTransaction, UnverifiedTransaction, ValidatorExtend, H160, H256,
+};
+
+use crate::convert::decode_append_metadata;
+use crate::{vote_hash, BlstVerifier, LightClient, LightClientError, Proof, ValidatorSet};
+
+const GENESIS_PATH: &str = "../../devtools/chain/nodes/genesis_multi_nodes.json";
+/// The address of the metadata contract of `devtools/chain`.
+const METADATA_ADDRESS: &str = "0xb00d616b820c39619ee29e5144d0226cf8b5c15a";
+/// The end of the epoch 0 in the genesis metadata.
+const EPOCH_0_END: u64 = 99_999_999;
+/// The block which appends the metadata of the epoch 1, where the receipts
+/// root commits to the success of the transactions.
+const UPDATE_NUMBER: u64 = EPOCH_0_END - 1;
+
+/// The private key, the public key, the BLS public key and the address of the
+/// nodes.
+const NODES: [(&str, &str, &str, &str); 4] = [
+    (
+        "37aa0f893d05914a4def0460c0a984d3611546cfb26924d7a7ca6e0db9950a2d",
+        "0x031ddc35212b7fc7ff6685b17d91f77c972535aee5c7ae5684d3e72b986f08834b",
+        "0xac85bbb40347b6e06ac2dc2da1f75eece029cdc0ed2d456c457d27e288bfbfbcd4c5c19716e9b250134a0e76ce50fa22",
+        "0x8ab0cf264df99d83525e9e11c7e4db01558ae1b1",
+    ),
+    (
+        "383fcff8683b8115e31613949be24254b4204ffbe43c227408a76334a2e3fb32",
+        "0x02b77c74eb68af3d4d6cc7884ed6709f1a2a1af0f713382a4438ec2ea3a70d4d7f",
+        "0x91ed9f3c51c580e56948b1bda9d00c2159665f8a6e284191ab816ee64ef2487d78453a547a0f14efbf842bba5b5a3b4f",
+        "0xf386573563c3a75dbbd269fce9782620826ddac2",
+    ),
+    (
+        "51ce21643b911347c5d5c85c323d9d5421810dc89f46b688720b2715f5e8e936",
+        "0x027ffd6a6a231561f2afe5878b1c743323b34263d16787130b1815fe35649b0bf5",
+        "0x92e5d0856fb20ea9cb5ab5da2d3331c38d32cc96507f6ad902fa3da9400096a485fb4e09834bc93de55db224f26c229c",
+        "0x8af204ac5d7cb8815a6c53a50b72d01e729d3b22",
+    ),
+    (
+        "69ff51f4c22f30615f68b88efa740f8f1b9169e88842b83d189748d06f1a948e",
+        "0x0232c489c23b1207107e9a24648c1e4754a8c1c0b38db96df57a526201035058cb",
+        "0xa694f4e48a5a173b61731998f8f1204342dc5c8eb1e32cdae37415c20d11ae035ddac4a39f105e9c2d4d3691024d385d",
+        "0xf4cc1652dcec2e5de9ce6fb1b6f9fa9456e957f1",
+    ),
+];
+
+/// The signature and the hash of the metadata update transaction, which is
+/// signed by the node 0 with the nonce 5.
+const UPDATE_TX_SIGNATURE: (&str, &str, u8) = (
+    "55b905b9e614bdfaab18a6043bb04975737f2928ca5864a0b155e9638668f094",
+    "159049b2daed079efad36c49937a10e29260ac858ba96eeae1dde495b793dc5a",
+    1,
+);
+const UPDATE_TX_HASH: &str = "0x24f030a483267320c9e714ed98c37cb3835a97ae592699fe05bf28a8ff8a9d96";
+
+fn mock_metadata(epoch: u64, start: u64, end: u64, nodes: &[usize]) -> Metadata {
+    let verifier_list = nodes
+        .iter()
+        .map(|i| ValidatorExtend {
+            bls_pub_key:    Hex::from_string(NODES[*i].2.to_string()).unwrap(),
+            pub_key:        Hex::from_string(NODES[*i].1.to_string()).unwrap(),
+            address:        H160::from_slice(&hex_decode(NODES[*i].3).unwrap()),
+            propose_weight: 1,
+            vote_weight:    1,
+        })
+        .collect();
+
+    Metadata {
+        version: MetadataVersion::new(start, end),
+        epoch,
+        verifier_list,
+        ..Default::default()
+    }
+}
+
+fn mock_block(number: u64) -> Block {
+    let mut block = Block::default();
+    block.header.number = number;
+    block.header.chain_id = 0x41786f6e;
+    block
+}
+
+/// Sign the proof of the block by the given nodes of the metadata.
+fn mock_proof(block: &Block, round: u64, metadata: &Metadata, signers: &[usize]) -> types::Proof {
+    let mut proof = types::Proof {
+        number: block.header.number,
+        round,
+        block_hash: Proposal::from(block).hash(),
+        ..Default::default()
+    };
+
+    let hash = HashValue::try_from(vote_hash(&(&proof).into()).as_ref()).unwrap();
+    let set = ValidatorSet::from(metadata);
+    let mut bitmap = vec![0u8; (set.validators().len() + 7) / 8];
+    let mut sigs_and_pub_keys = Vec::new();
+    for i in signers.iter() {
+        let pub_key = Hex::from_string(NODES[*i].1.to_string())
+            .unwrap()
+            .as_bytes();
+        let index = set
+            .validators()
+            .iter()
+            .position(|v| v.pub_key == pub_key)
+            .unwrap();
+        bitmap[index / 8] |= 0x80 >> (index % 8);
+
+        let private_key =
+            BlsPrivateKey::try_from(hex_decode(NODES[*i].0).unwrap().as_ref()).unwrap();
+        let bls_pub_key = Hex::from_string(NODES[*i].2.to_string()).unwrap();
+        sigs_and_pub_keys.push((
+            private_key.sign_message(&hash),
+            BlsPublicKey::try_from(bls_pub_key.as_bytes().as_ref()).unwrap(),
+        ));
+    }
+
+    proof.signature = BlsSignature::combine(sigs_and_pub_keys).unwrap().to_bytes();
+    proof.bitmap = Bytes::from(bitmap);
+    proof
+}
+
+#[test]
+fn test_vote_hash() {
+    let proof = types::Proof {
+        number: 100,
+        round: 2,
+        block_hash: Hasher::digest(b"block"),
+        ..Default::default()
+    };
+    let vote = Vote {
+        height:     proof.number,
+        round:      proof.round,
+        vote_type:  VoteType::Precommit,
+        block_hash: Bytes::from(proof.block_hash.as_bytes().to_vec()),
+    };
+
+    assert_eq!(
+        vote_hash(&Proof::from(&proof)),
+        Hasher::digest(rlp::encode(&vote)).0
+    );
+}
+
+#[test]
+fn test_verify_block() {
+    let metadata = mock_metadata(0, 0, 100, &[0, 1, 2, 3]);
+    let mut client = LightClient::new(BlstVerifier, (&metadata).into());
+
+    let block = mock_block(1);
+    let proof = mock_proof(&block, 0, &metadata, &[0, 2, 3]);
+    client.verify_block(&block, &proof).unwrap();
+    assert_eq!(client.latest_number(), Some(1));
+
+    let block = mock_block(2);
+    let proof = mock_proof(&block, 1, &metadata, &[0, 1]);
+    assert_eq!(
+        client.verify_block(&block, &proof),
+        Err(LightClientError::InsufficientWeight {
+            signed: 2,
+            total:  4,
+        })
+    );
+
+    let proof = mock_proof(&block, 1, &metadata, &[0, 1, 3]);
+    assert_eq!(
+        client.verify_block(&mock_block(3), &proof),
+        Err(LightClientError::HeightMismatch { proof: 2, block: 3 })
+    );
+    let mut other = mock_block(2);
+    other.header.timestamp = 1;
+    assert_eq!(
+        client.verify_block(&other, &proof),
+        Err(LightClientError::HashMismatch)
+    );
+
+    // The bitmap claims a signer which does not sign.
+    let mut forged = proof.clone();
+    forged.bitmap = Bytes::from(vec![0xf0]);
+    assert_eq!(
+        client.verify_block(&block, &forged),
+        Err(LightClientError::InvalidSignature)
+    );
+    let mut forged = proof.clone();
+    forged.round = 2;
+    assert_eq!(
+        client.verify_block(&block, &forged),
+        Err(LightClientError::InvalidSignature)
+    );
+
+    client.verify_block(&block, &proof).unwrap();
+    assert_eq!(client.latest_number(), Some(2));
+}
+
+fn metadata_address() -> H160 {
+    H160::from_slice(&hex_decode(METADATA_ADDRESS).unwrap())
+}
+
+/// The `appendMetadata` call of the genesis.
+fn genesis_metadata_tx() -> UnverifiedTransaction {
+    let reader = BufReader::new(File::open(GENESIS_PATH).unwrap());
+    let genesis: RichBlock = serde_json::from_reader(reader).unwrap();
+    genesis
+        .txs
+        .into_iter()
+        .map(|tx| tx.transaction)
+        .find(|tx| tx.unsigned.to() == Some(metadata_address()))
+        .unwrap()
+}
+
+/// The call which appends the metadata of the epoch 1, whose verifier list is
+/// the one of the genesis.
+fn update_metadata_tx() -> UnverifiedTransaction {
+    let mut tx = genesis_metadata_tx();
+    let mut data = tx.unsigned.data().to_vec();
+    // The words of the version and the epoch of the metadata.
+    for (index, value) in [(1, EPOCH_0_END), (2, 2 * EPOCH_0_END + 1), (3, 1)] {
+        let word = &mut data[4 + 32 * index..4 + 32 * (index + 1)];
+        word.fill(0);
+        word[24..].copy_from_slice(&value.to_be_bytes());
+    }
+
+    tx.unsigned.set_data(data.into());
+    if let UnsignedTransaction::Eip1559(ref mut raw) = tx.unsigned {
+        raw.nonce = 5u64.into();
+    }
+    tx.signature = Some(SignatureComponents {
+        r:          Bytes::from(hex_decode(UPDATE_TX_SIGNATURE.0).unwrap()),
+        s:          Bytes::from(hex_decode(UPDATE_TX_SIGNATURE.1).unwrap()),
+        standard_v: UPDATE_TX_SIGNATURE.2,
+    });
+    tx.calc_hash()
+}
+
+/// The block of the update with the transactions, whose receipts root commits
+/// to their results. The receipt proofs of them are returned too.
+fn mock_update_block(txs: &[(H256, TxResp)]) -> (Block, Vec<Vec<Vec<u8>>>) {
+    let leaves = txs
+        .iter()
+        .map(|(_, resp)| receipt_leaf(UPDATE_NUMBER, resp))
+        .collect::<Vec<_>>();
+    let mut trie = TrieMerkle::from_iter(leaves.iter().enumerate());
+
+    let mut block = mock_block(UPDATE_NUMBER);
+    block.tx_hashes = txs.iter().map(|(hash, _)| *hash).collect();
+    block.header.receipts_root = trie.root_hash().unwrap();
+    let proofs = (0..txs.len())
+        .map(|index| trie.get_proof_by_index(index).unwrap())
+        .collect();
+    (block, proofs)
+}
+
+#[test]
+fn test_genesis_metadata() {
+    let metadata = decode_append_metadata(genesis_metadata_tx().unsigned.data()).unwrap();
+    assert_eq!(metadata.epoch, 0);
+    assert_eq!(
+        ValidatorSet::from(&metadata),
+        ValidatorSet::from(&mock_metadata(0, 0, EPOCH_0_END, &[0, 1, 2, 3]))
+    );
+}
+
+#[test]
+fn test_update_metadata_tx() {
+    let tx = update_metadata_tx();
+    assert_eq!(
+        tx.hash,
+        H256::from_slice(&hex_decode(UPDATE_TX_HASH).unwrap())
+    );
+
+    let sender = SignedTransaction::try_from(tx).unwrap().sender;
+    assert_eq!(sender, H160::from_slice(&hex_decode(NODES[0].3).unwrap()));
+}
+
+#[test]
+fn test_follow_epochs() {
+    SPECULATIVE_EXECUTION_FROM.store(Arc::new(UPDATE_NUMBER));
+    let epoch_0 = decode_append_metadata(genesis_metadata_tx().unsigned.data()).unwrap();
+    let mut client = LightClient::new(BlstVerifier, (&epoch_0).into());
+
+    let tx = update_metadata_tx();
+    let epoch_1 = decode_append_metadata(tx.unsigned.data()).unwrap();
+    assert_eq!(epoch_1.epoch, 1);
+    assert_eq!(
+        epoch_1.version,
+        MetadataVersion::new(EPOCH_0_END, 2 * EPOCH_0_END + 1)
+    );
+
+    let block = mock_block(EPOCH_0_END);
+    let proof = mock_proof(&block, 0, &epoch_1, &[1, 2, 3]);
+    assert_eq!(
+        client.verify_block(&block, &proof),
+        Err(LightClientError::UnknownValidatorSet(EPOCH_0_END))
+    );
+
+    // The update must be in a block verified by the current set.
+    let (update_block, _) = mock_update_block(&[]);
+    let update_proof = mock_proof(&update_block, 0, &epoch_0, &[0, 1, 2]);
+    assert_eq!(
+        client.update_metadata(
+            metadata_address(),
+            &update_block,
+            &update_proof,
+            &tx,
+            vec![]
+        ),
+        Err(LightClientError::InvalidMetadataUpdate(UPDATE_NUMBER))
+    );
+
+    let (update_block, receipt_proofs) = mock_update_block(&[(tx.hash, TxResp::default())]);
+    let receipt_proof = receipt_proofs[0].clone();
+    let update_proof = mock_proof(&update_block, 0, &epoch_0, &[0, 1, 2]);
+    let forged_proof = mock_proof(&update_block, 0, &epoch_0, &[0, 1]);
+    assert_eq!(
+        client.update_metadata(
+            metadata_address(),
+            &update_block,
+            &forged_proof,
+            &tx,
+            receipt_proof.clone()
+        ),
+        Err(LightClientError::InsufficientWeight {
+            signed: 2,
+            total:  4,
+        })
+    );
+    assert_eq!(
+        client.update_metadata(
+            H160::zero(),
+            &update_block,
+            &update_proof,
+            &tx,
+            receipt_proof.clone()
+        ),
+        Err(LightClientError::InvalidMetadataUpdate(UPDATE_NUMBER))
+    );
+
+    // The forged metadata is not the one of the transaction in the block.
+    let mut forged = tx.clone();
+    let mut data = forged.unsigned.data().to_vec();
+    data[4 + 32 * 3 + 31] = 2;
+    forged.unsigned.set_data(data.into());
+    let forged = forged.calc_hash();
+    assert_eq!(
+        client.update_metadata(
+            metadata_address(),
+            &update_block,
+            &update_proof,
+            &forged,
+            receipt_proof.clone()
+        ),
+        Err(LightClientError::InvalidMetadataUpdate(UPDATE_NUMBER))
+    );
+
+    client
+        .update_metadata(
+            metadata_address(),
+            &update_block,
+            &update_proof,
+            &tx,
+            receipt_proof.clone(),
+        )
+        .unwrap();
+    assert_eq!(client.validator_sets().count(), 2);
+    client.verify_block(&block, &proof).unwrap();
+    assert_eq!(client.validator_sets().count(), 1);
+
+    // The genesis block never proves an update.
+    assert_eq!(
+        client.update_metadata(
+            metadata_address(),
+            &mock_block(0),
+            &update_proof,
+            &tx,
+            receipt_proof
+        ),
+        Err(LightClientError::InvalidMetadataUpdate(0))
+    );
+}
+
+#[test]
+fn test_refuse_metadata_update() {
+    SPECULATIVE_EXECUTION_FROM.store(Arc::new(UPDATE_NUMBER));
+    let tx = update_metadata_tx();
+
+    // The update of the node 0 lists itself, which is not a validator of the
+    // block.
+    let epoch_0 = mock_metadata(0, 0, EPOCH_0_END, &[1, 2, 3]);
+    let mut client = LightClient::new(BlstVerifier, (&epoch_0).into());
+    let (update_block, receipt_proofs) = mock_update_block(&[(tx.hash, TxResp::default())]);
+    let update_proof = mock_proof(&update_block, 0, &epoch_0, &[1, 2, 3]);
+    assert_eq!(
+        client.update_metadata(
+            metadata_address(),
+            &update_block,
+            &update_proof,
+            &tx,
+            receipt_proofs[0].clone()
+        ),
+        Err(LightClientError::InvalidMetadataUpdate(UPDATE_NUMBER))
+    );
+    assert_eq!(client.validator_sets().count(), 1);
+
+    // The update is reverted by the metadata contract.
+    let epoch_0 = mock_metadata(0, 0, EPOCH_0_END, &[0, 1, 2, 3]);
+    let mut client = LightClient::new(BlstVerifier, (&epoch_0).into());
+    let reverted = TxResp {
+        exit_reason: ExitReason::Revert(ExitRevert::Reverted),
+        ..Default::default()
+    };
+    let other = Hasher::digest(b"other");
+    let (update_block, receipt_proofs) =
+        mock_update_block(&[(other, TxResp::default()), (tx.hash, reverted)]);
+    let update_proof = mock_proof(&update_block, 0, &epoch_0, &[0, 1, 2]);
+    for receipt_proof in receipt_proofs {
+        assert_eq!(
+            client.update_metadata(
+                metadata_address(),
+                &update_block,
+                &update_proof,
+                &tx,
+                receipt_proof
+            ),
+            Err(LightClientError::InvalidMetadataUpdate(UPDATE_NUMBER))
+        );
+    }
+    assert_eq!(client.validator_sets().count(), 1);
+}
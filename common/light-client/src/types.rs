@@ -0,0 +1,67 @@
+use alloc::vec::Vec;
+
+pub type Hash = [u8; 32];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Validator {
+    /// The secp256k1 public key, which is the address of the validator in
+    /// consensus and decides its bit in the bitmap of a proof.
+    pub pub_key:     Vec<u8>,
+    /// The compressed BLS public key.
+    pub bls_pub_key: Vec<u8>,
+    pub vote_weight: u32,
+}
+
+/// The verifier list of an epoch, which verifies the blocks in
+/// `[start, end)` as the version of the metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorSet {
+    pub epoch:  u64,
+    pub start:  u64,
+    pub end:    u64,
+    validators: Vec<Validator>,
+}
+
+impl ValidatorSet {
+    pub fn new(epoch: u64, start: u64, end: u64, mut validators: Vec<Validator>) -> Self {
+        // The bits of the bitmap follow the order of the sorted authority list
+        // of overlord, whose nodes are ordered by the address first.
+        validators.sort_by(|a, b| {
+            a.pub_key
+                .cmp(&b.pub_key)
+                .then(a.vote_weight.cmp(&b.vote_weight))
+        });
+
+        ValidatorSet {
+            epoch,
+            start,
+            end,
+            validators,
+        }
+    }
+
+    pub fn contains(&self, number: u64) -> bool {
+        self.start <= number && number < self.end
+    }
+
+    /// The validators sorted by their public keys.
+    pub fn validators(&self) -> &[Validator] {
+        &self.validators
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.validators
+            .iter()
+            .map(|v| u64::from(v.vote_weight))
+            .sum()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Proof {
+    pub number:     u64,
+    pub round:      u64,
+    pub block_hash: Hash,
+    pub signature:  Vec<u8>,
+    pub bitmap:     Vec<u8>,
+}
@@ -0,0 +1,134 @@
+use alloc::vec::Vec;
+
+use rlp::RlpStream;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::types::{Hash, Proof, Validator, ValidatorSet};
+use crate::{AggregateVerifier, LightClientError, LightClientResult};
+
+/// The vote type of a precommit vote of overlord.
+pub const PRECOMMIT: u8 = 2;
+
+/// The hash of the precommit vote of the proof, which is the message of the
+/// aggregated signature. It is the keccak hash of the RLP encoded vote of
+/// overlord.
+pub fn vote_hash(proof: &Proof) -> Hash {
+    let mut stream = RlpStream::new_list(4);
+    stream
+        .append(&proof.number)
+        .append(&proof.round)
+        .append(&PRECOMMIT)
+        .append(&proof.block_hash.to_vec());
+
+    let mut hash = [0u8; 32];
+    let mut keccak = Keccak::v256();
+    keccak.update(&stream.out());
+    keccak.finalize(&mut hash);
+    hash
+}
+
+/// Extract the signers from the bitmap, whose bits are in the order of the
+/// sorted validators with the most significant bit of a byte first. The bits
+/// beyond the validators are ignored as overlord does.
+pub fn extract_signers<'a>(set: &'a ValidatorSet, bitmap: &[u8]) -> Vec<&'a Validator> {
+    set.validators()
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            bitmap
+                .get(i / 8)
+                .map_or(false, |byte| byte & (0x80 >> (i % 8)) != 0)
+        })
+        .map(|(_, v)| v)
+        .collect()
+}
+
+/// The signers must own more than 2/3 of the vote weight of the set.
+pub fn verify_weight(set: &ValidatorSet, signers: &[&Validator]) -> LightClientResult<()> {
+    let total = set.total_weight();
+    let signed = signers
+        .iter()
+        .map(|v| u64::from(v.vote_weight))
+        .sum::<u64>();
+
+    if 3 * signed <= 2 * total {
+        return Err(LightClientError::InsufficientWeight { signed, total });
+    }
+
+    Ok(())
+}
+
+/// Verify the proof against the validator set of its block, which checks the
+/// weight of the signers and their aggregated signature on the precommit
+/// vote. The block hash of the proof is not checked here.
+pub fn verify_proof<V: AggregateVerifier>(
+    verifier: &V,
+    set: &ValidatorSet,
+    proof: &Proof,
+) -> LightClientResult<()> {
+    if !set.contains(proof.number) {
+        return Err(LightClientError::UnknownValidatorSet(proof.number));
+    }
+
+    let signers = extract_signers(set, &proof.bitmap);
+    verify_weight(set, &signers)?;
+
+    let pub_keys = signers
+        .iter()
+        .map(|v| v.bls_pub_key.as_slice())
+        .collect::<Vec<_>>();
+    verifier.verify_aggregated(&vote_hash(proof), &proof.signature, &pub_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn mock_set(weights: &[u32]) -> ValidatorSet {
+        let validators = weights
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, w)| Validator {
+                pub_key:     vec![i as u8; 33],
+                bls_pub_key: vec![i as u8; 48],
+                vote_weight: *w,
+            })
+            .collect();
+        ValidatorSet::new(0, 0, 100, validators)
+    }
+
+    #[test]
+    fn test_extract_signers() {
+        let set = mock_set(&[1; 10]);
+        let signers = extract_signers(&set, &[0b1010_0000, 0b0100_0000, 0xff]);
+        let signers = signers.iter().map(|v| v.pub_key[0]).collect::<Vec<_>>();
+        assert_eq!(signers, vec![0, 2, 9]);
+
+        assert!(extract_signers(&set, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_verify_weight() {
+        let set = mock_set(&[1, 1, 1, 1]);
+        let signers = extract_signers(&set, &[0b1110_0000]);
+        assert!(verify_weight(&set, &signers).is_ok());
+        let signers = extract_signers(&set, &[0b1100_0000]);
+        assert_eq!(
+            verify_weight(&set, &signers),
+            Err(LightClientError::InsufficientWeight {
+                signed: 2,
+                total:  4,
+            })
+        );
+
+        // Exactly 2/3 of the weight is not enough.
+        let set = mock_set(&[2, 1, 3]);
+        let signers = extract_signers(&set, &[0b1010_0000]);
+        assert!(verify_weight(&set, &signers).is_ok());
+        let signers = extract_signers(&set, &[0b0110_0000]);
+        assert!(verify_weight(&set, &signers).is_err());
+    }
+}
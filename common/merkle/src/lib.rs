@@ -93,4 +93,19 @@ impl TrieMerkle {
         let ret = self.0.get_proof(&key)?;
         Ok(ret)
     }
+
+    /// Verify the proof of the value at the index against the root, returns
+    /// the value if the proof is valid.
+    pub fn verify_proof_by_index(
+        root: &Hash,
+        index: usize,
+        proof: Vec<Vec<u8>>,
+    ) -> Option<Vec<u8>> {
+        let key = rlp::encode(&index).to_vec();
+        Self::default()
+            .0
+            .verify_proof(root.as_bytes(), &key, proof)
+            .ok()
+            .flatten()
+    }
 }
@@ -3,8 +3,9 @@ use std::sync::Arc;
 use core_executor::{AxonExecutor, AxonExecutorAdapter, MPTTrie};
 use protocol::traits::{APIAdapter, Context, Executor, ExecutorAdapter, MemPool, Network, Storage};
 use protocol::types::{
-    Account, BigEndianHash, Block, BlockNumber, Bytes, ExecutorContext, Hash, Header, Proposal,
-    Receipt, SignedTransaction, TxResp, H160, MAX_BLOCK_GAS_LIMIT, NIL_DATA, RLP_NULL, U256,
+    Account, BigEndianHash, Block, BlockNumber, Bytes, DoubleSignEvidence, DroppedTx,
+    ExecutorContext, Hash, Header, Proposal, Receipt, SignedTransaction, TxResp, H160,
+    MAX_BLOCK_GAS_LIMIT, NIL_DATA, RLP_NULL, U256,
 };
 use protocol::{async_trait, codec::ProtocolCodec, trie, ProtocolResult};
 
@@ -70,6 +71,14 @@ where
         self.mempool.insert(ctx, signed_tx).await
     }
 
+    async fn insert_private_signed_txs(
+        &self,
+        ctx: Context,
+        signed_tx: SignedTransaction,
+    ) -> ProtocolResult<()> {
+        self.mempool.insert_private(ctx, signed_tx).await
+    }
+
     async fn get_block_by_number(
         &self,
         ctx: Context,
@@ -114,6 +123,17 @@ where
         block_number: u64,
         tx_hashes: &[Hash],
     ) -> ProtocolResult<Vec<Option<Receipt>>> {
+        // The blocks up to the snapshot sync one are not executed locally.
+        if let Some(number) = self.storage.get_snapshot_number(ctx.clone()).await? {
+            if block_number <= number && !tx_hashes.is_empty() {
+                return Err(APIError::Storage(format!(
+                    "receipts of block {} are unknown, the state is synced at block {}",
+                    block_number, number
+                ))
+                .into());
+            }
+        }
+
         self.storage
             .get_receipts(ctx, block_number, tx_hashes)
             .await
@@ -127,6 +147,22 @@ where
         self.storage.get_transaction_by_hash(ctx, &tx_hash).await
     }
 
+    async fn get_pending_tx(
+        &self,
+        ctx: Context,
+        tx_hash: Hash,
+    ) -> ProtocolResult<Option<SignedTransaction>> {
+        Ok(self.mempool.get_tx_from_mem(ctx, &tx_hash))
+    }
+
+    async fn get_dropped_tx(
+        &self,
+        ctx: Context,
+        tx_hash: Hash,
+    ) -> ProtocolResult<Option<DroppedTx>> {
+        Ok(self.mempool.get_dropped_tx(ctx, &tx_hash))
+    }
+
     async fn get_transactions_by_hashes(
         &self,
         ctx: Context,
@@ -221,4 +257,12 @@ where
             .get(hash.as_bytes())?
             .ok_or_else(|| APIError::Adapter("Can't find this position".to_string()).into())
     }
+
+    async fn get_evidences(
+        &self,
+        ctx: Context,
+        number: BlockNumber,
+    ) -> ProtocolResult<Vec<DoubleSignEvidence>> {
+        self.storage.get_evidences(ctx, number).await
+    }
 }
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use jsonrpsee::core::Error;
+
+use protocol::traits::PeerStoreAdmin;
+use protocol::types::PeerStoreSnapshot;
+
+use crate::jsonrpc::{AxonAdminRpcServer, RpcResult};
+
+pub struct AdminRpcImpl {
+    peer_store: Arc<dyn PeerStoreAdmin>,
+}
+
+impl AdminRpcImpl {
+    pub fn new(peer_store: Arc<dyn PeerStoreAdmin>) -> Self {
+        AdminRpcImpl { peer_store }
+    }
+}
+
+impl AxonAdminRpcServer for AdminRpcImpl {
+    fn peers(&self) -> RpcResult<PeerStoreSnapshot> {
+        self.peer_store
+            .peer_store_snapshot()
+            .map_err(|e| Error::Custom(e.to_string()))
+    }
+
+    fn ban_peer(&self, target: String, duration: u64, reason: String) -> RpcResult<usize> {
+        self.peer_store
+            .ban_peer(&target, duration, reason)
+            .map_err(|e| Error::Custom(e.to_string()))
+    }
+
+    fn unban_peer(&self, target: String) -> RpcResult<usize> {
+        self.peer_store
+            .unban_peer(&target)
+            .map_err(|e| Error::Custom(e.to_string()))
+    }
+
+    fn forget_peer(&self, target: String) -> RpcResult<usize> {
+        self.peer_store
+            .forget_peer(&target)
+            .map_err(|e| Error::Custom(e.to_string()))
+    }
+
+    fn import_peers(&self, addrs: Vec<String>) -> RpcResult<usize> {
+        self.peer_store
+            .import_peers(addrs)
+            .map_err(|e| Error::Custom(e.to_string()))
+    }
+
+    fn set_allowlist(&self, peer_ids: Vec<String>) -> RpcResult<usize> {
+        self.peer_store
+            .set_allowlist(peer_ids)
+            .map_err(|e| Error::Custom(e.to_string()))
+    }
+}
@@ -1,7 +1,9 @@
+mod admin;
 mod filter;
 mod node;
 mod web3;
 
+pub use admin::AdminRpcImpl;
 pub use filter::filter_module;
 pub use node::NodeRpcImpl;
 pub use web3::{from_receipt_to_web3_log, Web3RpcImpl};
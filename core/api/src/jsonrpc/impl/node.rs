@@ -4,11 +4,12 @@ use std::sync::Arc;
 
 use jsonrpsee::core::Error;
 
-use core_consensus::SYNC_STATUS;
+use core_consensus::{SYNC_STATUS, VALIDATOR_LIVENESS};
 use protocol::lazy::CHAIN_ID;
 use protocol::types::{Hash, Hasher, Hex, H160, H256, U256};
 
-use crate::jsonrpc::{web3_types::Web3SyncStatus, AxonNodeRpcServer, RpcResult};
+use crate::jsonrpc::web3_types::{Web3SyncStatus, Web3ValidatorLiveness};
+use crate::jsonrpc::{AxonNodeRpcServer, RpcResult};
 
 pub struct NodeRpcImpl {
     version: String,
@@ -120,6 +121,15 @@ impl AxonNodeRpcServer for NodeRpcImpl {
             Hex::decode(data.as_string()).map_err(|e| Error::Custom(e.to_string()))?;
         Ok(Hasher::digest(decode_data.as_ref()))
     }
+
+    fn validator_liveness(&self) -> RpcResult<Vec<Web3ValidatorLiveness>> {
+        Ok(VALIDATOR_LIVENESS
+            .read()
+            .stats()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
 }
 
 /// This function use `copy` then `remove_file` as a fallback when `rename`
@@ -8,12 +8,14 @@ use protocol::traits::{APIAdapter, Context};
 use protocol::types::{
     Block, BlockNumber, Bytes, Hash, Header, Hex, Receipt, SignedTransaction, TxResp,
     UnverifiedTransaction, H160, H256, H64, MAX_BLOCK_GAS_LIMIT, MIN_TRANSACTION_GAS_LIMIT, U256,
+    U64,
 };
 use protocol::{async_trait, codec::ProtocolCodec, ProtocolResult};
 
 use crate::jsonrpc::web3_types::{
-    BlockId, RichTransactionOrHash, Web3Block, Web3CallRequest, Web3FeeHistory, Web3Filter,
-    Web3Log, Web3Receipt, Web3Transaction,
+    BlockId, RichTransactionOrHash, TransactionStatusKind, Web3Block, Web3CallRequest,
+    Web3DoubleSignEvidence, Web3FeeHistory, Web3Filter, Web3Log, Web3Receipt, Web3Transaction,
+    Web3TransactionStatus,
 };
 use crate::jsonrpc::{error::RpcError, AxonWeb3RpcServer, RpcResult};
 use crate::APIError;
@@ -65,12 +67,8 @@ impl<Adapter: APIAdapter> Web3RpcImpl<Adapter> {
             )
             .await
     }
-}
 
-#[async_trait]
-impl<Adapter: APIAdapter + 'static> AxonWeb3RpcServer for Web3RpcImpl<Adapter> {
-    #[metrics_rpc("eth_sendRawTransaction")]
-    async fn send_raw_transaction(&self, tx: Hex) -> RpcResult<H256> {
+    fn decode_raw_transaction(&self, tx: Hex) -> RpcResult<SignedTransaction> {
         let utx = UnverifiedTransaction::decode(&tx.as_bytes())
             .map_err(|e| Error::Custom(e.to_string()))?;
 
@@ -102,7 +100,15 @@ impl<Adapter: APIAdapter + 'static> AxonWeb3RpcServer for Web3RpcImpl<Adapter> {
 
         utx.check_hash().map_err(|e| Error::Custom(e.to_string()))?;
 
-        let stx = SignedTransaction::try_from(utx).map_err(|e| Error::Custom(e.to_string()))?;
+        SignedTransaction::try_from(utx).map_err(|e| Error::Custom(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl<Adapter: APIAdapter + 'static> AxonWeb3RpcServer for Web3RpcImpl<Adapter> {
+    #[metrics_rpc("eth_sendRawTransaction")]
+    async fn send_raw_transaction(&self, tx: Hex) -> RpcResult<H256> {
+        let stx = self.decode_raw_transaction(tx)?;
         let hash = stx.transaction.hash;
 
         self.adapter
@@ -113,6 +119,19 @@ impl<Adapter: APIAdapter + 'static> AxonWeb3RpcServer for Web3RpcImpl<Adapter> {
         Ok(hash)
     }
 
+    #[metrics_rpc("eth_sendPrivateRawTransaction")]
+    async fn send_private_raw_transaction(&self, tx: Hex) -> RpcResult<H256> {
+        let stx = self.decode_raw_transaction(tx)?;
+        let hash = stx.transaction.hash;
+
+        self.adapter
+            .insert_private_signed_txs(Context::new(), stx)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+
+        Ok(hash)
+    }
+
     #[metrics_rpc("eth_getTransactionByHash")]
     async fn get_transaction_by_hash(&self, hash: H256) -> RpcResult<Option<Web3Transaction>> {
         let res = self
@@ -140,6 +159,65 @@ impl<Adapter: APIAdapter + 'static> AxonWeb3RpcServer for Web3RpcImpl<Adapter> {
         }
     }
 
+    #[metrics_rpc("axon_getTransactionStatus")]
+    async fn get_transaction_status(&self, hash: H256) -> RpcResult<Web3TransactionStatus> {
+        if self
+            .adapter
+            .get_transaction_by_hash(Context::new(), hash)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?
+            .is_some()
+        {
+            return Ok(Web3TransactionStatus::new(
+                hash,
+                TransactionStatusKind::Committed,
+            ));
+        }
+
+        // A dropped tx stays in the mempool until the next flush, so check the
+        // dropped record before the pending one.
+        if let Some(dropped) = self
+            .adapter
+            .get_dropped_tx(Context::new(), hash)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?
+        {
+            return Ok(dropped.into());
+        }
+
+        if self
+            .adapter
+            .get_pending_tx(Context::new(), hash)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?
+            .is_some()
+        {
+            return Ok(Web3TransactionStatus::new(
+                hash,
+                TransactionStatusKind::Pending,
+            ));
+        }
+
+        Ok(Web3TransactionStatus::new(
+            hash,
+            TransactionStatusKind::Unknown,
+        ))
+    }
+
+    #[metrics_rpc("axon_getDoubleSignEvidences")]
+    async fn get_double_sign_evidences(
+        &self,
+        number: U64,
+    ) -> RpcResult<Vec<Web3DoubleSignEvidence>> {
+        let evidences = self
+            .adapter
+            .get_evidences(Context::new(), number.as_u64())
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+
+        Ok(evidences.into_iter().map(Into::into).collect())
+    }
+
     #[metrics_rpc("eth_getBlockByNumber")]
     async fn get_block_by_number(
         &self,
@@ -3,19 +3,21 @@ mod r#impl;
 mod web3_types;
 mod ws_subscription;
 
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
 use jsonrpsee::server::{ServerBuilder, ServerHandle};
 use jsonrpsee::{core::Error, proc_macros::rpc};
 
 use common_config_parser::types::Config;
-use protocol::traits::APIAdapter;
-use protocol::types::{Hash, Hex, H160, H256, U256};
+use protocol::traits::{APIAdapter, PeerStoreAdmin};
+use protocol::types::{Hash, Hex, PeerStoreSnapshot, H160, H256, U256, U64};
 use protocol::ProtocolResult;
 
 use crate::jsonrpc::web3_types::{
-    BlockId, FilterChanges, RawLoggerFilter, Web3Block, Web3CallRequest, Web3FeeHistory,
-    Web3Filter, Web3Log, Web3Receipt, Web3SyncStatus, Web3Transaction,
+    BlockId, FilterChanges, RawLoggerFilter, Web3Block, Web3CallRequest, Web3DoubleSignEvidence,
+    Web3FeeHistory, Web3Filter, Web3Log, Web3Receipt, Web3SyncStatus, Web3Transaction,
+    Web3TransactionStatus, Web3ValidatorLiveness,
 };
 use crate::jsonrpc::ws_subscription::{ws_subscription_module, HexIdProvider};
 use crate::APIError;
@@ -28,10 +30,30 @@ pub trait AxonWeb3Rpc {
     #[method(name = "eth_sendRawTransaction")]
     async fn send_raw_transaction(&self, tx: Hex) -> RpcResult<H256>;
 
+    /// Sends signed transaction to the validators only without gossiping it,
+    /// returning its hash. The transaction is dropped if it is not committed
+    /// in a few blocks.
+    #[method(name = "eth_sendPrivateRawTransaction")]
+    async fn send_private_raw_transaction(&self, tx: Hex) -> RpcResult<H256>;
+
     /// Get transaction by its hash.
     #[method(name = "eth_getTransactionByHash")]
     async fn get_transaction_by_hash(&self, hash: H256) -> RpcResult<Option<Web3Transaction>>;
 
+    /// Returns whether the transaction is committed, pending in the mempool or
+    /// dropped from it, with the drop reason for the latter.
+    #[method(name = "axon_getTransactionStatus")]
+    async fn get_transaction_status(&self, hash: H256) -> RpcResult<Web3TransactionStatus>;
+
+    /// Returns the double-sign evidences of the validators at the given
+    /// height. The `raw` field is the RLP encoded evidence which a slashing
+    /// transaction carries.
+    #[method(name = "axon_getDoubleSignEvidences")]
+    async fn get_double_sign_evidences(
+        &self,
+        number: U64,
+    ) -> RpcResult<Vec<Web3DoubleSignEvidence>>;
+
     /// Returns block with given number.
     #[method(name = "eth_getBlockByNumber")]
     async fn get_block_by_number(
@@ -198,13 +220,85 @@ pub trait AxonNodeRpc {
 
     #[method(name = "pprof")]
     fn pprof(&self, enable: bool) -> RpcResult<bool>;
+
+    /// Returns the signed, missed and proposed blocks of the validators in
+    /// the recent blocks.
+    #[method(name = "axon_getValidatorLiveness")]
+    fn validator_liveness(&self) -> RpcResult<Vec<Web3ValidatorLiveness>>;
+}
+
+/// The peer store operations for the operators, which are served on the admin
+/// listening address only. A target is a peer id, a multiaddr, an IP address
+/// or an IP network in CIDR notation.
+#[rpc(server, client)]
+pub trait AxonAdminRpc {
+    /// Returns the saved addresses and the banned networks of the peer store.
+    #[method(name = "admin_peers")]
+    fn peers(&self) -> RpcResult<PeerStoreSnapshot>;
+
+    /// Bans the networks of the target for the seconds given and disconnects
+    /// the banned peers, returning the count of the banned networks.
+    #[method(name = "admin_banPeer")]
+    fn ban_peer(&self, target: String, duration: u64, reason: String) -> RpcResult<usize>;
+
+    /// Lifts the bans inside the target, returning the count of them.
+    #[method(name = "admin_unbanPeer")]
+    fn unban_peer(&self, target: String) -> RpcResult<usize>;
+
+    /// Removes the saved addresses of the target, returning the count of them.
+    #[method(name = "admin_forgetPeer")]
+    fn forget_peer(&self, target: String) -> RpcResult<usize>;
+
+    /// Saves the multiaddrs with peer id, returning the count of the saved
+    /// ones.
+    #[method(name = "admin_importPeers")]
+    fn import_peers(&self, addrs: Vec<String>) -> RpcResult<usize>;
+
+    /// Replaces the allowlist by the peer ids. In the allowlist only mode, the
+    /// connected peers out of it and the consensus list are disconnected,
+    /// returning the count of them.
+    #[method(name = "admin_setAllowlist")]
+    fn set_allowlist(&self, peer_ids: Vec<String>) -> RpcResult<usize>;
+}
+
+/// The client of the admin RPC of a running node.
+pub fn admin_rpc_client(addr: SocketAddr) -> ProtocolResult<HttpClient> {
+    let client = HttpClientBuilder::default()
+        .build(format!("http://{}", addr))
+        .map_err(|e| APIError::AdminClient(e.to_string()))?;
+    Ok(client)
 }
 
 pub async fn run_jsonrpc_server<Adapter: APIAdapter + 'static>(
     config: Config,
     adapter: Arc<Adapter>,
-) -> ProtocolResult<(Option<ServerHandle>, Option<ServerHandle>)> {
-    let mut ret = (None, None);
+    peer_store: Arc<dyn PeerStoreAdmin>,
+) -> ProtocolResult<(
+    Option<ServerHandle>,
+    Option<ServerHandle>,
+    Option<ServerHandle>,
+)> {
+    let mut ret = (None, None, None);
+
+    if let Some(addr) = config.rpc.admin_listening_address {
+        // The admin RPC has no authentication
+        if !addr.ip().is_loopback() {
+            return Err(APIError::AdminAddress(addr).into());
+        }
+
+        let server = ServerBuilder::new()
+            .http_only()
+            .max_connections(config.rpc.maxconn)
+            .build(addr)
+            .await
+            .map_err(|e| APIError::HttpServer(e.to_string()))?;
+
+        ret.2 = Some(
+            server
+                .start(r#impl::AdminRpcImpl::new(peer_store).into_rpc())
+                .map_err(|e| APIError::HttpServer(e.to_string()))?,
+        );
+    }
 
     let mut rpc = r#impl::Web3RpcImpl::new(Arc::clone(&adapter), config.rpc.gas_cap).into_rpc();
     let node_rpc =
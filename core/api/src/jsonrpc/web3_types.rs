@@ -3,11 +3,12 @@ use std::fmt;
 use serde::de::{Error, MapAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use core_consensus::SyncStatus as InnerSyncStatus;
+use core_consensus::{SyncStatus as InnerSyncStatus, ValidatorLiveness};
 use protocol::codec::ProtocolCodec;
 use protocol::types::{
-    AccessList, Block, Bloom, Bytes, Hash, Header, Hex, Public, Receipt, SignedTransaction, H160,
-    H256, H64, MAX_PRIORITY_FEE_PER_GAS, U256, U64,
+    AccessList, Block, Bloom, Bytes, DoubleSignEvidence, DroppedTx, EvidenceKind, Hash, Header,
+    Hex, Public, Receipt, SignedMessage, SignedTransaction, TxDropReason, H160, H256, H64,
+    MAX_PRIORITY_FEE_PER_GAS, U256, U64,
 };
 
 pub const EMPTY_UNCLE_HASH: H256 = H256([
@@ -752,6 +753,114 @@ pub struct RawLoggerFilter {
     pub topics:     Option<Vec<MultiNestType<Hash>>>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionStatusKind {
+    Committed,
+    Pending,
+    Dropped,
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Web3TransactionStatus {
+    pub hash:        Hash,
+    pub status:      TransactionStatusKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drop_reason: Option<TxDropReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dropped_at:  Option<U64>,
+}
+
+impl Web3TransactionStatus {
+    pub fn new(hash: Hash, status: TransactionStatusKind) -> Self {
+        Web3TransactionStatus {
+            hash,
+            status,
+            drop_reason: None,
+            dropped_at: None,
+        }
+    }
+}
+
+impl From<DroppedTx> for Web3TransactionStatus {
+    fn from(dropped: DroppedTx) -> Self {
+        Web3TransactionStatus {
+            hash:        dropped.hash,
+            status:      TransactionStatusKind::Dropped,
+            drop_reason: Some(dropped.reason),
+            dropped_at:  Some(dropped.dropped_at.into()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Web3SignedMessage {
+    pub message:   Hex,
+    pub signature: Hex,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Web3DoubleSignEvidence {
+    pub hash:   Hash,
+    pub kind:   EvidenceKind,
+    pub height: U64,
+    pub round:  U64,
+    pub signer: Hex,
+    pub first:  Web3SignedMessage,
+    pub second: Web3SignedMessage,
+    pub raw:    Hex,
+}
+
+impl From<DoubleSignEvidence> for Web3DoubleSignEvidence {
+    fn from(evidence: DoubleSignEvidence) -> Self {
+        let message = |signed: &SignedMessage| Web3SignedMessage {
+            message:   Hex::encode(&signed.message),
+            signature: Hex::encode(&signed.signature),
+        };
+
+        Web3DoubleSignEvidence {
+            hash:   evidence.hash(),
+            kind:   evidence.kind,
+            height: evidence.height.into(),
+            round:  evidence.round.into(),
+            signer: Hex::encode(&evidence.signer),
+            first:  message(&evidence.first),
+            second: message(&evidence.second),
+            raw:    Hex::encode(evidence.encode().unwrap()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Web3ValidatorLiveness {
+    pub address:            H160,
+    pub blocks:             U64,
+    pub missed_blocks:      U64,
+    pub proposed_blocks:    U64,
+    pub expected_proposals: U64,
+    pub view_changes:       U64,
+    pub last_signed_block:  Option<U64>,
+}
+
+impl From<ValidatorLiveness> for Web3ValidatorLiveness {
+    fn from(liveness: ValidatorLiveness) -> Self {
+        Web3ValidatorLiveness {
+            address:            liveness.address,
+            blocks:             liveness.blocks.into(),
+            missed_blocks:      liveness.missed_blocks.into(),
+            proposed_blocks:    liveness.proposed_blocks.into(),
+            expected_proposals: liveness.expected_proposals.into(),
+            view_changes:       liveness.view_changes.into(),
+            last_signed_block:  liveness.last_signed_block.map(Into::into),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -773,4 +882,49 @@ mod tests {
         let json = json::parse(&serde_json::to_string(&status).unwrap()).unwrap();
         assert!(json.is_object());
     }
+
+    #[test]
+    fn test_transaction_status_json() {
+        let status = Web3TransactionStatus::new(H256::random(), TransactionStatusKind::Pending);
+        let json = json::parse(&serde_json::to_string(&status).unwrap()).unwrap();
+        assert_eq!(json["status"], "pending");
+        assert!(json["dropReason"].is_null());
+
+        let status = Web3TransactionStatus::from(DroppedTx {
+            hash:       H256::random(),
+            reason:     TxDropReason::NonceTooLow,
+            dropped_at: 1,
+        });
+        let json = json::parse(&serde_json::to_string(&status).unwrap()).unwrap();
+        assert_eq!(json["status"], "dropped");
+        assert_eq!(json["dropReason"], "nonceTooLow");
+    }
+
+    #[test]
+    fn test_double_sign_evidence_json() {
+        let message = |message: &'static [u8]| SignedMessage {
+            message:   Bytes::from_static(message),
+            signature: Bytes::from_static(b"signature"),
+        };
+        let evidence = DoubleSignEvidence::new(
+            EvidenceKind::Precommit,
+            16,
+            2,
+            Bytes::from_static(b"signer"),
+            message(b"first"),
+            message(b"second"),
+        );
+
+        let web3_evidence = Web3DoubleSignEvidence::from(evidence.clone());
+        let json = json::parse(&serde_json::to_string(&web3_evidence).unwrap()).unwrap();
+        assert_eq!(json["kind"], "Precommit");
+        assert_eq!(json["height"], "0x10");
+        assert_eq!(json["round"], "0x2");
+
+        let raw = Hex::from_string(json["raw"].as_str().unwrap().to_string()).unwrap();
+        assert_eq!(
+            DoubleSignEvidence::decode(raw.as_bytes()).unwrap(),
+            evidence
+        );
+    }
 }
@@ -6,7 +6,7 @@ pub mod jsonrpc;
 
 pub use adapter::DefaultAPIAdapter;
 
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 
 use protocol::{Display, ProtocolError, ProtocolErrorKind};
 
@@ -21,6 +21,12 @@ pub enum APIError {
     #[display(fmt = "web socket server error {:?}", _0)]
     WebSocketServer(String),
 
+    #[display(fmt = "admin rpc client error {:?}", _0)]
+    AdminClient(String),
+
+    #[display(fmt = "admin rpc listening address {} is not a loopback one", _0)]
+    AdminAddress(SocketAddr),
+
     #[display(fmt = "storage error {:?}", _0)]
     Storage(String),
 
@@ -1,9 +1,9 @@
-use std::path::Path;
+use std::{fs, path::Path};
 
 use clap::{crate_version, Arg, ArgMatches, Command};
 
 use common_config_parser::{parse_file, types::Config};
-use core_run::Axon;
+use core_run::{Axon, PeersCommand};
 use protocol::types::RichBlock;
 
 pub struct AxonCli {
@@ -31,6 +31,166 @@ impl AxonCli {
                     .num_args(1),
             )
             .subcommand(Command::new("run").about("Run axon process"))
+            .subcommand(
+                Command::new("export")
+                    .about("Export the chain to a checksummed archive")
+                    .arg(
+                        Arg::new("height")
+                            .long("height")
+                            .help("The last block to export")
+                            .required(true)
+                            .num_args(1)
+                            .value_parser(clap::value_parser!(u64)),
+                    )
+                    .arg(
+                        Arg::new("path")
+                            .short('p')
+                            .long("path")
+                            .help("Archive path")
+                            .required(true)
+                            .num_args(1),
+                    ),
+            )
+            .subcommand(
+                Command::new("import")
+                    .about("Import the chain from an archive into an empty data path")
+                    .arg(
+                        Arg::new("path")
+                            .short('p')
+                            .long("path")
+                            .help("Archive path")
+                            .required(true)
+                            .num_args(1),
+                    ),
+            )
+            .subcommand(
+                Command::new("rollback")
+                    .about("Roll back the chain to the given block")
+                    .arg(
+                        Arg::new("to")
+                            .long("to")
+                            .help("The block to roll back to")
+                            .required(true)
+                            .num_args(1)
+                            .value_parser(clap::value_parser!(u64)),
+                    ),
+            )
+            .subcommand(
+                Command::new("wal")
+                    .about("Inspect, repair or compact the WALs offline")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("inspect")
+                            .about("List the stored heights and transaction counts"),
+                    )
+                    .subcommand(
+                        Command::new("repair")
+                            .about("Cut the corrupt tails and remove the invalid files"),
+                    )
+                    .subcommand(
+                        Command::new("compact")
+                            .about("Remove the committed heights and rewrite the old files"),
+                    ),
+            )
+            .subcommand(
+                Command::new("blocks")
+                    .about("Export blocks or replay them on the local chain")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("export")
+                            .about("Export blocks as a length-prefixed RLP stream")
+                            .arg(
+                                Arg::new("from")
+                                    .long("from")
+                                    .help("The first block to export")
+                                    .required(true)
+                                    .num_args(1)
+                                    .value_parser(clap::value_parser!(u64)),
+                            )
+                            .arg(
+                                Arg::new("to")
+                                    .long("to")
+                                    .help("The last block to export")
+                                    .required(true)
+                                    .num_args(1)
+                                    .value_parser(clap::value_parser!(u64)),
+                            )
+                            .arg(
+                                Arg::new("path")
+                                    .short('p')
+                                    .long("path")
+                                    .help("Block stream path")
+                                    .required(true)
+                                    .num_args(1),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("import")
+                            .about("Re-execute the blocks of a stream and compare the roots")
+                            .arg(
+                                Arg::new("path")
+                                    .short('p')
+                                    .long("path")
+                                    .help("Block stream path")
+                                    .required(true)
+                                    .num_args(1),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("peers")
+                    .about(
+                        "List or change the peer store, through the admin RPC if the node is \
+                         running",
+                    )
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("list")
+                            .about("List the saved addresses and the banned networks"),
+                    )
+                    .subcommand(
+                        Command::new("ban")
+                            .about("Ban a peer id, a multiaddr, an IP address or an IP network")
+                            .arg(Arg::new("target").required(true).num_args(1))
+                            .arg(
+                                Arg::new("duration")
+                                    .long("duration")
+                                    .help("The seconds to ban")
+                                    .default_value("86400")
+                                    .num_args(1)
+                                    .value_parser(clap::value_parser!(u64)),
+                            )
+                            .arg(
+                                Arg::new("reason")
+                                    .long("reason")
+                                    .help("The ban reason")
+                                    .default_value("banned by operator")
+                                    .num_args(1),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("unban")
+                            .about(
+                                "Lift the bans inside an IP network, an IP address or a multiaddr",
+                            )
+                            .arg(Arg::new("target").required(true).num_args(1)),
+                    )
+                    .subcommand(
+                        Command::new("forget")
+                            .about("Remove the saved addresses of a target")
+                            .arg(Arg::new("target").required(true).num_args(1)),
+                    )
+                    .subcommand(
+                        Command::new("import")
+                            .about("Save the multiaddrs with peer id, or the ones listed in files")
+                            .arg(Arg::new("addrs").required(true).num_args(1..)),
+                    )
+                    .subcommand(
+                        Command::new("allowlist")
+                            .about("Replace the allowlist of the running node by the peer ids")
+                            .arg(Arg::new("peer_ids").num_args(0..)),
+                    ),
+            )
             .get_matches();
 
         AxonCli { matches }
@@ -52,8 +212,98 @@ impl AxonCli {
 
         register_log(&config);
 
-        Axon::new(config, genesis).run().unwrap();
+        let axon = Axon::new(config, genesis);
+        match self.matches.subcommand() {
+            Some(("export", matches)) => axon
+                .export(
+                    *matches.get_one::<u64>("height").unwrap(),
+                    Path::new(matches.get_one::<String>("path").unwrap()),
+                )
+                .unwrap(),
+            Some(("import", matches)) => axon
+                .import(Path::new(matches.get_one::<String>("path").unwrap()))
+                .unwrap(),
+            Some(("rollback", matches)) => axon
+                .rollback(*matches.get_one::<u64>("to").unwrap())
+                .unwrap(),
+            Some(("wal", matches)) => match matches.subcommand() {
+                Some(("inspect", _)) => axon.inspect_wal().unwrap(),
+                Some(("repair", _)) => axon.repair_wal().unwrap(),
+                Some(("compact", _)) => axon.compact_wal().unwrap(),
+                _ => unreachable!(),
+            },
+            Some(("blocks", matches)) => match matches.subcommand() {
+                Some(("export", matches)) => axon
+                    .export_blocks(
+                        *matches.get_one::<u64>("from").unwrap(),
+                        *matches.get_one::<u64>("to").unwrap(),
+                        Path::new(matches.get_one::<String>("path").unwrap()),
+                    )
+                    .unwrap(),
+                Some(("import", matches)) => axon
+                    .import_blocks(Path::new(matches.get_one::<String>("path").unwrap()))
+                    .unwrap(),
+                _ => unreachable!(),
+            },
+            Some(("peers", matches)) => {
+                let cmd = match matches.subcommand() {
+                    Some(("list", _)) => PeersCommand::List,
+                    Some(("ban", matches)) => PeersCommand::Ban {
+                        target:   matches.get_one::<String>("target").unwrap().clone(),
+                        duration: *matches.get_one::<u64>("duration").unwrap(),
+                        reason:   matches.get_one::<String>("reason").unwrap().clone(),
+                    },
+                    Some(("unban", matches)) => {
+                        PeersCommand::Unban(matches.get_one::<String>("target").unwrap().clone())
+                    }
+                    Some(("forget", matches)) => {
+                        PeersCommand::Forget(matches.get_one::<String>("target").unwrap().clone())
+                    }
+                    Some(("import", matches)) => {
+                        match read_addrs(matches.get_many::<String>("addrs").unwrap()) {
+                            Ok(addrs) => PeersCommand::Import(addrs),
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                    Some(("allowlist", matches)) => PeersCommand::Allowlist(
+                        matches
+                            .get_many::<String>("peer_ids")
+                            .map(|ids| ids.cloned().collect())
+                            .unwrap_or_default(),
+                    ),
+                    _ => unreachable!(),
+                };
+                axon.peers(cmd).unwrap()
+            }
+            _ => axon.run().unwrap(),
+        }
+    }
+}
+
+/// The arguments starting with `/` are multiaddrs, the others are files with
+/// a multiaddr in each line.
+fn read_addrs<'a>(args: impl Iterator<Item = &'a String>) -> Result<Vec<String>, String> {
+    let mut addrs = Vec::new();
+    for arg in args {
+        if arg.starts_with('/') {
+            addrs.push(arg.clone());
+            continue;
+        }
+
+        let content = fs::read_to_string(arg)
+            .map_err(|e| format!("Read the addresses from {} failed: {}", arg, e))?;
+        addrs.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(ToOwned::to_owned),
+        );
     }
+    Ok(addrs)
 }
 
 fn register_log(config: &Config) {
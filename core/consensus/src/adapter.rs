@@ -7,23 +7,26 @@ use parking_lot::RwLock;
 
 use common_apm::Instant;
 use common_apm_derive::trace_span;
-use core_executor::{AxonExecutor, AxonExecutorAdapter};
+use core_executor::{system_contract, AxonExecutor, AxonExecutorAdapter};
 use core_network::{PeerId, PeerIdExt};
 use protocol::traits::{
     CommonConsensusAdapter, ConsensusAdapter, Context, Executor, Gossip, MemPool, MessageTarget,
     MetadataControl, Network, PeerTrust, Priority, Rpc, Storage, SynchronizationAdapter,
+    TrustFeedback,
 };
 use protocol::types::{
-    BatchSignedTxs, Block, BlockNumber, Bytes, ExecResp, Hash, Header, Hex, MerkleRoot, Metadata,
-    PackedTxHashes, Proof, Proposal, Receipt, SignedTransaction, Validator, U256,
+    BatchSignedTxs, Block, BlockNumber, Bytes, DoubleSignEvidence, ExecResp, Hash, Header, Hex,
+    MerkleRoot, Metadata, PackedTxHashes, Proof, Proposal, Receipt, SignedTransaction, TrieKind,
+    Validator, H160, U256,
 };
 use protocol::{async_trait, tokio::task, trie, ProtocolResult};
 
 use crate::consensus::gen_overlord_status;
 use crate::message::{
-    BROADCAST_HEIGHT, RPC_SYNC_PULL_BLOCK, RPC_SYNC_PULL_PROOF, RPC_SYNC_PULL_TXS,
+    BROADCAST_HEIGHT, END_GOSSIP_SIGNED_PROPOSAL, END_GOSSIP_SIGNED_VOTE, RPC_SYNC_PULL_BLOCK,
+    RPC_SYNC_PULL_PROOF, RPC_SYNC_PULL_TRIE_NODES, RPC_SYNC_PULL_TXS,
 };
-use crate::types::PullTxsRequest;
+use crate::types::{PullTrieNodesRequest, PullTrieNodesResponse, PullTxsRequest};
 use crate::util::{convert_hex_to_bls_pubkeys, OverlordCrypto};
 use crate::BlockHeaderField::PreviousBlockHash;
 use crate::BlockProofField::{BitMap, HashMismatch, HeightMismatch, Signature, WeightNotFound};
@@ -83,14 +86,21 @@ where
         end: &str,
         target: MessageTarget,
     ) -> ProtocolResult<()> {
+        // The proposals and votes go through the direct mesh between the
+        // validators
+        let priority = match end {
+            END_GOSSIP_SIGNED_PROPOSAL | END_GOSSIP_SIGNED_VOTE => Priority::Consensus,
+            _ => Priority::High,
+        };
+
         match target {
-            MessageTarget::Broadcast => self.network.broadcast(ctx, end, msg, Priority::High).await,
+            MessageTarget::Broadcast => self.network.broadcast(ctx, end, msg, priority).await,
 
             MessageTarget::Specified(pub_key) => {
                 let peer_id_bytes = PeerId::from_pubkey_bytes(pub_key)?.into_bytes_ext();
 
                 self.network
-                    .multicast(ctx, end, [peer_id_bytes], msg, Priority::High)
+                    .multicast(ctx, end, [peer_id_bytes], msg, priority)
                     .await
             }
         }
@@ -122,6 +132,33 @@ where
 
         Ok(())
     }
+
+    #[trace_span(kind = "consensus.adapter", logs = "{txs_len: txs.len()}")]
+    async fn relay_proposal_txs(
+        &self,
+        ctx: Context,
+        number: u64,
+        txs: Vec<SignedTransaction>,
+        pub_keys: Vec<Bytes>,
+    ) -> ProtocolResult<()> {
+        let peer_ids = pub_keys
+            .iter()
+            .map(|pk| PeerId::from_pubkey_bytes(pk).map(PeerIdExt::into_bytes_ext))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.mempool
+            .relay_proposal_txs(ctx, number, txs, peer_ids)
+            .await
+    }
+
+    #[trace_span(kind = "consensus.adapter")]
+    async fn save_evidence(
+        &self,
+        ctx: Context,
+        evidence: DoubleSignEvidence,
+    ) -> ProtocolResult<()> {
+        self.storage.insert_evidence(ctx, evidence).await
+    }
 }
 
 #[async_trait]
@@ -170,7 +207,7 @@ where
     async fn get_block_from_remote(&self, ctx: Context, number: u64) -> ProtocolResult<Block> {
         let res = self
             .network
-            .call::<BlockNumber, Block>(ctx, RPC_SYNC_PULL_BLOCK, number, Priority::High)
+            .call_trusted::<BlockNumber, Block>(ctx, RPC_SYNC_PULL_BLOCK, number, Priority::High)
             .await;
         match res {
             Ok(data) => {
@@ -201,7 +238,7 @@ where
     ) -> ProtocolResult<Vec<SignedTransaction>> {
         let res = self
             .network
-            .call::<PullTxsRequest, BatchSignedTxs>(
+            .call_trusted::<PullTxsRequest, BatchSignedTxs>(
                 ctx,
                 RPC_SYNC_PULL_TXS,
                 PullTxsRequest::new(number, hashes.to_vec()),
@@ -216,7 +253,7 @@ where
     async fn get_proof_from_remote(&self, ctx: Context, number: u64) -> ProtocolResult<Proof> {
         let ret = self
             .network
-            .call::<BlockNumber, Proof>(ctx, RPC_SYNC_PULL_PROOF, number, Priority::High)
+            .call_trusted::<BlockNumber, Proof>(ctx, RPC_SYNC_PULL_PROOF, number, Priority::High)
             .await?;
         Ok(ret)
     }
@@ -224,6 +261,76 @@ where
     fn get_tx_from_mem(&self, ctx: Context, tx_hash: &Hash) -> Option<SignedTransaction> {
         self.mempool.get_tx_from_mem(ctx, tx_hash)
     }
+
+    fn report_bad_peer(&self, ctx: Context, reason: String) {
+        self.network.report(ctx, TrustFeedback::Worse(reason));
+    }
+
+    fn peer_trust_score(&self, ctx: &Context) -> Option<u8> {
+        self.network.trust_score(ctx)
+    }
+
+    #[trace_span(kind = "consensus.adapter", logs = "{hashes_len: hashes.len()}")]
+    async fn get_trie_nodes_from_remote(
+        &self,
+        ctx: Context,
+        kind: TrieKind,
+        hashes: &[Hash],
+    ) -> ProtocolResult<Vec<Bytes>> {
+        let res = self
+            .network
+            .call_trusted::<PullTrieNodesRequest, PullTrieNodesResponse>(
+                ctx,
+                RPC_SYNC_PULL_TRIE_NODES,
+                PullTrieNodesRequest::new(kind, hashes.to_vec()),
+                Priority::High,
+            )
+            .await?;
+        Ok(res.nodes)
+    }
+
+    async fn save_trie_nodes(
+        &self,
+        _ctx: Context,
+        kind: TrieKind,
+        nodes: Vec<(Hash, Bytes)>,
+    ) -> ProtocolResult<()> {
+        match kind {
+            TrieKind::State => {
+                let (keys, values): (Vec<_>, Vec<_>) = nodes
+                    .into_iter()
+                    .map(|(hash, node)| (hash.as_bytes().to_vec(), node.to_vec()))
+                    .unzip();
+                self.trie_db
+                    .insert_batch(keys, values)
+                    .map_err(|e| ConsensusError::Other(e.to_string()))?;
+                Ok(())
+            }
+            TrieKind::Metadata | TrieKind::HeaderCell => {
+                system_contract::insert_trie_nodes(kind, nodes)
+            }
+            TrieKind::Code => Err(ConsensusError::Other(
+                "contract codes should be saved with their addresses".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    async fn save_code(
+        &self,
+        ctx: Context,
+        address: H160,
+        code_hash: Hash,
+        code: Bytes,
+    ) -> ProtocolResult<()> {
+        self.storage
+            .insert_code(ctx, address.into(), code_hash, code)
+            .await
+    }
+
+    async fn save_snapshot_number(&self, ctx: Context, number: u64) -> ProtocolResult<()> {
+        self.storage.update_snapshot_number(ctx, number).await
+    }
 }
 
 #[async_trait]
@@ -604,6 +711,30 @@ where
         }
         Ok(())
     }
+
+    fn get_system_contract_roots(
+        &self,
+        _ctx: Context,
+        state_root: MerkleRoot,
+    ) -> ProtocolResult<(Hash, Hash)> {
+        let backend = AxonExecutorAdapter::from_root(
+            state_root,
+            Arc::clone(&self.trie_db),
+            Arc::clone(&self.storage),
+            Default::default(),
+        )?;
+
+        Ok(system_contract::system_contract_roots(&backend))
+    }
+
+    fn update_system_contract_roots(
+        &self,
+        _ctx: Context,
+        metadata_root: Hash,
+        header_cell_root: Hash,
+    ) {
+        system_contract::update_system_contract_roots(metadata_root, header_cell_root)
+    }
 }
 
 impl<M, N, S, MT, DB> OverlordConsensusAdapter<M, N, S, MT, DB>
@@ -8,25 +8,34 @@ use overlord::{DurationConfig, Overlord, OverlordHandler};
 use protocol::traits::{Consensus, ConsensusAdapter, Context, NodeInfo};
 use protocol::types::{Proposal, Validator, H160};
 use protocol::{
-    async_trait, codec::ProtocolCodec, tokio::sync::Mutex as AsyncMutex, ProtocolResult,
+    async_trait,
+    codec::{hex_encode, ProtocolCodec},
+    tokio::{self, sync::mpsc, sync::Mutex as AsyncMutex},
+    ProtocolResult,
 };
 
 use common_apm::tracing::{AxonTracer, Tag};
 use common_apm_derive::trace_span;
 
+use crate::evidence::{ConsensusMessage, EvidenceCollector};
 use crate::wal::{ConsensusWal, SignedTxsWAL};
 use crate::{
     engine::ConsensusEngine, status::StatusAgent, util::OverlordCrypto, ConsensusError,
     ConsensusType,
 };
 
+/// The max number of the messages waiting for the double sign check, the
+/// messages beyond are not checked.
+const EVIDENCE_QUEUE_SIZE: usize = 1024;
+
 /// Provide consensus
 pub struct OverlordConsensus<Adapter: ConsensusAdapter + 'static> {
     /// Overlord consensus protocol instance.
     inner:
         Arc<Overlord<Proposal, ConsensusEngine<Adapter>, OverlordCrypto, ConsensusEngine<Adapter>>>,
     /// An overlord consensus protocol handler.
-    handler: OverlordHandler<Proposal>,
+    handler:  OverlordHandler<Proposal>,
+    evidence: mpsc::Sender<(Context, ConsensusMessage)>,
 }
 
 #[async_trait]
@@ -35,6 +44,7 @@ impl<Adapter: ConsensusAdapter + 'static> Consensus for OverlordConsensus<Adapte
     async fn set_proposal(&self, ctx: Context, proposal: Vec<u8>) -> ProtocolResult<()> {
         let signed_proposal = SignedProposal::<Proposal>::decode(proposal)
             .map_err(|_| ConsensusError::DecodeErr(ConsensusType::SignedProposal))?;
+        self.check_double_sign(ctx.clone(), (&signed_proposal).into());
 
         let msg = OverlordMsg::SignedProposal(signed_proposal);
         tracing_overlord_message(ctx.clone(), &msg);
@@ -60,6 +70,7 @@ impl<Adapter: ConsensusAdapter + 'static> Consensus for OverlordConsensus<Adapte
 
         let signed_vote = SignedVote::decode(vote)
             .map_err(|_| ConsensusError::DecodeErr(ConsensusType::SignedVote))?;
+        self.check_double_sign(ctx.clone(), (&signed_vote).into());
 
         let msg = OverlordMsg::SignedVote(signed_vote);
         tracing_overlord_message(ctx.clone(), &msg);
@@ -88,6 +99,7 @@ impl<Adapter: ConsensusAdapter + 'static> Consensus for OverlordConsensus<Adapte
     async fn set_choke(&self, ctx: Context, choke: Vec<u8>) -> ProtocolResult<()> {
         let signed_choke = SignedChoke::decode(choke)
             .map_err(|_| ConsensusError::DecodeErr(ConsensusType::SignedChoke))?;
+        self.check_double_sign(ctx.clone(), (&signed_choke).into());
 
         let msg = OverlordMsg::SignedChoke(signed_choke);
         tracing_overlord_message(ctx.clone(), &msg);
@@ -110,6 +122,13 @@ impl<Adapter: ConsensusAdapter + 'static> OverlordConsensus<Adapter> {
         lock: Arc<AsyncMutex<()>>,
         consensus_wal: Arc<ConsensusWal>,
     ) -> Self {
+        let (evidence, evidence_rx) = mpsc::channel(EVIDENCE_QUEUE_SIZE);
+        tokio::spawn(collect_evidence(
+            EvidenceCollector::new(Arc::clone(&crypto)),
+            status.clone(),
+            Arc::clone(&adapter),
+            evidence_rx,
+        ));
         let engine = Arc::new(ConsensusEngine::new(
             status,
             metadata_address,
@@ -144,8 +163,9 @@ impl<Adapter: ConsensusAdapter + 'static> OverlordConsensus<Adapter> {
         }
 
         Self {
-            inner:   Arc::new(overlord),
+            inner: Arc::new(overlord),
             handler: overlord_handler,
+            evidence,
         }
     }
 
@@ -153,6 +173,15 @@ impl<Adapter: ConsensusAdapter + 'static> OverlordConsensus<Adapter> {
         self.handler.clone()
     }
 
+    /// Queue the signed message from the other validators for the double
+    /// sign check. The signatures are verified in the background, so a
+    /// failure or a full queue never delays the message from being handled.
+    fn check_double_sign(&self, ctx: Context, msg: ConsensusMessage) {
+        if self.evidence.try_send((ctx, msg)).is_err() {
+            log::debug!("[consensus] evidence queue is full, skip double sign check");
+        }
+    }
+
     pub async fn run(
         &self,
         init_height: u64,
@@ -169,6 +198,35 @@ impl<Adapter: ConsensusAdapter + 'static> OverlordConsensus<Adapter> {
     }
 }
 
+/// Record the queued messages, and save the evidence if a signer has signed
+/// a different one at the same height and round.
+async fn collect_evidence<Adapter: ConsensusAdapter + 'static>(
+    collector: EvidenceCollector,
+    status: StatusAgent,
+    adapter: Arc<Adapter>,
+    mut messages: mpsc::Receiver<(Context, ConsensusMessage)>,
+) {
+    while let Some((ctx, msg)) = messages.recv().await {
+        let current_height = status.inner().last_number + 1;
+        let evidence = match collector.record(current_height, msg) {
+            Some(evidence) => evidence,
+            None => continue,
+        };
+
+        log::error!(
+            "[consensus] validator 0x{} double signs {:?} at height {} round {}",
+            hex_encode(&evidence.signer),
+            evidence.kind,
+            evidence.height,
+            evidence.round
+        );
+
+        if let Err(e) = adapter.save_evidence(ctx, evidence).await {
+            log::error!("[consensus] save double sign evidence error {:?}", e);
+        }
+    }
+}
+
 pub fn gen_overlord_status(
     height: u64,
     interval: u64,
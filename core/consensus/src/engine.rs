@@ -2,6 +2,7 @@ use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Duration;
 
 use json::JsonValue;
 use log::{error, info};
@@ -18,17 +19,18 @@ use common_logger::{json, log};
 use common_merkle::TrieMerkle;
 use protocol::traits::{ConsensusAdapter, Context, MessageTarget, NodeInfo};
 use protocol::types::{
-    Block, Bytes, ExecResp, Hash, Hasher, Hex, Log, MerkleRoot, Metadata, Proof, Proposal, Receipt,
-    SignedTransaction, TransactionAction, ValidatorExtend, BASE_FEE_PER_GAS, H160,
-    MAX_BLOCK_GAS_LIMIT, RLP_NULL, U256,
+    is_speculative_execution, Block, Bytes, ExecResp, Hash, Hasher, Hex, Log, MerkleRoot, Metadata,
+    Proof, Proposal, Receipt, SignedTransaction, TransactionAction, ValidatorExtend,
+    BASE_FEE_PER_GAS, H160, MAX_BLOCK_GAS_LIMIT, RLP_NULL, U256,
 };
 use protocol::{
-    async_trait, lazy::CURRENT_STATE_ROOT, tokio::sync::Mutex as AsyncMutex, ProtocolError,
-    ProtocolResult,
+    async_trait, lazy::CURRENT_STATE_ROOT, lazy::SYSTEM_CONTRACT_ROOTS_LOCK, tokio,
+    tokio::sync::Mutex as AsyncMutex, ProtocolError, ProtocolResult,
 };
 
 use core_executor::logs_bloom;
 
+use crate::liveness::VALIDATOR_LIVENESS;
 use crate::message::{
     END_GOSSIP_AGGREGATED_VOTE, END_GOSSIP_SIGNED_CHOKE, END_GOSSIP_SIGNED_PROPOSAL,
     END_GOSSIP_SIGNED_VOTE,
@@ -45,6 +47,9 @@ pub struct ConsensusEngine<Adapter> {
     node_info:        NodeInfo,
     metadata_address: H160,
     exemption_hash:   RwLock<HashSet<Hash>>,
+    // The results of speculative execution by the proposal hash, with the
+    // state root which the proposal is executed on.
+    exec_cache:       Arc<RwLock<HashMap<Hash, (MerkleRoot, ExecResp)>>>,
 
     adapter: Arc<Adapter>,
     txs_wal: Arc<SignedTxsWAL>,
@@ -75,6 +80,8 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
             )
             .await?;
         let signed_txs = self.adapter.get_full_txs(ctx.clone(), &txs.hashes).await?;
+        self.relay_proposal_txs(ctx.clone(), next_number, signed_txs.clone());
+
         let txs_root = if !txs.hashes.is_empty() {
             TrieMerkle::from_iter(txs.hashes.iter().enumerate())
                 .root_hash()
@@ -83,7 +90,7 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
             RLP_NULL
         };
 
-        let proposal = Proposal {
+        let mut proposal = Proposal {
             prev_hash:                  status.prev_hash,
             proposer:                   self.node_info.self_address.0,
             transactions_root:          txs_root,
@@ -99,6 +106,8 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
             chain_id:                   self.node_info.chain_id,
             call_system_script_count:   txs.call_system_script_count,
             tx_hashes:                  txs.hashes,
+            state_root:                 None,
+            receipts_root:              None,
         };
 
         if proposal.number != proposal.proof.number + 1 {
@@ -109,7 +118,42 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
             .into());
         }
 
+        let resp = if is_speculative_execution(next_number) {
+            match self
+                .spawn_speculative_exec(ctx.clone(), status.last_state_root, &proposal, signed_txs)
+                .await?
+            {
+                Some(resp) => Some(resp),
+                None => {
+                    // The roots are part of the block hash from here, so an
+                    // empty block is proposed in place of the slow one.
+                    proposal = empty_proposal(proposal);
+                    let resp = Self::speculative_exec(
+                        self.adapter.as_ref(),
+                        ctx.clone(),
+                        status.last_state_root,
+                        &proposal,
+                        &[],
+                    )
+                    .await?;
+                    Some(resp)
+                }
+            }
+        } else {
+            None
+        };
+        if let Some(resp) = resp.as_ref() {
+            proposal.state_root = Some(resp.state_root);
+            proposal.receipts_root = Some(resp.receipt_root);
+        }
+
         let hash = proposal.hash();
+        if let Some(resp) = resp {
+            self.exec_cache
+                .write()
+                .insert(hash, (status.last_state_root, resp));
+        }
+
         let mut set = self.exemption_hash.write();
         set.insert(hash);
 
@@ -166,12 +210,19 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
         info!("[consensus-engine]: check block cost {:?}", time.elapsed());
 
         let time = Instant::now();
-        let txs = self.adapter.get_full_txs(ctx, &tx_hashes).await?;
+        let txs = self.adapter.get_full_txs(ctx.clone(), &tx_hashes).await?;
         common_apm::metrics::consensus::CONSENSUS_CHECK_BLOCK_HISTOGRAM_VEC_STATIC
             .get_txs_cost
             .observe(common_apm::metrics::duration_to_sec(time.elapsed()));
         info!("[consensus-engine]: get txs cost {:?}", time.elapsed());
 
+        let hash = Hash::from_slice(hash.as_ref());
+        if let Err(e) = self.check_post_state(ctx, hash, &proposal, &txs).await {
+            let mut reason = self.last_check_block_fail_reason.write();
+            *reason = e.to_string();
+            return Err(e.into());
+        }
+
         let time = Instant::now();
         self.txs_wal
             .save(next_number, proposal.transactions_root, txs)?;
@@ -256,16 +307,35 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
                 .load(current_number, proposal.transactions_root)?,
         };
 
-        // Execute transactions
-        let resp = self
-            .adapter
-            .exec(
-                ctx.clone(),
-                self.status.inner().last_state_root,
-                &proposal,
-                &signed_txs,
-            )
-            .await?;
+        // Execute transactions, unless the proposal has been executed
+        // speculatively on the same state. The system contracts are switched
+        // to the new roots from here, no speculative execution may run until
+        // the status is updated.
+        let roots_lock = SYSTEM_CONTRACT_ROOTS_LOCK.lock().await;
+        let last_state_root = self.status.inner().last_state_root;
+        let cached = self
+            .exec_cache
+            .write()
+            .remove(&proof.block_hash)
+            .filter(|(state_root, _)| *state_root == last_state_root);
+        let resp = match cached {
+            Some((_, resp)) => {
+                let (metadata_root, header_cell_root) = self
+                    .adapter
+                    .get_system_contract_roots(ctx.clone(), resp.state_root)?;
+                self.adapter.update_system_contract_roots(
+                    ctx.clone(),
+                    metadata_root,
+                    header_cell_root,
+                );
+                resp
+            }
+            None => {
+                self.adapter
+                    .exec(ctx.clone(), last_state_root, &proposal, &signed_txs)
+                    .await?
+            }
+        };
 
         info!(
             "[consensus]: validator of number {} is {:?}",
@@ -273,8 +343,26 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
             metadata.verifier_list
         );
 
-        self.update_status(ctx.clone(), resp, proposal.clone(), proof, signed_txs)
-            .await?;
+        let verifier_list = self
+            .adapter
+            .get_metadata_unchecked(ctx.clone(), current_number)
+            .verifier_list;
+        self.update_status(
+            ctx.clone(),
+            resp,
+            proposal.clone(),
+            proof.clone(),
+            signed_txs,
+        )
+        .await?;
+        drop(roots_lock);
+
+        if let Err(e) = VALIDATOR_LIVENESS
+            .write()
+            .record(proposal.proposer, &proof, &verifier_list)
+        {
+            log::warn!("[consensus] record validator liveness error {:?}", e);
+        }
 
         self.adapter
             .flush_mempool(ctx.clone(), &proposal.tx_hashes, current_number)
@@ -284,6 +372,7 @@ impl<Adapter: ConsensusAdapter + 'static> Engine<Proposal> for ConsensusEngine<A
 
         {
             self.exemption_hash.write().clear();
+            self.exec_cache.write().clear();
         }
 
         let next_block_number = current_number + 1;
@@ -461,6 +550,7 @@ impl<Adapter: ConsensusAdapter + 'static> ConsensusEngine<Adapter> {
             metadata_address,
             node_info,
             exemption_hash: RwLock::new(HashSet::new()),
+            exec_cache: Arc::new(RwLock::new(HashMap::new())),
             txs_wal: wal,
             adapter,
             crypto,
@@ -475,12 +565,6 @@ impl<Adapter: ConsensusAdapter + 'static> ConsensusEngine<Adapter> {
         self.status.inner()
     }
 
-    fn contains_change_metadata(&self, txs: &[SignedTransaction]) -> bool {
-        let action = TransactionAction::Call(self.metadata_address);
-        txs.iter()
-            .any(|tx| tx.transaction.unsigned.action() == &action)
-    }
-
     async fn inner_check_block(&self, ctx: Context, proposal: &Proposal) -> ProtocolResult<()> {
         let current_timestamp = time_now();
 
@@ -543,6 +627,130 @@ impl<Adapter: ConsensusAdapter + 'static> ConsensusEngine<Adapter> {
         self.check_order_transactions(ctx.clone(), proposal, &signed_txs)
     }
 
+    /// In speculative execution mode, re-execute the proposal and check the
+    /// post-execution roots which the proposer commits to. The result is
+    /// cached for the commit.
+    async fn check_post_state(
+        &self,
+        ctx: Context,
+        hash: Hash,
+        proposal: &Proposal,
+        signed_txs: &[SignedTransaction],
+    ) -> ProtocolResult<()> {
+        let (state_root, receipts_root) = match post_state_roots(proposal)? {
+            Some(roots) => roots,
+            None => return Ok(()),
+        };
+
+        if self.exec_cache.read().contains_key(&hash) {
+            return Ok(());
+        }
+
+        let last_state_root = self.status.inner().last_state_root;
+        let resp = Self::speculative_exec(
+            self.adapter.as_ref(),
+            ctx,
+            last_state_root,
+            proposal,
+            signed_txs,
+        )
+        .await?;
+
+        if resp.state_root != state_root {
+            return Err(ConsensusError::InvalidStateRoot {
+                expect: state_root,
+                actual: resp.state_root,
+            }
+            .into());
+        }
+
+        if resp.receipt_root != receipts_root {
+            return Err(ConsensusError::InvalidReceiptsRoot {
+                expect: receipts_root,
+                actual: resp.receipt_root,
+            }
+            .into());
+        }
+
+        self.exec_cache
+            .write()
+            .insert(hash, (last_state_root, resp));
+        Ok(())
+    }
+
+    /// Execute the proposal of self in a background task. It waits for half
+    /// of the propose timeout at most, so a slow execution does not delay the
+    /// proposal. An empty block is proposed in that case, and the result is
+    /// still cached by the hash of the full one.
+    async fn spawn_speculative_exec(
+        &self,
+        ctx: Context,
+        last_state_root: MerkleRoot,
+        proposal: &Proposal,
+        signed_txs: Vec<SignedTransaction>,
+    ) -> ProtocolResult<Option<ExecResp>> {
+        let metadata = self
+            .adapter
+            .get_metadata_unchecked(ctx.clone(), proposal.number);
+        let timeout = Duration::from_millis(metadata.interval * metadata.propose_ratio / 20);
+
+        let adapter = Arc::clone(&self.adapter);
+        let exec_cache = Arc::clone(&self.exec_cache);
+        let number = proposal.number;
+        let proposal = proposal.clone();
+        let handle = tokio::spawn(async move {
+            let resp = Self::speculative_exec(
+                adapter.as_ref(),
+                ctx,
+                last_state_root,
+                &proposal,
+                &signed_txs,
+            )
+            .await?;
+            exec_cache
+                .write()
+                .insert(proposal.hash(), (last_state_root, resp.clone()));
+            Ok::<_, ProtocolError>(resp)
+        });
+
+        match tokio::time::timeout(timeout, handle).await {
+            Ok(Ok(resp)) => resp.map(Some),
+            Ok(Err(e)) => Err(ConsensusError::Other(e.to_string()).into()),
+            Err(_) => {
+                log::warn!(
+                    "[consensus] speculative execution of block {} is not done in {:?}",
+                    number,
+                    timeout
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Execute a proposal before it is committed. The execution switches the
+    /// system contracts to the new roots, so they are switched back to the
+    /// current state since the proposal may not be committed. The roots are
+    /// process-global, the lock keeps commit and sync out meanwhile.
+    async fn speculative_exec(
+        adapter: &Adapter,
+        ctx: Context,
+        last_state_root: MerkleRoot,
+        proposal: &Proposal,
+        signed_txs: &[SignedTransaction],
+    ) -> ProtocolResult<ExecResp> {
+        let _roots_lock = SYSTEM_CONTRACT_ROOTS_LOCK.lock().await;
+
+        let resp = adapter
+            .exec(ctx.clone(), last_state_root, proposal, signed_txs)
+            .await;
+
+        let (metadata_root, header_cell_root) =
+            adapter.get_system_contract_roots(ctx.clone(), **CURRENT_STATE_ROOT.load())?;
+        adapter.update_system_contract_roots(ctx, metadata_root, header_cell_root);
+
+        resp
+    }
+
     #[trace_span(kind = "consensus.engine", logs = "{txs_len: signed_txs.len()}")]
     fn check_order_transactions(
         &self,
@@ -592,7 +800,7 @@ impl<Adapter: ConsensusAdapter + 'static> ConsensusEngine<Adapter> {
         let block = Block::new(proposal, resp.clone());
         let block_number = block.header.number;
         let block_hash = block.hash();
-        let is_change_metadata = self.contains_change_metadata(&txs);
+        let is_change_metadata = contains_change_metadata(self.metadata_address, &txs);
         let next_block_number = block_number + 1;
 
         let (receipts, _logs) = generate_receipts_and_logs(
@@ -666,6 +874,40 @@ impl<Adapter: ConsensusAdapter + 'static> ConsensusEngine<Adapter> {
         Ok(())
     }
 
+    // Push the proposal txs to the other validators ahead of the proposal, so
+    // they can rebuild the block from their own pool instead of pulling the
+    // txs in `check_block`. The relay runs in the background and never delays
+    // the proposal.
+    fn relay_proposal_txs(
+        &self,
+        ctx: Context,
+        next_number: u64,
+        signed_txs: Vec<SignedTransaction>,
+    ) {
+        if signed_txs.is_empty() {
+            return;
+        }
+
+        let pub_keys = self
+            .adapter
+            .get_metadata_unchecked(ctx.clone(), next_number)
+            .verifier_list
+            .iter()
+            .map(|v| v.pub_key.as_bytes())
+            .filter(|pk| pk != &self.node_info.self_pub_key)
+            .collect::<Vec<_>>();
+
+        let adapter = Arc::clone(&self.adapter);
+        tokio::spawn(async move {
+            if let Err(e) = adapter
+                .relay_proposal_txs(ctx, next_number, signed_txs, pub_keys)
+                .await
+            {
+                log::warn!("[consensus] relay proposal txs error {:?}", e);
+            }
+        });
+    }
+
     fn update_overlord_crypto(&self, metadata: Metadata) -> ProtocolResult<()> {
         self.crypto.update(generate_new_crypto_map(metadata)?);
         Ok(())
@@ -735,6 +977,42 @@ fn validate_timestamp(
     true
 }
 
+/// The post-execution roots of a proposal to check. They are mandatory from
+/// the speculative execution height, since the block hash commits to them.
+fn post_state_roots(proposal: &Proposal) -> ProtocolResult<Option<(MerkleRoot, MerkleRoot)>> {
+    match (
+        is_speculative_execution(proposal.number),
+        proposal.state_root,
+        proposal.receipts_root,
+    ) {
+        (false, None, None) => Ok(None),
+        (true, Some(state_root), Some(receipts_root)) => Ok(Some((state_root, receipts_root))),
+        _ => Err(ConsensusError::InvalidPostState(proposal.number).into()),
+    }
+}
+
+/// The proposal without transactions, in place of one whose speculative
+/// execution is not done in time
+fn empty_proposal(proposal: Proposal) -> Proposal {
+    Proposal {
+        transactions_root: RLP_NULL,
+        signed_txs_hash: digest_signed_transactions(&[]),
+        call_system_script_count: 0,
+        tx_hashes: vec![],
+        state_root: None,
+        receipts_root: None,
+        ..proposal
+    }
+}
+
+/// Whether the transactions of a block call the metadata contract, the
+/// metadata is updated after such a block is committed.
+pub fn contains_change_metadata(metadata_address: H160, txs: &[SignedTransaction]) -> bool {
+    let action = TransactionAction::Call(metadata_address);
+    txs.iter()
+        .any(|tx| tx.transaction.unsigned.action() == &action)
+}
+
 pub fn generate_receipts_and_logs(
     block_number: u64,
     block_hash: Hash,
@@ -778,7 +1056,14 @@ fn gauge_txs_len(proposal: &Proposal) {
 
 #[cfg(test)]
 mod tests {
-    use super::validate_timestamp;
+    use std::sync::Arc;
+
+    use protocol::lazy::SPECULATIVE_EXECUTION_FROM;
+    use protocol::types::{Block, ExecResp, Hasher, Proof, Proposal, RLP_NULL};
+
+    use super::{empty_proposal, post_state_roots, validate_timestamp};
+
+    const SPECULATIVE_FROM: u64 = 1 << 40;
 
     #[test]
     fn test_validate_timestamp() {
@@ -791,4 +1076,53 @@ mod tests {
         // current 10, proposal 9, previous 11. true
         assert!(!validate_timestamp(10, 9, 11));
     }
+
+    #[test]
+    fn test_timed_out_proposal() {
+        SPECULATIVE_EXECUTION_FROM.store(Arc::new(SPECULATIVE_FROM));
+
+        let number = SPECULATIVE_FROM + 1;
+        let proposal = Proposal {
+            number,
+            transactions_root: Hasher::digest("txs"),
+            tx_hashes: vec![Hasher::digest("tx")],
+            call_system_script_count: 1,
+            proof: Proof {
+                number: number - 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // A proposal without roots is refused from the speculative height
+        assert!(post_state_roots(&proposal).is_err());
+        let legacy = Proposal {
+            number: SPECULATIVE_FROM - 1,
+            ..proposal.clone()
+        };
+        assert!(post_state_roots(&legacy).unwrap().is_none());
+
+        // The empty block proposed in place of it carries the roots
+        let resp = ExecResp {
+            state_root:   Hasher::digest("state"),
+            receipt_root: Hasher::digest("receipts"),
+            gas_used:     0,
+            tx_resp:      vec![],
+        };
+        let mut empty = empty_proposal(proposal);
+        empty.state_root = Some(resp.state_root);
+        empty.receipts_root = Some(resp.receipt_root);
+        assert!(empty.tx_hashes.is_empty());
+        assert_eq!(empty.transactions_root, RLP_NULL);
+        assert_eq!(
+            post_state_roots(&empty).unwrap(),
+            Some((resp.state_root, resp.receipt_root))
+        );
+
+        // The committed block hashes the same as the voted proposal, which
+        // the proof, the sync and the light client check
+        let block = Block::new(empty.clone(), resp);
+        assert_eq!(Proposal::from(&block).hash(), empty.hash());
+        assert_eq!(block.hash(), empty.hash());
+    }
 }
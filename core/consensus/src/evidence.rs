@@ -0,0 +1,295 @@
+//! A validator double-signs when it signs two different consensus messages
+//! of the same kind at the same height and round. The messages received from
+//! the other validators are recorded for the recent heights, and a conflicting
+//! one is turned into a `DoubleSignEvidence`. A message is recorded only if it
+//! is signed by a validator, so that a forged message never makes an evidence
+//! nor takes the room of the valid ones.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use overlord::types::{HashChoke, SignedChoke, SignedProposal, SignedVote, VoteType};
+use overlord::Crypto;
+use parking_lot::Mutex;
+
+use protocol::types::{Bytes, DoubleSignEvidence, EvidenceKind, Proposal, SignedMessage};
+
+use crate::util::OverlordCrypto;
+
+/// The number of the recent heights whose messages are recorded.
+const EVIDENCE_HEIGHT_WINDOW: u64 = 8;
+/// The max number of the recorded messages of a height, which bounds the
+/// memory used by the messages of numerous rounds.
+const MAX_MESSAGES_PER_HEIGHT: usize = 4096;
+
+/// The kind, the round and the signer of a message.
+type MessageKey = (EvidenceKind, u64, Bytes);
+
+#[derive(Clone, Debug)]
+pub struct ConsensusMessage {
+    pub(crate) kind:   EvidenceKind,
+    pub(crate) height: u64,
+    pub(crate) round:  u64,
+    pub(crate) signer: Bytes,
+    pub(crate) signed: SignedMessage,
+}
+
+impl From<&SignedProposal<Proposal>> for ConsensusMessage {
+    fn from(signed: &SignedProposal<Proposal>) -> Self {
+        let proposal = &signed.proposal;
+        ConsensusMessage {
+            kind:   EvidenceKind::Proposal,
+            height: proposal.height,
+            round:  proposal.round,
+            signer: proposal.proposer.clone(),
+            signed: SignedMessage {
+                message:   rlp::encode(proposal).freeze(),
+                signature: signed.signature.clone(),
+            },
+        }
+    }
+}
+
+impl From<&SignedVote> for ConsensusMessage {
+    fn from(signed: &SignedVote) -> Self {
+        let vote = &signed.vote;
+        let kind = match vote.vote_type {
+            VoteType::Prevote => EvidenceKind::Prevote,
+            VoteType::Precommit => EvidenceKind::Precommit,
+        };
+
+        ConsensusMessage {
+            kind,
+            height: vote.height,
+            round: vote.round,
+            signer: signed.voter.clone(),
+            signed: SignedMessage {
+                message:   rlp::encode(vote).freeze(),
+                signature: signed.signature.clone(),
+            },
+        }
+    }
+}
+
+impl From<&SignedChoke> for ConsensusMessage {
+    fn from(signed: &SignedChoke) -> Self {
+        let choke = &signed.choke;
+        // Overlord signs the height and the round of a choke only.
+        let hash_choke = HashChoke {
+            height: choke.height,
+            round:  choke.round,
+        };
+
+        ConsensusMessage {
+            kind:   EvidenceKind::Choke,
+            height: choke.height,
+            round:  choke.round,
+            signer: signed.address.clone(),
+            signed: SignedMessage {
+                message:   rlp::encode(&hash_choke).freeze(),
+                signature: signed.signature.clone(),
+            },
+        }
+    }
+}
+
+pub struct EvidenceCollector {
+    crypto:   Arc<OverlordCrypto>,
+    messages: Mutex<BTreeMap<u64, HashMap<MessageKey, SignedMessage>>>,
+}
+
+impl EvidenceCollector {
+    pub fn new(crypto: Arc<OverlordCrypto>) -> Self {
+        EvidenceCollector {
+            crypto,
+            messages: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record a message received when the consensus is at the given height.
+    /// Return the evidence if the signer has signed a different message of
+    /// the same kind at the same height and round.
+    pub fn record(&self, current_height: u64, msg: ConsensusMessage) -> Option<DoubleSignEvidence> {
+        let lowest = current_height.saturating_sub(EVIDENCE_HEIGHT_WINDOW - 1);
+        if msg.height < lowest || msg.height > current_height + 1 {
+            return None;
+        }
+
+        let key = (msg.kind, msg.round, msg.signer.clone());
+        if self.is_recorded(lowest, msg.height, &key, &msg.signed) {
+            return None;
+        }
+
+        if !self.crypto.is_validator(&msg.signer) {
+            return None;
+        }
+
+        if !self.verify(&msg.signer, &msg.signed) {
+            log::warn!(
+                "[consensus] invalid signature of {:?} at height {} round {}",
+                msg.kind,
+                msg.height,
+                msg.round
+            );
+            return None;
+        }
+
+        let mut messages = self.messages.lock();
+        let height_messages = messages.entry(msg.height).or_default();
+        let recorded = match height_messages.get(&key) {
+            Some(recorded) if recorded.message == msg.signed.message => return None,
+            Some(recorded) => recorded.clone(),
+            None => {
+                if height_messages.len() < MAX_MESSAGES_PER_HEIGHT {
+                    height_messages.insert(key, msg.signed);
+                }
+                return None;
+            }
+        };
+
+        Some(DoubleSignEvidence::new(
+            msg.kind, msg.height, msg.round, msg.signer, recorded, msg.signed,
+        ))
+    }
+
+    /// Drop the messages out of the window, and return whether the same
+    /// message has been recorded, which needs no verification again.
+    fn is_recorded(
+        &self,
+        lowest: u64,
+        height: u64,
+        key: &MessageKey,
+        signed: &SignedMessage,
+    ) -> bool {
+        let mut messages = self.messages.lock();
+        *messages = messages.split_off(&lowest);
+
+        messages
+            .get(&height)
+            .and_then(|height_messages| height_messages.get(key))
+            .map(|recorded| recorded.message == signed.message)
+            .unwrap_or(false)
+    }
+
+    fn verify(&self, signer: &Bytes, signed: &SignedMessage) -> bool {
+        let hash = self.crypto.hash(signed.message.clone());
+        self.crypto
+            .verify_signature(signed.signature.clone(), hash, signer.clone())
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use common_crypto::{BlsPrivateKey, BlsPublicKey, ToBlsPublicKey};
+    use protocol::codec::hex_decode;
+
+    use super::*;
+
+    const PRIVATE_KEY: &str = "37aa0f893d05914a4def0460c0a984d3611546cfb26924d7a7ca6e0db9950a2d";
+
+    fn signer() -> Bytes {
+        Bytes::from_static(b"validator")
+    }
+
+    fn mock_crypto() -> Arc<OverlordCrypto> {
+        let private_key =
+            BlsPrivateKey::try_from(hex_decode(PRIVATE_KEY).unwrap().as_ref()).unwrap();
+        let public_key: BlsPublicKey = private_key.pub_key(&"axon".into());
+        let mut pub_keys = HashMap::new();
+        pub_keys.insert(signer(), public_key);
+
+        Arc::new(OverlordCrypto::new(private_key, pub_keys, "axon".into()))
+    }
+
+    fn mock_message(
+        crypto: &OverlordCrypto,
+        kind: EvidenceKind,
+        height: u64,
+        message: &'static [u8],
+    ) -> ConsensusMessage {
+        let message = Bytes::from_static(message);
+        let signature = crypto.sign(crypto.hash(message.clone())).unwrap();
+
+        ConsensusMessage {
+            kind,
+            height,
+            round: 0,
+            signer: signer(),
+            signed: SignedMessage { message, signature },
+        }
+    }
+
+    #[test]
+    fn test_double_sign() {
+        let crypto = mock_crypto();
+        let collector = EvidenceCollector::new(Arc::clone(&crypto));
+
+        let first = mock_message(&crypto, EvidenceKind::Prevote, 10, b"first");
+        assert!(collector.record(10, first.clone()).is_none());
+        assert!(collector.record(10, first.clone()).is_none());
+
+        // Different kinds and heights do not conflict.
+        let precommit = mock_message(&crypto, EvidenceKind::Precommit, 10, b"second");
+        assert!(collector.record(10, precommit).is_none());
+        let next = mock_message(&crypto, EvidenceKind::Prevote, 11, b"second");
+        assert!(collector.record(10, next).is_none());
+
+        let second = mock_message(&crypto, EvidenceKind::Prevote, 10, b"second");
+        let evidence = collector.record(10, second.clone()).unwrap();
+        assert_eq!(evidence.kind, EvidenceKind::Prevote);
+        assert_eq!(evidence.height, 10);
+        assert_eq!(evidence.signer, signer());
+        assert_eq!(
+            evidence,
+            DoubleSignEvidence::new(
+                EvidenceKind::Prevote,
+                10,
+                0,
+                signer(),
+                second.signed,
+                first.signed
+            )
+        );
+    }
+
+    #[test]
+    fn test_forged_message() {
+        let crypto = mock_crypto();
+        let collector = EvidenceCollector::new(Arc::clone(&crypto));
+
+        // The forged message is not recorded.
+        let mut forged = mock_message(&crypto, EvidenceKind::Proposal, 10, b"forged");
+        forged.signed.signature = Bytes::from_static(b"invalid signature");
+        assert!(collector.record(10, forged.clone()).is_none());
+        assert!(collector.messages.lock().get(&10).is_none());
+
+        let valid = mock_message(&crypto, EvidenceKind::Proposal, 10, b"valid");
+        assert!(collector.record(10, valid.clone()).is_none());
+        assert!(collector.record(10, forged).is_none());
+        assert!(collector.record(10, valid).is_none());
+        assert_eq!(collector.messages.lock().get(&10).unwrap().len(), 1);
+
+        // The messages out of the window are ignored.
+        let stale = mock_message(&crypto, EvidenceKind::Proposal, 1, b"stale");
+        assert!(collector.record(10, stale).is_none());
+        assert!(collector.messages.lock().get(&1).is_none());
+    }
+
+    #[test]
+    fn test_non_validator_message() {
+        let crypto = mock_crypto();
+        let collector = EvidenceCollector::new(Arc::clone(&crypto));
+
+        let mut first = mock_message(&crypto, EvidenceKind::Prevote, 10, b"first");
+        first.signer = Bytes::from_static(b"stranger");
+        let mut second = mock_message(&crypto, EvidenceKind::Prevote, 10, b"second");
+        second.signer = Bytes::from_static(b"stranger");
+
+        assert!(collector.record(10, first).is_none());
+        assert!(collector.record(10, second).is_none());
+        assert!(collector.messages.lock().get(&10).is_none());
+    }
+}
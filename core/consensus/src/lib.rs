@@ -8,7 +8,10 @@
 pub mod adapter;
 pub mod consensus;
 pub mod engine;
+pub mod evidence;
+pub mod liveness;
 pub mod message;
+pub mod snapshot;
 pub mod status;
 pub mod synchronization;
 
@@ -23,13 +26,14 @@ use std::error::Error;
 
 use common_crypto::Error as CryptoError;
 
-use protocol::types::{ExitReason, Hash, MerkleRoot};
+use protocol::types::{ExitReason, Hash, MerkleRoot, TrieKind};
 use protocol::{Display, ProtocolError, ProtocolErrorKind};
 
 pub use crate::adapter::OverlordConsensusAdapter;
 pub use crate::consensus::OverlordConsensus;
+pub use crate::liveness::{ValidatorLiveness, VALIDATOR_LIVENESS};
 pub use crate::synchronization::{OverlordSynchronization, RichBlock, SyncStatus, SYNC_STATUS};
-pub use crate::wal::{ConsensusWal, SignedTxsWAL};
+pub use crate::wal::{ConsensusWal, SignedTxsWAL, WalFileInfo};
 pub use overlord::{types::Node, DurationConfig};
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
@@ -95,6 +99,9 @@ pub enum ConsensusError {
     )]
     InvalidOrderSignedTransactionsHash { expect: Hash, actual: Hash },
 
+    #[display(fmt = "Check invalid post state roots of proposal {}", _0)]
+    InvalidPostState(u64),
+
     #[display(fmt = "Check invalid status vec")]
     InvalidStatusVec,
 
@@ -162,6 +169,16 @@ pub enum ConsensusError {
     #[display(fmt = "Wal transactions mismatch, height {}", _0)]
     WalTxsMismatch(u64),
 
+    #[display(
+        fmt = "Wal transactions truncated, height {}, {} corrupt bytes",
+        _0,
+        _1
+    )]
+    WalTxsTruncated(u64, usize),
+
+    #[display(fmt = "Unsupported wal format version {}", _0)]
+    WalVersion(u32),
+
     #[display(
         fmt = "Commit an outdated block, block_height {}, last_committed_height {}",
         _0,
@@ -190,6 +207,24 @@ pub enum ConsensusError {
 
     #[display(fmt = "Build trie merkle tree error {}", _0)]
     BuildMerkle(String),
+
+    #[display(fmt = "Snapshot sync got an unexpected {:?} trie node {:?}", _0, _1)]
+    UnexpectedTrieNode(TrieKind, Hash),
+
+    #[display(fmt = "Snapshot sync decode {:?} trie node {:?} error {}", _0, _1, _2)]
+    DecodeTrieNode(TrieKind, Hash, String),
+
+    #[display(
+        fmt = "Snapshot sync block {} mismatches the trusted checkpoint, expect {:?} get {:?}",
+        number,
+        expect,
+        actual
+    )]
+    InvalidSnapshotCheckpoint {
+        number: u64,
+        expect: Hash,
+        actual: Hash,
+    },
 }
 
 #[derive(Debug, Display)]
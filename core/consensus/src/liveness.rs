@@ -0,0 +1,337 @@
+//! The liveness of the validators is tracked with the committed blocks. The
+//! signers of a block are taken from the bitmap of its proof, and the rounds
+//! of the proof tell which elected proposers failed to make the block. The
+//! statistics are kept for the recent blocks only, so that a validator which
+//! goes offline stands out before the chain stalls.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use overlord::extract_voters;
+use overlord::types::Node;
+use parking_lot::RwLock;
+
+use common_apm::metrics::consensus::{on_validator_liveness, remove_validator_liveness};
+use protocol::types::{Bytes, Proof, ValidatorExtend, H160};
+use protocol::ProtocolResult;
+
+use crate::ConsensusError;
+
+/// The number of the recent blocks counted in the statistics.
+pub const LIVENESS_WINDOW: usize = 1000;
+
+lazy_static::lazy_static! {
+    pub static ref VALIDATOR_LIVENESS: RwLock<LivenessTracker> =
+        RwLock::new(LivenessTracker::new(LIVENESS_WINDOW));
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidatorLiveness {
+    pub address:            H160,
+    /// The blocks in the window committed while being a verifier.
+    pub blocks:             u64,
+    pub missed_blocks:      u64,
+    pub proposed_blocks:    u64,
+    pub expected_proposals: u64,
+    /// The rounds which are elected to propose but end with a view change.
+    pub view_changes:       u64,
+    pub last_signed_block:  Option<u64>,
+}
+
+/// The changes of the statistics made by a block, which are reverted when
+/// the block leaves the window.
+#[derive(Clone, Copy, Debug, Default)]
+struct LivenessDelta {
+    blocks:             u64,
+    missed_blocks:      u64,
+    proposed_blocks:    u64,
+    expected_proposals: u64,
+    view_changes:       u64,
+}
+
+impl LivenessDelta {
+    fn apply(&self, stats: &mut ValidatorLiveness) {
+        stats.blocks += self.blocks;
+        stats.missed_blocks += self.missed_blocks;
+        stats.proposed_blocks += self.proposed_blocks;
+        stats.expected_proposals += self.expected_proposals;
+        stats.view_changes += self.view_changes;
+    }
+
+    fn revert(&self, stats: &mut ValidatorLiveness) {
+        stats.blocks -= self.blocks;
+        stats.missed_blocks -= self.missed_blocks;
+        stats.proposed_blocks -= self.proposed_blocks;
+        stats.expected_proposals -= self.expected_proposals;
+        stats.view_changes -= self.view_changes;
+    }
+}
+
+pub struct LivenessTracker {
+    window: usize,
+    blocks: VecDeque<(u64, BTreeMap<H160, LivenessDelta>)>,
+    stats:  BTreeMap<H160, ValidatorLiveness>,
+}
+
+impl LivenessTracker {
+    pub fn new(window: usize) -> Self {
+        LivenessTracker {
+            window,
+            blocks: VecDeque::with_capacity(window),
+            stats: BTreeMap::new(),
+        }
+    }
+
+    pub fn latest_number(&self) -> Option<u64> {
+        self.blocks.back().map(|(number, _)| *number)
+    }
+
+    pub fn stats(&self) -> Vec<ValidatorLiveness> {
+        self.stats.values().cloned().collect()
+    }
+
+    /// Record a committed block by its proposer, its proof and the verifier
+    /// list of its height. A block which is not higher than the latest
+    /// recorded one is ignored.
+    pub fn record(
+        &mut self,
+        proposer: H160,
+        proof: &Proof,
+        verifier_list: &[ValidatorExtend],
+    ) -> ProtocolResult<()> {
+        if self.latest_number().map_or(false, |n| proof.number <= n) {
+            return Ok(());
+        }
+
+        let mut authority_list = verifier_list
+            .iter()
+            .map(|v| Node {
+                address:        v.pub_key.as_bytes(),
+                propose_weight: v.propose_weight,
+                vote_weight:    v.vote_weight,
+            })
+            .collect::<Vec<_>>();
+        authority_list.sort();
+
+        let voters = extract_voters(&mut authority_list, &proof.bitmap)
+            .map_err(|_| ConsensusError::Other("extract voters error".to_string()))?;
+
+        let address_of = |pub_key: &Bytes| {
+            verifier_list
+                .iter()
+                .find(|v| &v.pub_key.as_bytes() == pub_key)
+                .map(|v| v.address)
+        };
+
+        let mut deltas = BTreeMap::new();
+        for v in verifier_list.iter() {
+            let signed = voters.contains(&v.pub_key.as_bytes());
+            deltas.insert(v.address, LivenessDelta {
+                blocks: 1,
+                missed_blocks: u64::from(!signed),
+                ..Default::default()
+            });
+
+            let stats = self
+                .stats
+                .entry(v.address)
+                .or_insert_with(|| ValidatorLiveness {
+                    address: v.address,
+                    ..Default::default()
+                });
+            if signed {
+                stats.last_signed_block = Some(proof.number);
+            }
+        }
+
+        if let Some(delta) = deltas.get_mut(&proposer) {
+            delta.proposed_blocks += 1;
+        }
+
+        for round in 0..=proof.round {
+            let elected = match elect_proposer(&authority_list, proof.number, round)
+                .and_then(|node| address_of(&node.address))
+            {
+                Some(address) => address,
+                None => break,
+            };
+
+            if let Some(delta) = deltas.get_mut(&elected) {
+                delta.expected_proposals += 1;
+                if round < proof.round {
+                    delta.view_changes += 1;
+                }
+            }
+        }
+
+        for (address, delta) in deltas.iter() {
+            if let Some(stats) = self.stats.get_mut(address) {
+                delta.apply(stats);
+            }
+        }
+        self.blocks.push_back((proof.number, deltas));
+
+        while self.blocks.len() > self.window {
+            let (_, deltas) = self.blocks.pop_front().expect("checked len");
+            for (address, delta) in deltas.iter() {
+                if let Some(stats) = self.stats.get_mut(address) {
+                    delta.revert(stats);
+                }
+            }
+        }
+
+        // The validators which leave the verifier list are dropped after
+        // their blocks leave the window.
+        self.stats.retain(|address, stats| {
+            let keep = stats.blocks > 0;
+            if !keep {
+                remove_validator_liveness(&format!("{:?}", address));
+            }
+            keep
+        });
+        self.update_metrics();
+
+        Ok(())
+    }
+
+    fn update_metrics(&self) {
+        for stats in self.stats.values() {
+            on_validator_liveness(
+                &format!("{:?}", stats.address),
+                stats.missed_blocks,
+                stats.proposed_blocks,
+                stats.expected_proposals,
+                stats.view_changes,
+            );
+        }
+    }
+}
+
+/// Elect the proposer of the given height and round from the sorted
+/// authority list, which mirrors the rotation election of overlord. The
+/// election with the `random_leader` feature is not mirrored, so `None` is
+/// returned then.
+fn elect_proposer(authority_list: &[Node], height: u64, round: u64) -> Option<&Node> {
+    if cfg!(feature = "random_leader") || authority_list.is_empty() {
+        return None;
+    }
+
+    let len = authority_list.len() as u64;
+    let index = (height % len + round % len) % len;
+    authority_list.get(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use bit_vec::BitVec;
+
+    use protocol::types::Hex;
+
+    use super::*;
+
+    fn mock_validators(count: u8) -> Vec<ValidatorExtend> {
+        (0..count)
+            .map(|i| ValidatorExtend {
+                bls_pub_key:    Hex::encode([i; 48]),
+                pub_key:        Hex::encode([i; 33]),
+                address:        H160::repeat_byte(i),
+                propose_weight: 1,
+                vote_weight:    1,
+            })
+            .collect()
+    }
+
+    /// Build the proof signed by the given validators, the bits of the bitmap
+    /// follow the order of the sorted authority list.
+    fn mock_proof(
+        number: u64,
+        round: u64,
+        validators: &[ValidatorExtend],
+        signers: &[H160],
+    ) -> Proof {
+        let mut sorted = validators.to_vec();
+        sorted.sort_by_key(|v| v.pub_key.as_bytes());
+
+        let mut bitmap = BitVec::from_elem(sorted.len(), false);
+        for (i, v) in sorted.iter().enumerate() {
+            bitmap.set(i, signers.contains(&v.address));
+        }
+
+        Proof {
+            number,
+            round,
+            bitmap: Bytes::from(bitmap.to_bytes()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_liveness_tracker() {
+        let validators = mock_validators(4);
+        let online = validators[..3]
+            .iter()
+            .map(|v| v.address)
+            .collect::<Vec<_>>();
+        let offline = validators[3].address;
+        let mut tracker = LivenessTracker::new(8);
+
+        for number in 1..=10u64 {
+            let proof = mock_proof(number, 0, &validators, &online);
+            tracker.record(online[0], &proof, &validators).unwrap();
+        }
+        // An old block is ignored.
+        let proof = mock_proof(5, 0, &validators, &online);
+        tracker.record(online[0], &proof, &validators).unwrap();
+        assert_eq!(tracker.latest_number(), Some(10));
+
+        let stats = tracker.stats();
+        assert_eq!(stats.len(), 4);
+        let offline_stats = stats.iter().find(|s| s.address == offline).unwrap();
+        assert_eq!(offline_stats.blocks, 8);
+        assert_eq!(offline_stats.missed_blocks, 8);
+        assert_eq!(offline_stats.last_signed_block, None);
+
+        let proposer_stats = stats.iter().find(|s| s.address == online[0]).unwrap();
+        assert_eq!(proposer_stats.missed_blocks, 0);
+        assert_eq!(proposer_stats.proposed_blocks, 8);
+        assert_eq!(proposer_stats.last_signed_block, Some(10));
+
+        // Every validator is elected twice in the 8 blocks of the window.
+        assert!(stats.iter().all(|s| s.expected_proposals == 2));
+        assert!(stats.iter().all(|s| s.view_changes == 0));
+    }
+
+    #[test]
+    fn test_view_changes() {
+        let validators = mock_validators(4);
+        let signers = validators.iter().map(|v| v.address).collect::<Vec<_>>();
+        let mut tracker = LivenessTracker::new(8);
+
+        let mut authority_list = validators
+            .iter()
+            .map(|v| Node {
+                address:        v.pub_key.as_bytes(),
+                propose_weight: v.propose_weight,
+                vote_weight:    v.vote_weight,
+            })
+            .collect::<Vec<_>>();
+        authority_list.sort();
+        let failed = elect_proposer(&authority_list, 1, 0)
+            .unwrap()
+            .address
+            .clone();
+        let failed = validators
+            .iter()
+            .find(|v| v.pub_key.as_bytes() == failed)
+            .unwrap()
+            .address;
+
+        let proof = mock_proof(1, 1, &validators, &signers);
+        tracker.record(signers[0], &proof, &validators).unwrap();
+
+        let stats = tracker.stats();
+        let failed_stats = stats.iter().find(|s| s.address == failed).unwrap();
+        assert_eq!(failed_stats.view_changes, 1);
+        assert_eq!(stats.iter().map(|s| s.expected_proposals).sum::<u64>(), 2);
+        assert_eq!(stats.iter().map(|s| s.view_changes).sum::<u64>(), 1);
+    }
+}
@@ -10,12 +10,15 @@ use common_apm_derive::trace_span;
 use protocol::traits::{
     Consensus, Context, MessageHandler, Priority, Rpc, Storage, Synchronization, TrustFeedback,
 };
-use protocol::types::BatchSignedTxs;
-use protocol::{async_trait, types::BlockNumber, ProtocolError};
+use protocol::types::{BatchSignedTxs, Bytes, TrieKind};
+use protocol::{async_trait, trie, types::BlockNumber, ProtocolError, ProtocolResult};
 
+use core_executor::system_contract;
 use core_storage::StorageError;
 
-pub use crate::types::PullTxsRequest;
+use crate::ConsensusError;
+
+pub use crate::types::{PullTrieNodesRequest, PullTrieNodesResponse, PullTxsRequest};
 
 pub const END_GOSSIP_SIGNED_PROPOSAL: &str = "/gossip/consensus/signed_proposal";
 pub const END_GOSSIP_SIGNED_VOTE: &str = "/gossip/consensus/signed_vote";
@@ -28,6 +31,14 @@ pub const RPC_RESP_SYNC_PULL_TXS: &str = "/rpc_resp/consensus/sync_pull_txs";
 pub const BROADCAST_HEIGHT: &str = "/gossip/consensus/broadcast_height";
 pub const RPC_SYNC_PULL_PROOF: &str = "/rpc_call/consensus/sync_pull_proof";
 pub const RPC_RESP_SYNC_PULL_PROOF: &str = "/rpc_resp/consensus/sync_pull_proof";
+pub const RPC_SYNC_PULL_TRIE_NODES: &str = "/rpc_call/consensus/sync_pull_trie_nodes";
+pub const RPC_RESP_SYNC_PULL_TRIE_NODES: &str = "/rpc_resp/consensus/sync_pull_trie_nodes";
+
+/// The max number of trie nodes served in one response of snapshot sync.
+pub const MAX_PULL_TRIE_NODES: usize = 384;
+/// The max number of transactions served in one response of sync, the
+/// requests of more are refused.
+pub const MAX_PULL_TXS: usize = 5000;
 
 macro_rules! overlord_message {
     ($msg_name: ident, $overlord_type_name: ident) => {
@@ -336,17 +347,100 @@ impl<R: Rpc + 'static, S: Storage + 'static> MessageHandler for PullTxsRpcHandle
     async fn process(&self, ctx: Context, msg: PullTxsRequest) -> TrustFeedback {
         let PullTxsRequest { height, inner } = msg;
 
-        let ret = self
-            .storage
-            .get_transactions(ctx.clone(), height, &inner)
-            .await
-            .map(|txs| BatchSignedTxs(txs.into_iter().flatten().collect::<Vec<_>>()));
+        let (ret, feedback) = if inner.len() > MAX_PULL_TXS {
+            let reason = format!("pull {} txs, more than {}", inner.len(), MAX_PULL_TXS);
+            (
+                Err(ConsensusError::Other(reason.clone()).into()),
+                TrustFeedback::Bad(reason),
+            )
+        } else {
+            let ret = self
+                .storage
+                .get_transactions(ctx.clone(), height, &inner)
+                .await
+                .map(|txs| BatchSignedTxs(txs.into_iter().flatten().collect::<Vec<_>>()));
+            (ret, TrustFeedback::Neutral)
+        };
 
         self.rpc
             .response(ctx, RPC_RESP_SYNC_PULL_TXS, ret, Priority::High)
             .unwrap_or_else(move |e: ProtocolError| warn!("[core_consensus] push txs {:?}", e))
             .await;
 
+        feedback
+    }
+}
+
+#[derive(Debug)]
+pub struct PullTrieNodesRpcHandler<R, S, DB> {
+    rpc:     Arc<R>,
+    storage: Arc<S>,
+    trie_db: Arc<DB>,
+}
+
+impl<R, S, DB> PullTrieNodesRpcHandler<R, S, DB>
+where
+    R: Rpc + 'static,
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+{
+    pub fn new(rpc: Arc<R>, storage: Arc<S>, trie_db: Arc<DB>) -> Self {
+        PullTrieNodesRpcHandler {
+            rpc,
+            storage,
+            trie_db,
+        }
+    }
+
+    async fn get_nodes(
+        &self,
+        ctx: Context,
+        msg: PullTrieNodesRequest,
+    ) -> ProtocolResult<PullTrieNodesResponse> {
+        let mut nodes = Vec::with_capacity(msg.hashes.len().min(MAX_PULL_TRIE_NODES));
+
+        for hash in msg.hashes.iter().take(MAX_PULL_TRIE_NODES) {
+            let node = match msg.kind {
+                TrieKind::State => self
+                    .trie_db
+                    .get(hash.as_bytes())
+                    .map_err(|e| ConsensusError::Other(e.to_string()))?
+                    .map(Bytes::from),
+                TrieKind::Metadata | TrieKind::HeaderCell => {
+                    system_contract::get_trie_node(msg.kind, hash)?.map(Bytes::from)
+                }
+                TrieKind::Code => self.storage.get_code_by_hash(ctx.clone(), hash).await?,
+            };
+
+            if let Some(node) = node {
+                nodes.push(node);
+            }
+        }
+
+        Ok(PullTrieNodesResponse { nodes })
+    }
+}
+
+#[async_trait]
+impl<R, S, DB> MessageHandler for PullTrieNodesRpcHandler<R, S, DB>
+where
+    R: Rpc + 'static,
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+{
+    type Message = PullTrieNodesRequest;
+
+    #[trace_span(name = "pull_trie_nodes_rpc", kind = "consensus.message")]
+    async fn process(&self, ctx: Context, msg: PullTrieNodesRequest) -> TrustFeedback {
+        let ret = self.get_nodes(ctx.clone(), msg).await;
+
+        self.rpc
+            .response(ctx, RPC_RESP_SYNC_PULL_TRIE_NODES, ret, Priority::High)
+            .unwrap_or_else(move |e: ProtocolError| {
+                warn!("[core_consensus] push trie nodes {:?}", e)
+            })
+            .await;
+
         TrustFeedback::Neutral
     }
 }
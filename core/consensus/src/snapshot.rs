@@ -0,0 +1,361 @@
+//! The tries of a recent block are downloaded from the root to the leaves in
+//! snapshot sync. Every node is verified by its hash, which is referred by the
+//! verified parent node, so that the whole trie is verified by the root.
+
+use std::collections::{HashMap, HashSet};
+
+use rlp::{DecoderError, Rlp};
+
+use protocol::codec::ProtocolCodec;
+use protocol::types::{Account, Bytes, Hash, Hasher, TrieKind, H160, NIL_DATA, RLP_NULL};
+use protocol::ProtocolResult;
+
+use crate::ConsensusError;
+
+const HASH_LEN: usize = 32;
+const BRANCH_NODE_LEN: usize = 17;
+const SHORT_NODE_LEN: usize = 2;
+const ADDRESS_NIBBLES_LEN: usize = 40;
+
+/// The position of a node in the trie.
+#[derive(Clone, Debug, Default)]
+struct NodePath {
+    // The nibbles from the root of the trie to the node.
+    nibbles: Vec<u8>,
+    // Whether the node is in the account trie. The leaves of the account trie
+    // refer to the storage tries and the contract codes.
+    account: bool,
+}
+
+impl NodePath {
+    fn join(&self, nibbles: &[u8]) -> Self {
+        let mut path = self.clone();
+        path.nibbles.extend_from_slice(nibbles);
+        path
+    }
+}
+
+pub struct TrieSync {
+    kind:    TrieKind,
+    pending: HashMap<Hash, NodePath>,
+    seen:    HashSet<Hash>,
+    // The contract codes with the addresses which deploy them.
+    codes:   HashMap<Hash, Vec<H160>>,
+}
+
+impl TrieSync {
+    /// Sync the trie of the given root, the storage tries and the contract
+    /// codes are found in the leaves of the state trie. A zero root means the
+    /// trie has not been created.
+    pub fn new(kind: TrieKind, root: Hash) -> Self {
+        let mut sync = TrieSync {
+            kind,
+            pending: HashMap::new(),
+            seen: HashSet::new(),
+            codes: HashMap::new(),
+        };
+
+        if !root.is_zero() {
+            sync.push(root, NodePath {
+                nibbles: Vec::new(),
+                account: kind == TrieKind::State,
+            });
+        }
+
+        sync
+    }
+
+    pub fn new_codes(codes: HashMap<Hash, Vec<H160>>) -> Self {
+        let mut sync = TrieSync::new(TrieKind::Code, Hash::default());
+        codes
+            .keys()
+            .for_each(|hash| sync.push(*hash, NodePath::default()));
+        sync.codes = codes;
+        sync
+    }
+
+    pub fn kind(&self) -> TrieKind {
+        self.kind
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn pending_hashes(&self, limit: usize) -> Vec<Hash> {
+        self.pending.keys().take(limit).copied().collect()
+    }
+
+    /// Return the addresses which deploy the contract code.
+    pub fn code_addresses(&self, code_hash: &Hash) -> &[H160] {
+        self.codes.get(code_hash).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Take the contract codes found in the account trie.
+    pub fn take_codes(&mut self) -> HashMap<Hash, Vec<H160>> {
+        std::mem::take(&mut self.codes)
+    }
+
+    /// Verify the received nodes and queue their children. All the nodes
+    /// must be pending ones, otherwise nothing is changed and an error is
+    /// returned. The verified nodes are returned with their hashes.
+    pub fn process(&mut self, nodes: Vec<Bytes>) -> ProtocolResult<Vec<(Hash, Bytes)>> {
+        let hashes = nodes.iter().map(Hasher::digest).collect::<Vec<_>>();
+
+        let mut received = HashSet::with_capacity(hashes.len());
+        for hash in hashes.iter() {
+            if !self.pending.contains_key(hash) || !received.insert(*hash) {
+                return Err(ConsensusError::UnexpectedTrieNode(self.kind, *hash).into());
+            }
+        }
+
+        let kind = self.kind;
+        let mut verified = Vec::with_capacity(nodes.len());
+        for (hash, node) in hashes.into_iter().zip(nodes.into_iter()) {
+            let path = self.pending.remove(&hash).expect("checked above");
+
+            if kind != TrieKind::Code {
+                self.walk_node(&Rlp::new(&node), &path)
+                    .map_err(|e| ConsensusError::DecodeTrieNode(kind, hash, e.to_string()))?;
+            }
+
+            verified.push((hash, node));
+        }
+
+        Ok(verified)
+    }
+
+    fn push(&mut self, hash: Hash, path: NodePath) {
+        if self.seen.insert(hash) {
+            self.pending.insert(hash, path);
+        }
+    }
+
+    fn walk_node(&mut self, node: &Rlp, path: &NodePath) -> Result<(), DecoderError> {
+        // The root of an empty trie.
+        if node.is_empty() {
+            return Ok(());
+        }
+
+        match node.item_count()? {
+            BRANCH_NODE_LEN => {
+                for i in 0..BRANCH_NODE_LEN - 1 {
+                    self.walk_child(&node.at(i)?, path.join(&[i as u8]))?;
+                }
+
+                let value = node.at(BRANCH_NODE_LEN - 1)?;
+                if !value.is_empty() {
+                    self.walk_value(value.data()?, path)?;
+                }
+            }
+            SHORT_NODE_LEN => {
+                let (nibbles, is_leaf) = decode_compact(node.at(0)?.data()?)?;
+                let path = path.join(&nibbles);
+
+                if is_leaf {
+                    self.walk_value(node.at(1)?.data()?, &path)?;
+                } else {
+                    self.walk_child(&node.at(1)?, path)?;
+                }
+            }
+            _ => return Err(DecoderError::RlpIncorrectListLen),
+        }
+
+        Ok(())
+    }
+
+    fn walk_child(&mut self, child: &Rlp, path: NodePath) -> Result<(), DecoderError> {
+        if child.is_empty() {
+            return Ok(());
+        }
+
+        // A node shorter than a hash is embedded in its parent.
+        if child.is_list() {
+            return self.walk_node(child, &path);
+        }
+
+        let data = child.data()?;
+        if data.len() != HASH_LEN {
+            return Err(DecoderError::RlpInvalidLength);
+        }
+
+        self.push(Hash::from_slice(data), path);
+        Ok(())
+    }
+
+    fn walk_value(&mut self, value: &[u8], path: &NodePath) -> Result<(), DecoderError> {
+        if !path.account {
+            return Ok(());
+        }
+
+        if path.nibbles.len() != ADDRESS_NIBBLES_LEN {
+            return Err(DecoderError::Custom("Invalid account key"));
+        }
+
+        let address = H160::from_slice(&nibbles_to_bytes(&path.nibbles));
+        let account =
+            Account::decode(value).map_err(|_| DecoderError::Custom("Invalid account"))?;
+
+        if account.storage_root != RLP_NULL {
+            self.push(account.storage_root, NodePath::default());
+        }
+
+        if account.code_hash != NIL_DATA {
+            self.codes
+                .entry(account.code_hash)
+                .or_insert_with(Vec::new)
+                .push(address);
+        }
+
+        Ok(())
+    }
+}
+
+/// Decode the hex-prefix encoded key of a leaf or an extension node, return
+/// the nibbles and whether the node is a leaf.
+fn decode_compact(compact: &[u8]) -> Result<(Vec<u8>, bool), DecoderError> {
+    let first = *compact.first().ok_or(DecoderError::RlpIsTooShort)?;
+    let flag = first >> 4;
+    let mut nibbles = Vec::with_capacity(compact.len() * 2);
+
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0f);
+    }
+
+    for byte in compact[1..].iter() {
+        nibbles.push(byte >> 4);
+        nibbles.push(byte & 0x0f);
+    }
+
+    Ok((nibbles, flag & 2 == 2))
+}
+
+fn nibbles_to_bytes(nibbles: &[u8]) -> Vec<u8> {
+    nibbles
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or_default())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use core_executor::MPTTrie;
+    use protocol::trie::{MemoryDB, DB};
+    use protocol::types::{H256, U256};
+
+    use super::*;
+
+    fn fetch_all(sync: &mut TrieSync, db: &MemoryDB) -> Vec<(Hash, Bytes)> {
+        let mut synced = Vec::new();
+
+        while !sync.is_finished() {
+            let nodes = sync
+                .pending_hashes(3)
+                .iter()
+                .map(|hash| Bytes::from(db.get(hash.as_bytes()).unwrap().unwrap()))
+                .collect::<Vec<_>>();
+            synced.extend(sync.process(nodes).unwrap());
+        }
+
+        synced
+    }
+
+    #[test]
+    fn test_decode_compact() {
+        assert_eq!(decode_compact(&[0x00, 0x12]).unwrap(), (vec![1, 2], false));
+        assert_eq!(
+            decode_compact(&[0x13, 0x45]).unwrap(),
+            (vec![3, 4, 5], false)
+        );
+        assert_eq!(decode_compact(&[0x20, 0x12]).unwrap(), (vec![1, 2], true));
+        assert_eq!(decode_compact(&[0x3f]).unwrap(), (vec![0xf], true));
+        assert!(decode_compact(&[]).is_err());
+        assert_eq!(nibbles_to_bytes(&[1, 2, 0xa, 0xb]), vec![0x12, 0xab]);
+    }
+
+    #[test]
+    fn test_trie_sync() {
+        let db = Arc::new(MemoryDB::new(false));
+
+        let mut storage_trie = MPTTrie::new(Arc::clone(&db));
+        for i in 0..50u64 {
+            storage_trie
+                .insert(H256::from_low_u64_be(i).as_bytes(), &[1u8; 32])
+                .unwrap();
+        }
+        let storage_root = storage_trie.commit().unwrap();
+
+        let code = Bytes::from_static(b"contract code");
+        let contract = H160::from_low_u64_be(7);
+        let mut state_trie = MPTTrie::new(Arc::clone(&db));
+        for i in 0..100u64 {
+            let address = H160::from_low_u64_be(i);
+            let (storage_root, code_hash) = if address == contract {
+                (storage_root, Hasher::digest(&code))
+            } else {
+                (RLP_NULL, NIL_DATA)
+            };
+            let account = Account {
+                nonce: U256::zero(),
+                balance: U256::from(i),
+                storage_root,
+                code_hash,
+            };
+            state_trie
+                .insert(address.as_bytes(), &account.encode().unwrap())
+                .unwrap();
+        }
+        let state_root = state_trie.commit().unwrap();
+
+        let mut sync = TrieSync::new(TrieKind::State, state_root);
+        let synced = fetch_all(&mut sync, &db);
+        assert!(synced.iter().any(|(hash, _)| *hash == state_root));
+        assert!(synced.iter().any(|(hash, _)| *hash == storage_root));
+        assert!(synced
+            .iter()
+            .all(|(hash, node)| *hash == Hasher::digest(node)));
+
+        let codes = sync.take_codes();
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes.get(&Hasher::digest(&code)).unwrap(), &vec![contract]);
+
+        let mut code_sync = TrieSync::new_codes(codes);
+        assert_eq!(code_sync.pending_len(), 1);
+        assert_eq!(code_sync.process(vec![code.clone()]).unwrap(), vec![(
+            Hasher::digest(&code),
+            code.clone()
+        )]);
+        assert!(code_sync.is_finished());
+        assert_eq!(code_sync.code_addresses(&Hasher::digest(&code)), &[
+            contract
+        ]);
+    }
+
+    #[test]
+    fn test_trie_sync_unexpected_node() {
+        let db = Arc::new(MemoryDB::new(false));
+        let mut trie = MPTTrie::new(Arc::clone(&db));
+        trie.insert(b"key", b"value").unwrap();
+        let root = trie.commit().unwrap();
+
+        let mut sync = TrieSync::new(TrieKind::Metadata, root);
+        assert!(sync
+            .process(vec![Bytes::from_static(b"unexpected")])
+            .is_err());
+        assert_eq!(sync.pending_hashes(10), vec![root]);
+
+        // A node can not be received twice.
+        let node = Bytes::from(db.get(root.as_bytes()).unwrap().unwrap());
+        assert!(sync.process(vec![node.clone(), node.clone()]).is_err());
+        assert_eq!(sync.process(vec![node]).unwrap().len(), 1);
+        assert!(sync.is_finished());
+
+        assert!(TrieSync::new(TrieKind::HeaderCell, Hash::default()).is_finished());
+    }
+}
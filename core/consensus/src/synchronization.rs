@@ -1,21 +1,40 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
+use futures::stream::{self, StreamExt};
 use parking_lot::RwLock;
 
 use common_apm::Instant;
 use common_apm_derive::trace_span;
+use core_network::NetworkContext;
+use protocol::lazy::{CURRENT_STATE_ROOT, SYSTEM_CONTRACT_ROOTS_LOCK};
 use protocol::tokio::{sync::Mutex, time::sleep};
 use protocol::traits::{Context, Synchronization, SynchronizationAdapter};
-use protocol::types::{Block, Proof, Proposal, Receipt, SignedTransaction, U256};
-use protocol::{async_trait, lazy::CURRENT_STATE_ROOT, ProtocolResult};
-
+use protocol::types::{
+    Block, Bytes, Hash, Proof, Proposal, Receipt, SignedTransaction, TrieKind, U256,
+};
+use protocol::{async_trait, ProtocolError, ProtocolResult};
+
+use crate::liveness::VALIDATOR_LIVENESS;
+use crate::message::{MAX_PULL_TRIE_NODES, MAX_PULL_TXS};
+use crate::snapshot::TrieSync;
 use crate::status::{CurrentStatus, StatusAgent};
 use crate::util::digest_signed_transactions;
+use crate::BlockProofField::{HashMismatch, HeightMismatch};
 use crate::{engine::generate_receipts_and_logs, ConsensusError};
 
 const POLLING_BROADCAST: u64 = 2000;
-const ONCE_SYNC_BLOCK_LIMIT: u64 = 50;
+const ONCE_SYNC_BLOCK_LIMIT: u64 = 1000;
+// The max number of blocks being downloaded and verified at the same time.
+const MAX_CONCURRENT_SYNC_BLOCKS: usize = 16;
+// The max number of peers tried for one block.
+const MAX_SYNC_BLOCK_RETRY: usize = 3;
+// A peer which has not broadcast its height for this long is not used to sync.
+const SYNC_PEER_EXPIRY: Duration = Duration::from_millis(3 * POLLING_BROADCAST);
+// The sync peers whose trust scores are in the same bucket of this size are
+// equally preferred.
+const TRUST_SCORE_BUCKET: u8 = 10;
 
 lazy_static::lazy_static! {
     pub static ref SYNC_STATUS: RwLock<SyncStatus> = RwLock::new(SyncStatus::default());
@@ -27,19 +46,42 @@ pub struct RichBlock {
     pub txs:   Vec<SignedTransaction>,
 }
 
+/// A downloaded block with its proof. The proof is verified in the download
+/// stage only if the metadata of the block is known at that time.
+struct SyncedBlock {
+    rich_block:     RichBlock,
+    proof:          Proof,
+    proof_verified: bool,
+    // The context of the peer which served the block.
+    peer_ctx:       Context,
+}
+
+/// A peer which has broadcast its height, the context carries its session.
+#[derive(Clone)]
+struct SyncPeer {
+    ctx:        Context,
+    number:     u64,
+    updated_at: Instant,
+}
+
 pub struct OverlordSynchronization<Adapter: SynchronizationAdapter> {
-    adapter: Arc<Adapter>,
-    status:  StatusAgent,
-    lock:    Arc<Mutex<()>>,
-    syncing: Mutex<()>,
+    adapter:    Arc<Adapter>,
+    status:     StatusAgent,
+    lock:       Arc<Mutex<()>>,
+    syncing:    Mutex<()>,
+    sync_peers: RwLock<HashMap<usize, SyncPeer>>,
 
     sync_txs_chunk_size: usize,
+    // The number and the hash of the trusted checkpoint block of snapshot sync.
+    snapshot_checkpoint: Option<(u64, Hash)>,
 }
 
 #[async_trait]
 impl<Adapter: SynchronizationAdapter> Synchronization for OverlordSynchronization<Adapter> {
     #[trace_span(kind = "consensus.sync", logs = "{remote_number: remote_number}")]
     async fn receive_remote_block(&self, ctx: Context, remote_number: u64) -> ProtocolResult<()> {
+        self.record_sync_peer(&ctx, remote_number);
+
         let syncing_lock = self.syncing.try_lock();
         if syncing_lock.is_err() {
             return Ok(());
@@ -53,6 +95,9 @@ impl<Adapter: SynchronizationAdapter> Synchronization for OverlordSynchronizatio
         if commit_lock.is_err() {
             return Ok(());
         }
+        // The system contracts are switched block by block, keep speculative
+        // execution out until the sync ends.
+        let _roots_lock = SYSTEM_CONTRACT_ROOTS_LOCK.lock().await;
 
         let current_number = self.status.inner().last_number;
 
@@ -67,14 +112,21 @@ impl<Adapter: SynchronizationAdapter> Synchronization for OverlordSynchronizatio
         );
 
         let sync_status_agent = self.init_status_agent().await?;
-        let sync_resp = self
-            .start_sync(
-                ctx.clone(),
-                sync_status_agent.clone(),
-                current_number,
-                remote_number,
-            )
-            .await;
+        let sync_resp = match self.snapshot_checkpoint {
+            Some((number, hash)) if self.need_snapshot_sync(current_number, remote_number) => {
+                self.start_snapshot_sync(ctx.clone(), sync_status_agent.clone(), number, hash)
+                    .await
+            }
+            _ => {
+                self.start_sync(
+                    ctx.clone(),
+                    sync_status_agent.clone(),
+                    current_number,
+                    remote_number,
+                )
+                .await
+            }
+        };
         let sync_status = sync_status_agent.inner();
 
         if let Err(e) = sync_resp {
@@ -102,6 +154,7 @@ impl<Adapter: SynchronizationAdapter> Synchronization for OverlordSynchronizatio
 impl<Adapter: SynchronizationAdapter> OverlordSynchronization<Adapter> {
     pub fn new(
         sync_txs_chunk_size: usize,
+        snapshot_checkpoint: Option<(u64, Hash)>,
         adapter: Arc<Adapter>,
         status: StatusAgent,
         lock: Arc<Mutex<()>>,
@@ -113,8 +166,11 @@ impl<Adapter: SynchronizationAdapter> OverlordSynchronization<Adapter> {
             status,
             lock,
             syncing,
+            sync_peers: RwLock::new(HashMap::new()),
 
-            sync_txs_chunk_size,
+            // The peers refuse to serve more transactions at once
+            sync_txs_chunk_size: sync_txs_chunk_size.min(MAX_PULL_TXS),
+            snapshot_checkpoint,
         }
     }
 
@@ -157,7 +213,24 @@ impl<Adapter: SynchronizationAdapter> OverlordSynchronization<Adapter> {
                 .start(current_consented_number, remote_number);
         }
 
-        while current_consented_number < remote_number {
+        // The proofs of the blocks in the current epoch can be verified in the
+        // download stage, the others have to wait for the metadata of their
+        // epochs which is known after the previous blocks are executed.
+        let metadata_version = self
+            .adapter
+            .get_metadata_unchecked(ctx.clone(), current_number + 1)
+            .version;
+
+        // The blocks are downloaded and verified from several peers at the same
+        // time, but yielded in order.
+        let mut synced_blocks = stream::iter(current_number + 1..=remote_number)
+            .map(|number| {
+                self.get_synced_block(ctx.clone(), number, metadata_version.contains(number))
+            })
+            .buffered(MAX_CONCURRENT_SYNC_BLOCKS);
+
+        let mut previous_proof: Option<Proof> = None;
+        while let Some(synced_block) = synced_blocks.next().await {
             let consenting_number = current_consented_number + 1;
             log::info!(
                 "[synchronization]: try syncing block, current_consented_number {},syncing_number {}",
@@ -165,26 +238,32 @@ impl<Adapter: SynchronizationAdapter> OverlordSynchronization<Adapter> {
                 consenting_number
             );
 
-            let consenting_rich_block: RichBlock = self
-                .get_rich_block_from_remote(ctx.clone(), consenting_number)
-                .await
-                .map_err(|e| {
-                    log::error!(
-                        "[synchronization]: get_rich_block_from_remote error, number: {:?}",
-                        consenting_number
-                    );
-                    e
-                })?;
+            let synced_block = synced_block.map_err(|e| {
+                log::error!(
+                    "[synchronization]: get synced block error, number: {:?}",
+                    consenting_number
+                );
+                e
+            })?;
 
-            let consenting_proof = self
-                .verify_block(ctx.clone(), &consenting_rich_block)
-                .await?;
+            if let Err(e) = self
+                .verify_block_in_order(ctx.clone(), &synced_block, previous_proof.as_ref())
+                .await
+            {
+                self.penalise_sync_peer(&synced_block.peer_ctx, consenting_number);
+                return Err(e);
+            }
 
             let inst = Instant::now();
+            let SyncedBlock {
+                rich_block: consenting_rich_block,
+                proof: consenting_proof,
+                ..
+            } = synced_block;
             self.commit_block(
                 ctx.clone(),
                 consenting_rich_block,
-                consenting_proof,
+                consenting_proof.clone(),
                 sync_status_agent.clone(),
             )
             .await
@@ -196,8 +275,8 @@ impl<Adapter: SynchronizationAdapter> OverlordSynchronization<Adapter> {
                 e
             })?;
 
+            previous_proof = Some(consenting_proof);
             current_consented_number += 1;
-
             common_apm::metrics::consensus::ENGINE_SYNC_BLOCK_COUNTER.inc_by(1u64);
             common_apm::metrics::consensus::ENGINE_SYNC_BLOCK_HISTOGRAM
                 .observe(common_apm::metrics::duration_to_sec(inst.elapsed()));
@@ -207,91 +286,466 @@ impl<Adapter: SynchronizationAdapter> OverlordSynchronization<Adapter> {
         Ok(())
     }
 
-    async fn verify_block(
+    /// A new node downloads the state of the trusted checkpoint block instead
+    /// of executing all the blocks up to it, once the remote chain reaches it.
+    fn need_snapshot_sync(&self, current_number: u64, remote_number: u64) -> bool {
+        match self.snapshot_checkpoint {
+            Some((number, _)) => current_number == 0 && number > 0 && remote_number >= number,
+            None => false,
+        }
+    }
+
+    /// Download the state trie and the system contract tries of the trusted
+    /// checkpoint block. The block is checked against the checkpoint hash
+    /// before anything is downloaded, so the roots in its header are trusted
+    /// and the tries are verified by them. The metadata of the synced state is
+    /// trusted in turn to verify the proof. The following blocks are
+    /// synchronized by executing them.
+    #[trace_span(kind = "consensus.sync", logs = "{number: number}")]
+    async fn start_snapshot_sync(
         &self,
         ctx: Context,
-        consenting_rich_block: &RichBlock,
-    ) -> ProtocolResult<Proof> {
-        let consenting_number = consenting_rich_block.block.header.number;
-        let proposal: Proposal = (&consenting_rich_block.block).into();
+        sync_status_agent: StatusAgent,
+        number: u64,
+        checkpoint_hash: Hash,
+    ) -> ProtocolResult<()> {
+        SYNC_STATUS.write().start(0, number);
+
+        let inst = Instant::now();
+        let (rich_block, proof) = futures::try_join!(
+            self.get_rich_block_from_remote(ctx.clone(), number),
+            self.adapter.get_proof_from_remote(ctx.clone(), number)
+        )?;
+
+        let block = &rich_block.block;
+        let block_hash = Proposal::from(block).hash();
+        if block.header.number != number || block_hash != checkpoint_hash {
+            return Err(ConsensusError::InvalidSnapshotCheckpoint {
+                number,
+                expect: checkpoint_hash,
+                actual: block_hash,
+            }
+            .into());
+        }
+        self.verify_remote_block(ctx.clone(), &rich_block, &proof, false)
+            .await?;
+
+        // Check the proof refers to the block before downloading the state, the
+        // signature is verified after the metadata is downloaded.
+        if proof.number != number {
+            return Err(
+                ConsensusError::VerifyProof(number, HeightMismatch(number, proof.number)).into(),
+            );
+        }
+        if block_hash != proof.block_hash {
+            return Err(ConsensusError::VerifyProof(number, HashMismatch).into());
+        }
+
+        log::info!(
+            "[synchronization]: snapshot sync start, block number {:?} state root {:?}",
+            number,
+            block.header.state_root
+        );
 
-        let consenting_proof: Proof = self
+        let mut state_sync = TrieSync::new(TrieKind::State, block.header.state_root);
+        self.sync_trie(ctx.clone(), &mut state_sync, number).await?;
+        let mut code_sync = TrieSync::new_codes(state_sync.take_codes());
+        self.sync_trie(ctx.clone(), &mut code_sync, number).await?;
+
+        let (metadata_root, header_cell_root) = self
             .adapter
-            .get_proof_from_remote(ctx.clone(), consenting_number)
-            .await
-            .map_err(|e| {
-                log::error!(
-                    "[synchronization]: get_proof_from_remote error, number {}",
-                    consenting_number
-                );
-                e
-            })?;
+            .get_system_contract_roots(ctx.clone(), block.header.state_root)?;
+        self.sync_trie(
+            ctx.clone(),
+            &mut TrieSync::new(TrieKind::Metadata, metadata_root),
+            number,
+        )
+        .await?;
+        self.sync_trie(
+            ctx.clone(),
+            &mut TrieSync::new(TrieKind::HeaderCell, header_cell_root),
+            number,
+        )
+        .await?;
 
         self.adapter
-            .verify_proof(
+            .update_system_contract_roots(ctx.clone(), metadata_root, header_cell_root);
+        if let Err(e) = self
+            .adapter
+            .verify_proof(ctx.clone(), block.clone(), proof.clone())
+            .await
+        {
+            // Switch the system contracts back to the current state.
+            let (metadata_root, header_cell_root) = self.adapter.get_system_contract_roots(
                 ctx.clone(),
-                consenting_rich_block.block.clone(),
-                consenting_proof.clone(),
+                sync_status_agent.inner().last_state_root,
+            )?;
+            self.adapter
+                .update_system_contract_roots(ctx, metadata_root, header_cell_root);
+            return Err(e);
+        }
+
+        let metadata = self.adapter.get_metadata(ctx.clone(), &block.header)?;
+        if self.adapter.need_change_metadata(number + 1) {
+            self.adapter.update_metadata(ctx.clone(), &block.header)?;
+        }
+
+        let new_status = CurrentStatus {
+            prev_hash:                  block.hash(),
+            last_number:                number,
+            last_state_root:            block.header.state_root,
+            tx_num_limit:               metadata.tx_num_limit,
+            max_tx_size:                metadata.max_tx_size.into(),
+            proof:                      proof.clone(),
+            last_checkpoint_block_hash: metadata.last_checkpoint_block_hash,
+        };
+
+        // The receipts of the block are unknown without executing it, the block
+        // is marked as receipt-less.
+        self.adapter
+            .save_signed_txs(ctx.clone(), number, rich_block.txs.clone())
+            .await?;
+        self.adapter
+            .save_snapshot_number(ctx.clone(), number)
+            .await?;
+        self.adapter
+            .save_proof(ctx.clone(), block.header.proof.clone())
+            .await?;
+        self.adapter.save_block(ctx, block.clone()).await?;
+
+        CURRENT_STATE_ROOT.swap(Arc::new(block.header.state_root));
+        sync_status_agent.swap(new_status);
+
+        log::info!(
+            "[synchronization]: snapshot sync end, block number {:?} cost {:?}",
+            number,
+            inst.elapsed()
+        );
+
+        Ok(())
+    }
+
+    /// Download all the nodes of the trie in chunks, the chunks are requested
+    /// from the alive peers in turn.
+    async fn sync_trie(
+        &self,
+        ctx: Context,
+        trie_sync: &mut TrieSync,
+        number: u64,
+    ) -> ProtocolResult<()> {
+        let kind = trie_sync.kind();
+        let mut round = 0usize;
+        let mut synced = 0usize;
+
+        while !trie_sync.is_finished() {
+            let hashes = trie_sync.pending_hashes(MAX_PULL_TRIE_NODES);
+            let nodes = self
+                .get_trie_nodes(ctx.clone(), trie_sync, &hashes, number, round)
+                .await?;
+            synced += nodes.len();
+            round += 1;
+
+            if kind == TrieKind::Code {
+                for (code_hash, code) in nodes.into_iter() {
+                    for address in trie_sync.code_addresses(&code_hash) {
+                        self.adapter
+                            .save_code(ctx.clone(), *address, code_hash, code.clone())
+                            .await?;
+                    }
+                }
+            } else {
+                self.adapter
+                    .save_trie_nodes(ctx.clone(), kind, nodes)
+                    .await?;
+            }
+
+            log::debug!(
+                "[synchronization]: snapshot sync {:?} trie, synced {} pending {}",
+                kind,
+                synced,
+                trie_sync.pending_len()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Get a chunk of trie nodes which makes progress, the peers which serve
+    /// invalid nodes are penalised.
+    async fn get_trie_nodes(
+        &self,
+        ctx: Context,
+        trie_sync: &mut TrieSync,
+        hashes: &[Hash],
+        number: u64,
+        round: usize,
+    ) -> ProtocolResult<Vec<(Hash, Bytes)>> {
+        let mut peers = self.select_sync_peers(&ctx, number);
+        let offset = round % peers.len();
+        peers.rotate_left(offset);
+
+        let kind = trie_sync.kind();
+        let mut last_err: Option<ProtocolError> = None;
+
+        for peer_ctx in peers.into_iter().take(MAX_SYNC_BLOCK_RETRY) {
+            let nodes = match self
+                .adapter
+                .get_trie_nodes_from_remote(peer_ctx.clone(), kind, hashes)
+                .await
+            {
+                Ok(nodes) if nodes.is_empty() => {
+                    last_err = Some(
+                        ConsensusError::Other(format!("peer has no {:?} trie nodes", kind)).into(),
+                    );
+                    continue;
+                }
+                Ok(nodes) => nodes,
+                Err(e) => {
+                    last_err = Some(e);
+                    continue;
+                }
+            };
+
+            match trie_sync.process(nodes) {
+                Ok(verified) => return Ok(verified),
+                Err(e) => {
+                    log::warn!("[synchronization]: snapshot sync error {:?}", e);
+                    self.adapter.report_bad_peer(
+                        peer_ctx.clone(),
+                        format!("sync invalid {:?} trie nodes", kind),
+                    );
+                    self.remove_sync_peer(&peer_ctx);
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        Err(last_err.expect("at least one peer is selected"))
+    }
+
+    fn record_sync_peer(&self, ctx: &Context, remote_number: u64) {
+        if let Ok(session_id) = ctx.session_id() {
+            self.sync_peers
+                .write()
+                .insert(session_id.value(), SyncPeer {
+                    ctx:        ctx.clone(),
+                    number:     remote_number,
+                    updated_at: Instant::now(),
+                });
+        }
+    }
+
+    fn remove_sync_peer(&self, ctx: &Context) {
+        if let Ok(session_id) = ctx.session_id() {
+            self.sync_peers.write().remove(&session_id.value());
+        }
+    }
+
+    fn penalise_sync_peer(&self, ctx: &Context, number: u64) {
+        self.adapter
+            .report_bad_peer(ctx.clone(), format!("sync invalid block {}", number));
+        self.remove_sync_peer(ctx);
+    }
+
+    /// Return the contexts of the alive peers which hold the block of the
+    /// given number. The peers are rotated by the number so that the
+    /// adjacent blocks are downloaded from different peers, then the peers of
+    /// higher trust are moved to the front. The given context is returned if
+    /// there is no such peer.
+    fn select_sync_peers(&self, ctx: &Context, number: u64) -> Vec<Context> {
+        let mut peers = self
+            .sync_peers
+            .read()
+            .iter()
+            .filter(|(_, peer)| {
+                peer.number >= number && peer.updated_at.elapsed() < SYNC_PEER_EXPIRY
+            })
+            .map(|(session_id, peer)| (*session_id, peer.ctx.clone()))
+            .collect::<Vec<_>>();
+
+        if peers.is_empty() {
+            return vec![ctx.clone()];
+        }
+
+        peers.sort_unstable_by_key(|(session_id, _)| *session_id);
+        let offset = (number % peers.len() as u64) as usize;
+        peers.rotate_left(offset);
+        peers.sort_by_cached_key(|(_, ctx)| {
+            std::cmp::Reverse(
+                self.adapter
+                    .peer_trust_score(ctx)
+                    .map_or(0, |score| score / TRUST_SCORE_BUCKET),
             )
+        });
+        peers.into_iter().map(|(_, ctx)| ctx).collect()
+    }
+
+    async fn get_synced_block(
+        &self,
+        ctx: Context,
+        number: u64,
+        verify_proof: bool,
+    ) -> ProtocolResult<SyncedBlock> {
+        let mut last_err = None;
+
+        for peer_ctx in self
+            .select_sync_peers(&ctx, number)
+            .into_iter()
+            .take(MAX_SYNC_BLOCK_RETRY)
+        {
+            match self
+                .get_synced_block_from_peer(peer_ctx, number, verify_proof)
+                .await
+            {
+                Ok(synced_block) => return Ok(synced_block),
+                Err(e) => {
+                    log::warn!(
+                        "[synchronization]: get block {} from peer error {:?}",
+                        number,
+                        e
+                    );
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        Err(last_err.expect("at least one peer is selected"))
+    }
+
+    async fn get_synced_block_from_peer(
+        &self,
+        ctx: Context,
+        number: u64,
+        verify_proof: bool,
+    ) -> ProtocolResult<SyncedBlock> {
+        let (rich_block, proof) = futures::try_join!(
+            self.get_rich_block_from_remote(ctx.clone(), number),
+            self.adapter.get_proof_from_remote(ctx.clone(), number)
+        )?;
+
+        if let Err(e) = self
+            .verify_remote_block(ctx.clone(), &rich_block, &proof, verify_proof)
             .await
-            .map_err(|e| {
-                log::error!(
-                    "[synchronization]: verify_proof error, syncing block header: {:?}, proof: {:?}",
-                    consenting_rich_block.block.header,
-                    consenting_proof,
-                );
-                e
-            })?;
+        {
+            self.penalise_sync_peer(&ctx, number);
+            return Err(e);
+        }
 
+        Ok(SyncedBlock {
+            rich_block,
+            proof,
+            proof_verified: verify_proof,
+            peer_ctx: ctx,
+        })
+    }
+
+    /// Verify the block with the data of itself, which does not depend on the
+    /// previous blocks.
+    async fn verify_remote_block(
+        &self,
+        ctx: Context,
+        rich_block: &RichBlock,
+        proof: &Proof,
+        verify_proof: bool,
+    ) -> ProtocolResult<()> {
+        let signed_txs_hash = digest_signed_transactions(&rich_block.txs);
+        if signed_txs_hash != rich_block.block.header.signed_txs_hash {
+            return Err(ConsensusError::InvalidOrderSignedTransactionsHash {
+                expect: signed_txs_hash,
+                actual: rich_block.block.header.signed_txs_hash,
+            }
+            .into());
+        }
+
+        if verify_proof {
+            self.adapter
+                .verify_proof(ctx, rich_block.block.clone(), proof.clone())
+                .await
+                .map_err(|e| {
+                    log::error!(
+                        "[synchronization]: verify_proof error, syncing block header: {:?}, proof: {:?}",
+                        rich_block.block.header,
+                        proof,
+                    );
+                    e
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Verify the block against the previous block, which has been committed.
+    /// The proof of the previous block in the header is verified again only if
+    /// it differs from the verified one.
+    async fn verify_block_in_order(
+        &self,
+        ctx: Context,
+        synced_block: &SyncedBlock,
+        previous_proof: Option<&Proof>,
+    ) -> ProtocolResult<()> {
+        let rich_block = &synced_block.rich_block;
+
+        if !synced_block.proof_verified {
+            self.adapter
+                .verify_proof(
+                    ctx.clone(),
+                    rich_block.block.clone(),
+                    synced_block.proof.clone(),
+                )
+                .await
+                .map_err(|e| {
+                    log::error!(
+                        "[synchronization]: verify_proof error, syncing block header: {:?}, proof: {:?}",
+                        rich_block.block.header,
+                        synced_block.proof,
+                    );
+                    e
+                })?;
+        }
+
+        let proposal: Proposal = (&rich_block.block).into();
         self.adapter
             .verify_block_header(ctx.clone(), &proposal)
             .await
             .map_err(|e| {
                 log::error!(
                     "[synchronization]: verify_block_header error, block header: {:?}",
-                    consenting_rich_block.block.header
+                    rich_block.block.header
                 );
                 e
             })?;
 
+        if previous_proof == Some(&rich_block.block.header.proof) {
+            return Ok(());
+        }
+
         let previous_block = self
             .adapter
-            .get_block_by_number(ctx.clone(), consenting_rich_block.block.header.number - 1)
+            .get_block_by_number(ctx.clone(), rich_block.block.header.number - 1)
             .await
             .map_err(|e| {
                 log::error!(
                     "[synchronization] get previous block {} error",
-                    consenting_rich_block.block.header.number - 1
+                    rich_block.block.header.number - 1
                 );
                 e
             })?;
 
         self.adapter
             .verify_proof(
-                ctx.clone(),
+                ctx,
                 previous_block.clone(),
-                consenting_rich_block.block.header.proof.clone(),
+                rich_block.block.header.proof.clone(),
             )
             .await
             .map_err(|e| {
                 log::error!(
                     "[synchronization]: verify_proof error, previous block header: {:?}, proof: {:?}",
                     previous_block.header,
-                    consenting_rich_block.block.header.proof
+                    rich_block.block.header.proof
                 );
                 e
-            })?;
-
-        let signed_txs_hash = digest_signed_transactions(&consenting_rich_block.txs);
-        if signed_txs_hash != consenting_rich_block.block.header.signed_txs_hash {
-            return Err(ConsensusError::InvalidOrderSignedTransactionsHash {
-                expect: signed_txs_hash,
-                actual: consenting_rich_block.block.header.signed_txs_hash,
-            }
-            .into());
-        }
-
-        Ok(consenting_proof)
+            })
     }
 
     async fn init_status_agent(&self) -> ProtocolResult<StatusAgent> {
@@ -364,6 +818,14 @@ impl<Adapter: SynchronizationAdapter> OverlordSynchronization<Adapter> {
         CURRENT_STATE_ROOT.swap(Arc::new(resp.state_root));
         status_agent.swap(new_status);
 
+        if let Err(e) = VALIDATOR_LIVENESS.write().record(
+            block.header.proposer,
+            &proof,
+            &metadata.verifier_list,
+        ) {
+            log::warn!("[synchronization] record validator liveness error {:?}", e);
+        }
+
         // If there are transactions in the transaction pool that have been on chain
         // after this execution, make sure they are cleaned up.
         self.adapter
@@ -578,7 +1040,9 @@ mod tests {
         let synchronization = get_mock_synchronization();
         let ctx = Context::default();
         let genesis = get_mock_rick_block();
-        let result = synchronization.verify_block(ctx, &genesis).await;
+        let result = synchronization
+            .verify_remote_block(ctx, &genesis, &Proof::default(), true)
+            .await;
         assert!(result.is_err());
     }
 
@@ -603,6 +1067,61 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_select_sync_peers() {
+        let sync = get_mock_synchronization();
+        let origin = Context::default();
+
+        // Fall back to the given context without any alive peer.
+        assert_eq!(sync.select_sync_peers(&origin, 1).len(), 1);
+
+        for session_id in 0..3usize {
+            let ctx = Context::default().with_value::<usize>("session_id", session_id);
+            sync.record_sync_peer(&ctx, 10 + session_id as u64);
+        }
+
+        let session_ids = |peers: Vec<Context>| {
+            peers
+                .iter()
+                .map(|ctx| ctx.session_id().unwrap().value())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(session_ids(sync.select_sync_peers(&origin, 10)), vec![
+            1, 2, 0
+        ]);
+        assert_eq!(session_ids(sync.select_sync_peers(&origin, 11)), vec![2, 1]);
+        assert_eq!(session_ids(sync.select_sync_peers(&origin, 12)), vec![2]);
+        assert_eq!(sync.select_sync_peers(&origin, 13).len(), 1);
+
+        sync.remove_sync_peer(&Context::default().with_value::<usize>("session_id", 2));
+        assert_eq!(session_ids(sync.select_sync_peers(&origin, 10)), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_need_snapshot_sync() {
+        let mut sync = get_mock_synchronization();
+        assert!(!sync.need_snapshot_sync(0, ONCE_SYNC_BLOCK_LIMIT + 1));
+
+        sync.snapshot_checkpoint = Some((ONCE_SYNC_BLOCK_LIMIT, Hash::default()));
+        assert!(sync.need_snapshot_sync(0, ONCE_SYNC_BLOCK_LIMIT + 1));
+        assert!(sync.need_snapshot_sync(0, ONCE_SYNC_BLOCK_LIMIT));
+        assert!(!sync.need_snapshot_sync(0, ONCE_SYNC_BLOCK_LIMIT - 1));
+        assert!(!sync.need_snapshot_sync(1, ONCE_SYNC_BLOCK_LIMIT + 2));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_sync_untrusted_block() {
+        let sync = get_mock_synchronization();
+        let res = sync
+            .start_snapshot_sync(Context::new(), sync.status.clone(), 0, Hash::repeat_byte(1))
+            .await;
+
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("InvalidSnapshotCheckpoint"));
+    }
+
     #[tokio::test]
     async fn test_need_sync() {
         let sync = get_mock_synchronization();
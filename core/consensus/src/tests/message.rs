@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use protocol::traits::{Context, MessageCodec, MessageHandler, Priority, Rpc, TrustFeedback};
+use protocol::types::Hash;
+use protocol::{async_trait, tokio, ProtocolResult};
+
+use core_storage::{adapter::memory::MemoryAdapter, ImplStorage};
+
+use crate::message::{PullTxsRequest, PullTxsRpcHandler, MAX_PULL_TXS, RPC_RESP_SYNC_PULL_TXS};
+use crate::ConsensusError;
+
+/// Records the endpoints and the errors of the responses
+#[derive(Default)]
+struct MockRpc {
+    responses: Mutex<Vec<(String, Option<String>)>>,
+}
+
+#[async_trait]
+impl Rpc for MockRpc {
+    async fn call<M, R>(&self, _: Context, end: &str, _: M, _: Priority) -> ProtocolResult<R>
+    where
+        M: MessageCodec,
+        R: MessageCodec,
+    {
+        Err(ConsensusError::Other(format!("unexpected call to {}", end)).into())
+    }
+
+    async fn call_trusted<M, R>(
+        &self,
+        _: Context,
+        end: &str,
+        _: M,
+        _: Priority,
+    ) -> ProtocolResult<R>
+    where
+        M: MessageCodec,
+        R: MessageCodec,
+    {
+        Err(ConsensusError::Other(format!("unexpected call to {}", end)).into())
+    }
+
+    async fn response<M>(
+        &self,
+        _: Context,
+        end: &str,
+        ret: ProtocolResult<M>,
+        _: Priority,
+    ) -> ProtocolResult<()>
+    where
+        M: MessageCodec,
+    {
+        let err = ret.err().map(|e| e.to_string());
+        self.responses.lock().unwrap().push((end.to_owned(), err));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_pull_txs_limit() {
+    let rpc = Arc::new(MockRpc::default());
+    let storage = Arc::new(ImplStorage::new(Arc::new(MemoryAdapter::new()), 10));
+    let handler = PullTxsRpcHandler::new(Arc::clone(&rpc), storage);
+
+    let feedback = handler
+        .process(
+            Context::new(),
+            PullTxsRequest::new(1, vec![Hash::default(); MAX_PULL_TXS]),
+        )
+        .await;
+    assert!(matches!(feedback, TrustFeedback::Neutral));
+
+    // The request is refused with an error response
+    let feedback = handler
+        .process(
+            Context::new(),
+            PullTxsRequest::new(1, vec![Hash::default(); MAX_PULL_TXS + 1]),
+        )
+        .await;
+    assert!(matches!(feedback, TrustFeedback::Bad(_)));
+
+    let responses = rpc.responses.lock().unwrap();
+    assert_eq!(responses.len(), 2);
+    assert!(responses
+        .iter()
+        .all(|(end, _)| end == RPC_RESP_SYNC_PULL_TXS));
+    assert!(responses[0].1.is_none());
+    assert!(responses[1]
+        .1
+        .as_ref()
+        .unwrap()
+        .contains(&format!("more than {}", MAX_PULL_TXS)));
+}
@@ -1,6 +1,7 @@
 #![allow(unused_variables)]
 
 // mod engine;
+mod message;
 pub mod synchronization;
 
 use std::{collections::HashMap, str::FromStr};
@@ -13,7 +14,7 @@ use protocol::{
     types::{
         Address, Block, BlockNumber, Bytes, Eip1559Transaction, ExecResp, Hash, Hasher, Header,
         Hex, MerkleRoot, Metadata, Proof, Proposal, Public, Receipt, SignatureComponents,
-        SignedTransaction, TransactionAction, UnsignedTransaction, UnverifiedTransaction,
+        SignedTransaction, TransactionAction, TrieKind, UnsignedTransaction, UnverifiedTransaction,
         Validator, H160, H256, U256,
     },
     ProtocolResult,
@@ -194,6 +195,44 @@ impl SynchronizationAdapter for MockSyncAdapter {
         );
         Some(tx)
     }
+
+    fn report_bad_peer(&self, ctx: Context, reason: String) {}
+
+    fn peer_trust_score(&self, ctx: &Context) -> Option<u8> {
+        None
+    }
+
+    async fn get_trie_nodes_from_remote(
+        &self,
+        ctx: Context,
+        kind: TrieKind,
+        hashes: &[Hash],
+    ) -> ProtocolResult<Vec<Bytes>> {
+        Ok(vec![])
+    }
+
+    async fn save_trie_nodes(
+        &self,
+        ctx: Context,
+        kind: TrieKind,
+        nodes: Vec<(Hash, Bytes)>,
+    ) -> ProtocolResult<()> {
+        Ok(())
+    }
+
+    async fn save_code(
+        &self,
+        ctx: Context,
+        address: H160,
+        code_hash: Hash,
+        code: Bytes,
+    ) -> ProtocolResult<()> {
+        Ok(())
+    }
+
+    async fn save_snapshot_number(&self, ctx: Context, number: u64) -> ProtocolResult<()> {
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -339,4 +378,20 @@ impl CommonConsensusAdapter for MockSyncAdapter {
     ) -> ProtocolResult<()> {
         Ok(())
     }
+
+    fn get_system_contract_roots(
+        &self,
+        ctx: Context,
+        state_root: MerkleRoot,
+    ) -> ProtocolResult<(Hash, Hash)> {
+        Ok((H256::default(), H256::default()))
+    }
+
+    fn update_system_contract_roots(
+        &self,
+        ctx: Context,
+        metadata_root: Hash,
+        header_cell_root: Hash,
+    ) {
+    }
 }
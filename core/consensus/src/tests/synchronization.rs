@@ -19,7 +19,13 @@ pub fn get_mock_synchronization() -> OverlordSynchronization<MockSyncAdapter> {
     let status_agent = StatusAgent::new(CurrentStatus::default());
     let lock = Arc::new(AsyncMutex::new(()));
 
-    OverlordSynchronization::<_>::new(sync_txs_chunk_size, consensus_adapter, status_agent, lock)
+    OverlordSynchronization::<_>::new(
+        sync_txs_chunk_size,
+        None,
+        consensus_adapter,
+        status_agent,
+        lock,
+    )
 }
 
 pub fn get_mock_rick_block() -> RichBlock {
@@ -76,6 +82,7 @@ async fn test_receive_remote_block() {
 
     let synchronization = Arc::new(OverlordSynchronization::<_>::new(
         sync_txs_chunk_size,
+        None,
         consensus_adapter,
         status_agent.clone(),
         lock,
@@ -1,7 +1,7 @@
 use rlp_derive::{RlpDecodable, RlpEncodable};
 
 use protocol::codec::ProtocolCodec;
-use protocol::types::{BatchSignedTxs, Block, Bytes, Hash};
+use protocol::types::{BatchSignedTxs, Block, Bytes, Hash, TrieKind};
 use protocol::{traits::MessageCodec, ProtocolResult};
 
 use crate::{ConsensusError, ConsensusType};
@@ -67,3 +67,20 @@ impl PullTxsRequest {
         PullTxsRequest { height, inner }
     }
 }
+
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct PullTrieNodesRequest {
+    pub kind:   TrieKind,
+    pub hashes: Vec<Hash>,
+}
+
+impl PullTrieNodesRequest {
+    pub fn new(kind: TrieKind, hashes: Vec<Hash>) -> Self {
+        PullTrieNodesRequest { kind, hashes }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, RlpEncodable, RlpDecodable)]
+pub struct PullTrieNodesResponse {
+    pub nodes: Vec<Bytes>,
+}
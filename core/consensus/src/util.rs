@@ -140,6 +140,11 @@ impl OverlordCrypto {
         *map = new_addr_pubkey;
     }
 
+    /// Whether the address belongs to a validator of the current metadata.
+    pub fn is_validator(&self, address: &Bytes) -> bool {
+        self.addr_pubkey.read().contains_key(address)
+    }
+
     pub fn inner_verify_aggregated_signature(
         &self,
         hash: Bytes,
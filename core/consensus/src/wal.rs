@@ -1,14 +1,45 @@
-use std::io::{ErrorKind, Read, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::{fs, str::FromStr, time::SystemTime};
 
 use common_apm_derive::trace_span;
+use common_merkle::TrieMerkle;
+use protocol::codec::ProtocolCodec;
 use protocol::traits::{Context, MessageCodec};
-use protocol::types::{BatchSignedTxs, BufMut, Bytes, BytesMut, Hash, Hasher, SignedTransaction};
+use protocol::types::{
+    BatchSignedTxs, BufMut, Bytes, BytesMut, Hash, Hasher, MerkleRoot, SignedTransaction, RLP_NULL,
+};
 use protocol::ProtocolResult;
 
 use crate::ConsensusError;
 
+/// A record of the WAL files is `len (u32 BE) | keccak(payload) | payload`, so
+/// that a torn write at the tail of a file is detected when it is read.
+const RECORD_HEADER_LEN: usize = 4 + 32;
+/// A WAL file starts with the magic and the format version (u32 BE) before
+/// its records. The files of the signed transactions without the magic are
+/// written by the older versions as a RLP encoded `BatchSignedTxs`, and the
+/// consensus WAL files as `keccak(info) | info`.
+const TXS_MAGIC: &[u8; 4] = b"AXTX";
+const TXS_VERSION: u32 = 1;
+const CONSENSUS_MAGIC: &[u8; 4] = b"AXWL";
+const CONSENSUS_VERSION: u32 = 1;
+const FILE_HEADER_LEN: usize = 4 + 4;
+const LEGACY_CHECKSUM_LEN: usize = 32;
+const TXS_EXTENSION: &str = "txt";
+const TMP_EXTENSION: &str = "tmp";
+
+/// The valid records and the corrupt tail of a WAL file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalFileInfo {
+    pub path:          PathBuf,
+    /// The number of the valid records, which is the number of the
+    /// transactions for a file of the signed transactions.
+    pub records:       usize,
+    /// The length of the corrupt tail, which is skipped when the file is read.
+    pub corrupt_bytes: usize,
+}
+
 #[derive(Debug)]
 pub struct SignedTxsWAL {
     path: PathBuf,
@@ -38,28 +69,14 @@ impl SignedTxsWAL {
         }
 
         wal_path.push(ordered_signed_transactions_hash.to_string());
-        wal_path.set_extension("txt");
+        wal_path.set_extension(TXS_EXTENSION);
 
-        let mut wal_file = match fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(wal_path)
-        {
-            Ok(file) => file,
-            Err(err) => {
-                if err.kind() == ErrorKind::AlreadyExists {
-                    return Ok(());
-                } else {
-                    return Err(ConsensusError::WALErr(err).into());
-                }
-            }
-        };
+        // The file of the same hash contains the same transactions.
+        if wal_path.exists() && matches!(read_txs_file(&wal_path), Ok((_, 0))) {
+            return Ok(());
+        }
 
-        wal_file
-            .write_all(BatchSignedTxs(txs).encode_msg()?.as_ref())
-            .map_err(ConsensusError::WALErr)?;
-        Ok(())
+        write_atomically(&wal_path, &encode_txs_file(&txs)?)
     }
 
     pub fn available_number(&self) -> ProtocolResult<Vec<u64>> {
@@ -69,7 +86,9 @@ impl SignedTxsWAL {
             let item = item.map_err(ConsensusError::WALErr)?;
 
             if item.path().is_dir() {
-                availables.push(item.file_name().to_str().unwrap().parse().unwrap())
+                if let Some(number) = item.file_name().to_str().and_then(|n| n.parse().ok()) {
+                    availables.push(number);
+                }
             }
         }
         Ok(availables)
@@ -90,9 +109,18 @@ impl SignedTxsWAL {
         let mut file_path = self.path.clone();
         file_path.push(number.to_string());
         file_path.push(ordered_signed_transactions_hash.to_string());
-        file_path.set_extension("txt");
+        file_path.set_extension(TXS_EXTENSION);
 
-        self.recover_stxs(file_path)
+        // The transactions are executed as a whole, so a torn file must not be
+        // taken as the transactions of the block.
+        let (txs, corrupt_bytes) = read_txs_file(&file_path)?;
+        if corrupt_bytes > 0 {
+            return Err(ConsensusError::WalTxsTruncated(number, corrupt_bytes).into());
+        }
+        if transactions_root(&txs) != ordered_signed_transactions_hash {
+            return Err(ConsensusError::WalTxsMismatch(number).into());
+        }
+        Ok(txs)
     }
 
     pub fn load_by_number(&self, number: u64) -> Vec<SignedTransaction> {
@@ -106,8 +134,18 @@ impl SignedTxsWAL {
 
         let mut ret = Vec::new();
         for entry in dir.flatten() {
-            if let Ok(mut stxs) = self.recover_stxs(entry.path()) {
-                ret.append(&mut stxs);
+            if !has_extension(&entry.path(), TXS_EXTENSION) {
+                continue;
+            }
+
+            match read_txs_file(&entry.path()) {
+                Ok((mut stxs, 0)) => ret.append(&mut stxs),
+                Ok((_, corrupt_bytes)) => log::warn!(
+                    "[consensus] skip wal file {:?} with a corrupt tail of {} bytes",
+                    entry.path(),
+                    corrupt_bytes
+                ),
+                Err(e) => log::warn!("[consensus] read wal file {:?} error {}", entry.path(), e),
             }
         }
         ret
@@ -135,14 +173,85 @@ impl SignedTxsWAL {
         Ok(())
     }
 
-    fn recover_stxs(&self, file_path: PathBuf) -> ProtocolResult<Vec<SignedTransaction>> {
-        let mut read_buf = Vec::new();
-        let mut file = fs::File::open(file_path).map_err(ConsensusError::WALErr)?;
-        let _ = file
-            .read_to_end(&mut read_buf)
-            .map_err(ConsensusError::WALErr)?;
-        let txs = BatchSignedTxs::decode_msg(Bytes::from(read_buf))?;
-        Ok(txs.inner())
+    /// List the files of the stored heights in ascending order.
+    pub fn inspect(&self) -> ProtocolResult<Vec<(u64, Vec<WalFileInfo>)>> {
+        let mut numbers = self.available_number()?;
+        numbers.sort_unstable();
+
+        let mut ret = Vec::with_capacity(numbers.len());
+        for number in numbers {
+            let mut files = Vec::new();
+            for path in self.files_of(number)? {
+                if has_extension(&path, TXS_EXTENSION) {
+                    let (txs, corrupt_bytes) = read_txs_file(&path)?;
+                    files.push(WalFileInfo {
+                        path,
+                        records: txs.len(),
+                        corrupt_bytes,
+                    });
+                }
+            }
+            ret.push((number, files));
+        }
+        Ok(ret)
+    }
+
+    /// Remove the files with corrupt tails, whose valid records never match
+    /// the transactions root of a block, and the temporary files left by a
+    /// crash. Return the number of the removed files.
+    pub fn repair(&self) -> ProtocolResult<usize> {
+        let mut repaired = 0;
+        for number in self.available_number()? {
+            for path in self.files_of(number)? {
+                let corrupt = has_extension(&path, TMP_EXTENSION)
+                    || (has_extension(&path, TXS_EXTENSION) && read_txs_file(&path)?.1 > 0);
+
+                if corrupt {
+                    fs::remove_file(&path).map_err(ConsensusError::WALErr)?;
+                    repaired += 1;
+                }
+            }
+        }
+        Ok(repaired)
+    }
+
+    /// Remove the heights which the node never loads again after the given
+    /// block is committed, as the commit does, and rewrite the files of the
+    /// older versions in the current format. Return the number of the changed
+    /// files.
+    pub fn compact(&self, committed_number: u64) -> ProtocolResult<usize> {
+        let stale_number = committed_number.saturating_sub(2);
+        let mut compacted = 0;
+        for number in self.available_number()? {
+            if number <= stale_number {
+                compacted += self.files_of(number)?.len();
+                continue;
+            }
+
+            for path in self.files_of(number)? {
+                if !has_extension(&path, TXS_EXTENSION) || has_magic(&path, TXS_MAGIC)? {
+                    continue;
+                }
+                if let (txs, 0) = read_txs_file(&path)? {
+                    write_atomically(&path, &encode_txs_file(&txs)?)?;
+                    compacted += 1;
+                }
+            }
+        }
+        self.remove(stale_number)?;
+        Ok(compacted)
+    }
+
+    fn files_of(&self, number: u64) -> ProtocolResult<Vec<PathBuf>> {
+        let mut dir = self.path.clone();
+        dir.push(number.to_string());
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).map_err(ConsensusError::WALErr)? {
+            files.push(entry.map_err(ConsensusError::WALErr)?.path());
+        }
+        files.sort();
+        Ok(files)
     }
 }
 
@@ -171,11 +280,7 @@ impl ConsensusWal {
         }
 
         // 2nd, write info into file
-        let check_sum = Hasher::digest(info.clone());
-
-        let mut content = BytesMut::new();
-        content.put(check_sum.as_bytes());
-        content.put(info);
+        let content = encode_consensus_file(&[info]);
 
         let (data_path, timestamp) = {
             loop {
@@ -195,25 +300,7 @@ impl ConsensusWal {
             }
         };
 
-        let mut data_file = match fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(data_path)
-        {
-            Ok(file) => file,
-            Err(err) => {
-                if err.kind() == ErrorKind::AlreadyExists {
-                    return Ok(());
-                } else {
-                    return Err(ConsensusError::WALErr(err).into());
-                }
-            }
-        };
-
-        data_file
-            .write_all(content.as_ref())
-            .map_err(ConsensusError::WALErr)?;
+        write_atomically(&data_path, &content)?;
 
         // 3rd, we can safely clean other old wal files
         for item in fs::read_dir(dir_path).map_err(ConsensusError::WALErr)? {
@@ -225,8 +312,12 @@ impl ConsensusWal {
                 .ok_or(ConsensusError::FileNameTimestamp)?
                 .to_owned();
 
-            let file_name_timestamp = u128::from_str(file_name.as_str())
-                .map_err(|_| ConsensusError::FileNameTimestamp)?;
+            // The temporary files are left by the writes which are interrupted.
+            let file_name_timestamp = match u128::from_str(file_name.as_str()) {
+                Ok(file_name_timestamp) => file_name_timestamp,
+                Err(_) if has_extension(&item.path(), TMP_EXTENSION) => 0,
+                Err(_) => return Err(ConsensusError::FileNameTimestamp.into()),
+            };
 
             if file_name_timestamp < timestamp {
                 fs::remove_file(item.path()).map_err(ConsensusError::WALErr)?;
@@ -244,8 +335,83 @@ impl ConsensusWal {
             return Err(ConsensusError::ConsensusWalDirNotExist.into());
         }
 
-        // 2 read all log files and sort by timestamp in their names
-        let files = fs::read_dir(dir_path.clone()).map_err(ConsensusError::WALErr)?;
+        // 2nd, get a latest and valid wal if possible
+        for file_name_timestamp in self.timestamps()?.into_iter().rev() {
+            let mut log_path = dir_path.clone();
+            log_path.push(file_name_timestamp.to_string());
+
+            match read_consensus_file_with_warning(&log_path) {
+                Ok((records, _)) if !records.is_empty() => return Ok(records[0].clone()),
+                Ok(_) => log::warn!("[consensus] skip invalid wal file {:?}", log_path),
+                Err(e) => log::warn!("[consensus] read wal file {:?} error {}", log_path, e),
+            }
+        }
+
+        Err(ConsensusError::ConsensusWalNoWalFile.into())
+    }
+
+    /// Keep the latest valid state only, in the current format.
+    pub fn compact(&self) -> ProtocolResult<()> {
+        match self.load_overlord_wal(Context::new()) {
+            Ok(info) => self.update_overlord_wal(Context::new(), info),
+            Err(_) => Ok(()),
+        }
+    }
+
+    pub fn clear(&self) -> ProtocolResult<()> {
+        let dir_path = self.path.clone();
+        if !dir_path.exists() {
+            return Ok(());
+        }
+
+        for item in fs::read_dir(dir_path).map_err(ConsensusError::WALErr)? {
+            let item = item.map_err(ConsensusError::WALErr)?;
+
+            fs::remove_file(item.path()).map_err(ConsensusError::WALErr)?;
+        }
+        Ok(())
+    }
+
+    /// List the wal files in ascending order of their timestamps.
+    pub fn inspect(&self) -> ProtocolResult<Vec<WalFileInfo>> {
+        let mut ret = Vec::new();
+        for file_name_timestamp in self.timestamps()? {
+            let mut log_path = self.path.clone();
+            log_path.push(file_name_timestamp.to_string());
+            let (records, corrupt_bytes) = read_consensus_file(&log_path)?;
+            ret.push(WalFileInfo {
+                path: log_path,
+                records: records.len(),
+                corrupt_bytes,
+            });
+        }
+        Ok(ret)
+    }
+
+    /// Remove the invalid wal files and the temporary files left by a crash,
+    /// and cut the corrupt tails of the others. Return the number of the
+    /// repaired files.
+    pub fn repair(&self) -> ProtocolResult<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+
+        let mut repaired = 0;
+        for item in fs::read_dir(&self.path).map_err(ConsensusError::WALErr)? {
+            let path = item.map_err(ConsensusError::WALErr)?.path();
+
+            if has_extension(&path, TMP_EXTENSION) {
+                fs::remove_file(&path).map_err(ConsensusError::WALErr)?;
+                repaired += 1;
+            } else if repair_wal_file(&path)? {
+                repaired += 1;
+            }
+        }
+        Ok(repaired)
+    }
+
+    fn timestamps(&self) -> ProtocolResult<Vec<u128>> {
+        let files = fs::read_dir(&self.path).map_err(ConsensusError::WALErr)?;
 
         let mut file_names_timestamps = files
             .filter_map(|item| {
@@ -253,64 +419,196 @@ impl ConsensusWal {
                 let file_name = item.file_name();
                 let file_name = file_name.to_str()?;
 
-                let file_name_timestamp = u128::from_str(file_name).ok()?;
-
-                Some(file_name_timestamp)
+                u128::from_str(file_name).ok()
             })
             .collect::<Vec<_>>();
 
-        file_names_timestamps.sort_by_key(|&b| std::cmp::Reverse(b));
+        file_names_timestamps.sort_unstable();
+        Ok(file_names_timestamps)
+    }
+}
+
+fn put_record(buf: &mut BytesMut, payload: &[u8]) {
+    buf.put_u32(payload.len() as u32);
+    buf.put(Hasher::digest(payload).as_bytes());
+    buf.put(payload);
+}
 
-        // 3rd, get a latest and valid wal if possible
-        let mut index = 0;
-        let content = loop {
-            if index >= file_names_timestamps.len() {
-                break None;
-            }
+/// Read the records from the beginning until the end or the first corrupt
+/// one. Return the valid records and the length of the corrupt tail.
+fn read_records(content: Bytes) -> (Vec<Bytes>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
 
-            let file_name_timestamp = file_names_timestamps[index];
+    while content.len() - offset >= RECORD_HEADER_LEN {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&content[offset..offset + 4]);
+        let len = u32::from_be_bytes(len) as usize;
 
-            let mut log_path = dir_path.clone();
-            log_path.push(file_name_timestamp.to_string());
+        let start = offset + RECORD_HEADER_LEN;
+        if content.len() - start < len {
+            break;
+        }
 
-            let mut read_buf = Vec::new();
-            let mut file = fs::File::open(&log_path).map_err(ConsensusError::WALErr)?;
-            let res = file.read_to_end(&mut read_buf);
-            if res.is_err() {
-                continue;
-            }
+        let payload = content.slice(start..start + len);
+        if Hasher::digest(&payload).as_bytes() != &content[offset + 4..start] {
+            break;
+        }
 
-            let mut info = Bytes::from(read_buf);
+        records.push(payload);
+        offset = start + len;
+    }
 
-            if info.len() < Hash::default().as_bytes().len() {
-                continue;
-            }
+    (records, content.len() - offset)
+}
 
-            let content = info.split_off(Hash::default().as_bytes().len());
+/// Read the transactions of a file and the length of its corrupt tail. A file
+/// of an unknown format version is an error, rather than being taken as
+/// corrupt, since it is written by a newer version.
+fn read_txs_file(path: &Path) -> ProtocolResult<(Vec<SignedTransaction>, usize)> {
+    let content = Bytes::from(fs::read(path).map_err(ConsensusError::WALErr)?);
 
-            if info == Hasher::digest(&content).as_bytes() {
-                break Some(content);
-            } else {
-                index += 1;
-            }
+    if !content.starts_with(TXS_MAGIC) {
+        return match BatchSignedTxs::decode_msg(content.clone()) {
+            Ok(txs) => Ok((txs.inner(), 0)),
+            Err(_) => Ok((Vec::new(), content.len())),
         };
+    }
+
+    let (records, corrupt_bytes) = read_versioned_records(content, TXS_VERSION)?;
+    let txs = records
+        .into_iter()
+        .map(SignedTransaction::decode)
+        .collect::<ProtocolResult<Vec<_>>>()?;
+    Ok((txs, corrupt_bytes))
+}
 
-        content.ok_or_else(|| ConsensusError::ConsensusWalNoWalFile.into())
+/// The root of the transaction hashes, which is the `transactions_root` of
+/// the proposal.
+fn transactions_root(txs: &[SignedTransaction]) -> MerkleRoot {
+    if txs.is_empty() {
+        return RLP_NULL;
     }
 
-    pub fn clear(&self) -> ProtocolResult<()> {
-        let dir_path = self.path.clone();
-        if !dir_path.exists() {
-            return Ok(());
-        }
+    TrieMerkle::from_iter(txs.iter().map(|tx| &tx.transaction.hash).enumerate())
+        .root_hash()
+        .unwrap_or_default()
+}
 
-        for item in fs::read_dir(dir_path).map_err(ConsensusError::WALErr)? {
-            let item = item.map_err(ConsensusError::WALErr)?;
+fn encode_txs_file(txs: &[SignedTransaction]) -> ProtocolResult<BytesMut> {
+    let mut content = BytesMut::new();
+    content.put(&TXS_MAGIC[..]);
+    content.put_u32(TXS_VERSION);
+    for tx in txs.iter() {
+        put_record(&mut content, &tx.encode()?);
+    }
+    Ok(content)
+}
 
-            fs::remove_file(item.path()).map_err(ConsensusError::WALErr)?;
-        }
-        Ok(())
+fn encode_consensus_file(records: &[Bytes]) -> BytesMut {
+    let mut content = BytesMut::new();
+    content.put(&CONSENSUS_MAGIC[..]);
+    content.put_u32(CONSENSUS_VERSION);
+    for record in records.iter() {
+        put_record(&mut content, record);
+    }
+    content
+}
+
+/// Read the records of a consensus WAL file and the length of its corrupt
+/// tail. The whole file of an older version is the only record.
+fn read_consensus_file(path: &Path) -> ProtocolResult<(Vec<Bytes>, usize)> {
+    let content = Bytes::from(fs::read(path).map_err(ConsensusError::WALErr)?);
+
+    if !content.starts_with(CONSENSUS_MAGIC) {
+        let valid = content.len() >= LEGACY_CHECKSUM_LEN
+            && Hasher::digest(&content[LEGACY_CHECKSUM_LEN..]).as_bytes()
+                == &content[..LEGACY_CHECKSUM_LEN];
+        return if valid {
+            Ok((vec![content.slice(LEGACY_CHECKSUM_LEN..)], 0))
+        } else {
+            Ok((Vec::new(), content.len()))
+        };
+    }
+
+    read_versioned_records(content, CONSENSUS_VERSION)
+}
+
+/// Read the records after the header of a file, whose magic is checked by the
+/// caller.
+fn read_versioned_records(content: Bytes, expect: u32) -> ProtocolResult<(Vec<Bytes>, usize)> {
+    if content.len() < FILE_HEADER_LEN {
+        return Ok((Vec::new(), content.len()));
+    }
+
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&content[4..FILE_HEADER_LEN]);
+    let version = u32::from_be_bytes(version);
+    if version != expect {
+        return Err(ConsensusError::WalVersion(version).into());
+    }
+
+    Ok(read_records(content.slice(FILE_HEADER_LEN..)))
+}
+
+fn has_magic(path: &Path, magic: &[u8; 4]) -> ProtocolResult<bool> {
+    let content = fs::read(path).map_err(ConsensusError::WALErr)?;
+    Ok(content.starts_with(magic))
+}
+
+fn read_consensus_file_with_warning(path: &Path) -> ProtocolResult<(Vec<Bytes>, usize)> {
+    let (records, corrupt_bytes) = read_consensus_file(path)?;
+
+    if corrupt_bytes > 0 {
+        log::warn!(
+            "[consensus] skip the corrupt tail of {} bytes in wal file {:?}, {} records are valid",
+            corrupt_bytes,
+            path,
+            records.len()
+        );
+    }
+    Ok((records, corrupt_bytes))
+}
+
+/// Rewrite the file by its valid records if it has a corrupt tail, or remove
+/// it if there is no valid record. Return whether the file is changed.
+fn repair_wal_file(path: &Path) -> ProtocolResult<bool> {
+    let (records, corrupt_bytes) = read_consensus_file(path)?;
+
+    if corrupt_bytes == 0 {
+        return Ok(false);
+    }
+
+    if records.is_empty() {
+        fs::remove_file(path).map_err(ConsensusError::WALErr)?;
+    } else {
+        write_atomically(path, &encode_consensus_file(&records))?;
+    }
+    Ok(true)
+}
+
+/// Write the content to a temporary file and rename it to the path after it
+/// is synced, so that a crash never leaves a partially written file there.
+/// The directory is synced too, or the rename may be lost by a crash.
+fn write_atomically(path: &Path, content: &[u8]) -> ProtocolResult<()> {
+    let tmp_path = path.with_extension(TMP_EXTENSION);
+
+    let mut file = fs::File::create(&tmp_path).map_err(ConsensusError::WALErr)?;
+    file.write_all(content).map_err(ConsensusError::WALErr)?;
+    file.sync_all().map_err(ConsensusError::WALErr)?;
+    fs::rename(&tmp_path, path).map_err(ConsensusError::WALErr)?;
+
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(ConsensusError::WALErr)?;
     }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().map_or(false, |e| e == extension)
 }
 
 #[cfg(test)]
@@ -320,9 +618,10 @@ mod tests {
     };
 
     use protocol::rand::{random, rngs::OsRng};
+    use protocol::traits::MessageCodec;
     use protocol::types::{
-        Bytes, Eip1559Transaction, Hash, SignatureComponents, SignedTransaction, TransactionAction,
-        UnsignedTransaction, UnverifiedTransaction,
+        BatchSignedTxs, Bytes, Eip1559Transaction, Hash, SignatureComponents, SignedTransaction,
+        TransactionAction, UnsignedTransaction, UnverifiedTransaction,
     };
 
     use super::*;
@@ -331,6 +630,14 @@ mod tests {
 
     static FULL_CONSENSUS_PATH: &str = "./free-space/wal/consensus";
 
+    static CORRUPT_TXS_PATH: &str = "./free-space/wal/corrupt_txs";
+
+    static CORRUPT_CONSENSUS_PATH: &str = "./free-space/wal/corrupt_consensus";
+
+    static FORMAT_TXS_PATH: &str = "./free-space/wal/format_txs";
+
+    static FORMAT_CONSENSUS_PATH: &str = "./free-space/wal/format_consensus";
+
     fn mock_hash() -> Hash {
         Hasher::digest(get_random_bytes(10))
     }
@@ -382,14 +689,14 @@ mod tests {
 
         let wal = SignedTxsWAL::new(FULL_TXS_PATH);
         let txs_01 = mock_wal_txs(100);
-        let hash_01 = Hasher::digest(BatchSignedTxs::new(txs_01.clone()).encode_msg().unwrap());
+        let hash_01 = transactions_root(&txs_01);
         wal.save(1u64, hash_01, txs_01.clone()).unwrap();
         let txs_02 = mock_wal_txs(100);
-        let hash_02 = Hasher::digest(BatchSignedTxs::new(txs_02.clone()).encode_msg().unwrap());
+        let hash_02 = transactions_root(&txs_02);
         wal.save(3u64, hash_02, txs_02.clone()).unwrap();
 
         let txs_03 = mock_wal_txs(100);
-        let hash_03 = Hasher::digest(BatchSignedTxs::new(txs_03.clone()).encode_msg().unwrap());
+        let hash_03 = transactions_root(&txs_03);
         wal.save(3u64, hash_03, txs_03.clone()).unwrap();
 
         let res = wal.load_by_number(3);
@@ -475,6 +782,172 @@ mod tests {
         fs::remove_dir_all(PathBuf::from_str(FULL_CONSENSUS_PATH).unwrap()).unwrap();
     }
 
+    fn truncate(path: &Path, len: usize) {
+        let content = fs::read(path).unwrap();
+        fs::write(path, &content[..content.len() - len]).unwrap();
+    }
+
+    #[test]
+    fn test_txs_wal_corrupt_tail() {
+        let _ = fs::remove_dir_all(PathBuf::from_str(CORRUPT_TXS_PATH).unwrap());
+
+        let wal = SignedTxsWAL::new(CORRUPT_TXS_PATH);
+        let txs = mock_wal_txs(10);
+        let hash = transactions_root(&txs);
+        wal.save(1u64, hash, txs.clone()).unwrap();
+        let txs_02 = mock_wal_txs(5);
+        wal.save(2u64, transactions_root(&txs_02), txs_02).unwrap();
+
+        // A torn write of the last transaction.
+        let path = wal.inspect().unwrap()[0].1[0].path.clone();
+        truncate(&path, 10);
+
+        assert!(wal.load(1u64, hash).is_err());
+        assert!(wal.load_by_number(1u64).is_empty());
+
+        // A file with a corrupt tail is replaced when it is saved again.
+        wal.save(1u64, hash, txs.clone()).unwrap();
+        assert_eq!(wal.load(1u64, hash).unwrap(), txs);
+        truncate(&path, 10);
+        // A temporary file left by a crash.
+        fs::write(path.with_extension(TMP_EXTENSION), b"partial").unwrap();
+
+        let info = wal.inspect().unwrap();
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0].0, 1);
+        assert_eq!(info[0].1.len(), 1);
+        assert_eq!(info[0].1[0].records, 9);
+        assert!(info[0].1[0].corrupt_bytes > 0);
+        assert_eq!(info[1].0, 2);
+        assert_eq!(info[1].1[0].records, 5);
+        assert_eq!(info[1].1[0].corrupt_bytes, 0);
+
+        assert_eq!(wal.repair().unwrap(), 2);
+        assert_eq!(wal.repair().unwrap(), 0);
+        let info = wal.inspect().unwrap();
+        assert!(info[0].1.is_empty());
+        assert_eq!(info[1].1[0].records, 5);
+        assert!(!path.exists());
+        assert!(!path.with_extension(TMP_EXTENSION).exists());
+
+        fs::remove_dir_all(PathBuf::from_str(CORRUPT_TXS_PATH).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_txs_wal_format() {
+        let _ = fs::remove_dir_all(PathBuf::from_str(FORMAT_TXS_PATH).unwrap());
+
+        let wal = SignedTxsWAL::new(FORMAT_TXS_PATH);
+        let txs = mock_wal_txs(10);
+        let hash = transactions_root(&txs);
+
+        // The transactions which are cut at a record boundary.
+        wal.save(1u64, hash, txs[..9].to_vec()).unwrap();
+        assert!(wal.load(1u64, hash).is_err());
+
+        // A file written by an older version.
+        wal.save(2u64, hash, vec![]).unwrap();
+        let path = wal.inspect().unwrap()[1].1[0].path.clone();
+        fs::write(
+            &path,
+            BatchSignedTxs::new(txs.clone()).encode_msg().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(wal.load(2u64, hash).unwrap(), txs);
+        assert_eq!(wal.load_by_number(2u64), txs);
+
+        // The compaction removes the committed height and upgrades the file.
+        assert_eq!(wal.compact(3u64).unwrap(), 2);
+        assert_eq!(wal.available_number().unwrap(), vec![2]);
+        assert!(has_magic(&path, TXS_MAGIC).unwrap());
+        assert_eq!(wal.load(2u64, hash).unwrap(), txs);
+        assert_eq!(wal.compact(3u64).unwrap(), 0);
+
+        // A file written by a newer version.
+        let mut content = BytesMut::new();
+        content.put(&TXS_MAGIC[..]);
+        content.put_u32(TXS_VERSION + 1);
+        fs::write(&path, &content).unwrap();
+        assert!(wal.load(2u64, hash).is_err());
+        assert!(wal.inspect().is_err());
+
+        fs::remove_dir_all(PathBuf::from_str(FORMAT_TXS_PATH).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_consensus_wal_corrupt_tail() {
+        let _ = fs::remove_dir_all(PathBuf::from_str(CORRUPT_CONSENSUS_PATH).unwrap());
+
+        let wal = ConsensusWal::new(CORRUPT_CONSENSUS_PATH);
+        let info = get_random_bytes(1000);
+        wal.update_overlord_wal(Context::new(), info.clone())
+            .unwrap();
+
+        // A newer file which is torn, and a file which is too short to be a
+        // record.
+        let path = wal.inspect().unwrap()[0].path.clone();
+        let timestamp = u128::from_str(path.file_name().unwrap().to_str().unwrap()).unwrap();
+        let torn = path.with_file_name((timestamp + 1).to_string());
+        fs::copy(&path, &torn).unwrap();
+        truncate(&torn, 1);
+        let short = path.with_file_name((timestamp + 2).to_string());
+        fs::write(&short, b"short").unwrap();
+
+        assert_eq!(wal.load_overlord_wal(Context::new()).unwrap(), info);
+
+        let files = wal.inspect().unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].records, 1);
+        assert_eq!(files[1].records, 0);
+        assert_eq!(files[2].records, 0);
+
+        assert_eq!(wal.repair().unwrap(), 2);
+        assert_eq!(wal.inspect().unwrap().len(), 1);
+        assert_eq!(wal.load_overlord_wal(Context::new()).unwrap(), info);
+
+        fs::remove_dir_all(PathBuf::from_str(CORRUPT_CONSENSUS_PATH).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_consensus_wal_format() {
+        let _ = fs::remove_dir_all(PathBuf::from_str(FORMAT_CONSENSUS_PATH).unwrap());
+
+        let wal = ConsensusWal::new(FORMAT_CONSENSUS_PATH);
+        wal.update_overlord_wal(Context::new(), get_random_bytes(1000))
+            .unwrap();
+        let path = wal.inspect().unwrap()[0].path.clone();
+
+        // A file written by an older version.
+        let info = get_random_bytes(1000);
+        let mut content = BytesMut::new();
+        content.put(Hasher::digest(&info).as_bytes());
+        content.put(info.as_ref());
+        fs::write(&path, &content).unwrap();
+
+        assert_eq!(wal.load_overlord_wal(Context::new()).unwrap(), info);
+        let files = wal.inspect().unwrap();
+        assert_eq!(files[0].records, 1);
+        assert_eq!(files[0].corrupt_bytes, 0);
+        assert_eq!(wal.repair().unwrap(), 0);
+
+        // The compaction rewrites the state in the current format.
+        wal.compact().unwrap();
+        let files = wal.inspect().unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(has_magic(&files[0].path, CONSENSUS_MAGIC).unwrap());
+        assert_eq!(wal.load_overlord_wal(Context::new()).unwrap(), info);
+
+        // A file written by a newer version.
+        let mut content = BytesMut::new();
+        content.put(&CONSENSUS_MAGIC[..]);
+        content.put_u32(CONSENSUS_VERSION + 1);
+        fs::write(&files[0].path, &content).unwrap();
+        assert!(wal.inspect().is_err());
+        assert!(wal.load_overlord_wal(Context::new()).is_err());
+
+        fs::remove_dir_all(PathBuf::from_str(FORMAT_CONSENSUS_PATH).unwrap()).unwrap();
+    }
+
     #[test]
     fn test_wal_txs_codec() {
         for _ in 0..1 {
@@ -26,8 +26,9 @@ use common_merkle::TrieMerkle;
 use protocol::codec::ProtocolCodec;
 use protocol::traits::{ApplyBackend, Backend, Executor, ExecutorAdapter as Adapter};
 use protocol::types::{
-    data_gas_cost, Account, Config, ExecResp, Hasher, SignedTransaction, TransactionAction, TxResp,
-    ValidatorExtend, GAS_CALL_TRANSACTION, GAS_CREATE_TRANSACTION, H160, NIL_DATA, RLP_NULL, U256,
+    data_gas_cost, receipt_leaf, Account, Config, ExecResp, SignedTransaction, TransactionAction,
+    TxResp, ValidatorExtend, GAS_CALL_TRANSACTION, GAS_CREATE_TRANSACTION, H160, NIL_DATA,
+    RLP_NULL, U256,
 };
 
 use crate::precompiles::build_precompile_set;
@@ -141,7 +142,7 @@ impl Executor for AxonExecutor {
             gas += r.gas_used;
             fee = fee.checked_add(r.fee_cost).unwrap_or(U256::max_value());
 
-            hashes.push(Hasher::digest(&r.ret));
+            hashes.push(receipt_leaf(block_number.as_u64(), &r));
             res.push(r);
         }
 
@@ -2,6 +2,7 @@ use std::io;
 
 use thiserror::Error;
 
+use protocol::types::TrieKind;
 use protocol::{ProtocolError, ProtocolErrorKind};
 
 #[derive(Error, Debug)]
@@ -57,6 +58,9 @@ pub enum SystemScriptError {
     #[error("TrieDB has not been initialized")]
     TrieDbNotInit,
 
+    #[error("{0:?} is not a system contract trie")]
+    NotSystemTrie(TrieKind),
+
     #[error("Data length mismatch expect {expect:?}, actual: {actual:?}")]
     DataLengthMismatch { expect: usize, actual: usize },
 
@@ -24,10 +24,11 @@ use ckb_types::core::cell::{CellProvider, CellStatus};
 use ckb_types::core::HeaderView;
 use ckb_types::{packed, prelude::*};
 
-use protocol::ckb_blake2b_256;
 use protocol::traits::{ApplyBackend, Backend};
-use protocol::types::{Bytes, Hasher, SignedTransaction, TxResp, H160, H256};
+use protocol::types::{Bytes, Hasher, SignedTransaction, TrieKind, TxResp, H160, H256};
+use protocol::{ckb_blake2b_256, trie, ProtocolResult};
 
+use crate::system_contract::error::SystemScriptError;
 use crate::system_contract::image_cell::utils::always_success_script_deploy_cell;
 use crate::system_contract::trie_db::RocksTrieDB;
 use crate::system_contract::utils::update_mpt_root;
@@ -72,10 +73,28 @@ pub fn init<P: AsRef<Path>, B: Backend + ApplyBackend>(
     config: ConfigRocksDB,
     mut backend: B,
 ) {
-    // Init metadata db
     let current_metadata_root = backend.storage(MetadataContract::ADDRESS, *METADATA_ROOT_KEY);
     CURRENT_METADATA_ROOT.store(Arc::new(current_metadata_root));
 
+    init_db(path, config);
+
+    let current_cell_root = backend.storage(CkbLightClientContract::ADDRESS, *HEADER_CELL_ROOT_KEY);
+
+    if current_cell_root.is_zero() {
+        // todo need refactoring
+        ImageCellContract::default()
+            .save_cells(vec![always_success_script_deploy_cell()], 0)
+            .unwrap();
+        return update_mpt_root(&mut backend, CkbLightClientContract::ADDRESS);
+    }
+
+    CURRENT_HEADER_CELL_ROOT.store(Arc::new(current_cell_root));
+}
+
+/// Open the databases of system contracts without touching the roots, which
+/// is enough to read and write the trie nodes offline.
+pub fn init_db<P: AsRef<Path>>(path: P, config: ConfigRocksDB) {
+    // Init metadata db
     let metadata_db_path = path.as_ref().join("metadata");
     METADATA_DB.get_or_init(|| {
         Arc::new(
@@ -96,18 +115,48 @@ pub fn init<P: AsRef<Path>, B: Backend + ApplyBackend>(
             .expect("[system contract] header&cell new rocksdb error"),
         )
     });
+}
 
-    let current_cell_root = backend.storage(CkbLightClientContract::ADDRESS, *HEADER_CELL_ROOT_KEY);
+/// Read the metadata root and the header cell root which are saved in the
+/// storage of system contracts.
+pub fn system_contract_roots<B: Backend>(backend: &B) -> (H256, H256) {
+    (
+        backend.storage(MetadataContract::ADDRESS, *METADATA_ROOT_KEY),
+        backend.storage(CkbLightClientContract::ADDRESS, *HEADER_CELL_ROOT_KEY),
+    )
+}
 
-    if current_cell_root.is_zero() {
-        // todo need refactoring
-        ImageCellContract::default()
-            .save_cells(vec![always_success_script_deploy_cell()], 0)
-            .unwrap();
-        return update_mpt_root(&mut backend, CkbLightClientContract::ADDRESS);
+/// Switch the system contracts to the given roots. It is used after the tries
+/// are downloaded by snapshot sync or the chain is rolled back.
+pub fn update_system_contract_roots(metadata_root: H256, header_cell_root: H256) {
+    CURRENT_METADATA_ROOT.store(Arc::new(metadata_root));
+    CURRENT_HEADER_CELL_ROOT.store(Arc::new(header_cell_root));
+}
+
+pub fn get_trie_node(kind: TrieKind, hash: &H256) -> ProtocolResult<Option<Vec<u8>>> {
+    trie::DB::get(system_trie_db(kind)?.as_ref(), hash.as_bytes())
+}
+
+pub fn contains_trie_node(kind: TrieKind, hash: &H256) -> ProtocolResult<bool> {
+    trie::DB::contains(system_trie_db(kind)?.as_ref(), hash.as_bytes())
+}
+
+pub fn insert_trie_nodes(kind: TrieKind, nodes: Vec<(H256, Bytes)>) -> ProtocolResult<()> {
+    let db = system_trie_db(kind)?;
+    for (hash, node) in nodes.into_iter() {
+        trie::DB::insert(db.as_ref(), hash.as_bytes().to_vec(), node.to_vec())?;
     }
+    Ok(())
+}
 
-    CURRENT_HEADER_CELL_ROOT.store(Arc::new(current_cell_root));
+fn system_trie_db(kind: TrieKind) -> ProtocolResult<&'static Arc<RocksTrieDB>> {
+    let db = match kind {
+        TrieKind::Metadata => METADATA_DB.get(),
+        TrieKind::HeaderCell => HEADER_CELL_DB.get(),
+        _ => return Err(SystemScriptError::NotSystemTrie(kind).into()),
+    };
+
+    db.ok_or_else(|| SystemScriptError::TrieDbNotInit.into())
 }
 
 pub trait SystemContract {
@@ -148,6 +148,8 @@ fn mock_proposal() -> Proposal {
         chain_id:                   Default::default(),
         call_system_script_count:   1,
         tx_hashes:                  vec![],
+        state_root:                 None,
+        receipts_root:              None,
     }
 }
 
@@ -6,7 +6,7 @@ use protocol::tokio;
 use protocol::traits::{Context, MemPool};
 use std::sync::Arc;
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use mock::*;
 
 fn criterion_insert(c: &mut Criterion) {
@@ -116,6 +116,77 @@ fn criterion_get_full_txs(c: &mut Criterion) {
     });
 }
 
+fn criterion_insert_batch(c: &mut Criterion) {
+    c.bench_function("insert batch 100", |b| {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let txs = default_mock_txs(100);
+
+        b.iter_batched(
+            || Arc::new(default_mempool_sync()),
+            |mempool| runtime.block_on(exec_insert_batch(txs.clone(), mempool)),
+            BatchSize::SmallInput,
+        );
+    });
+    c.bench_function("insert batch 1000", |b| {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let txs = default_mock_txs(1000);
+
+        b.iter_batched(
+            || Arc::new(default_mempool_sync()),
+            |mempool| runtime.block_on(exec_insert_batch(txs.clone(), mempool)),
+            BatchSize::SmallInput,
+        );
+    });
+    // The same txs inserted one by one, to compare with the batch ones.
+    c.bench_function("insert concurrent 1000", |b| {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let txs = default_mock_txs(1000);
+
+        b.iter_batched(
+            || Arc::new(default_mempool_sync()),
+            |mempool| runtime.block_on(concurrent_insert(txs.clone(), mempool)),
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+// The proposal txs are unknown to the validator and pulled in
+// `ensure_order_txs`, a real pull also pays an extra rpc round trip.
+fn criterion_ensure_order_txs(c: &mut Criterion) {
+    c.bench_function("ensure 1000 txs by pull", |b| {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let txs = default_mock_txs(1000);
+        let tx_hashes = txs.iter().map(|tx| tx.transaction.hash).collect::<Vec<_>>();
+
+        b.iter_batched(
+            || {
+                let mempool = Arc::new(default_mempool_sync());
+                runtime.block_on(concurrent_broadcast(txs.clone(), Arc::clone(&mempool)));
+                mempool
+            },
+            |mempool| runtime.block_on(exec_ensure_order_txs(tx_hashes.clone(), mempool)),
+            BatchSize::SmallInput,
+        );
+    });
+    // The proposal txs have been relayed by the proposer, so the block is
+    // rebuilt from the local pool.
+    c.bench_function("ensure 1000 txs by relay", |b| {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let txs = default_mock_txs(1000);
+        let tx_hashes = txs.iter().map(|tx| tx.transaction.hash).collect::<Vec<_>>();
+
+        b.iter_batched(
+            || {
+                let mempool = Arc::new(default_mempool_sync());
+                runtime.block_on(exec_insert_relayed_txs(txs.clone(), Arc::clone(&mempool)));
+                mempool
+            },
+            |mempool| runtime.block_on(exec_ensure_order_txs(tx_hashes.clone(), mempool)),
+            BatchSize::SmallInput,
+        );
+    });
+}
+
 fn criterion_check_sig_serial(c: &mut Criterion) {
     // MacOS M1 Pro, 16GB: time: 323.55 ns
     c.bench_function("check sig serial 1", |b| {
@@ -228,8 +299,10 @@ fn criterion_other(c: &mut Criterion) {
 criterion_group!(
     benches,
     criterion_check_sig_serial,
+    criterion_ensure_order_txs,
     criterion_get_full_txs,
     criterion_insert,
+    criterion_insert_batch,
     criterion_other,
 );
 criterion_main!(benches);
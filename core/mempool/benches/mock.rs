@@ -3,6 +3,7 @@
 use std::sync::Arc;
 
 use dashmap::DashMap;
+use rayon::prelude::*;
 
 use common_crypto::{
     Crypto, PrivateKey, Secp256k1Recoverable, Secp256k1RecoverablePrivateKey,
@@ -13,8 +14,8 @@ use protocol::rand::{random, rngs::OsRng};
 use protocol::traits::{Context, MemPool, MemPoolAdapter};
 use protocol::types::{
     public_to_address, recover_intact_pub_key, Bytes, Eip1559Transaction, Hash, PackedTxHashes,
-    Public, SignedTransaction, TransactionAction, UnsignedTransaction, UnverifiedTransaction, H160,
-    H256, U256,
+    Public, ShortTxId, SignedTransaction, TransactionAction, UnsignedTransaction,
+    UnverifiedTransaction, H160, H256, U256,
 };
 use protocol::{async_trait, tokio, ProtocolResult};
 
@@ -28,6 +29,8 @@ pub const POOL_SIZE: usize = 100_000;
 pub const MAX_TX_SIZE: u64 = 1024; // 1KB
 pub const TIMEOUT: u64 = 1000;
 pub const TIMEOUT_GAP: u64 = 100;
+pub const DROPPED_CACHE_SIZE: usize = 1000;
+const PRIVATE_TX_TIMEOUT_GAP: u64 = 5;
 pub const NATIVE_TOKEN_ISSUE_ADDRESS: H160 = system_contract_address(0x0);
 
 pub struct HashMemPoolAdapter {
@@ -69,6 +72,26 @@ impl MemPoolAdapter for HashMemPoolAdapter {
         Ok(())
     }
 
+    async fn push_compact_txs(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _txs: Vec<SignedTransaction>,
+        _peer_ids: Vec<Bytes>,
+    ) -> ProtocolResult<()> {
+        Ok(())
+    }
+
+    async fn forward_private_tx(
+        &self,
+        _ctx: Context,
+        _tx: SignedTransaction,
+    ) -> ProtocolResult<()> {
+        Ok(())
+    }
+
+    fn mark_known_txs(&self, _ctx: Context, _short_ids: &[ShortTxId]) {}
+
     async fn check_authorization(
         &self,
         _ctx: Context,
@@ -77,11 +100,32 @@ impl MemPoolAdapter for HashMemPoolAdapter {
         Ok(U256::zero())
     }
 
+    async fn check_authorizations(
+        &self,
+        _ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<U256>> {
+        txs.iter().map(|_| Ok(U256::zero())).collect()
+    }
+
     async fn check_transaction(&self, _ctx: Context, tx: &SignedTransaction) -> ProtocolResult<()> {
         check_hash(tx)?;
         check_sig(tx)
     }
 
+    async fn check_transactions(
+        &self,
+        _ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<()>> {
+        txs.par_iter()
+            .map(|tx| {
+                check_hash(tx)?;
+                check_sig(tx)
+            })
+            .collect()
+    }
+
     async fn check_storage_exist(&self, _ctx: Context, _tx_hash: &Hash) -> ProtocolResult<()> {
         Ok(())
     }
@@ -127,7 +171,17 @@ pub async fn new_mempool(
     _max_tx_size: u64,
 ) -> MemPoolImpl<HashMemPoolAdapter> {
     let adapter = HashMemPoolAdapter::new();
-    MemPoolImpl::new(pool_size, 20, adapter, vec![], Default::default()).await
+    MemPoolImpl::new(
+        pool_size,
+        20,
+        0,
+        PRIVATE_TX_TIMEOUT_GAP,
+        DROPPED_CACHE_SIZE,
+        adapter,
+        vec![],
+        Default::default(),
+    )
+    .await
 }
 
 pub async fn default_mempool() -> MemPoolImpl<HashMemPoolAdapter> {
@@ -248,6 +302,50 @@ pub async fn exec_get_full_txs(
         .unwrap()
 }
 
+pub async fn concurrent_broadcast(
+    txs: Vec<SignedTransaction>,
+    mempool: Arc<MemPoolImpl<HashMemPoolAdapter>>,
+) {
+    for tx in txs.into_iter() {
+        mempool
+            .get_adapter()
+            .broadcast_tx(Context::new(), None, tx)
+            .await
+            .unwrap();
+    }
+}
+
+pub async fn exec_insert_relayed_txs(
+    txs: Vec<SignedTransaction>,
+    mempool: Arc<MemPoolImpl<HashMemPoolAdapter>>,
+) {
+    mempool
+        .insert_relayed_txs(Context::new(), CURRENT_HEIGHT + 1, vec![], txs)
+        .await
+        .unwrap();
+}
+
+pub async fn exec_ensure_order_txs(
+    require_hashes: Vec<Hash>,
+    mempool: Arc<MemPoolImpl<HashMemPoolAdapter>>,
+) {
+    mempool
+        .ensure_order_txs(Context::new(), None, &require_hashes)
+        .await
+        .unwrap();
+}
+
+pub async fn exec_insert_batch(
+    txs: Vec<SignedTransaction>,
+    mempool: Arc<MemPoolImpl<HashMemPoolAdapter>>,
+) {
+    for res in mempool.insert_batch(Context::new(), txs).await.into_iter() {
+        if let Err(e) = res {
+            println!("{:?}", e);
+        }
+    }
+}
+
 pub async fn exec_insert(
     signed_tx: SignedTransaction,
     mempool: Arc<MemPoolImpl<HashMemPoolAdapter>>,
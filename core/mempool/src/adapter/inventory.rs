@@ -0,0 +1,104 @@
+use std::collections::{HashSet, VecDeque};
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+
+use protocol::types::{Bytes, ShortTxId};
+
+pub const DEFAULT_MAX_INVENTORY_PEERS: usize = 512;
+pub const DEFAULT_MAX_INVENTORY_TXS: usize = 40_000;
+
+/// The short ids of the transactions held by a peer. The oldest id is
+/// forgotten when the capacity is reached.
+#[derive(Default)]
+struct KnownTxs {
+    ids:   HashSet<ShortTxId>,
+    order: VecDeque<ShortTxId>,
+}
+
+impl KnownTxs {
+    fn insert(&mut self, id: ShortTxId, capacity: usize) {
+        if !self.ids.insert(id) {
+            return;
+        }
+
+        self.order.push_back(id);
+        if self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Remember which transactions each peer has seen, keyed by peer id bytes.
+/// A false positive only costs a pull of the transaction, so the inventory
+/// is bounded rather than exact.
+pub struct PeerInventory {
+    peers:     DashMap<Bytes, Mutex<KnownTxs>>,
+    max_peers: usize,
+    max_txs:   usize,
+}
+
+impl Default for PeerInventory {
+    fn default() -> Self {
+        PeerInventory::new(DEFAULT_MAX_INVENTORY_PEERS, DEFAULT_MAX_INVENTORY_TXS)
+    }
+}
+
+impl PeerInventory {
+    pub fn new(max_peers: usize, max_txs: usize) -> Self {
+        PeerInventory {
+            peers: DashMap::new(),
+            max_peers,
+            max_txs,
+        }
+    }
+
+    pub fn insert<I: IntoIterator<Item = ShortTxId>>(&self, peer_id: &Bytes, ids: I) {
+        if !self.peers.contains_key(peer_id) && self.peers.len() >= self.max_peers {
+            let evict = self.peers.iter().next().map(|kv| kv.key().clone());
+            if let Some(evict) = evict {
+                self.peers.remove(&evict);
+            }
+        }
+
+        let entry = self.peers.entry(peer_id.clone()).or_default();
+        let mut known = entry.lock();
+        for id in ids {
+            known.insert(id, self.max_txs);
+        }
+    }
+
+    pub fn contains(&self, peer_id: &Bytes, id: &ShortTxId) -> bool {
+        self.peers
+            .get(peer_id)
+            .map(|known| known.lock().ids.contains(id))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_inventory() {
+        let inventory = PeerInventory::new(2, 3);
+        let (peer_a, peer_b, peer_c) = (
+            Bytes::from_static(b"a"),
+            Bytes::from_static(b"b"),
+            Bytes::from_static(b"c"),
+        );
+
+        inventory.insert(&peer_a, [1, 2, 3, 4]);
+        assert!(!inventory.contains(&peer_a, &1));
+        assert!(inventory.contains(&peer_a, &4));
+        assert!(!inventory.contains(&peer_b, &4));
+
+        inventory.insert(&peer_b, [5]);
+        inventory.insert(&peer_c, [6]);
+        assert_eq!(inventory.peers.len(), 2);
+        assert!(inventory.contains(&peer_c, &6));
+    }
+}
@@ -1,18 +1,21 @@
 use std::sync::Arc;
 
-use futures::future::{try_join_all, TryFutureExt};
+use futures::future::TryFutureExt;
 use rlp_derive::{RlpDecodable, RlpEncodable};
 
 use common_apm::Instant;
 use protocol::{
-    async_trait, tokio,
+    async_trait,
     traits::{Context, MemPool, MessageHandler, Priority, Rpc, TrustFeedback},
-    types::{BatchSignedTxs, Hash, SignedTransaction},
+    types::{BatchSignedTxs, Hash, ShortTxId, SignedTransaction},
 };
 
 use crate::context::TxContext;
 
 pub const END_GOSSIP_NEW_TXS: &str = "/gossip/mempool/new_txs";
+pub const END_GOSSIP_COMPACT_TXS: &str = "/gossip/mempool/compact_txs";
+pub const END_GOSSIP_ANNOUNCE_TXS: &str = "/gossip/mempool/announce_txs";
+pub const END_GOSSIP_PRIVATE_TXS: &str = "/gossip/mempool/private_txs";
 pub const RPC_PULL_TXS: &str = "/rpc_call/mempool/pull_txs";
 pub const RPC_RESP_PULL_TXS: &str = "/rpc_resp/mempool/pull_txs";
 pub const RPC_RESP_PULL_TXS_SYNC: &str = "/rpc_resp/mempool/pull_txs_sync";
@@ -39,42 +42,65 @@ where
 
     async fn process(&self, ctx: Context, msg: Self::Message) -> TrustFeedback {
         let ctx = ctx.mark_network_origin_new_txs();
+        let txs = msg.inner();
 
-        let insert_stx = |stx: SignedTransaction| -> _ {
-            let mem_pool = Arc::clone(&self.mem_pool);
-            let ctx = ctx.clone();
+        let inst = Instant::now();
+        common_apm::metrics::mempool::MEMPOOL_COUNTER_STATIC
+            .insert_tx_from_p2p
+            .inc_by(txs.len() as f64);
 
-            tokio::spawn(async move {
-                let inst = Instant::now();
-                common_apm::metrics::mempool::MEMPOOL_COUNTER_STATIC
+        for res in self.mem_pool.insert_batch(ctx, txs).await.into_iter() {
+            if let Err(e) = res {
+                log::debug!("[core_mempool] insert tx from p2p error {}", e);
+                common_apm::metrics::mempool::MEMPOOL_RESULT_COUNTER_STATIC
                     .insert_tx_from_p2p
+                    .failure
                     .inc();
-
-                let res = mem_pool.insert(ctx, stx).await;
-
-                if res.is_err() {
-                    common_apm::metrics::mempool::MEMPOOL_RESULT_COUNTER_STATIC
-                        .insert_tx_from_p2p
-                        .failure
-                        .inc();
-                }
+            } else {
                 common_apm::metrics::mempool::MEMPOOL_RESULT_COUNTER_STATIC
                     .insert_tx_from_p2p
                     .success
                     .inc();
-                common_apm::metrics::mempool::MEMPOOL_TIME_STATIC
-                    .insert_tx_from_p2p
-                    .observe(common_apm::metrics::duration_to_sec(inst.elapsed()));
-            })
-        };
+            }
+        }
 
-        // Concurrently insert them
-        if try_join_all(msg.inner().into_iter().map(insert_stx).collect::<Vec<_>>())
-            .await
-            .map(|_| ())
-            .is_err()
-        {
-            log::error!("[core_mempool] mempool batch insert error");
+        common_apm::metrics::mempool::MEMPOOL_TIME_STATIC
+            .insert_tx_from_p2p
+            .observe(common_apm::metrics::duration_to_sec(inst.elapsed()));
+
+        TrustFeedback::Neutral
+    }
+}
+
+/// Handle the private txs forwarded by the other nodes. These txs are kept
+/// private and never gossiped again.
+pub struct PrivateTxsHandler<M> {
+    mem_pool: Arc<M>,
+}
+
+impl<M> PrivateTxsHandler<M>
+where
+    M: MemPool,
+{
+    pub fn new(mem_pool: Arc<M>) -> Self {
+        PrivateTxsHandler { mem_pool }
+    }
+}
+
+#[async_trait]
+impl<M> MessageHandler for PrivateTxsHandler<M>
+where
+    M: MemPool + 'static,
+{
+    type Message = BatchSignedTxs;
+
+    async fn process(&self, ctx: Context, msg: Self::Message) -> TrustFeedback {
+        let ctx = ctx.mark_network_origin_new_txs();
+
+        for tx in msg.inner().into_iter() {
+            if let Err(e) = self.mem_pool.insert_private(ctx.clone(), tx).await {
+                log::debug!("[core_mempool] insert private tx from p2p error {}", e);
+            }
         }
 
         TrustFeedback::Neutral
@@ -130,3 +156,88 @@ where
         TrustFeedback::Neutral
     }
 }
+
+/// The compact form of the transactions in a proposal. The transactions which
+/// the receiver holds are sent by `short_ids`, and the rest in full by `txs`.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct MsgCompactTxs {
+    pub height:    u64,
+    pub short_ids: Vec<ShortTxId>,
+    pub txs:       Vec<SignedTransaction>,
+}
+
+pub struct CompactTxsHandler<M> {
+    mem_pool: Arc<M>,
+}
+
+impl<M> CompactTxsHandler<M>
+where
+    M: MemPool,
+{
+    pub fn new(mem_pool: Arc<M>) -> Self {
+        CompactTxsHandler { mem_pool }
+    }
+}
+
+#[async_trait]
+impl<M> MessageHandler for CompactTxsHandler<M>
+where
+    M: MemPool + 'static,
+{
+    type Message = MsgCompactTxs;
+
+    async fn process(&self, ctx: Context, msg: Self::Message) -> TrustFeedback {
+        let ctx = ctx.mark_network_origin_new_txs();
+
+        // The stale heights and the txs which no longer fit the state are
+        // skipped, an error means the txs themselves are invalid
+        if let Err(e) = self
+            .mem_pool
+            .insert_relayed_txs(ctx, msg.height, msg.short_ids, msg.txs)
+            .await
+        {
+            log::warn!("[core_mempool] insert relayed txs {}", e);
+            return TrustFeedback::Bad(format!("Mempool invalid relayed txs {}", e));
+        }
+
+        TrustFeedback::Neutral
+    }
+}
+
+/// The hashes of the new transactions which the sender holds. The receiver
+/// pulls the unknown ones from the sender.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct MsgAnnounceTxs {
+    pub hashes: Vec<Hash>,
+}
+
+pub struct AnnounceTxsHandler<M> {
+    mem_pool: Arc<M>,
+}
+
+impl<M> AnnounceTxsHandler<M>
+where
+    M: MemPool,
+{
+    pub fn new(mem_pool: Arc<M>) -> Self {
+        AnnounceTxsHandler { mem_pool }
+    }
+}
+
+#[async_trait]
+impl<M> MessageHandler for AnnounceTxsHandler<M>
+where
+    M: MemPool + 'static,
+{
+    type Message = MsgAnnounceTxs;
+
+    async fn process(&self, ctx: Context, msg: Self::Message) -> TrustFeedback {
+        let ctx = ctx.mark_network_origin_new_txs();
+
+        if let Err(e) = self.mem_pool.fetch_announced_txs(ctx, msg.hashes).await {
+            log::debug!("[core_mempool] fetch announced txs {}", e);
+        }
+
+        TrustFeedback::Neutral
+    }
+}
@@ -1,27 +1,32 @@
 use super::TxContext;
 
+mod inventory;
 pub mod message;
 
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::{collections::HashMap, error::Error, marker::PhantomData, sync::Arc, time::Duration};
+use std::{error::Error, marker::PhantomData, sync::Arc, time::Duration};
 
 use ckb_types::core::cell::{CellProvider, CellStatus};
 use ckb_types::{core::TransactionView, prelude::*};
 use dashmap::DashMap;
 use futures::{
     channel::mpsc::{unbounded, TrySendError, UnboundedReceiver, UnboundedSender},
+    channel::oneshot,
     stream::StreamExt,
 };
 use log::{debug, error};
 use parking_lot::Mutex;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 
 use protocol::traits::{
     Context, Executor, Gossip, Interoperation, MemPoolAdapter, MetadataControl, PeerTrust,
     Priority, Rpc, Storage, TrustFeedback,
 };
 use protocol::types::{
-    recover_intact_pub_key, AddressSource, BatchSignedTxs, CellDepWithPubKey, CellWithData, Hash,
-    Hasher, MerkleRoot, SignatureComponents, SignatureR, SignatureS, SignedTransaction, H160, U256,
+    recover_intact_pub_key, short_tx_id, AddressSource, BatchSignedTxs, Bytes, CellDepWithPubKey,
+    CellWithData, Hash, Hasher, MerkleRoot, ShortTxId, SignatureComponents, SignatureR, SignatureS,
+    SignedTransaction, H160, U256,
 };
 use protocol::{
     async_trait, ckb_blake2b_256, codec::ProtocolCodec, lazy::CURRENT_STATE_ROOT, tokio, trie,
@@ -34,12 +39,177 @@ use core_executor::{
     is_call_system_script, system_contract::DataProvider, AxonExecutor, AxonExecutorAdapter,
 };
 use core_interoperation::{utils::is_dummy_out_point, InteroperationImpl};
+use core_network::{NetworkContext, PeerId, PeerIdExt};
 
-use crate::adapter::message::{MsgPullTxs, END_GOSSIP_NEW_TXS, RPC_PULL_TXS};
+use crate::adapter::inventory::PeerInventory;
+use crate::adapter::message::{
+    MsgAnnounceTxs, MsgCompactTxs, MsgPullTxs, END_GOSSIP_ANNOUNCE_TXS, END_GOSSIP_COMPACT_TXS,
+    END_GOSSIP_NEW_TXS, END_GOSSIP_PRIVATE_TXS, RPC_PULL_TXS,
+};
 use crate::MemPoolError;
 
 const MAX_VERIFY_CKB_VM_CYCLES: u64 = 50_000_000;
 
+/// Verify the signature of a transaction. It doesn't touch the adapter so
+/// that it can run on the verify thread pool.
+fn verify_tx_signature(stx: &SignedTransaction) -> ProtocolResult<()> {
+    if stx.transaction.signature.is_none() {
+        return Err(AdapterError::VerifySignature("missing signature".to_string()).into());
+    }
+
+    if stx.public.is_none() {
+        return Err(AdapterError::VerifySignature("missing public key".to_string()).into());
+    }
+
+    let signature = stx.transaction.signature.clone().unwrap();
+    if signature.len() == SignatureComponents::ETHEREUM_TX_LEN {
+        // use original Secp256k1 library to verify
+        Secp256k1Recoverable::verify_signature(
+            stx.transaction.signature_hash(true).as_bytes(),
+            signature.as_bytes().as_ref(),
+            recover_intact_pub_key(&stx.public.unwrap()).as_bytes(),
+        )
+        .map_err(|err| AdapterError::VerifySignature(err.to_string()))?;
+
+        return Ok(());
+    }
+
+    match signature.r[0] {
+        0u8 => {
+            let r =
+                rlp::decode::<CellDepWithPubKey>(&signature.r[1..]).map_err(AdapterError::Rlp)?;
+
+            InteroperationImpl::call_ckb_vm(
+                Default::default(),
+                &DataProvider::default(),
+                r.cell_dep,
+                &[r.pub_key, signature.s],
+                u64::MAX,
+            )
+            .map_err(|e| AdapterError::VerifySignature(e.to_string()))?;
+        }
+        _ => {
+            let r = SignatureR::decode(&signature.r)?;
+            let s = SignatureS::decode(&signature.s)?;
+
+            if r.inputs_len() != s.witnesses.len() {
+                return Err(
+                    AdapterError::VerifySignature("signature item mismatch".to_string()).into(),
+                );
+            }
+
+            let dummy_ckb_tx = InteroperationImpl::dummy_transaction(r.clone(), s);
+            let dummy_input = r.dummy_input();
+
+            verify_cell_mapping_sender(
+                stx.sender,
+                &dummy_ckb_tx,
+                dummy_input.clone(),
+                r.address_source(),
+            )?;
+
+            InteroperationImpl::verify_by_ckb_vm(
+                Default::default(),
+                &DataProvider::default(),
+                &dummy_ckb_tx,
+                dummy_input,
+                MAX_VERIFY_CKB_VM_CYCLES,
+            )
+            .map_err(|e| AdapterError::VerifySignature(e.to_string()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify the signatures on the given thread pool rather than on the runtime
+/// worker, the results come back by a oneshot channel.
+async fn verify_signatures(
+    pool: &ThreadPool,
+    txs: Vec<SignedTransaction>,
+) -> Vec<ProtocolResult<()>> {
+    let len = txs.len();
+    let (tx, rx) = oneshot::channel();
+    pool.spawn(move || {
+        let res = txs.par_iter().map(verify_tx_signature).collect::<Vec<_>>();
+        let _ = tx.send(res);
+    });
+
+    rx.await.unwrap_or_else(|_| {
+        (0..len)
+            .map(|_| Err(AdapterError::VerifySignature("verify pool canceled".to_string()).into()))
+            .collect()
+    })
+}
+
+fn verify_cell_mapping_sender(
+    sender: H160,
+    ckb_tx_view: &TransactionView,
+    dummy_input: Option<CellWithData>,
+    address_source: AddressSource,
+) -> ProtocolResult<()> {
+    let input = ckb_tx_view
+        .inputs()
+        .get(address_source.index as usize)
+        .ok_or(MemPoolError::InvalidAddressSource(address_source))?;
+
+    log::debug!("[mempool]: verify interoperation tx sender \ntx view \n{:?}\ndummy input\n {:?}\naddress source\n{:?}\n", ckb_tx_view, dummy_input, address_source);
+
+    if is_dummy_out_point(&input.previous_output()) {
+        log::debug!("[mempool]: verify interoperation tx dummy input mode.");
+
+        if let Some(cell) = dummy_input {
+            if address_source.type_ == 1 && cell.type_script.is_none() {
+                return Err(MemPoolError::InvalidAddressSource(address_source).into());
+            }
+
+            let script_hash = if address_source.type_ == 0 {
+                cell.lock_script_hash()
+            } else {
+                cell.type_script_hash().unwrap()
+            };
+
+            let expect_sender: H160 = Hasher::digest(script_hash).into();
+            if expect_sender != sender {
+                return Err(MemPoolError::InvalidSender {
+                    expect: expect_sender,
+                    actual: sender,
+                }
+                .into());
+            }
+
+            return Ok(());
+        }
+
+        return Err(MemPoolError::InvalidDummyInput.into());
+    }
+
+    log::debug!("[mempool]: verify interoperation tx reality input mode.");
+    match DataProvider.cell(&input.previous_output(), true) {
+        CellStatus::Live(cell) => {
+            let script_hash = if address_source.type_ == 0 {
+                ckb_blake2b_256(cell.cell_output.lock().as_slice())
+            } else if let Some(type_script) = cell.cell_output.type_().to_opt() {
+                ckb_blake2b_256(type_script.as_slice())
+            } else {
+                return Err(MemPoolError::InvalidAddressSource(address_source).into());
+            };
+
+            let expect_sender: H160 = Hasher::digest(script_hash).into();
+            if expect_sender != sender {
+                return Err(MemPoolError::InvalidSender {
+                    expect: expect_sender,
+                    actual: sender,
+                }
+                .into());
+            }
+
+            Ok(())
+        }
+        _ => Err(MemPoolError::InvalidAddressSource(address_source).into()),
+    }
+}
+
 struct IntervalTxsBroadcaster;
 
 impl IntervalTxsBroadcaster {
@@ -104,14 +274,24 @@ impl IntervalTxsBroadcaster {
         };
 
         for (origin, batch_stxs) in txs_cache.drain() {
+            let announce_msg = MsgAnnounceTxs {
+                hashes: batch_stxs.iter().map(|stx| stx.transaction.hash).collect(),
+            };
             let gossip_msg = BatchSignedTxs(batch_stxs);
 
             let ctx = Context::new();
-            let end = END_GOSSIP_NEW_TXS;
 
             report_if_err(
                 gossip
-                    .gossip(ctx, origin, end, gossip_msg, Priority::Normal)
+                    .push_and_announce(
+                        ctx,
+                        origin,
+                        END_GOSSIP_NEW_TXS,
+                        gossip_msg,
+                        END_GOSSIP_ANNOUNCE_TXS,
+                        announce_msg,
+                        Priority::Normal,
+                    )
                     .await,
             )
         }
@@ -132,6 +312,9 @@ pub struct DefaultMemPoolAdapter<C, N, S, DB, M, I> {
     stx_tx: UnboundedSender<(Option<usize>, SignedTransaction)>,
     err_rx: Mutex<UnboundedReceiver<ProtocolError>>,
 
+    inventory:   PeerInventory,
+    verify_pool: ThreadPool,
+
     pin_c: PhantomData<C>,
     pin_i: PhantomData<I>,
 }
@@ -155,6 +338,7 @@ where
         max_tx_size: usize,
         broadcast_txs_size: usize,
         broadcast_txs_interval: u64,
+        verify_pool_size: usize,
     ) -> Self {
         let (stx_tx, stx_rx) = unbounded();
         let (err_tx, err_rx) = unbounded();
@@ -181,6 +365,13 @@ where
             stx_tx,
             err_rx: Mutex::new(err_rx),
 
+            inventory: PeerInventory::default(),
+            verify_pool: ThreadPoolBuilder::new()
+                .num_threads(verify_pool_size)
+                .thread_name(|i| format!("mempool-verify-{}", i))
+                .build()
+                .expect("build mempool verify thread pool"),
+
             pin_c: PhantomData,
             pin_i: PhantomData,
         }
@@ -208,73 +399,16 @@ where
         .into())
     }
 
-    fn verify_cell_mapping_sender(
-        &self,
-        sender: H160,
-        ckb_tx_view: &TransactionView,
-        dummy_input: Option<CellWithData>,
-        address_source: AddressSource,
-    ) -> ProtocolResult<()> {
-        let input = ckb_tx_view
-            .inputs()
-            .get(address_source.index as usize)
-            .ok_or(MemPoolError::InvalidAddressSource(address_source))?;
-
-        log::debug!("[mempool]: verify interoperation tx sender \ntx view \n{:?}\ndummy input\n {:?}\naddress source\n{:?}\n", ckb_tx_view, dummy_input, address_source);
-
-        if is_dummy_out_point(&input.previous_output()) {
-            log::debug!("[mempool]: verify interoperation tx dummy input mode.");
-
-            if let Some(cell) = dummy_input {
-                if address_source.type_ == 1 && cell.type_script.is_none() {
-                    return Err(MemPoolError::InvalidAddressSource(address_source).into());
-                }
-
-                let script_hash = if address_source.type_ == 0 {
-                    cell.lock_script_hash()
-                } else {
-                    cell.type_script_hash().unwrap()
-                };
-
-                let expect_sender: H160 = Hasher::digest(script_hash).into();
-                if expect_sender != sender {
-                    return Err(MemPoolError::InvalidSender {
-                        expect: expect_sender,
-                        actual: sender,
-                    }
-                    .into());
-                }
-
-                return Ok(());
-            }
-
-            return Err(MemPoolError::InvalidDummyInput.into());
-        }
-
-        log::debug!("[mempool]: verify interoperation tx reality input mode.");
-        match DataProvider.cell(&input.previous_output(), true) {
-            CellStatus::Live(cell) => {
-                let script_hash = if address_source.type_ == 0 {
-                    ckb_blake2b_256(cell.cell_output.lock().as_slice())
-                } else if let Some(type_script) = cell.cell_output.type_().to_opt() {
-                    ckb_blake2b_256(type_script.as_slice())
-                } else {
-                    return Err(MemPoolError::InvalidAddressSource(address_source).into());
-                };
-
-                let expect_sender: H160 = Hasher::digest(script_hash).into();
-                if expect_sender != sender {
-                    return Err(MemPoolError::InvalidSender {
-                        expect: expect_sender,
-                        actual: sender,
-                    }
-                    .into());
-                }
+    fn verify_transaction(&self, ctx: Context, stx: &SignedTransaction) -> ProtocolResult<()> {
+        self.verify_tx_limits(ctx, stx)?;
+        verify_tx_signature(stx)
+    }
 
-                Ok(())
-            }
-            _ => Err(MemPoolError::InvalidAddressSource(address_source).into()),
-        }
+    fn verify_tx_limits(&self, ctx: Context, stx: &SignedTransaction) -> ProtocolResult<()> {
+        self.verify_chain_id(ctx.clone(), stx)?;
+        self.verify_tx_size(ctx.clone(), stx)?;
+        self.verify_gas_price(stx)?;
+        self.verify_gas_limit(ctx, stx)
     }
 
     fn verify_chain_id(&self, ctx: Context, stx: &SignedTransaction) -> ProtocolResult<()> {
@@ -376,7 +510,7 @@ where
 
         let resp_msg = self
             .network
-            .call::<MsgPullTxs, BatchSignedTxs>(ctx, RPC_PULL_TXS, pull_msg, Priority::High)
+            .call_trusted::<MsgPullTxs, BatchSignedTxs>(ctx, RPC_PULL_TXS, pull_msg, Priority::High)
             .await?;
 
         Ok(resp_msg.inner())
@@ -403,6 +537,91 @@ where
         Ok(())
     }
 
+    async fn push_compact_txs(
+        &self,
+        ctx: Context,
+        height: u64,
+        txs: Vec<SignedTransaction>,
+        peer_ids: Vec<Bytes>,
+    ) -> ProtocolResult<()> {
+        let short_ids = txs
+            .iter()
+            .map(|tx| short_tx_id(&tx.transaction.hash))
+            .collect::<Vec<_>>();
+
+        let pushes = peer_ids.into_iter().map(|peer_id| {
+            // The txs which the peer holds are sent by the short ids only
+            let (known, missing): (Vec<_>, Vec<_>) = txs
+                .iter()
+                .zip(short_ids.iter())
+                .partition(|(_, id)| self.inventory.contains(&peer_id, id));
+
+            common_apm::metrics::mempool::MEMPOOL_COUNTER_STATIC
+                .relay_push_tx
+                .inc_by(missing.len() as f64);
+            common_apm::metrics::mempool::MEMPOOL_COUNTER_STATIC
+                .relay_skip_tx
+                .inc_by(known.len() as f64);
+
+            let msg = MsgCompactTxs {
+                height,
+                short_ids: known.into_iter().map(|(_, id)| *id).collect(),
+                txs: missing.into_iter().map(|(tx, _)| tx.clone()).collect(),
+            };
+            let ctx = ctx.clone();
+            let short_ids = &short_ids;
+
+            async move {
+                let ret = self
+                    .network
+                    .multicast(
+                        ctx,
+                        END_GOSSIP_COMPACT_TXS,
+                        [peer_id.clone()],
+                        msg,
+                        Priority::High,
+                    )
+                    .await;
+
+                match ret {
+                    Ok(()) => self.inventory.insert(&peer_id, short_ids.iter().copied()),
+                    Err(e) => log::warn!("[core_mempool] push compact txs {}", e),
+                }
+            }
+        });
+        futures::future::join_all(pushes).await;
+
+        Ok(())
+    }
+
+    async fn forward_private_tx(&self, ctx: Context, tx: SignedTransaction) -> ProtocolResult<()> {
+        let block = self.storage.get_latest_block_header(ctx.clone()).await?;
+        let peer_ids = self
+            .metadata
+            .get_metadata_unchecked(ctx.clone(), block.number + 1)
+            .verifier_list
+            .iter()
+            .map(|v| PeerId::from_pubkey_bytes(v.pub_key.as_bytes()).map(PeerIdExt::into_bytes_ext))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.network
+            .multicast(
+                ctx,
+                END_GOSSIP_PRIVATE_TXS,
+                peer_ids,
+                BatchSignedTxs::new(vec![tx]),
+                Priority::High,
+            )
+            .await
+    }
+
+    fn mark_known_txs(&self, ctx: Context, short_ids: &[ShortTxId]) {
+        if let Ok(peer_id) = ctx.remote_peer_id() {
+            self.inventory
+                .insert(&peer_id.into_bytes_ext(), short_ids.iter().copied());
+        }
+    }
+
     async fn check_authorization(
         &self,
         ctx: Context,
@@ -463,81 +682,75 @@ where
         Ok(tx.transaction.unsigned.nonce() - account.nonce)
     }
 
-    async fn check_transaction(&self, ctx: Context, stx: &SignedTransaction) -> ProtocolResult<()> {
-        if stx.transaction.signature.is_none() {
-            return Err(AdapterError::VerifySignature("missing signature".to_string()).into());
+    async fn check_authorizations(
+        &self,
+        ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<U256>> {
+        // Load the accounts of all the uncached senders from one state snapshot,
+        // then the txs are checked against the nonce cache.
+        let uncached_senders = txs
+            .iter()
+            .filter(|tx| !is_call_system_script(tx.transaction.unsigned.action()))
+            .map(|tx| tx.sender)
+            .filter(|addr| !self.addr_nonce.contains_key(addr))
+            .collect::<HashSet<_>>();
+
+        if !uncached_senders.is_empty() {
+            match AxonExecutorAdapter::from_root(
+                **CURRENT_STATE_ROOT.load(),
+                Arc::clone(&self.trie_db),
+                Arc::clone(&self.storage),
+                Default::default(),
+            ) {
+                Ok(backend) => {
+                    let executor = AxonExecutor::default();
+                    for addr in uncached_senders.into_iter() {
+                        let account = executor.get_account(&backend, &addr);
+                        self.addr_nonce
+                            .insert(addr, (account.nonce, account.balance));
+                    }
+                }
+                Err(e) => log::warn!("[mempool]: open state snapshot error {:?}", e),
+            }
         }
 
-        if stx.public.is_none() {
-            return Err(AdapterError::VerifySignature("missing public key".to_string()).into());
+        let mut res = Vec::with_capacity(txs.len());
+        for tx in txs.iter() {
+            res.push(self.check_authorization(ctx.clone(), tx).await);
         }
+        res
+    }
 
-        self.verify_chain_id(ctx.clone(), stx)?;
-        self.verify_tx_size(ctx.clone(), stx)?;
-        self.verify_gas_price(stx)?;
-        self.verify_gas_limit(ctx, stx)?;
-
-        // Verify signature
-        let signature = stx.transaction.signature.clone().unwrap();
-        if signature.len() == SignatureComponents::ETHEREUM_TX_LEN {
-            // use original Secp256k1 library to verify
-            Secp256k1Recoverable::verify_signature(
-                stx.transaction.signature_hash(true).as_bytes(),
-                signature.as_bytes().as_ref(),
-                recover_intact_pub_key(&stx.public.unwrap()).as_bytes(),
-            )
-            .map_err(|err| AdapterError::VerifySignature(err.to_string()))?;
+    async fn check_transaction(&self, ctx: Context, stx: &SignedTransaction) -> ProtocolResult<()> {
+        self.verify_transaction(ctx, stx)
+    }
 
-            return Ok(());
+    async fn check_transactions(
+        &self,
+        ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<()>> {
+        let mut res = txs
+            .iter()
+            .map(|stx| self.verify_tx_limits(ctx.clone(), stx))
+            .collect::<Vec<_>>();
+        let (idx, to_verify): (Vec<_>, Vec<_>) = txs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| res[*i].is_ok())
+            .map(|(i, stx)| (i, stx.clone()))
+            .unzip();
+        if to_verify.is_empty() {
+            return res;
         }
 
-        match signature.r[0] {
-            0u8 => {
-                let r = rlp::decode::<CellDepWithPubKey>(&signature.r[1..])
-                    .map_err(AdapterError::Rlp)?;
-
-                InteroperationImpl::call_ckb_vm(
-                    Default::default(),
-                    &DataProvider::default(),
-                    r.cell_dep,
-                    &[r.pub_key, signature.s],
-                    u64::MAX,
-                )
-                .map_err(|e| AdapterError::VerifySignature(e.to_string()))?;
-            }
-            _ => {
-                let r = SignatureR::decode(&signature.r)?;
-                let s = SignatureS::decode(&signature.s)?;
-
-                if r.inputs_len() != s.witnesses.len() {
-                    return Err(AdapterError::VerifySignature(
-                        "signature item mismatch".to_string(),
-                    )
-                    .into());
-                }
-
-                let dummy_ckb_tx = InteroperationImpl::dummy_transaction(r.clone(), s);
-                let dummy_input = r.dummy_input();
-
-                self.verify_cell_mapping_sender(
-                    stx.sender,
-                    &dummy_ckb_tx,
-                    dummy_input.clone(),
-                    r.address_source(),
-                )?;
-
-                InteroperationImpl::verify_by_ckb_vm(
-                    Default::default(),
-                    &DataProvider::default(),
-                    &dummy_ckb_tx,
-                    dummy_input,
-                    MAX_VERIFY_CKB_VM_CYCLES,
-                )
-                .map_err(|e| AdapterError::VerifySignature(e.to_string()))?;
-            }
+        let verified = verify_signatures(&self.verify_pool, to_verify).await;
+        for (i, r) in idx.into_iter().zip(verified.into_iter()) {
+            res[i] = r;
         }
 
-        Ok(())
+        res
     }
 
     async fn check_storage_exist(&self, ctx: Context, tx_hash: &Hash) -> ProtocolResult<()> {
@@ -637,7 +850,7 @@ mod tests {
 
     use protocol::{traits::MessageCodec, types::Bytes};
 
-    use crate::tests::default_mock_txs;
+    use crate::tests::{default_mock_txs, mock_txs};
 
     #[derive(Clone)]
     struct MockGossip {
@@ -711,6 +924,30 @@ mod tests {
         {
             unreachable!()
         }
+
+        async fn push_and_announce<M, A>(
+            &self,
+            _: Context,
+            _: Option<usize>,
+            _: &str,
+            mut msg: M,
+            _: &str,
+            _: A,
+            _: Priority,
+        ) -> ProtocolResult<()>
+        where
+            M: MessageCodec,
+            A: MessageCodec,
+        {
+            let bytes = msg.encode_msg().expect("encode message fail");
+            self.msgs.lock().push(bytes);
+
+            self.signal_tx
+                .unbounded_send(())
+                .expect("send broadcast signal fail");
+
+            Ok(())
+        }
     }
 
     macro_rules! pop_msg {
@@ -809,4 +1046,14 @@ mod tests {
         let msg = pop_msg!(msgs);
         assert_eq!(msg.0.len(), 10, "first message should only have 10 stx");
     }
+
+    #[tokio::test]
+    async fn test_verify_signatures_on_current_thread_runtime() {
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let res = verify_signatures(&pool, mock_txs(10, 5, 0)).await;
+
+        assert_eq!(res.len(), 15);
+        assert!(res[..10].iter().all(|r| r.is_ok()));
+        assert!(res[10..].iter().all(|r| r.is_err()));
+    }
 }
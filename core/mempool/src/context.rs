@@ -2,11 +2,17 @@ use protocol::traits::Context;
 
 const TXS_ORIGINAL_KEY: &str = "txs_original";
 const NETWORK_TXS: usize = 1;
+const PRIVATE_TXS_KEY: &str = "private_txs";
 
 pub(crate) trait TxContext {
     fn mark_network_origin_new_txs(&self) -> Self;
 
     fn is_network_origin_txs(&self) -> bool;
+
+    /// Mark the txs as private, which are not broadcast to the network.
+    fn mark_private_txs(&self) -> Self;
+
+    fn is_private_txs(&self) -> bool;
 }
 
 impl TxContext for Context {
@@ -17,4 +23,12 @@ impl TxContext for Context {
     fn is_network_origin_txs(&self) -> bool {
         self.get::<usize>(TXS_ORIGINAL_KEY) == Some(&NETWORK_TXS)
     }
+
+    fn mark_private_txs(&self) -> Self {
+        self.with_value::<bool>(PRIVATE_TXS_KEY, true)
+    }
+
+    fn is_private_txs(&self) -> bool {
+        self.get::<bool>(PRIVATE_TXS_KEY) == Some(&true)
+    }
 }
@@ -12,25 +12,30 @@ mod tests;
 mod tx_wrapper;
 
 pub use adapter::message::{
-    MsgPullTxs, NewTxsHandler, PullTxsHandler, END_GOSSIP_NEW_TXS, RPC_PULL_TXS, RPC_RESP_PULL_TXS,
-    RPC_RESP_PULL_TXS_SYNC,
+    AnnounceTxsHandler, CompactTxsHandler, MsgAnnounceTxs, MsgCompactTxs, MsgPullTxs,
+    NewTxsHandler, PrivateTxsHandler, PullTxsHandler, END_GOSSIP_ANNOUNCE_TXS,
+    END_GOSSIP_COMPACT_TXS, END_GOSSIP_NEW_TXS, END_GOSSIP_PRIVATE_TXS, RPC_PULL_TXS,
+    RPC_RESP_PULL_TXS, RPC_RESP_PULL_TXS_SYNC,
 };
 pub use adapter::{AdapterError, DefaultMemPoolAdapter};
 
 use std::collections::HashSet;
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Duration;
 
-use futures::future::try_join_all;
+use dashmap::{DashMap, DashSet};
+use futures::future::join_all;
 
 use common_apm::Instant;
 use core_executor::{is_call_system_script, is_transaction_call};
 use core_network::NetworkContext;
 use protocol::traits::{Context, MemPool, MemPoolAdapter};
 use protocol::types::{
-    AddressSource, BlockNumber, Hash, PackedTxHashes, SignedTransaction, H160, H256, U256,
+    short_tx_id, AddressSource, BlockNumber, Bytes, DroppedTx, Hash, PackedTxHashes, ShortTxId,
+    SignedTransaction, TxDropReason, H160, H256, U256,
 };
-use protocol::{async_trait, tokio, Display, ProtocolError, ProtocolErrorKind, ProtocolResult};
+use protocol::{async_trait, Display, ProtocolError, ProtocolErrorKind, ProtocolResult};
 
 use crate::context::TxContext;
 use crate::pool::PriorityPool;
@@ -39,23 +44,36 @@ pub struct MemPoolImpl<Adapter> {
     pool:                PriorityPool,
     adapter:             Arc<Adapter>,
     cross_chain_address: H160,
+    // The announced txs which are being pulled from the announcers.
+    fetching_txs:        DashSet<Hash>,
+    // The private txs and the block number they expire at.
+    private_txs:         DashMap<Hash, BlockNumber>,
+    private_timeout_gap: u64,
 }
 
 impl<Adapter> MemPoolImpl<Adapter>
 where
     Adapter: MemPoolAdapter + 'static,
 {
+    /// A `tx_ttl` of zero seconds disables the wall-clock expiry.
     pub async fn new(
         pool_size: usize,
         timeout_gap: u64,
+        tx_ttl: u64,
+        private_timeout_gap: u64,
+        dropped_cache_size: usize,
         adapter: Adapter,
         initial_txs: Vec<SignedTransaction>,
         cross_chain_address: H160,
     ) -> Self {
+        let tx_ttl = (tx_ttl != 0).then_some(Duration::from_secs(tx_ttl));
         let mempool = MemPoolImpl {
-            pool: PriorityPool::new(pool_size, timeout_gap).await,
+            pool: PriorityPool::new(pool_size, timeout_gap, tx_ttl, dropped_cache_size).await,
             adapter: Arc::new(adapter),
             cross_chain_address,
+            fetching_txs: DashSet::new(),
+            private_txs: DashMap::new(),
+            private_timeout_gap,
         };
 
         for tx in initial_txs.into_iter() {
@@ -114,6 +132,11 @@ where
             return Err(MemPoolError::ReachLimit(i).into());
         }
 
+        if ctx.is_network_origin_txs() {
+            self.adapter
+                .mark_known_txs(ctx.clone(), &[short_tx_id(tx_hash)]);
+        }
+
         if self.pool.contains(tx_hash) {
             return Ok(());
         } else {
@@ -122,27 +145,94 @@ where
             self.adapter
                 .check_storage_exist(ctx.clone(), tx_hash)
                 .await?;
+            self.insert_checked_tx(ctx, tx, is_system_script, check_nonce)
+                .await?;
+        }
 
-            if is_system_script {
-                self.pool.insert_system_script_tx(tx.clone())?;
-            } else {
-                self.pool.insert(tx.clone(), true, check_nonce)?;
-            }
+        Ok(())
+    }
 
-            if !ctx.is_network_origin_txs() {
-                self.adapter.broadcast_tx(ctx, None, tx).await?;
-            } else {
-                let origin = ctx.session_id().unwrap();
-                self.adapter
-                    .broadcast_tx(ctx.clone(), Some(origin.value()), tx)
-                    .await?;
-                self.adapter.report_good(ctx);
-            }
+    async fn insert_checked_tx(
+        &self,
+        ctx: Context,
+        tx: SignedTransaction,
+        is_system_script: bool,
+        check_nonce: U256,
+    ) -> ProtocolResult<()> {
+        if is_system_script {
+            self.pool.insert_system_script_tx(tx.clone())?;
+        } else {
+            self.pool.insert(tx.clone(), true, check_nonce)?;
+        }
+
+        if ctx.is_private_txs() {
+            return self.after_insert_private_tx(ctx, tx).await;
+        }
+
+        if !ctx.is_network_origin_txs() {
+            self.adapter.broadcast_tx(ctx, None, tx).await?;
+        } else {
+            let origin = ctx.session_id().unwrap();
+            self.adapter
+                .broadcast_tx(ctx.clone(), Some(origin.value()), tx)
+                .await?;
+            self.adapter.report_good(ctx);
         }
 
         Ok(())
     }
 
+    // A private tx is never broadcast. The node which receives it from RPC
+    // forwards it to the validators only.
+    async fn after_insert_private_tx(
+        &self,
+        ctx: Context,
+        tx: SignedTransaction,
+    ) -> ProtocolResult<()> {
+        let latest_height = self.adapter.get_latest_height(ctx.clone()).await?;
+        self.private_txs.insert(
+            tx.transaction.hash,
+            latest_height + self.private_timeout_gap,
+        );
+
+        if ctx.is_network_origin_txs() {
+            self.adapter.report_good(ctx);
+            Ok(())
+        } else {
+            self.adapter.forward_private_tx(ctx, tx).await
+        }
+    }
+
+    // Check a batch of txs with the batch methods of adapter, the result of
+    // each tx is the same as checking it alone.
+    async fn check_txs_in_batch(
+        &self,
+        ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<U256>> {
+        let check_nonces = self.adapter.check_authorizations(ctx.clone(), txs).await;
+        let check_txs = self.adapter.check_transactions(ctx.clone(), txs).await;
+        let check_storages = join_all(txs.iter().map(|tx| {
+            self.adapter
+                .check_storage_exist(ctx.clone(), &tx.transaction.hash)
+        }))
+        .await;
+
+        check_nonces
+            .into_iter()
+            .zip(check_txs.into_iter())
+            .zip(check_storages.into_iter())
+            .map(|((check_nonce, check_tx), check_storage)| {
+                check_nonce.and_then(|nonce| check_tx.and(check_storage).map(|_| nonce))
+            })
+            .collect()
+    }
+
+    fn is_system_script_tx(&self, tx: &SignedTransaction) -> bool {
+        is_call_system_script(tx.transaction.unsigned.action())
+            || is_transaction_call(tx.transaction.unsigned.action(), &self.cross_chain_address)
+    }
+
     async fn verify_tx_in_parallel(
         &self,
         ctx: Context,
@@ -151,29 +241,9 @@ where
         let inst = Instant::now();
         let len = txs.len();
 
-        let futs = txs
-            .into_iter()
-            .map(|tx| {
-                let adapter = Arc::clone(&self.adapter);
-                let ctx = ctx.clone();
-
-                tokio::spawn(async move {
-                    let check_nonce = adapter.check_authorization(ctx.clone(), &tx).await?;
-                    adapter.check_transaction(ctx.clone(), &tx).await?;
-                    adapter
-                        .check_storage_exist(ctx.clone(), &tx.transaction.hash)
-                        .await?;
-                    Ok(check_nonce)
-                })
-            })
-            .collect::<Vec<tokio::task::JoinHandle<Result<_, ProtocolError>>>>();
-
-        let res: Vec<U256> = try_join_all(futs)
+        let res = self
+            .check_txs_in_batch(ctx, &txs)
             .await
-            .map_err(|e| {
-                log::error!("[mempool] verify batch txs error {:?}", e);
-                MemPoolError::VerifyBatchTransactions
-            })?
             .into_iter()
             .collect::<Result<Vec<U256>, ProtocolError>>()?;
 
@@ -185,6 +255,54 @@ where
         Ok(res)
     }
 
+    async fn insert_verified_txs(
+        &self,
+        ctx: Context,
+        txs: Vec<SignedTransaction>,
+        check_limit: bool,
+    ) -> ProtocolResult<()> {
+        let check_nonces = self.verify_tx_in_parallel(ctx.clone(), txs.clone()).await?;
+
+        for (signed_tx, check_nonce) in txs.into_iter().zip(check_nonces.into_iter()) {
+            let is_call_system_script =
+                is_call_system_script(signed_tx.transaction.unsigned.action());
+            if is_call_system_script {
+                self.pool.insert_system_script_tx(signed_tx)?;
+            } else {
+                self.pool.insert(signed_tx, check_limit, check_nonce)?;
+            }
+        }
+
+        self.adapter.report_good(ctx);
+        Ok(())
+    }
+
+    // The committed txs may change the balance of their senders, so the rest
+    // txs of these senders should be checked again. They are checked in one
+    // batch, which reads the accounts from one state snapshot.
+    async fn drop_invalid_txs(&self, ctx: Context, senders: Vec<H160>) {
+        let txs = senders
+            .iter()
+            .flat_map(|sender| self.pool.get_pending_txs_by_sender(sender))
+            .collect::<Vec<_>>();
+        if txs.is_empty() {
+            return;
+        }
+
+        let checks = self.adapter.check_authorizations(ctx, &txs).await;
+        for (tx, check) in txs.iter().zip(checks.into_iter()) {
+            if let Err(e) = check {
+                log::debug!(
+                    "[mempool]: drop tx {:?} after state change {:?}",
+                    tx.transaction.hash,
+                    e
+                );
+                self.pool
+                    .drop_tx(&tx.transaction.hash, TxDropReason::InvalidAfterStateChange);
+            }
+        }
+    }
+
     pub fn get_tx_cache(&self) -> &PriorityPool {
         &self.pool
     }
@@ -196,8 +314,7 @@ where
     Adapter: MemPoolAdapter + 'static,
 {
     async fn insert(&self, ctx: Context, tx: SignedTransaction) -> ProtocolResult<()> {
-        let is_call_system_script = is_call_system_script(tx.transaction.unsigned.action())
-            || is_transaction_call(tx.transaction.unsigned.action(), &self.cross_chain_address);
+        let is_call_system_script = self.is_system_script_tx(&tx);
 
         log::debug!(
             "[mempool]: is call system script {:?}",
@@ -207,6 +324,59 @@ where
         self.insert_tx(ctx, tx, is_call_system_script).await
     }
 
+    async fn insert_private(&self, ctx: Context, tx: SignedTransaction) -> ProtocolResult<()> {
+        let is_call_system_script = self.is_system_script_tx(&tx);
+        self.insert_tx(ctx.mark_private_txs(), tx, is_call_system_script)
+            .await
+    }
+
+    async fn insert_batch(
+        &self,
+        ctx: Context,
+        txs: Vec<SignedTransaction>,
+    ) -> Vec<ProtocolResult<()>> {
+        if let Err(i) = self.pool.reach_limit() {
+            return txs
+                .iter()
+                .map(|_| Err(MemPoolError::ReachLimit(i).into()))
+                .collect();
+        }
+
+        if ctx.is_network_origin_txs() {
+            let short_ids = txs
+                .iter()
+                .map(|tx| short_tx_id(&tx.transaction.hash))
+                .collect::<Vec<_>>();
+            self.adapter.mark_known_txs(ctx.clone(), &short_ids);
+        }
+
+        // The txs which are already in pool or duplicated in the batch are
+        // treated as inserted.
+        let mut res = txs.iter().map(|_| Ok(())).collect::<Vec<_>>();
+        let mut seen = HashSet::with_capacity(txs.len());
+        let (indexes, unknown_txs): (Vec<_>, Vec<_>) = txs
+            .into_iter()
+            .enumerate()
+            .filter(|(_, tx)| {
+                seen.insert(tx.transaction.hash) && !self.pool.contains(&tx.transaction.hash)
+            })
+            .unzip();
+
+        let checks = self.check_txs_in_batch(ctx.clone(), &unknown_txs).await;
+        for ((idx, tx), check) in indexes.into_iter().zip(unknown_txs).zip(checks) {
+            let is_system_script = self.is_system_script_tx(&tx);
+            res[idx] = match check {
+                Ok(check_nonce) => {
+                    self.insert_checked_tx(ctx.clone(), tx, is_system_script, check_nonce)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+        }
+
+        res
+    }
+
     async fn package(
         &self,
         _ctx: Context,
@@ -231,7 +401,7 @@ where
 
     async fn flush(
         &self,
-        _ctx: Context,
+        ctx: Context,
         tx_hashes: &[Hash],
         current_number: BlockNumber,
     ) -> ProtocolResult<()> {
@@ -240,7 +410,19 @@ where
             tx_hashes.len(),
         );
         self.adapter.clear_nonce_cache();
-        self.pool.flush(tx_hashes, current_number);
+        for hash in tx_hashes.iter() {
+            self.private_txs.remove(hash);
+        }
+        self.private_txs.retain(|hash, expire_at| {
+            if *expire_at > current_number {
+                return true;
+            }
+
+            self.pool.drop_tx(hash, TxDropReason::Timeout);
+            false
+        });
+        let senders = self.pool.flush(tx_hashes, current_number);
+        self.drop_invalid_txs(ctx, senders).await;
         Ok(())
     }
 
@@ -269,7 +451,7 @@ where
         if !missing_hashes.is_empty() {
             full_txs.extend(
                 self.adapter
-                    .get_transactions_from_storage(ctx, height, &missing_hashes)
+                    .get_transactions_from_storage(ctx.clone(), height, &missing_hashes)
                     .await?
                     .into_iter()
                     .flatten(),
@@ -283,6 +465,9 @@ where
             }
             .into())
         } else {
+            // The remote peer will hold these txs after the response.
+            let short_ids = tx_hashes.iter().map(short_tx_id).collect::<Vec<_>>();
+            self.adapter.mark_known_txs(ctx, &short_ids);
             Ok(full_txs)
         }
     }
@@ -296,8 +481,16 @@ where
         check_dup_order_hashes(order_tx_hashes)?;
 
         let unknown_hashes = self.show_unknown_txs(order_tx_hashes).await;
+        common_apm::metrics::mempool::MEMPOOL_COUNTER_STATIC
+            .ensure_hit_tx
+            .inc_by((order_tx_hashes.len() - unknown_hashes.len()) as f64);
+
         if !unknown_hashes.is_empty() {
             let unknown_len = unknown_hashes.len();
+            common_apm::metrics::mempool::MEMPOOL_COUNTER_STATIC
+                .ensure_pull_tx
+                .inc_by(unknown_len as f64);
+
             let txs = self
                 .adapter
                 .pull_txs(ctx.clone(), height, unknown_hashes)
@@ -312,24 +505,155 @@ where
                 .into());
             }
 
-            let check_nonces = self.verify_tx_in_parallel(ctx.clone(), txs.clone()).await?;
+            self.insert_verified_txs(ctx, txs, false).await?;
+        }
 
-            for (signed_tx, check_nonce) in txs.into_iter().zip(check_nonces.into_iter()) {
-                let is_call_system_script =
-                    is_call_system_script(signed_tx.transaction.unsigned.action());
-                if is_call_system_script {
-                    self.pool.insert_system_script_tx(signed_tx)?;
-                } else {
-                    self.pool.insert(signed_tx, false, check_nonce)?;
-                }
+        Ok(())
+    }
+
+    async fn relay_proposal_txs(
+        &self,
+        ctx: Context,
+        height: u64,
+        txs: Vec<SignedTransaction>,
+        peer_ids: Vec<Bytes>,
+    ) -> ProtocolResult<()> {
+        if txs.is_empty() || peer_ids.is_empty() {
+            return Ok(());
+        }
+
+        self.adapter
+            .push_compact_txs(ctx, height, txs, peer_ids)
+            .await
+    }
+
+    // Unlike `ensure_order_txs`, the relayed txs respect the pool limit. They
+    // will be pulled by `ensure_order_txs` if the pool is full. The short ids
+    // which are not in the pool are pulled by `ensure_order_txs` as well.
+    async fn insert_relayed_txs(
+        &self,
+        ctx: Context,
+        height: u64,
+        short_ids: Vec<ShortTxId>,
+        txs: Vec<SignedTransaction>,
+    ) -> ProtocolResult<()> {
+        let full_ids = txs.iter().map(|tx| short_tx_id(&tx.transaction.hash));
+        let known_ids = short_ids
+            .iter()
+            .copied()
+            .chain(full_ids)
+            .collect::<Vec<_>>();
+        self.adapter.mark_known_txs(ctx.clone(), &known_ids);
+
+        match self.adapter.get_latest_height(ctx.clone()).await {
+            Ok(latest_height) if height <= latest_height => {
+                log::debug!("[core_mempool] skip relayed txs of stale height {}", height);
+                return Ok(());
             }
+            Ok(_) => (),
+            Err(e) => log::warn!("[core_mempool] get latest height {}", e),
+        }
 
-            self.adapter.report_good(ctx);
+        let txs = txs
+            .into_iter()
+            .filter(|tx| !self.pool.contains(&tx.transaction.hash))
+            .collect::<Vec<_>>();
+        if txs.is_empty() || self.pool.reach_limit().is_err() {
+            return Ok(());
         }
 
+        log::debug!(
+            "[core_mempool] insert {:?} relayed txs of height {:?}",
+            txs.len(),
+            height
+        );
+
+        // Only the invalid txs fail the relay, the ones which are committed
+        // or don't fit the latest state are skipped.
+        let check_txs = self.adapter.check_transactions(ctx.clone(), &txs).await;
+        if let Some(e) = check_txs.into_iter().find_map(Result::err) {
+            return Err(e);
+        }
+        let check_nonces = self.adapter.check_authorizations(ctx.clone(), &txs).await;
+        let check_storages = join_all(txs.iter().map(|tx| {
+            self.adapter
+                .check_storage_exist(ctx.clone(), &tx.transaction.hash)
+        }))
+        .await;
+
+        for ((tx, check_nonce), check_storage) in txs
+            .into_iter()
+            .zip(check_nonces.into_iter())
+            .zip(check_storages.into_iter())
+        {
+            let hash = tx.transaction.hash;
+            let ret = check_nonce
+                .and_then(|nonce| check_storage.map(|_| nonce))
+                .and_then(|nonce| {
+                    if is_call_system_script(tx.transaction.unsigned.action()) {
+                        self.pool.insert_system_script_tx(tx)
+                    } else {
+                        self.pool.insert(tx, true, nonce)
+                    }
+                });
+            if let Err(e) = ret {
+                log::debug!("[core_mempool] skip relayed tx {:?} {}", hash, e);
+            }
+        }
+
+        self.adapter.report_good(ctx);
         Ok(())
     }
 
+    // The same tx may be announced by several peers at the same time, only the
+    // first announcement triggers a pull.
+    async fn fetch_announced_txs(&self, ctx: Context, tx_hashes: Vec<Hash>) -> ProtocolResult<()> {
+        let short_ids = tx_hashes.iter().map(short_tx_id).collect::<Vec<_>>();
+        self.adapter.mark_known_txs(ctx.clone(), &short_ids);
+
+        if self.pool.reach_limit().is_err() {
+            return Ok(());
+        }
+
+        let unknown_hashes = self
+            .show_unknown_txs(&tx_hashes)
+            .await
+            .into_iter()
+            .filter(|hash| self.fetching_txs.insert(*hash))
+            .collect::<Vec<_>>();
+        if unknown_hashes.is_empty() {
+            return Ok(());
+        }
+
+        let res = self
+            .adapter
+            .pull_txs(ctx.clone(), None, unknown_hashes.clone())
+            .await;
+        let ret = match res {
+            Ok(txs) => {
+                let requested = unknown_hashes.iter().collect::<HashSet<_>>();
+                let txs = txs
+                    .into_iter()
+                    .filter(|tx| requested.contains(&tx.transaction.hash))
+                    .collect::<Vec<_>>();
+
+                for res in self.insert_batch(ctx, txs).await.into_iter() {
+                    if let Err(e) = res {
+                        log::debug!("[core_mempool] insert announced tx error {}", e);
+                    }
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        };
+
+        for hash in unknown_hashes.iter() {
+            self.fetching_txs.remove(hash);
+        }
+
+        ret
+    }
+
     async fn get_tx_count_by_address(&self, _ctx: Context, address: H160) -> ProtocolResult<usize> {
         Ok(self.pool.get_tx_count_by_address(address))
     }
@@ -338,6 +662,10 @@ where
         self.pool.get_by_hash(tx_hash)
     }
 
+    fn get_dropped_tx(&self, _ctx: Context, tx_hash: &Hash) -> Option<DroppedTx> {
+        self.pool.get_dropped(tx_hash)
+    }
+
     fn set_args(&self, context: Context, state_root: H256, gas_limit: u64, max_tx_size: u64) {
         self.adapter
             .set_args(context, state_root, gas_limit, max_tx_size);
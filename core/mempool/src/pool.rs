@@ -1,4 +1,4 @@
-use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
@@ -10,7 +10,10 @@ use dashmap::DashMap;
 use parking_lot::{Mutex, RwLock};
 
 use protocol::tokio::{self, time::sleep};
-use protocol::types::{BlockNumber, Bytes, Hash, PackedTxHashes, SignedTransaction, H160, U256};
+use protocol::types::{
+    BlockNumber, Bytes, DroppedTx, Hash, PackedTxHashes, SignedTransaction, TxDropReason, H160,
+    U256,
+};
 use protocol::ProtocolResult;
 
 use crate::tx_wrapper::{PendingQueue, TxPtr, TxWrapper};
@@ -25,12 +28,20 @@ pub struct PriorityPool {
     stock_len:              AtomicUsize,
     pub(crate) timeout_gap: Mutex<BTreeMap<BlockNumber, HashSet<Hash>>>,
     timeout_config:         u64,
+    // `None` means the wall-clock expiry is disabled
+    tx_ttl:                 Option<Duration>,
+    dropped:                DroppedTxCache,
 
     flush_lock: Arc<RwLock<()>>,
 }
 
 impl PriorityPool {
-    pub async fn new(size: usize, timeout_config: u64) -> Self {
+    pub async fn new(
+        size: usize,
+        timeout_config: u64,
+        tx_ttl: Option<Duration>,
+        dropped_cache_size: usize,
+    ) -> Self {
         let pool = PriorityPool {
             sys_tx_bucket: BuiltInContractTxBucket::new(),
             pending_queue: Arc::new(DashMap::new()),
@@ -40,6 +51,8 @@ impl PriorityPool {
             stock_len: AtomicUsize::new(0),
             timeout_gap: Mutex::new(BTreeMap::new()),
             timeout_config,
+            tx_ttl,
+            dropped: DroppedTxCache::new(dropped_cache_size),
             flush_lock: Arc::new(RwLock::new(())),
         };
 
@@ -59,7 +72,7 @@ impl PriorityPool {
 
                         // drop this tx
                         if pending_queue.len() > 64 {
-                            tx.set_dropped();
+                            tx.set_dropped(TxDropReason::Evicted);
                             continue;
                         }
 
@@ -155,7 +168,7 @@ impl PriorityPool {
         hashes.extend(
             q.iter()
                 .filter_map(|ptr| {
-                    if ptr.is_dropped() {
+                    if ptr.is_dropped() || self.drop_if_expired(ptr) {
                         None
                     } else {
                         Some(ptr.hash())
@@ -178,7 +191,7 @@ impl PriorityPool {
 
             // drop this tx
             if pending_queue.len() > 64 {
-                tx.set_dropped();
+                tx.set_dropped(TxDropReason::Evicted);
                 continue;
             }
 
@@ -216,25 +229,67 @@ impl PriorityPool {
     pub fn get_by_hash(&self, hash: &Hash) -> Option<SignedTransaction> {
         let _flushing = self.flush_lock.read();
 
-        match self.tx_map.get(hash).map(|r| r.raw_tx()) {
+        let tx = self
+            .tx_map
+            .get(hash)
+            .filter(|ptr| !self.drop_if_expired(ptr))
+            .map(|ptr| ptr.raw_tx());
+        match tx {
             Some(tx) => Some(tx),
             None => self.sys_tx_bucket.get_tx_by_hash(hash),
         }
     }
 
-    pub fn flush(&self, hashes: &[Hash], number: BlockNumber) {
+    /// Get the dropped record of a tx. A tx which has been dropped but not
+    /// yet cleaned up by the next flush is reported as well, while a dropped tx
+    /// which is inserted again is not.
+    pub fn get_dropped(&self, hash: &Hash) -> Option<DroppedTx> {
+        if let Some(ptr) = self.tx_map.get(hash) {
+            return ptr.drop_record().map(|(reason, dropped_at)| DroppedTx {
+                hash: *hash,
+                reason,
+                dropped_at,
+            });
+        }
+
+        self.dropped.get(hash)
+    }
+
+    /// Drop a tx which is still in the pool. It is removed on the next flush.
+    pub fn drop_tx(&self, hash: &Hash, reason: TxDropReason) {
+        if let Some(ptr) = self.tx_map.get(hash) {
+            ptr.set_dropped(reason);
+        }
+    }
+
+    pub fn get_pending_txs_by_sender(&self, sender: &H160) -> Vec<SignedTransaction> {
+        self.pending_queue
+            .get(sender)
+            .map(|queue| queue.pending_txs().map(|ptr| ptr.raw_tx()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Flush the committed txs out of the pool and return the senders of them.
+    pub fn flush(&self, hashes: &[Hash], number: BlockNumber) -> Vec<H160> {
         let _flushing = self.flush_lock.write();
         self.flush_to_pending_queue();
         let mut reduce_len = 0;
-        self.flush_inner(hashes, &mut reduce_len, number);
+        let senders = self.flush_inner(hashes, &mut reduce_len, number);
         self.sys_tx_bucket.flush(hashes, &mut reduce_len);
 
         if reduce_len != 0 {
             self.stock_len.fetch_sub(reduce_len, Ordering::AcqRel);
         }
+
+        senders
     }
 
-    fn flush_inner(&self, hashes: &[Hash], reduce_len: &mut usize, number: BlockNumber) {
+    fn flush_inner(
+        &self,
+        hashes: &[Hash],
+        reduce_len: &mut usize,
+        number: BlockNumber,
+    ) -> Vec<H160> {
         let mut q = self.real_queue.lock();
         let mut timeout_gap = self.timeout_gap.lock();
 
@@ -255,6 +310,7 @@ impl PriorityPool {
             }
         }
 
+        let senders = remove_tip_nonce.keys().copied().collect::<Vec<_>>();
         for (k, v) in remove_tip_nonce {
             if let Some(mut value) = self.pending_queue.get_mut(&k) {
                 value.value_mut().set_drop_by_nonce_tip(v);
@@ -270,25 +326,51 @@ impl PriorityPool {
         };
 
         let mut retain_keys = Vec::with_capacity(self.len() / 4);
+        let mut dropped = Vec::new();
         self.tx_map.retain(|hash, v| {
-            if !v.is_dropped() && (timeout.is_empty() || !timeout.contains(&v.hash())) {
-                retain_keys.push(*hash);
-                return true;
+            if !v.is_dropped() && !self.drop_if_expired(v) {
+                if timeout.is_empty() || !timeout.contains(&v.hash()) {
+                    retain_keys.push(*hash);
+                    return true;
+                }
+
+                v.set_dropped(TxDropReason::Timeout);
             }
 
-            v.set_dropped();
+            if let Some((reason, dropped_at)) = v.drop_record() {
+                dropped.push(DroppedTx {
+                    hash: *hash,
+                    reason,
+                    dropped_at,
+                });
+            }
             *reduce_len += 1;
             false
         });
 
         timeout_gap.entry(number).or_default().extend(retain_keys);
+        self.dropped.extend(dropped);
 
         q.retain(|ptr| !ptr.is_dropped());
 
         self.pending_queue.retain(|_, v| {
             v.clear_droped();
             !v.need_remove()
-        })
+        });
+
+        senders
+    }
+
+    /// Drop the tx if it stays in the pool longer than the wall-clock TTL, so
+    /// it is neither packaged nor served to the other nodes before the flush.
+    fn drop_if_expired(&self, tx: &TxWrapper) -> bool {
+        match self.tx_ttl {
+            Some(ttl) if tx.is_expired(ttl) => {
+                tx.set_dropped(TxDropReason::Timeout);
+                true
+            }
+            _ => false,
+        }
     }
 
     pub fn real_queue_len(&self) -> usize {
@@ -375,6 +457,49 @@ impl BuiltInContractTxBucket {
     }
 }
 
+/// A bounded FIFO cache of recently dropped txs.
+struct DroppedTxCache {
+    capacity: usize,
+    inner:    Mutex<(HashMap<Hash, DroppedTx>, VecDeque<Hash>)>,
+}
+
+impl DroppedTxCache {
+    fn new(capacity: usize) -> Self {
+        DroppedTxCache {
+            capacity,
+            inner: Mutex::new((
+                HashMap::with_capacity(capacity),
+                VecDeque::with_capacity(capacity),
+            )),
+        }
+    }
+
+    fn extend(&self, dropped: Vec<DroppedTx>) {
+        if dropped.is_empty() || self.capacity == 0 {
+            return;
+        }
+
+        let mut guard = self.inner.lock();
+        let (map, order) = &mut *guard;
+
+        for record in dropped {
+            if map.insert(record.hash, record).is_none() {
+                order.push_back(record.hash);
+            }
+
+            while order.len() > self.capacity {
+                if let Some(oldest) = order.pop_front() {
+                    map.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    fn get(&self, hash: &Hash) -> Option<DroppedTx> {
+        self.inner.lock().0.get(hash).copied()
+    }
+}
+
 fn pop_all_item<T>(queue: Arc<ArrayQueue<T>>) -> impl Iterator<Item = T> {
     (0..queue.len()).map(move |_| queue.pop().unwrap())
 }
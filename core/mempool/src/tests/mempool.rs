@@ -1,9 +1,11 @@
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
-use protocol::types::Hasher;
+use protocol::types::{short_tx_id, Hasher, TxDropReason};
 
 use super::*;
+use crate::pool::PriorityPool;
 
 macro_rules! insert {
     (normal($pool_size: expr, $input: expr, $output: expr)) => {
@@ -55,6 +57,21 @@ async fn test_insert() {
     insert!(invalid(80, 10, 80));
 }
 
+#[tokio::test]
+async fn test_insert_batch() {
+    let mempool = default_mempool().await;
+    let mut txs = mock_txs(10, 5, TIMEOUT);
+    txs.push(txs[0].clone());
+
+    let res = mempool.insert_batch(Context::new(), txs).await;
+    assert_eq!(res.len(), 16);
+    assert!(res[..10].iter().all(|r| r.is_ok()));
+    assert!(res[10..15].iter().all(|r| r.is_err()));
+    // the duplicated tx is treated as inserted
+    assert!(res[15].is_ok());
+    assert_eq!(mempool.get_tx_cache().len(), 10);
+}
+
 macro_rules! package {
     (normal($tx_num_limit: expr, $insert: expr, $expect_order: expr, $expect_propose: expr)) => {
         package!(inner(
@@ -314,6 +331,96 @@ async fn test_nonce_insert() {
     assert_eq!(0, pool.real_queue_len());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dropped_tx_reason() {
+    let mempool = Arc::new(new_mempool(1024, 0, 0, 0).await);
+
+    let priv_key = Secp256k1RecoverablePrivateKey::generate(&mut OsRng);
+    let pub_key = priv_key.pub_key();
+    let txs: Vec<SignedTransaction> = (0..2)
+        .map(|i| mock_signed_tx(&priv_key, &pub_key, 0, i as u64, true))
+        .collect();
+
+    let replace_tx = {
+        let mut tx = txs[1].clone();
+        match tx.transaction.unsigned {
+            UnsignedTransaction::Eip1559(ref mut p) => {
+                p.gas_price = 2.into();
+                p.max_priority_fee_per_gas = 2.into();
+            }
+            UnsignedTransaction::Eip2930(ref mut p) => p.gas_price = 2.into(),
+            UnsignedTransaction::Legacy(ref mut p) => p.gas_price = 2.into(),
+        }
+        tx.transaction.hash = H256::from_low_u64_le(2);
+
+        tx
+    };
+
+    let underpriced_tx = {
+        let mut tx = txs[1].clone();
+        tx.transaction.hash = H256::from_low_u64_le(3);
+        tx
+    };
+
+    let pool = mempool.get_tx_cache();
+    pool.insert(txs[0].clone(), false, 0.into()).unwrap();
+    pool.insert(txs[1].clone(), false, 1.into()).unwrap();
+    pool.insert(replace_tx.clone(), false, 1.into()).unwrap();
+    pool.insert(underpriced_tx.clone(), false, 1.into())
+        .unwrap();
+    pool.package(1000.into(), 10);
+
+    // The replaced tx is reported before the flush cleans it up
+    let replaced = mempool
+        .get_dropped_tx(Context::new(), &txs[1].transaction.hash)
+        .unwrap();
+    assert_eq!(replaced.reason, TxDropReason::Replaced);
+    assert!(replaced.dropped_at > 0);
+    assert!(mempool
+        .get_dropped_tx(Context::new(), &replace_tx.transaction.hash)
+        .is_none());
+    let dropped = mempool
+        .get_dropped_tx(Context::new(), &underpriced_tx.transaction.hash)
+        .unwrap();
+    assert_eq!(dropped.reason, TxDropReason::Underpriced);
+
+    pool.flush(&[txs[0].transaction.hash], 1);
+    assert_eq!(1, pool.len());
+    // The time of the drop is kept by the flush
+    let dropped = pool.get_dropped(&txs[1].transaction.hash).unwrap();
+    assert_eq!(dropped, replaced);
+
+    pool.timeout_gap
+        .lock()
+        .entry(0)
+        .or_default()
+        .insert(replace_tx.transaction.hash);
+    pool.flush(&[], 20);
+
+    assert_eq!(0, pool.len());
+    let dropped = pool.get_dropped(&replace_tx.transaction.hash).unwrap();
+    assert_eq!(dropped.reason, TxDropReason::Timeout);
+    assert!(pool.get_dropped(&txs[0].transaction.hash).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_expired_tx_not_served() {
+    let pool = PriorityPool::new(1024, 20, Some(Duration::ZERO), DROPPED_CACHE_SIZE).await;
+    let tx = default_mock_txs(1).pop().unwrap();
+    let hash = tx.transaction.hash;
+    pool.insert(tx, false, 0.into()).unwrap();
+
+    // Expired before the flush, so it is neither packaged nor served
+    assert!(pool.package(1000.into(), 10).hashes.is_empty());
+    assert!(pool.get_by_hash(&hash).is_none());
+    let dropped = pool.get_dropped(&hash).unwrap();
+    assert_eq!(dropped.reason, TxDropReason::Timeout);
+
+    pool.flush(&[], 1);
+    assert_eq!(0, pool.len());
+    assert_eq!(pool.get_dropped(&hash).unwrap(), dropped);
+}
+
 macro_rules! ensure_order_txs {
     ($in_pool: expr, $out_pool: expr, $pool_size: expr) => {
         let mempool = &Arc::new(new_mempool($pool_size, 0, 0, 0).await);
@@ -345,6 +452,124 @@ async fn test_ensure_order_txs() {
     ensure_order_txs!(50, 50, 50);
 }
 
+#[tokio::test]
+async fn test_ensure_order_txs_with_relayed_txs() {
+    let mempool = &Arc::new(default_mempool().await);
+
+    let txs = &default_mock_txs(101);
+    let (relayed_txs, unknown_txs) = txs.split_at(50);
+    let (unknown_txs, stale_txs) = unknown_txs.split_at(50);
+    let short_ids = unknown_txs
+        .iter()
+        .map(|tx| short_tx_id(&tx.transaction.hash))
+        .collect::<Vec<_>>();
+    let height = CURRENT_HEIGHT + 1;
+    mempool
+        .insert_relayed_txs(Context::new(), height, short_ids, relayed_txs.to_vec())
+        .await
+        .unwrap();
+
+    // The relayed txs are rebuilt from the pool without any pull.
+    let relayed_hashes: Vec<Hash> = relayed_txs.iter().map(|tx| tx.transaction.hash).collect();
+    mempool
+        .ensure_order_txs(Context::new(), Some(height), &relayed_hashes)
+        .await
+        .unwrap();
+
+    // The unknown txs are neither in the pool nor on the network.
+    let unknown_hashes: Vec<Hash> = unknown_txs.iter().map(|tx| tx.transaction.hash).collect();
+    assert!(mempool
+        .ensure_order_txs(Context::new(), Some(height), &unknown_hashes)
+        .await
+        .is_err());
+
+    // The relay of a committed height is skipped
+    mempool
+        .insert_relayed_txs(Context::new(), CURRENT_HEIGHT, vec![], stale_txs.to_vec())
+        .await
+        .unwrap();
+    assert!(!mempool
+        .get_tx_cache()
+        .contains(&stale_txs[0].transaction.hash));
+
+    // The invalid txs fail the relay
+    let mut invalid_tx = stale_txs[0].clone();
+    invalid_tx.transaction.hash = Hasher::digest(Bytes::from("invalid"));
+    assert!(mempool
+        .insert_relayed_txs(Context::new(), height, vec![], vec![invalid_tx])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_fetch_announced_txs() {
+    let mempool = &Arc::new(default_mempool().await);
+
+    let txs = default_mock_txs(100);
+    let (known_txs, announced_txs) = txs.split_at(20);
+    concurrent_insert(known_txs.to_vec(), Arc::clone(mempool)).await;
+    concurrent_broadcast(announced_txs.to_vec(), Arc::clone(mempool)).await;
+
+    let tx_hashes: Vec<Hash> = txs.iter().map(|tx| tx.transaction.hash).collect();
+    mempool
+        .fetch_announced_txs(Context::new(), tx_hashes.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(mempool.len(), txs.len());
+    assert!(tx_hashes
+        .iter()
+        .all(|hash| mempool.get_tx_cache().contains(hash)));
+    assert!(mempool.fetching_txs.is_empty());
+}
+
+#[tokio::test]
+async fn test_private_txs() {
+    let mempool = default_mempool().await;
+    let txs = default_mock_txs(2);
+    let (private_tx, public_tx) = (txs[0].clone(), txs[1].clone());
+
+    mempool
+        .insert_private(Context::new(), private_tx.clone())
+        .await
+        .unwrap();
+    mempool
+        .insert(Context::new(), public_tx.clone())
+        .await
+        .unwrap();
+
+    // The private tx is forwarded to the validators instead of broadcast.
+    let adapter = mempool.get_adapter();
+    let private_hash = private_tx.transaction.hash;
+    assert!(adapter.private_txs.contains_key(&private_hash));
+    assert!(!adapter.network_txs.contains_key(&private_hash));
+    assert!(adapter
+        .network_txs
+        .contains_key(&public_tx.transaction.hash));
+
+    mempool
+        .flush(
+            Context::new(),
+            &[],
+            CURRENT_HEIGHT + PRIVATE_TX_TIMEOUT_GAP - 1,
+        )
+        .await
+        .unwrap();
+    assert!(mempool.get_tx_cache().contains(&private_hash));
+
+    mempool
+        .flush(Context::new(), &[], CURRENT_HEIGHT + PRIVATE_TX_TIMEOUT_GAP)
+        .await
+        .unwrap();
+    let pool = mempool.get_tx_cache();
+    assert!(!pool.contains(&private_hash));
+    assert!(pool.contains(&public_tx.transaction.hash));
+    assert_eq!(
+        pool.get_dropped(&private_hash).unwrap().reason,
+        TxDropReason::Timeout
+    );
+}
+
 #[tokio::test]
 async fn bench_sign_with_spawn_list() {
     let adapter = Arc::new(HashMemPoolAdapter::new());
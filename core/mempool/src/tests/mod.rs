@@ -3,6 +3,7 @@ mod mempool;
 use std::sync::Arc;
 
 use dashmap::DashMap;
+use rayon::prelude::*;
 
 use common_crypto::{
     Crypto, PrivateKey, Secp256k1Recoverable, Secp256k1RecoverablePrivateKey,
@@ -13,8 +14,8 @@ use protocol::rand::{random, rngs::OsRng};
 use protocol::traits::{Context, MemPool, MemPoolAdapter};
 use protocol::types::{
     public_to_address, recover_intact_pub_key, Bytes, Eip1559Transaction, Hash, PackedTxHashes,
-    Public, SignedTransaction, TransactionAction, UnsignedTransaction, UnverifiedTransaction, H160,
-    H256, U256,
+    Public, ShortTxId, SignedTransaction, TransactionAction, UnsignedTransaction,
+    UnverifiedTransaction, H160, H256, U256,
 };
 use protocol::{async_trait, tokio, ProtocolResult};
 
@@ -27,16 +28,20 @@ const POOL_SIZE: usize = 100_000;
 const MAX_TX_SIZE: u64 = 1024; // 1KB
 const TIMEOUT: u64 = 1000;
 const TIMEOUT_GAP: u64 = 100;
+const DROPPED_CACHE_SIZE: usize = 1000;
+const PRIVATE_TX_TIMEOUT_GAP: u64 = 5;
 const NATIVE_TOKEN_ISSUE_ADDRESS: H160 = system_contract_address(0x0);
 
 pub struct HashMemPoolAdapter {
     network_txs: DashMap<Hash, SignedTransaction>,
+    private_txs: DashMap<Hash, SignedTransaction>,
 }
 
 impl HashMemPoolAdapter {
     fn new() -> HashMemPoolAdapter {
         HashMemPoolAdapter {
             network_txs: DashMap::new(),
+            private_txs: DashMap::new(),
         }
     }
 }
@@ -68,6 +73,23 @@ impl MemPoolAdapter for HashMemPoolAdapter {
         Ok(())
     }
 
+    async fn push_compact_txs(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _txs: Vec<SignedTransaction>,
+        _peer_ids: Vec<Bytes>,
+    ) -> ProtocolResult<()> {
+        Ok(())
+    }
+
+    async fn forward_private_tx(&self, _ctx: Context, tx: SignedTransaction) -> ProtocolResult<()> {
+        self.private_txs.insert(tx.transaction.hash, tx);
+        Ok(())
+    }
+
+    fn mark_known_txs(&self, _ctx: Context, _short_ids: &[ShortTxId]) {}
+
     async fn check_authorization(
         &self,
         _ctx: Context,
@@ -76,11 +98,32 @@ impl MemPoolAdapter for HashMemPoolAdapter {
         Ok(U256::zero())
     }
 
+    async fn check_authorizations(
+        &self,
+        _ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<U256>> {
+        txs.iter().map(|_| Ok(U256::zero())).collect()
+    }
+
     async fn check_transaction(&self, _ctx: Context, tx: &SignedTransaction) -> ProtocolResult<()> {
         check_hash(tx)?;
         check_sig(tx)
     }
 
+    async fn check_transactions(
+        &self,
+        _ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<()>> {
+        txs.par_iter()
+            .map(|tx| {
+                check_hash(tx)?;
+                check_sig(tx)
+            })
+            .collect()
+    }
+
     async fn check_storage_exist(&self, _ctx: Context, _tx_hash: &Hash) -> ProtocolResult<()> {
         Ok(())
     }
@@ -119,7 +162,7 @@ pub fn mock_sys_txs(size: usize) -> Vec<SignedTransaction> {
         .collect()
 }
 
-fn mock_txs(valid_size: usize, invalid_size: usize, timeout: u64) -> Vec<SignedTransaction> {
+pub fn mock_txs(valid_size: usize, invalid_size: usize, timeout: u64) -> Vec<SignedTransaction> {
     (0..valid_size + invalid_size)
         .map(|i| {
             let priv_key = Secp256k1RecoverablePrivateKey::generate(&mut OsRng);
@@ -140,7 +183,17 @@ async fn new_mempool(
     _max_tx_size: u64,
 ) -> MemPoolImpl<HashMemPoolAdapter> {
     let adapter = HashMemPoolAdapter::new();
-    MemPoolImpl::new(pool_size, 20, adapter, vec![], Default::default()).await
+    MemPoolImpl::new(
+        pool_size,
+        20,
+        0,
+        PRIVATE_TX_TIMEOUT_GAP,
+        DROPPED_CACHE_SIZE,
+        adapter,
+        vec![],
+        Default::default(),
+    )
+    .await
 }
 
 fn check_hash(tx: &SignedTransaction) -> ProtocolResult<()> {
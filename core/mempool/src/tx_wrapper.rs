@@ -1,27 +1,37 @@
 use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
 use std::collections::{btree_map::Entry, BTreeMap};
 use std::ops::Bound::{Included, Unbounded};
-use std::sync::atomic::{AtomicU8, Ordering as AtomicOrdering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering as AtomicOrdering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use protocol::types::{Hash, SignedTransaction, H160, U256};
+use protocol::types::{Hash, SignedTransaction, TxDropReason, H160, U256};
 
 pub type TxPtr = Arc<TxWrapper>;
 
+const DROP_REASON_SHIFT: u32 = 56;
+const DROP_TIME_MASK: u64 = (1 << DROP_REASON_SHIFT) - 1;
+
 #[derive(Debug)]
 pub struct TxWrapper {
     // 0x00 init
     // 0x01 package
     // 0x10 drop
-    state: AtomicU8,
-    tx:    SignedTransaction,
+    state:       AtomicU8,
+    // The drop reason in the highest byte and the unix time in milliseconds
+    // of the drop in the rest, 0 means not dropped
+    drop_record: AtomicU64,
+    insert_at:   Instant,
+    tx:          SignedTransaction,
 }
 
 impl From<SignedTransaction> for TxWrapper {
     fn from(stx: SignedTransaction) -> Self {
         TxWrapper {
-            tx:    stx,
-            state: AtomicU8::new(0),
+            tx:          stx,
+            state:       AtomicU8::new(0),
+            drop_record: AtomicU64::new(0),
+            insert_at:   Instant::now(),
         }
     }
 }
@@ -74,10 +84,30 @@ impl TxWrapper {
         self.state.load(AtomicOrdering::Acquire) & 0x10 == 0x10
     }
 
-    pub fn set_dropped(&self) {
+    /// Mark the tx as dropped. Only the first reason and time are kept.
+    pub fn set_dropped(&self, reason: TxDropReason) {
+        let record = (u64::from(encode_drop_reason(reason)) << DROP_REASON_SHIFT)
+            | (unix_now_ms() & DROP_TIME_MASK);
+        let _ = self.drop_record.compare_exchange(
+            0,
+            record,
+            AtomicOrdering::AcqRel,
+            AtomicOrdering::Acquire,
+        );
         self.state.fetch_or(0x10, AtomicOrdering::AcqRel);
     }
 
+    /// The reason and the unix time in milliseconds of the drop
+    pub fn drop_record(&self) -> Option<(TxDropReason, u64)> {
+        let record = self.drop_record.load(AtomicOrdering::Acquire);
+        decode_drop_reason((record >> DROP_REASON_SHIFT) as u8)
+            .map(|reason| (reason, record & DROP_TIME_MASK))
+    }
+
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        self.insert_at.elapsed() >= ttl
+    }
+
     fn set_package(&self) {
         self.state.fetch_or(0x01, AtomicOrdering::AcqRel);
     }
@@ -101,7 +131,7 @@ impl PendingQueue {
         let nonce = *tx.nonce();
         let current_tip = nonce - nonce_diff;
         if self.current_tip_nonce > nonce {
-            tx.set_dropped();
+            tx.set_dropped(TxDropReason::NonceTooLow);
             return false;
         }
 
@@ -113,14 +143,14 @@ impl PendingQueue {
             Entry::Occupied(mut o) => {
                 if o.get().gas_price() < tx.gas_price() {
                     let old = o.insert(Arc::clone(&tx));
-                    old.set_dropped();
+                    old.set_dropped(TxDropReason::Replaced);
                     // replace with package list tx
                     if old.is_package() {
                         tx.set_package();
                         return true;
                     }
                 } else {
-                    tx.set_dropped();
+                    tx.set_dropped(TxDropReason::Underpriced);
                 }
             }
             Entry::Vacant(v) => {
@@ -157,7 +187,7 @@ impl PendingQueue {
 
     pub fn set_drop_by_nonce_tip(&mut self, nonce: U256) {
         for (_, v) in self.queue.range((Included(0.into()), Included(nonce))) {
-            v.set_dropped();
+            v.set_dropped(TxDropReason::NonceTooLow);
         }
         self.pop_tip_nonce = nonce + 1;
         self.current_tip_nonce = self.pop_tip_nonce;
@@ -174,4 +204,38 @@ impl PendingQueue {
     pub fn len(&self) -> usize {
         self.queue.len()
     }
+
+    pub fn pending_txs(&self) -> impl Iterator<Item = &TxPtr> {
+        self.queue.values().filter(|tx| !tx.is_dropped())
+    }
+}
+
+fn encode_drop_reason(reason: TxDropReason) -> u8 {
+    match reason {
+        TxDropReason::Timeout => 1,
+        TxDropReason::NonceTooLow => 2,
+        TxDropReason::Replaced => 3,
+        TxDropReason::Evicted => 4,
+        TxDropReason::InvalidAfterStateChange => 5,
+        TxDropReason::Underpriced => 6,
+    }
+}
+
+fn decode_drop_reason(raw: u8) -> Option<TxDropReason> {
+    match raw {
+        1 => Some(TxDropReason::Timeout),
+        2 => Some(TxDropReason::NonceTooLow),
+        3 => Some(TxDropReason::Replaced),
+        4 => Some(TxDropReason::Evicted),
+        5 => Some(TxDropReason::InvalidAfterStateChange),
+        6 => Some(TxDropReason::Underpriced),
+        _ => None,
+    }
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
 }
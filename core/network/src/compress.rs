@@ -1,20 +1,240 @@
-use std::io;
+use std::{fmt, io};
 
 use protocol::types::{BufMut, Bytes, BytesMut};
 
+use common_apm::metrics::network::{
+    NETWORK_COMPRESSED_BYTES_COUNT_VEC, NETWORK_UNCOMPRESSED_BYTES_COUNT_VEC,
+};
 use log::debug;
 use snap::raw::{decompress_len, Decoder as SnapDecoder, Encoder as SnapEncoder};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
 
 pub(crate) const COMPRESSION_SIZE_THRESHOLD: usize = 1024;
 const UNCOMPRESS_FLAG: u8 = 0b0000_0000;
 const COMPRESS_FLAG: u8 = 0b1000_0000;
+const FORMAT_MASK: u8 = 0b0111_0000;
+const FORMAT_SHIFT: u8 = 4;
 const MAX_UNCOMPRESSED_LEN: usize = 1 << 23; // 8MB
+const ZSTD_LEVEL: i32 = 3;
+const LZ4_SIZE_PREFIX_LEN: usize = 4;
+
+/// Compression formats, in the order of preference
+///
+/// The discriminant is the format bits of the message flag. Snappy is 0 so
+/// that the messages compressed by the peers only knowing snappy stay the
+/// same.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    ZstdDict = 3,
+    Zstd = 1,
+    Lz4 = 2,
+    #[default]
+    Snappy = 0,
+}
+
+impl Compression {
+    pub(crate) fn from_u32(n: u32) -> Option<Self> {
+        match n {
+            0 => Some(Compression::Snappy),
+            1 => Some(Compression::Zstd),
+            2 => Some(Compression::Lz4),
+            3 => Some(Compression::ZstdDict),
+            _ => None,
+        }
+    }
+
+    fn from_flag(flag: u8) -> Option<Self> {
+        Compression::from_u32(u32::from((flag & FORMAT_MASK) >> FORMAT_SHIFT))
+    }
+
+    fn flag(self) -> u8 {
+        COMPRESS_FLAG | ((self as u8) << FORMAT_SHIFT)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::ZstdDict => "zstd_dict",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+            Compression::Snappy => "snappy",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Train a zstd dictionary from the sample payloads, such as the encoded
+/// blocks and transactions of a chain
+pub fn train_zstd_dict<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
+
+struct ZstdDict {
+    id:      u32,
+    encoder: EncoderDictionary<'static>,
+    decoder: DecoderDictionary<'static>,
+}
+
+/// Compressor of the messages
+///
+/// Every format is accepted on receiving, since the format is written in
+/// the message flag, but a message must be sent in the format negotiated
+/// with the peer.
+#[derive(Default)]
+pub struct Compressor {
+    dict: Option<ZstdDict>,
+}
+
+impl Compressor {
+    /// Create with an optional shared zstd dictionary. The dictionary is only
+    /// used with the peers advertising the same dictionary id.
+    pub fn new(dict: Option<&[u8]>) -> Self {
+        let dict = dict.and_then(|dict| {
+            let id = zstd::zstd_safe::get_dict_id_from_dict(dict)?.get();
+            Some(ZstdDict {
+                id,
+                encoder: EncoderDictionary::copy(dict, ZSTD_LEVEL),
+                decoder: DecoderDictionary::copy(dict),
+            })
+        });
+
+        Compressor { dict }
+    }
+
+    /// Supported formats, in the order of preference
+    pub fn supported(&self) -> Vec<Compression> {
+        let mut formats = Vec::with_capacity(4);
+        if self.dict.is_some() {
+            formats.push(Compression::ZstdDict);
+        }
+        formats.extend([Compression::Zstd, Compression::Lz4, Compression::Snappy]);
+        formats
+    }
+
+    /// Id of the zstd dictionary, 0 if there is no dictionary
+    pub fn dict_id(&self) -> u32 {
+        self.dict.as_ref().map_or(0, |dict| dict.id)
+    }
+
+    /// Choose the most preferred format supported by the remote peer. The
+    /// peers advertising nothing only know snappy.
+    pub fn negotiate(&self, remote_formats: &[u32], remote_dict_id: u32) -> Compression {
+        self.supported()
+            .into_iter()
+            .find(|format| match format {
+                Compression::ZstdDict => {
+                    remote_dict_id == self.dict_id()
+                        && remote_formats.contains(&(Compression::ZstdDict as u32))
+                }
+                Compression::Snappy => true,
+                _ => remote_formats.contains(&(*format as u32)),
+            })
+            .unwrap_or_default()
+    }
+
+    /// Compress data in the given format
+    pub fn compress(&self, src: Bytes, format: Compression) -> Bytes {
+        Message::from_raw(src).compress(self, format)
+    }
+
+    /// Decompress data in any supported format
+    pub fn decompress(&self, src: BytesMut) -> Result<Bytes, io::Error> {
+        Message::from_compressed(src).decompress(self)
+    }
+
+    fn compress_raw(&self, input: &[u8], format: Compression) -> io::Result<Vec<u8>> {
+        match format {
+            Compression::Snappy => SnapEncoder::new()
+                .compress_vec(input)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+            Compression::Zstd => zstd::bulk::compress(input, ZSTD_LEVEL),
+            Compression::ZstdDict => match self.dict.as_ref() {
+                Some(dict) => {
+                    zstd::bulk::Compressor::with_prepared_dictionary(&dict.encoder)?.compress(input)
+                }
+                None => Err(io::ErrorKind::Unsupported.into()),
+            },
+            Compression::Lz4 => Ok(lz4_flex::block::compress_prepend_size(input)),
+        }
+    }
+
+    fn decompress_raw(&self, input: &[u8], format: Compression) -> io::Result<Vec<u8>> {
+        match format {
+            Compression::Snappy => {
+                let len = decompress_len(input).map_err(|e| {
+                    debug!("snappy decompress_len error: {:?}", e);
+                    io::Error::from(io::ErrorKind::InvalidData)
+                })?;
+                check_uncompressed_len(len)?;
+
+                let mut buf = vec![0; len];
+                SnapDecoder::new()
+                    .decompress(input, &mut buf)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Ok(buf)
+            }
+            Compression::Zstd | Compression::ZstdDict => {
+                let len = match zstd::zstd_safe::get_frame_content_size(input) {
+                    Ok(Some(len)) => len as usize,
+                    _ => return Err(io::ErrorKind::InvalidData.into()),
+                };
+                check_uncompressed_len(len)?;
+
+                let mut decompressor = match (format, self.dict.as_ref()) {
+                    (Compression::Zstd, _) => zstd::bulk::Decompressor::new()?,
+                    (_, Some(dict)) => {
+                        zstd::bulk::Decompressor::with_prepared_dictionary(&dict.decoder)?
+                    }
+                    (_, None) => return Err(io::ErrorKind::Unsupported.into()),
+                };
+                decompressor.decompress(input, len)
+            }
+            Compression::Lz4 => {
+                if input.len() < LZ4_SIZE_PREFIX_LEN {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                let (prefix, block) = input.split_at(LZ4_SIZE_PREFIX_LEN);
+                let mut len_bytes = [0u8; LZ4_SIZE_PREFIX_LEN];
+                len_bytes.copy_from_slice(prefix);
+                let len = u32::from_le_bytes(len_bytes) as usize;
+                check_uncompressed_len(len)?;
+
+                lz4_flex::block::decompress(block, len)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+        }
+    }
+}
+
+fn check_uncompressed_len(len: usize) -> io::Result<()> {
+    if len > MAX_UNCOMPRESSED_LEN {
+        debug!(
+            "the maximum uncompressed bytes len limit is exceeded, limit: {}, len: {}",
+            MAX_UNCOMPRESSED_LEN, len
+        );
+        Err(io::ErrorKind::InvalidData.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn observe_bytes(direction: &str, format: &str, compressed: usize, uncompressed: usize) {
+    NETWORK_COMPRESSED_BYTES_COUNT_VEC
+        .with_label_values(&[direction, format])
+        .inc_by(compressed as f64);
+    NETWORK_UNCOMPRESSED_BYTES_COUNT_VEC
+        .with_label_values(&[direction, format])
+        .inc_by(uncompressed as f64);
+}
 
 /// Compressed decompression structure
 ///
-/// If you want to support multiple compression formats in the future,
-/// you can simply think that 0b1000 is in snappy format and 0b0000 is in
-/// uncompressed format.
+/// The format bits are 0 for snappy, 1 for zstd, 2 for lz4 and 3 for zstd
+/// with the shared dictionary, so the snappy flag is still 0b1000_0000.
 ///
 /// # Message in Bytes:
 ///
@@ -22,7 +242,8 @@ const MAX_UNCOMPRESSED_LEN: usize = 1 << 23; // 8MB
 /// | Bytes | Type | Function                                       |
 /// |-------+------+------------------------------------------------|
 /// |   0   |  u1  | Compress: true 1, false 0                      |
-/// |       |  u7  | Reserved                                       |
+/// |       |  u3  | Format                                         |
+/// |       |  u4  | Reserved                                       |
 /// +-------+------+------------------------------------------------+
 /// |  1~   |      | Payload (Serialized Data with Compress)        |
 /// +-------+------+------------------------------------------------+
@@ -46,60 +267,60 @@ impl Message {
     }
 
     /// Compress message
-    pub(crate) fn compress(mut self) -> Bytes {
+    pub(crate) fn compress(mut self, compressor: &Compressor, format: Compression) -> Bytes {
+        let raw_len = self.inner.len() - 1;
         if self.inner.len() > COMPRESSION_SIZE_THRESHOLD {
             let input = self.inner.split_off(1);
-            match SnapEncoder::new().compress_vec(&input) {
+            match compressor.compress_raw(&input, format) {
                 Ok(res) => {
+                    observe_bytes("sent", format.as_str(), res.len(), raw_len);
                     self.inner.extend_from_slice(&res);
-                    self.set_compress_flag();
+                    self.set_compress_flag(format);
+                    return self.inner.freeze();
                 }
                 Err(e) => {
-                    debug!("snappy compress error: {}", e);
+                    debug!("{} compress error: {}", format, e);
                     self.inner.unsplit(input);
                 }
             }
         }
+
+        observe_bytes("sent", "none", raw_len, raw_len);
         self.inner.freeze()
     }
 
     /// Decompress message
-    pub(crate) fn decompress(mut self) -> Result<Bytes, io::Error> {
+    pub(crate) fn decompress(mut self, compressor: &Compressor) -> Result<Bytes, io::Error> {
         if self.inner.is_empty() {
             Err(io::ErrorKind::InvalidData.into())
         } else if self.compress_flag() {
-            match decompress_len(&self.inner[1..]) {
-                Ok(decompressed_bytes_len) => {
-                    if decompressed_bytes_len > MAX_UNCOMPRESSED_LEN {
-                        debug!(
-                            "the maximum uncompressed bytes len limit is exceeded, limit: {}, len: {}",
-                            MAX_UNCOMPRESSED_LEN, decompressed_bytes_len
-                        );
-                        Err(io::ErrorKind::InvalidData.into())
-                    } else {
-                        let mut buf = vec![0; decompressed_bytes_len];
-                        match SnapDecoder::new().decompress(&self.inner[1..], &mut buf) {
-                            Ok(_) => Ok(buf.into()),
-                            Err(e) => {
-                                debug!("snappy decompress error: {:?}", e);
-                                Err(io::ErrorKind::InvalidData.into())
-                            }
-                        }
-                    }
+            let format = match Compression::from_flag(self.inner[0]) {
+                Some(format) => format,
+                None => {
+                    debug!("unknown compression format flag: {:#010b}", self.inner[0]);
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+            };
+
+            match compressor.decompress_raw(&self.inner[1..], format) {
+                Ok(buf) => {
+                    observe_bytes("received", format.as_str(), self.inner.len() - 1, buf.len());
+                    Ok(buf.into())
                 }
                 Err(e) => {
-                    debug!("snappy decompress_len error: {:?}", e);
+                    debug!("{} decompress error: {:?}", format, e);
                     Err(io::ErrorKind::InvalidData.into())
                 }
             }
         } else {
             let _ = self.inner.split_to(1);
+            observe_bytes("received", "none", self.inner.len(), self.inner.len());
             Ok(self.inner.freeze())
         }
     }
 
-    pub(crate) fn set_compress_flag(&mut self) {
-        self.inner[0] = COMPRESS_FLAG;
+    pub(crate) fn set_compress_flag(&mut self, format: Compression) {
+        self.inner[0] = format.flag();
     }
 
     pub(crate) fn compress_flag(&self) -> bool {
@@ -107,12 +328,96 @@ impl Message {
     }
 }
 
-/// Compress data
+/// Compress data with snappy, which every peer supports
 pub fn compress(src: Bytes) -> Bytes {
-    Message::from_raw(src).compress()
+    Message::from_raw(src).compress(&Compressor::default(), Compression::Snappy)
 }
 
-/// Decompress data
+/// Decompress data without the zstd dictionary
 pub fn decompress(src: BytesMut) -> Result<Bytes, io::Error> {
-    Message::from_compressed(src).decompress()
+    Message::from_compressed(src).decompress(&Compressor::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::types::{Bytes, BytesMut};
+
+    use super::{train_zstd_dict, Compression, Compressor, COMPRESSION_SIZE_THRESHOLD};
+
+    fn payload(seed: usize) -> Bytes {
+        let text = format!(
+            "{{\"block\":{},\"txs\":[\"0xdeadbeef\",\"0xcafebabe\"]}}",
+            seed
+        );
+        text.repeat(COMPRESSION_SIZE_THRESHOLD / text.len() + 8)
+            .into_bytes()
+            .into()
+    }
+
+    fn dict() -> Vec<u8> {
+        let samples = (0..1000).map(payload).collect::<Vec<_>>();
+        train_zstd_dict(&samples, 4096).unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let dict = dict();
+        let compressor = Compressor::new(Some(&dict));
+
+        for format in compressor.supported() {
+            let data = payload(1);
+            let compressed = compressor.compress(data.clone(), format);
+            assert!(compressed.len() < data.len(), "{}", format);
+            assert_eq!(compressed[0] >> 4, 0b1000 | format as u8);
+
+            let decompressed = compressor.decompress(BytesMut::from(&compressed[..]));
+            assert_eq!(decompressed.unwrap(), data, "{}", format);
+        }
+
+        // The small messages are not compressed.
+        let data = Bytes::from_static(b"axon");
+        let compressed = compressor.compress(data.clone(), Compression::Zstd);
+        assert_eq!(compressed[0], 0);
+        let decompressed = compressor.decompress(BytesMut::from(&compressed[..]));
+        assert_eq!(decompressed.unwrap(), data);
+    }
+
+    #[test]
+    fn test_snappy_compatible() {
+        let data = payload(1);
+        let compressed = super::compress(data.clone());
+        assert_eq!(compressed[0], 0b1000_0000);
+        assert_eq!(
+            super::decompress(BytesMut::from(&compressed[..])).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let dict = dict();
+        let compressor = Compressor::new(Some(&dict));
+        let all = [0, 1, 2, 3];
+
+        assert_eq!(
+            compressor.negotiate(&all, compressor.dict_id()),
+            Compression::ZstdDict
+        );
+        assert_eq!(compressor.negotiate(&all, 0), Compression::Zstd);
+        assert_eq!(compressor.negotiate(&[0, 2], 0), Compression::Lz4);
+        assert_eq!(compressor.negotiate(&[], 0), Compression::Snappy);
+        assert_eq!(
+            Compressor::default().negotiate(&all, compressor.dict_id()),
+            Compression::Zstd
+        );
+    }
+
+    #[test]
+    fn test_dict_required() {
+        let dict = dict();
+        let compressed = Compressor::new(Some(&dict)).compress(payload(1), Compression::ZstdDict);
+        assert!(Compressor::default()
+            .decompress(BytesMut::from(&compressed[..]))
+            .is_err());
+    }
 }
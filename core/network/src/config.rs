@@ -1,17 +1,24 @@
 use std::{
+    collections::HashMap,
     net::{IpAddr, Ipv4Addr},
     path::PathBuf,
     time::Duration,
 };
 
+use secp256k1::SecretKey;
 use tentacle::{
     multiaddr::{Multiaddr, Protocol},
     secio::{PeerId, SecioKeyPair},
 };
 
-use protocol::{codec::hex_decode, ProtocolResult};
+use protocol::{codec::hex_decode, rand, ProtocolResult};
 
+use crate::dns::TreeUrl;
+use crate::endpoint::Endpoint;
 use crate::error::NetworkError;
+use crate::noise::TransportSecurity;
+use crate::reactor::RateLimit;
+use crate::PeerIdExt;
 
 // TODO: 0.0.0.0 expose? 127.0.0.1 doesn't work because of tentacle-discovery.
 // Default listen address: 0.0.0.0:2337
@@ -44,12 +51,16 @@ pub const DEFAULT_PEER_DAT_FILE: &str = "./";
 pub const DEFAULT_PING_INTERVAL: u64 = 15;
 pub const DEFAULT_PING_TIMEOUT: u64 = 30;
 pub const DEFAULT_DISCOVERY_SYNC_INTERVAL: u64 = 60 * 60; // 1 hour
+pub const DEFAULT_DNS_SEED_INTERVAL: u64 = 30 * 60; // 30 minutes
 
 pub const DEFAULT_PEER_MANAGER_HEART_BEAT_INTERVAL: u64 = 30;
-pub const DEFAULT_SELF_HEART_BEAT_INTERVAL: u64 = 35;
+pub const DEFAULT_SELF_HEART_BEAT_INTERVAL: u64 = 10;
 
 pub const DEFAULT_RPC_TIMEOUT: u64 = 10;
 
+// Default consensus mesh queue size
+pub const DEFAULT_CONSENSUS_QUEUE_SIZE: usize = 1024;
+
 #[derive(Debug)]
 pub struct NetworkConfig {
     // connection
@@ -60,9 +71,14 @@ pub struct NetworkConfig {
     pub recv_buffer_size: usize,
     pub max_wait_streams: usize,
     pub write_timeout:    u64,
+    pub zstd_dict:        Option<Vec<u8>>,
+    pub announce_addrs:   Vec<Multiaddr>,
+    pub nat_port_mapping: bool,
 
     // peer manager
     pub bootstraps:             Vec<Multiaddr>,
+    pub dns_trees:              Vec<TreeUrl>,
+    pub dns_seed_interval:      Duration,
     pub allowlist:              Vec<PeerId>,
     pub allowlist_only:         bool,
     pub enable_save_restore:    bool,
@@ -75,7 +91,9 @@ pub struct NetworkConfig {
     pub inbound_conn_limit:     usize,
 
     // identity and encryption
-    pub secio_keypair: SecioKeyPair,
+    pub secret_key:         SecretKey,
+    pub secio_keypair:      SecioKeyPair,
+    pub transport_security: TransportSecurity,
 
     // protocol
     pub ping_interval:           Duration,
@@ -88,6 +106,12 @@ pub struct NetworkConfig {
 
     // rpc
     pub rpc_timeout: Duration,
+
+    // message limits of the endpoints
+    pub rate_limits: HashMap<Endpoint, RateLimit>,
+
+    // consensus mesh
+    pub consensus_queue_size: usize,
 }
 
 impl NetworkConfig {
@@ -97,6 +121,11 @@ impl NetworkConfig {
 
         let peer_manager_hb_interval =
             Duration::from_secs(DEFAULT_PEER_MANAGER_HEART_BEAT_INTERVAL);
+        let secret = loop {
+            if let Ok(sk) = SecretKey::from_slice(&rand::random::<[u8; 32]>()) {
+                break sk;
+            }
+        };
 
         NetworkConfig {
             default_listen:   listen_addr,
@@ -106,8 +135,13 @@ impl NetworkConfig {
             recv_buffer_size: DEFAULT_BUFFER_SIZE,
             max_wait_streams: DEFAULT_MAX_WAIT_STREAMS,
             write_timeout:    DEFAULT_WRITE_TIMEOUT,
+            zstd_dict:        None,
+            announce_addrs:   Default::default(),
+            nat_port_mapping: false,
 
             bootstraps:             Default::default(),
+            dns_trees:              Default::default(),
+            dns_seed_interval:      Duration::from_secs(DEFAULT_DNS_SEED_INTERVAL),
             allowlist:              Default::default(),
             allowlist_only:         false,
             enable_save_restore:    false,
@@ -119,7 +153,10 @@ impl NetworkConfig {
             same_ip_conn_limit:     DEFAULT_SAME_IP_CONN_LIMIT,
             inbound_conn_limit:     DEFAULT_INBOUND_CONN_LIMIT,
 
-            secio_keypair: SecioKeyPair::secp256k1_generated(),
+            secret_key:         secret,
+            secio_keypair:      SecioKeyPair::secp256k1_raw_key(secret.secret_bytes())
+                .expect("secio keypair of a valid secret key"),
+            transport_security: TransportSecurity::Secio,
 
             ping_interval:           Duration::from_secs(DEFAULT_PING_INTERVAL),
             ping_timeout:            Duration::from_secs(DEFAULT_PING_TIMEOUT),
@@ -129,6 +166,10 @@ impl NetworkConfig {
             heart_beat_interval:              Duration::from_secs(DEFAULT_SELF_HEART_BEAT_INTERVAL),
 
             rpc_timeout: Duration::from_secs(DEFAULT_RPC_TIMEOUT),
+
+            rate_limits: Default::default(),
+
+            consensus_queue_size: DEFAULT_CONSENSUS_QUEUE_SIZE,
         }
     }
 
@@ -149,6 +190,50 @@ impl NetworkConfig {
         Ok(self)
     }
 
+    pub fn inbound_conn_limit(mut self, limit: Option<usize>) -> ProtocolResult<Self> {
+        if let Some(limit) = limit {
+            if limit >= self.max_connections {
+                return Err(NetworkError::InboundLimitEqualOrSmallerThanMaxConn.into());
+            }
+
+            self.inbound_conn_limit = limit;
+        }
+
+        Ok(self)
+    }
+
+    pub fn same_ip_conn_limit(mut self, limit: Option<usize>) -> Self {
+        if let Some(limit) = limit {
+            self.same_ip_conn_limit = limit;
+        }
+
+        self
+    }
+
+    pub fn max_wait_streams(mut self, max: Option<usize>) -> Self {
+        if let Some(max) = max {
+            self.max_wait_streams = max;
+        }
+
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Option<u64>) -> Self {
+        if let Some(timeout) = timeout {
+            self.write_timeout = timeout;
+        }
+
+        self
+    }
+
+    pub fn selfcheck_interval(mut self, interval: Option<u64>) -> Self {
+        if let Some(interval) = interval {
+            self.heart_beat_interval = Duration::from_secs(interval);
+        }
+
+        self
+    }
+
     pub fn max_frame_length(mut self, max: Option<usize>) -> Self {
         if let Some(max) = max {
             self.max_frame_length = max;
@@ -173,16 +258,78 @@ impl NetworkConfig {
         self
     }
 
+    pub fn zstd_dict(mut self, path: Option<PathBuf>) -> ProtocolResult<Self> {
+        if let Some(path) = path {
+            let dict = std::fs::read(path).map_err(NetworkError::IoError)?;
+            self.zstd_dict = Some(dict);
+        }
+
+        Ok(self)
+    }
+
+    pub fn announce_addrs(mut self, addrs: Vec<Multiaddr>) -> Self {
+        self.announce_addrs = addrs;
+        self
+    }
+
+    pub fn nat_port_mapping(mut self, flag: Option<bool>) -> Self {
+        if let Some(flag) = flag {
+            self.nat_port_mapping = flag;
+        }
+
+        self
+    }
+
     pub fn bootstraps(mut self, addrs: Vec<Multiaddr>) -> Self {
         self.bootstraps = addrs;
         self
     }
 
+    pub fn dns_trees(mut self, urls: Vec<String>) -> ProtocolResult<Self> {
+        let trees = urls
+            .iter()
+            .map(|url| url.parse::<TreeUrl>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(NetworkError::from)?;
+
+        self.dns_trees = trees;
+        Ok(self)
+    }
+
+    pub fn dns_seed_interval(mut self, interval: Option<u64>) -> Self {
+        if let Some(interval) = interval {
+            self.dns_seed_interval = Duration::from_secs(interval);
+        }
+
+        self
+    }
+
+    pub fn allowlist(mut self, peer_id_strs: Vec<String>) -> ProtocolResult<Self> {
+        let peer_ids = peer_id_strs
+            .iter()
+            .map(PeerId::from_str_ext)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.allowlist = peer_ids;
+        Ok(self)
+    }
+
+    pub fn allowlist_only(mut self, flag: Option<bool>) -> Self {
+        if let Some(flag) = flag {
+            self.allowlist_only = flag;
+        }
+
+        self
+    }
+
+    /// The same key is the static key of the Noise handshake
     pub fn secio_keypair(mut self, sk_hex: &str) -> ProtocolResult<Self> {
-        let maybe_skp = hex_decode(sk_hex).map(SecioKeyPair::secp256k1_raw_key);
+        let maybe_sk = hex_decode(sk_hex).map(|sk| SecretKey::from_slice(&sk));
 
-        if let Ok(Ok(skp)) = maybe_skp {
-            self.secio_keypair = skp;
+        if let Ok(Ok(sk)) = maybe_sk {
+            self.secio_keypair = SecioKeyPair::secp256k1_raw_key(sk.secret_bytes())
+                .map_err(|_| NetworkError::InvalidPrivateKey)?;
+            self.secret_key = sk;
 
             Ok(self)
         } else {
@@ -190,6 +337,22 @@ impl NetworkConfig {
         }
     }
 
+    /// The Noise mode only talks to the Noise and the mixed nodes, and the
+    /// mixed mode accepts both but still dials by secio, so a network moves
+    /// from secio to Noise through the mixed mode. Tentacle can't dial its
+    /// observed addresses by Noise, so a Noise node behind a NAT should
+    /// announce its public address.
+    pub fn transport_security(mut self, security: Option<String>) -> ProtocolResult<Self> {
+        if let Some(security) = security {
+            let security = security
+                .parse::<TransportSecurity>()
+                .map_err(NetworkError::from)?;
+            self.transport_security = security;
+        }
+
+        Ok(self)
+    }
+
     pub fn ping_interval(mut self, interval: Option<u64>) -> Self {
         if let Some(interval) = interval {
             self.ping_interval = Duration::from_secs(interval);
@@ -210,6 +373,63 @@ impl NetworkConfig {
         self
     }
 
+    pub fn peer_trust_metric(
+        mut self,
+        interval: Option<u64>,
+        max_history: Option<u64>,
+    ) -> ProtocolResult<Self> {
+        if let Some(interval) = interval {
+            self.peer_trust_interval = Duration::from_secs(interval);
+        }
+
+        if let Some(max_history) = max_history {
+            self.peer_trust_max_history = Duration::from_secs(max_history);
+        }
+
+        // The metric should keep the events of at least 20 intervals
+        let min_history = self.peer_trust_interval * 20;
+        if self.peer_trust_max_history < min_history {
+            return Err(NetworkError::SmallTrustMaxHistory(min_history.as_secs()).into());
+        }
+
+        Ok(self)
+    }
+
+    pub fn peer_fatal_ban(mut self, duration: Option<u64>) -> Self {
+        if let Some(duration) = duration {
+            self.peer_fatal_ban = Duration::from_secs(duration);
+        }
+
+        self
+    }
+
+    pub fn peer_soft_ban(mut self, duration: Option<u64>) -> Self {
+        if let Some(duration) = duration {
+            self.peer_soft_ban = Duration::from_secs(duration);
+        }
+
+        self
+    }
+
+    pub fn consensus_queue_size(mut self, size: Option<usize>) -> Self {
+        if let Some(size) = size {
+            self.consensus_queue_size = size;
+        }
+
+        self
+    }
+
+    /// Limit the messages of every peer on the endpoint, `rate` per second
+    /// with `burst` at once. Both of them should be positive.
+    pub fn rate_limit(mut self, endpoint: &str, rate: u32, burst: u32) -> ProtocolResult<Self> {
+        if rate == 0 || burst == 0 {
+            return Err(NetworkError::ZeroRateLimit(endpoint.to_owned()).into());
+        }
+        let endpoint = endpoint.parse::<Endpoint>()?;
+        self.rate_limits.insert(endpoint, RateLimit { rate, burst });
+        Ok(self)
+    }
+
     pub fn peer_store_dir(mut self, path: PathBuf) -> Self {
         self.peer_store_path = path;
         self
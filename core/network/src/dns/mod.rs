@@ -0,0 +1,388 @@
+mod resolver;
+mod tree;
+
+pub use self::{
+    resolver::{Resolver, SystemResolver},
+    tree::{sync_tree, SyncedTree, TreeUrl},
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use derive_more::Display;
+use tentacle::{
+    multiaddr::{Multiaddr, Protocol},
+    utils::extract_peer_id,
+};
+
+use protocol::tokio::time::sleep;
+
+use crate::peer_manager::PeerManager;
+
+/// The most trees synced in a round, following the links of the trees
+const MAX_LINKED_TREES: usize = 16;
+
+#[derive(Debug, Display, PartialEq, Eq)]
+pub enum DnsError {
+    #[display(fmt = "resolve error: {}", _0)]
+    Resolve(String),
+
+    #[display(fmt = "invalid tree url {}", _0)]
+    InvalidUrl(String),
+
+    #[display(fmt = "invalid tree record {}", _0)]
+    InvalidRecord(String),
+
+    #[display(fmt = "invalid signature of tree root {}", _0)]
+    InvalidSignature(String),
+
+    #[display(fmt = "tree record {} missing", _0)]
+    MissingRecord(String),
+
+    #[display(fmt = "too many records in tree {}", _0)]
+    TooManyRecords(String),
+}
+
+impl std::error::Error for DnsError {}
+
+pub fn is_dns_addr(addr: &Multiaddr) -> bool {
+    matches!(
+        addr.iter().next(),
+        Some(Protocol::Dns4(_)) | Some(Protocol::Dns6(_))
+    )
+}
+
+/// Resolve the host of a `/dns4` or `/dns6` address to the addresses of the
+/// same IP version, keeping the rest of it
+pub async fn resolve_dns_addr<R: Resolver + ?Sized>(
+    resolver: &R,
+    addr: &Multiaddr,
+) -> Result<Vec<Multiaddr>, DnsError> {
+    let mut protos = addr.iter();
+    let (host, is_v4) = match protos.next() {
+        Some(Protocol::Dns4(host)) => (host, true),
+        Some(Protocol::Dns6(host)) => (host, false),
+        _ => return Ok(vec![addr.clone()]),
+    };
+    let rest = protos.collect::<Vec<_>>();
+
+    let addrs = resolver
+        .lookup_ip(&host)
+        .await?
+        .into_iter()
+        .filter_map(|ip| match ip {
+            IpAddr::V4(ip) if is_v4 => Some(Protocol::Ip4(ip)),
+            IpAddr::V6(ip) if !is_v4 => Some(Protocol::Ip6(ip)),
+            _ => None,
+        })
+        .map(|ip| {
+            std::iter::once(ip)
+                .chain(rest.iter().cloned())
+                .collect::<Multiaddr>()
+        })
+        .collect();
+
+    Ok(addrs)
+}
+
+/// Resolve the DNS bootstraps and sync the trees, with the trees linked from
+/// them. The failures are logged and skipped.
+///
+/// The resolved bootstraps keep their `/p2p` suffix, since the peer store only
+/// takes the addresses with peer id. `seqs` records the last seq of each tree,
+/// a tree rolled back to a lower seq is skipped.
+pub async fn resolve_seeds<R: Resolver + ?Sized>(
+    resolver: &R,
+    bootstraps: &[Multiaddr],
+    trees: &[TreeUrl],
+    seqs: &mut HashMap<String, u64>,
+) -> Vec<Multiaddr> {
+    let mut addrs = Vec::new();
+
+    for bootstrap in bootstraps.iter().filter(|addr| is_dns_addr(addr)) {
+        if extract_peer_id(bootstrap).is_none() {
+            log::warn!("dns bootstrap {} without peer id, skip", bootstrap);
+            continue;
+        }
+
+        match resolve_dns_addr(resolver, bootstrap).await {
+            Ok(resolved) => addrs.extend(resolved),
+            Err(err) => log::info!("resolve bootstrap {} failed: {}", bootstrap, err),
+        }
+    }
+
+    let mut pending = trees.to_vec();
+    let mut synced = HashSet::new();
+    while let Some(url) = pending.pop() {
+        if synced.len() >= MAX_LINKED_TREES {
+            log::info!("too many linked dns trees, skip {}", url);
+            break;
+        }
+        if !synced.insert(url.domain.clone()) {
+            continue;
+        }
+
+        match sync_tree(resolver, &url).await {
+            Ok(tree) => {
+                let last_seq = seqs.get(&url.to_string()).copied().unwrap_or_default();
+                if tree.seq < last_seq {
+                    log::warn!(
+                        "dns tree {} seq {} lower than the last {}, skip",
+                        url.domain,
+                        tree.seq,
+                        last_seq
+                    );
+                    continue;
+                }
+                seqs.insert(url.to_string(), tree.seq);
+
+                log::debug!(
+                    "dns tree {} seq {}: {} addrs",
+                    url.domain,
+                    tree.seq,
+                    tree.addrs.len()
+                );
+                addrs.extend(tree.addrs);
+                pending.extend(tree.links);
+            }
+            Err(err) => log::info!("sync dns tree {} failed: {}", url, err),
+        }
+    }
+
+    addrs
+}
+
+/// Seed the peer store with the addresses from DNS periodically, so that the
+/// bootstraps can be rotated by changing the DNS records
+pub struct DnsSeeder<R> {
+    resolver:     R,
+    bootstraps:   Vec<Multiaddr>,
+    trees:        Vec<TreeUrl>,
+    interval:     Duration,
+    peer_manager: Arc<PeerManager>,
+    // The last seq of the synced trees
+    seqs:         HashMap<String, u64>,
+}
+
+impl<R: Resolver> DnsSeeder<R> {
+    pub fn new(
+        resolver: R,
+        bootstraps: Vec<Multiaddr>,
+        trees: Vec<TreeUrl>,
+        interval: Duration,
+        peer_manager: Arc<PeerManager>,
+    ) -> Self {
+        DnsSeeder {
+            resolver,
+            bootstraps: bootstraps.into_iter().filter(is_dns_addr).collect(),
+            trees,
+            interval,
+            peer_manager,
+            seqs: HashMap::new(),
+        }
+    }
+
+    pub async fn run(mut self) {
+        loop {
+            self.seed_once().await;
+            sleep(self.interval).await;
+        }
+    }
+
+    async fn seed_once(&mut self) {
+        let addrs = resolve_seeds(
+            &self.resolver,
+            &self.bootstraps,
+            &self.trees,
+            &mut self.seqs,
+        )
+        .await;
+        log::info!("dns seeds resolved {} addrs", addrs.len());
+
+        self.peer_manager.with_peer_store_mut(|peer_store| {
+            for addr in addrs {
+                if let Err(err) = peer_store.add_addr(addr.clone()) {
+                    log::debug!("add dns seed {} to peer store failed: {:?}", addr, err);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        path::PathBuf,
+        sync::Arc,
+        time::Duration,
+    };
+
+    use secp256k1::SecretKey;
+    use tentacle::multiaddr::Multiaddr;
+
+    use protocol::tokio;
+
+    use super::{resolve_dns_addr, resolve_seeds, DnsSeeder};
+    use crate::config::NetworkConfig;
+    use crate::dns::{
+        resolver::tests::StubResolver,
+        tree::tests::{build_tree, peer_id, tree_url},
+        DnsError,
+    };
+    use crate::peer_manager::PeerManager;
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    /// The address with the `/p2p` suffix
+    fn peer_addr(s: &str) -> Multiaddr {
+        addr(&format!("{}/p2p/{}", s, peer_id().to_base58()))
+    }
+
+    fn resolver() -> StubResolver {
+        let mut resolver = StubResolver::default();
+        resolver.ips.insert("boot.axon.test".to_owned(), vec![
+            IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+            IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2)),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ]);
+        resolver
+    }
+
+    #[tokio::test]
+    async fn test_resolve_dns_addr() {
+        let resolver = resolver();
+
+        let resolved = resolve_dns_addr(&resolver, &addr("/dns4/boot.axon.test/tcp/2337"))
+            .await
+            .unwrap();
+        assert_eq!(resolved, vec![
+            addr("/ip4/1.1.1.1/tcp/2337"),
+            addr("/ip4/2.2.2.2/tcp/2337")
+        ]);
+
+        // The peer id is kept
+        let resolved = resolve_dns_addr(&resolver, &peer_addr("/dns4/boot.axon.test/tcp/2337"))
+            .await
+            .unwrap();
+        assert_eq!(resolved, vec![
+            peer_addr("/ip4/1.1.1.1/tcp/2337"),
+            peer_addr("/ip4/2.2.2.2/tcp/2337")
+        ]);
+
+        let resolved = resolve_dns_addr(&resolver, &addr("/dns6/boot.axon.test/tcp/2337"))
+            .await
+            .unwrap();
+        assert_eq!(resolved, vec![addr("/ip6/::1/tcp/2337")]);
+
+        let res = resolve_dns_addr(&resolver, &addr("/dns4/gone.axon.test/tcp/2337")).await;
+        assert!(matches!(res, Err(DnsError::Resolve(_))));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_seeds() {
+        let key_a = SecretKey::from_slice(&[1; 32]).unwrap();
+        let key_b = SecretKey::from_slice(&[2; 32]).unwrap();
+        let url_a = tree_url(&key_a, "a.axon.test");
+        let url_b = tree_url(&key_b, "b.axon.test");
+
+        // The trees link to each other
+        let mut txts = HashMap::new();
+        txts.extend(build_tree(
+            &key_a,
+            "a.axon.test",
+            1,
+            &[peer_addr("/ip4/3.3.3.3/tcp/2337")],
+            &[url_b],
+        ));
+        txts.extend(build_tree(
+            &key_b,
+            "b.axon.test",
+            1,
+            &[peer_addr("/ip4/4.4.4.4/tcp/2337")],
+            std::slice::from_ref(&url_a),
+        ));
+        let mut resolver = resolver();
+        resolver.txts = txts;
+
+        // The DNS bootstrap without peer id is skipped
+        let bootstraps = vec![
+            peer_addr("/dns4/boot.axon.test/tcp/2337"),
+            addr("/dns4/boot.axon.test/tcp/2338"),
+            peer_addr("/ip4/5.5.5.5/tcp/2337"),
+        ];
+        let mut seqs = HashMap::new();
+        let mut addrs = resolve_seeds(&resolver, &bootstraps, &[url_a], &mut seqs).await;
+        addrs.sort_by_key(|addr| addr.to_string());
+        assert_eq!(addrs, vec![
+            peer_addr("/ip4/1.1.1.1/tcp/2337"),
+            peer_addr("/ip4/2.2.2.2/tcp/2337"),
+            peer_addr("/ip4/3.3.3.3/tcp/2337"),
+            peer_addr("/ip4/4.4.4.4/tcp/2337"),
+        ]);
+        assert_eq!(seqs.values().collect::<Vec<_>>(), vec![&1, &1]);
+    }
+
+    #[tokio::test]
+    async fn test_dns_seeder() {
+        let key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let url = tree_url(&key, "nodes.axon.test");
+        let mut resolver = resolver();
+        resolver.txts = build_tree(
+            &key,
+            "nodes.axon.test",
+            2,
+            &[peer_addr("/ip4/3.3.3.3/tcp/2337")],
+            &[],
+        );
+
+        let dir = PathBuf::from("./free-space/network/dns");
+        let config = NetworkConfig::new().peer_store_dir(dir);
+        let peer_manager = Arc::new(PeerManager::new(Arc::new(config)));
+        let mut seeder = DnsSeeder::new(
+            resolver,
+            vec![peer_addr("/dns4/boot.axon.test/tcp/2337")],
+            vec![url],
+            Duration::from_secs(60),
+            Arc::clone(&peer_manager),
+        );
+        let stored_addrs = || {
+            peer_manager.with_peer_store(|peer_store| {
+                peer_store
+                    .addr_manager()
+                    .addrs_iter()
+                    .map(|info| info.addr.clone())
+                    .collect::<HashSet<_>>()
+            })
+        };
+
+        seeder.seed_once().await;
+        let addrs = stored_addrs();
+        for expected in [
+            "/ip4/1.1.1.1/tcp/2337",
+            "/ip4/2.2.2.2/tcp/2337",
+            "/ip4/3.3.3.3/tcp/2337",
+        ] {
+            assert!(addrs.contains(&peer_addr(expected)), "{} missing", expected);
+        }
+
+        // A tree rolled back to a lower seq is rejected
+        seeder.resolver.txts = build_tree(
+            &key,
+            "nodes.axon.test",
+            1,
+            &[peer_addr("/ip4/6.6.6.6/tcp/2337")],
+            &[],
+        );
+        seeder.seed_once().await;
+        assert!(!stored_addrs().contains(&peer_addr("/ip4/6.6.6.6/tcp/2337")));
+        assert_eq!(seeder.seqs.values().collect::<Vec<_>>(), vec![&2]);
+    }
+}
@@ -0,0 +1,100 @@
+use std::net::IpAddr;
+
+use trust_dns_resolver::TokioAsyncResolver;
+
+use protocol::async_trait;
+
+use crate::dns::DnsError;
+
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, DnsError>;
+
+    /// The TXT records of the name, each joined from its strings
+    async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, DnsError>;
+}
+
+/// The resolver configured by the system, `/etc/resolv.conf` on unix
+pub struct SystemResolver(TokioAsyncResolver);
+
+impl SystemResolver {
+    pub fn new() -> Result<Self, DnsError> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()
+            .map_err(|e| DnsError::Resolve(e.to_string()))?;
+        Ok(SystemResolver(resolver))
+    }
+}
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, DnsError> {
+        let ips = self
+            .0
+            .lookup_ip(host)
+            .await
+            .map_err(|e| DnsError::Resolve(e.to_string()))?;
+        Ok(ips.iter().collect())
+    }
+
+    async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, DnsError> {
+        let txts = self
+            .0
+            .txt_lookup(name)
+            .await
+            .map_err(|e| DnsError::Resolve(e.to_string()))?;
+
+        Ok(txts
+            .iter()
+            .map(|txt| {
+                txt.txt_data()
+                    .iter()
+                    .map(|data| String::from_utf8_lossy(data))
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{collections::HashMap, net::IpAddr};
+
+    use protocol::async_trait;
+
+    use super::Resolver;
+    use crate::dns::DnsError;
+
+    /// A resolver answering from the records given, the TXT records by the
+    /// name and the addresses by the host
+    #[derive(Default)]
+    pub(crate) struct StubResolver {
+        pub txts: HashMap<String, Vec<String>>,
+        pub ips:  HashMap<String, Vec<IpAddr>>,
+    }
+
+    impl StubResolver {
+        pub(crate) fn new(txts: HashMap<String, Vec<String>>) -> Self {
+            StubResolver {
+                txts,
+                ips: HashMap::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Resolver for StubResolver {
+        async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, DnsError> {
+            self.ips
+                .get(host)
+                .cloned()
+                .ok_or_else(|| DnsError::Resolve(format!("no address for {}", host)))
+        }
+
+        async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, DnsError> {
+            self.txts
+                .get(name)
+                .cloned()
+                .ok_or_else(|| DnsError::Resolve(format!("no txt for {}", name)))
+        }
+    }
+}
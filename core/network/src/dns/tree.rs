@@ -0,0 +1,391 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use base32::Alphabet;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1};
+use tentacle::{multiaddr::Multiaddr, utils::extract_peer_id};
+
+use protocol::types::Hasher;
+
+use crate::dns::{resolver::Resolver, DnsError};
+
+pub const ROOT_PREFIX: &str = "enrtree-root:v1";
+pub const BRANCH_PREFIX: &str = "enrtree-branch:";
+pub const LINK_PREFIX: &str = "enrtree://";
+pub const LEAF_PREFIX: &str = "multiaddr:";
+
+const BASE32: Alphabet = Alphabet::RFC4648 { padding: false };
+/// The bytes of the keccak hash used as the subdomain of a record
+const HASH_LEN: usize = 16;
+/// The most records fetched from a tree, to stop a malicious tree early
+const MAX_TREE_RECORDS: usize = 2048;
+
+/// The location and signer of a tree, `enrtree://<public key>@<domain>`,
+/// with the compressed public key in base32.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeUrl {
+    pub public_key: PublicKey,
+    pub domain:     String,
+}
+
+impl FromStr for TreeUrl {
+    type Err = DnsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DnsError::InvalidUrl(s.to_owned());
+
+        let (key, domain) = s
+            .strip_prefix(LINK_PREFIX)
+            .and_then(|rest| rest.split_once('@'))
+            .ok_or_else(invalid)?;
+        let key = base32::decode(BASE32, &key.to_ascii_uppercase()).ok_or_else(invalid)?;
+        let public_key = PublicKey::from_slice(&key).map_err(|_| invalid())?;
+        if domain.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(TreeUrl {
+            public_key,
+            domain: domain.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for TreeUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = base32::encode(BASE32, &self.public_key.serialize());
+        write!(f, "{}{}@{}", LINK_PREFIX, key, self.domain)
+    }
+}
+
+/// The root record, signed by the key of the tree
+///
+/// `enrtree-root:v1 e=<entries root> l=<links root> seq=<sequence> sig=<sig>`,
+/// where the signature is over the keccak hash of the record without ` sig=`,
+/// in base64 of the URL safe alphabet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Root {
+    pub entries_root: String,
+    pub links_root:   String,
+    pub seq:          u64,
+}
+
+impl Root {
+    pub fn parse(record: &str, public_key: &PublicKey) -> Result<Root, DnsError> {
+        let invalid = || DnsError::InvalidRecord(record.to_owned());
+
+        let (content, sig) = record.split_once(" sig=").ok_or_else(invalid)?;
+        let mut fields = content.split(' ');
+        if fields.next() != Some(ROOT_PREFIX) {
+            return Err(invalid());
+        }
+
+        let mut field = |name: &str| {
+            fields
+                .next()
+                .and_then(|field| field.strip_prefix(name))
+                .ok_or_else(invalid)
+        };
+        let entries_root = field("e=")?.to_owned();
+        let links_root = field("l=")?.to_owned();
+        let seq = field("seq=")?.parse().map_err(|_| invalid())?;
+
+        // The last byte is the recovery id, which is not needed with the key
+        let sig = URL_SAFE_NO_PAD.decode(sig).map_err(|_| invalid())?;
+        if sig.len() != 65 && sig.len() != 64 {
+            return Err(invalid());
+        }
+        let mut sig = Signature::from_compact(&sig[..64]).map_err(|_| invalid())?;
+        sig.normalize_s();
+        let msg = Message::from_slice(Hasher::digest(content).as_bytes()).map_err(|_| invalid())?;
+        Secp256k1::verification_only()
+            .verify_ecdsa(&msg, &sig, public_key)
+            .map_err(|_| DnsError::InvalidSignature(record.to_owned()))?;
+
+        Ok(Root {
+            entries_root,
+            links_root,
+            seq,
+        })
+    }
+}
+
+/// The record below the root, at the subdomain of its hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Branch(Vec<String>),
+    Leaf(Multiaddr),
+    Link(TreeUrl),
+}
+
+impl FromStr for Entry {
+    type Err = DnsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(children) = s.strip_prefix(BRANCH_PREFIX) {
+            let children = children
+                .split(',')
+                .filter(|child| !child.is_empty())
+                .map(str::to_owned)
+                .collect();
+            Ok(Entry::Branch(children))
+        } else if let Some(addr) = s.strip_prefix(LEAF_PREFIX) {
+            // The peer store keeps the addresses with peer id only
+            let addr = addr
+                .parse::<Multiaddr>()
+                .ok()
+                .filter(|addr| extract_peer_id(addr).is_some())
+                .ok_or_else(|| DnsError::InvalidRecord(s.to_owned()))?;
+            Ok(Entry::Leaf(addr))
+        } else if s.starts_with(LINK_PREFIX) {
+            Ok(Entry::Link(s.parse()?))
+        } else {
+            Err(DnsError::InvalidRecord(s.to_owned()))
+        }
+    }
+}
+
+/// The subdomain of a record, the base32 of the head of its keccak hash
+pub fn record_hash(record: &str) -> String {
+    let hash = Hasher::digest(record);
+    base32::encode(BASE32, &hash.as_bytes()[..HASH_LEN])
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncedTree {
+    pub seq:   u64,
+    pub addrs: Vec<Multiaddr>,
+    pub links: Vec<TreeUrl>,
+}
+
+/// Fetch all the records of the tree, the leaves under the entries root and
+/// the links to other trees under the links root
+pub async fn sync_tree<R: Resolver + ?Sized>(
+    resolver: &R,
+    url: &TreeUrl,
+) -> Result<SyncedTree, DnsError> {
+    let root = resolver
+        .lookup_txt(&url.domain)
+        .await?
+        .into_iter()
+        .find(|record| record.starts_with(ROOT_PREFIX))
+        .ok_or_else(|| DnsError::MissingRecord(url.domain.clone()))?;
+    let root = Root::parse(&root, &url.public_key)?;
+
+    let mut tree = SyncedTree {
+        seq: root.seq,
+        ..Default::default()
+    };
+    let mut visited = HashSet::new();
+
+    for (hash, is_links) in [(root.entries_root, false), (root.links_root, true)] {
+        let mut pending = vec![hash];
+
+        while let Some(hash) = pending.pop() {
+            if !visited.insert(hash.clone()) {
+                continue;
+            }
+            if visited.len() > MAX_TREE_RECORDS {
+                return Err(DnsError::TooManyRecords(url.domain.clone()));
+            }
+
+            match fetch_entry(resolver, &url.domain, &hash).await? {
+                Entry::Branch(children) => pending.extend(children),
+                Entry::Leaf(addr) if !is_links => tree.addrs.push(addr),
+                Entry::Link(link) if is_links => tree.links.push(link),
+                entry => {
+                    return Err(DnsError::InvalidRecord(format!(
+                        "{:?} in wrong subtree",
+                        entry
+                    )))
+                }
+            }
+        }
+    }
+
+    Ok(tree)
+}
+
+async fn fetch_entry<R: Resolver + ?Sized>(
+    resolver: &R,
+    domain: &str,
+    hash: &str,
+) -> Result<Entry, DnsError> {
+    let name = format!("{}.{}", hash, domain);
+    let record = resolver
+        .lookup_txt(&name)
+        .await?
+        .into_iter()
+        .find(|record| record_hash(record).eq_ignore_ascii_case(hash))
+        .ok_or(DnsError::MissingRecord(name))?;
+
+    record.parse()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+    use tentacle::{
+        multiaddr::Multiaddr,
+        secio::{PeerId, SecioKeyPair},
+    };
+
+    use protocol::{tokio, types::Hasher};
+
+    use super::{
+        record_hash, sync_tree, Root, SyncedTree, TreeUrl, BRANCH_PREFIX, LEAF_PREFIX, ROOT_PREFIX,
+    };
+    use crate::dns::{resolver::tests::StubResolver, DnsError};
+
+    /// The TXT records of a tree with the leaves and links under one branch
+    pub(crate) fn build_tree(
+        secret_key: &SecretKey,
+        domain: &str,
+        seq: u64,
+        addrs: &[Multiaddr],
+        links: &[TreeUrl],
+    ) -> HashMap<String, Vec<String>> {
+        let mut records = HashMap::new();
+        let mut insert = |record: String| {
+            let hash = record_hash(&record);
+            records.insert(format!("{}.{}", hash, domain), vec![record]);
+            hash
+        };
+
+        let leaves = addrs
+            .iter()
+            .map(|addr| insert(format!("{}{}", LEAF_PREFIX, addr)))
+            .collect::<Vec<_>>();
+        let entries_root = insert(format!("{}{}", BRANCH_PREFIX, leaves.join(",")));
+        let links = links
+            .iter()
+            .map(|link| insert(link.to_string()))
+            .collect::<Vec<_>>();
+        let links_root = insert(format!("{}{}", BRANCH_PREFIX, links.join(",")));
+
+        let content = format!(
+            "{} e={} l={} seq={}",
+            ROOT_PREFIX, entries_root, links_root, seq
+        );
+        let msg = Message::from_slice(Hasher::digest(&content).as_bytes()).unwrap();
+        let sig = Secp256k1::signing_only().sign_ecdsa(&msg, secret_key);
+        let mut sig = sig.serialize_compact().to_vec();
+        // The recovery id is not checked
+        sig.push(0);
+
+        records.insert(domain.to_owned(), vec![format!(
+            "{} sig={}",
+            content,
+            URL_SAFE_NO_PAD.encode(sig)
+        )]);
+        records
+    }
+
+    pub(crate) fn tree_url(secret_key: &SecretKey, domain: &str) -> TreeUrl {
+        TreeUrl {
+            public_key: PublicKey::from_secret_key(&Secp256k1::signing_only(), secret_key),
+            domain:     domain.to_owned(),
+        }
+    }
+
+    pub(crate) fn peer_id() -> PeerId {
+        SecioKeyPair::secp256k1_raw_key([1; 32]).unwrap().peer_id()
+    }
+
+    fn addrs() -> Vec<Multiaddr> {
+        vec![
+            format!(
+                "/dns4/node.axon.test/tcp/2337/p2p/{}",
+                peer_id().to_base58()
+            )
+            .parse()
+            .unwrap(),
+            format!("/ip4/1.1.1.1/tcp/2337/p2p/{}", peer_id().to_base58())
+                .parse()
+                .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_tree_url() {
+        let url = tree_url(&SecretKey::from_slice(&[1; 32]).unwrap(), "nodes.axon.test");
+        let parsed = url.to_string().parse::<TreeUrl>().unwrap();
+        assert_eq!(parsed, url);
+
+        assert!("enrtree://nodes.axon.test".parse::<TreeUrl>().is_err());
+        assert!("enrtree://AAAA@nodes.axon.test".parse::<TreeUrl>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_tree() {
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let links = vec![tree_url(
+            &SecretKey::from_slice(&[2; 32]).unwrap(),
+            "more.axon.test",
+        )];
+        let resolver = StubResolver::new(build_tree(
+            &secret_key,
+            "nodes.axon.test",
+            3,
+            &addrs(),
+            &links,
+        ));
+
+        let mut tree = sync_tree(&resolver, &tree_url(&secret_key, "nodes.axon.test"))
+            .await
+            .unwrap();
+        tree.addrs.sort_by_key(|addr| addr.to_string());
+        assert_eq!(tree, SyncedTree {
+            seq: 3,
+            addrs: addrs(),
+            links,
+        });
+    }
+
+    #[tokio::test]
+    async fn test_sync_tree_verify() {
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let mut records = build_tree(&secret_key, "nodes.axon.test", 1, &addrs(), &[]);
+
+        // Signed by another key
+        let other = tree_url(&SecretKey::from_slice(&[2; 32]).unwrap(), "nodes.axon.test");
+        let resolver = StubResolver::new(records.clone());
+        let res = sync_tree(&resolver, &other).await;
+        assert!(matches!(res, Err(DnsError::InvalidSignature(_))));
+
+        // A root changed after signing
+        let url = tree_url(&secret_key, "nodes.axon.test");
+        let root = records.get_mut("nodes.axon.test").unwrap();
+        root[0] = root[0].replace("seq=1", "seq=2");
+        let resolver = StubResolver::new(records.clone());
+        let res = sync_tree(&resolver, &url).await;
+        assert!(matches!(res, Err(DnsError::InvalidSignature(_))));
+
+        // A leaf not matching its hash
+        let mut records = build_tree(&secret_key, "nodes.axon.test", 1, &addrs(), &[]);
+        let leaf = records
+            .values_mut()
+            .find(|txts| txts[0].starts_with(LEAF_PREFIX))
+            .unwrap();
+        leaf[0] = format!(
+            "{}/ip4/6.6.6.6/tcp/2337/p2p/{}",
+            LEAF_PREFIX,
+            peer_id().to_base58()
+        );
+        let resolver = StubResolver::new(records);
+        let res = sync_tree(&resolver, &url).await;
+        assert!(matches!(res, Err(DnsError::MissingRecord(_))));
+
+        // A leaf without peer id
+        let no_peer_id = "/ip4/6.6.6.6/tcp/2337".parse::<Multiaddr>().unwrap();
+        let records = build_tree(&secret_key, "nodes.axon.test", 1, &[no_peer_id], &[]);
+        let resolver = StubResolver::new(records);
+        let res = sync_tree(&resolver, &url).await;
+        assert!(matches!(res, Err(DnsError::InvalidRecord(_))));
+
+        assert!(Root::parse("enrtree-root:v1 e=A l=B seq=1", &url.public_key).is_err());
+    }
+}
@@ -11,6 +11,8 @@ use tentacle::{
 use protocol::{types::Address, ProtocolError, ProtocolErrorKind};
 
 use crate::common::ConnectedAddr;
+use crate::dns::DnsError;
+use crate::noise::NoiseError;
 
 #[derive(Debug, Display)]
 pub enum ErrorKind {
@@ -159,6 +161,21 @@ pub enum NetworkError {
     #[display(fmt = "inbound connection limit is equal or smaller than max connections")]
     InboundLimitEqualOrSmallerThanMaxConn,
 
+    #[display(fmt = "invalid peer, address or network {}", _0)]
+    InvalidTarget(String),
+
+    #[display(fmt = "dns {}", _0)]
+    Dns(DnsError),
+
+    #[display(fmt = "noise {}", _0)]
+    Noise(NoiseError),
+
+    #[display(fmt = "the allowlist of a stopped node is set in the config")]
+    OfflineAllowlist,
+
+    #[display(fmt = "rate limit of {} should allow a message at least", _0)]
+    ZeroRateLimit(String),
+
     #[display(fmt = "internal error: {}", _0)]
     Internal(Box<dyn Error + Send>),
 }
@@ -189,6 +206,18 @@ impl From<std::io::Error> for NetworkError {
     }
 }
 
+impl From<DnsError> for NetworkError {
+    fn from(err: DnsError) -> NetworkError {
+        NetworkError::Dns(err)
+    }
+}
+
+impl From<NoiseError> for NetworkError {
+    fn from(err: NoiseError) -> NetworkError {
+        NetworkError::Noise(err)
+    }
+}
+
 impl From<tentacle::error::TransportErrorKind> for NetworkError {
     fn from(err: tentacle::error::TransportErrorKind) -> NetworkError {
         NetworkError::Transport(err)
@@ -3,9 +3,12 @@
 mod common;
 mod compress;
 mod config;
+mod dns;
 mod endpoint;
 mod error;
 mod message;
+mod nat;
+mod noise;
 mod outbound;
 mod peer_manager;
 mod protocols;
@@ -14,7 +17,10 @@ mod rpc;
 mod service;
 mod traits;
 
+pub use self::compress::{train_zstd_dict, Compression};
 pub use self::config::NetworkConfig;
+pub use self::noise::TransportSecurity;
+pub use self::peer_manager::OfflinePeerStore;
 pub use self::service::{NetworkService, NetworkServiceHandle};
 pub use self::traits::NetworkContext;
 pub use tentacle::{multiaddr, secio::PeerId};
@@ -0,0 +1,321 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket as StdUdpSocket},
+    sync::Arc,
+    time::Duration,
+};
+
+use derive_more::Display;
+use igd_next::{aio::tokio::search_gateway, PortMappingProtocol, SearchOptions};
+use tentacle::utils::is_reachable;
+
+use protocol::tokio::{
+    net::UdpSocket,
+    time::{sleep, timeout},
+};
+
+use crate::peer_manager::PeerManager;
+
+pub const NAT_PMP_PORT: u16 = 5351;
+const NAT_PMP_VERSION: u8 = 0;
+const NAT_PMP_OP_EXTERNAL_ADDR: u8 = 0;
+const NAT_PMP_OP_MAP_TCP: u8 = 2;
+const NAT_PMP_OP_RESPONSE: u8 = 128;
+// The first retransmission of a request is after 250ms and doubles
+const NAT_PMP_INITIAL_TIMEOUT: Duration = Duration::from_millis(250);
+const NAT_PMP_RETRIES: usize = 4;
+
+const UPNP_SEARCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAPPING_DESCRIPTION: &str = "axon";
+const MAPPING_LIFETIME: Duration = Duration::from_secs(2 * 60 * 60);
+const MAPPING_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+// The gateways may grant a much shorter lifetime than the one asked, even 0
+const MIN_RENEWAL_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Display)]
+pub enum NatError {
+    #[display(fmt = "io error: {}", _0)]
+    Io(io::Error),
+
+    #[display(fmt = "no gateway found")]
+    NoGateway,
+
+    #[display(fmt = "upnp error: {}", _0)]
+    Upnp(String),
+
+    #[display(fmt = "nat-pmp error result code {}", _0)]
+    NatPmp(u16),
+
+    #[display(fmt = "invalid nat-pmp response")]
+    InvalidResponse,
+
+    #[display(fmt = "nat-pmp request timeout")]
+    Timeout,
+
+    #[display(fmt = "external address {} is not public", _0)]
+    NotPublic(IpAddr),
+}
+
+impl std::error::Error for NatError {}
+
+impl From<io::Error> for NatError {
+    fn from(err: io::Error) -> Self {
+        NatError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortMapping {
+    pub external: SocketAddr,
+    pub lifetime: Duration,
+}
+
+/// Keep the listen port mapped on the gateway, by UPnP or else NAT-PMP, and
+/// tell the peer manager the external address to advertise
+pub async fn run_port_mapping(peer_manager: Arc<PeerManager>, local_port: u16) {
+    loop {
+        let interval = match map_port(local_port, MAPPING_LIFETIME).await {
+            Ok(mapping) => {
+                log::info!("port {} mapped to {}", local_port, mapping.external);
+                peer_manager.set_mapped_addr(Some(mapping.external));
+                renewal_interval(mapping.lifetime)
+            }
+            Err(err) => {
+                log::info!("port {} mapping failed: {}", local_port, err);
+                peer_manager.set_mapped_addr(None);
+                MAPPING_RETRY_INTERVAL
+            }
+        };
+
+        sleep(interval).await;
+    }
+}
+
+/// Renew the mapping at the half of its lifetime, but not in a busy loop
+fn renewal_interval(lifetime: Duration) -> Duration {
+    (lifetime / 2).max(MIN_RENEWAL_INTERVAL)
+}
+
+pub async fn map_port(local_port: u16, lifetime: Duration) -> Result<PortMapping, NatError> {
+    match upnp_map_port(local_port, lifetime).await {
+        Ok(mapping) => Ok(mapping),
+        Err(err) => {
+            log::debug!("upnp port mapping failed: {}, try nat-pmp", err);
+            let gateway = default_gateway().ok_or(NatError::NoGateway)?;
+            nat_pmp_map_port(
+                SocketAddr::new(gateway.into(), NAT_PMP_PORT),
+                local_port,
+                lifetime,
+            )
+            .await
+        }
+    }
+}
+
+pub async fn upnp_map_port(local_port: u16, lifetime: Duration) -> Result<PortMapping, NatError> {
+    let gateway = search_gateway(SearchOptions {
+        timeout: Some(UPNP_SEARCH_TIMEOUT),
+        ..Default::default()
+    })
+    .await
+    .map_err(|e| NatError::Upnp(e.to_string()))?;
+
+    let local_addr = SocketAddr::new(local_ip_to(gateway.addr)?, local_port);
+    gateway
+        .add_port(
+            PortMappingProtocol::TCP,
+            local_port,
+            local_addr,
+            lifetime.as_secs() as u32,
+            MAPPING_DESCRIPTION,
+        )
+        .await
+        .map_err(|e| NatError::Upnp(e.to_string()))?;
+
+    let external_ip = gateway
+        .get_external_ip()
+        .await
+        .map_err(|e| NatError::Upnp(e.to_string()))?;
+    check_public(external_ip)?;
+
+    Ok(PortMapping {
+        external: SocketAddr::new(external_ip, local_port),
+        lifetime,
+    })
+}
+
+/// Map the local port by NAT-PMP (RFC 6886), asking for the same external
+/// port
+pub async fn nat_pmp_map_port(
+    gateway: SocketAddr,
+    local_port: u16,
+    lifetime: Duration,
+) -> Result<PortMapping, NatError> {
+    let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)).await?;
+    socket.connect(gateway).await?;
+
+    let resp = nat_pmp_request(&socket, &[NAT_PMP_VERSION, NAT_PMP_OP_EXTERNAL_ADDR], 12).await?;
+    let external_ip = IpAddr::from([resp[8], resp[9], resp[10], resp[11]]);
+    check_public(external_ip)?;
+
+    let mut req = vec![NAT_PMP_VERSION, NAT_PMP_OP_MAP_TCP, 0, 0];
+    req.extend_from_slice(&local_port.to_be_bytes());
+    req.extend_from_slice(&local_port.to_be_bytes());
+    req.extend_from_slice(&(lifetime.as_secs() as u32).to_be_bytes());
+    let resp = nat_pmp_request(&socket, &req, 16).await?;
+    let external_port = u16::from_be_bytes([resp[10], resp[11]]);
+    let lifetime = u32::from_be_bytes([resp[12], resp[13], resp[14], resp[15]]);
+
+    Ok(PortMapping {
+        external: SocketAddr::new(external_ip, external_port),
+        lifetime: Duration::from_secs(lifetime.into()),
+    })
+}
+
+/// Send the request until a response of the same opcode is received, and
+/// check the result code of it
+async fn nat_pmp_request(
+    socket: &UdpSocket,
+    req: &[u8],
+    resp_len: usize,
+) -> Result<Vec<u8>, NatError> {
+    let mut wait = NAT_PMP_INITIAL_TIMEOUT;
+    let mut buf = [0u8; 16];
+
+    for _ in 0..NAT_PMP_RETRIES {
+        socket.send(req).await?;
+
+        if let Ok(len) = timeout(wait, socket.recv(&mut buf)).await {
+            let len = len?;
+            if len < resp_len || buf[0] != NAT_PMP_VERSION || buf[1] != req[1] | NAT_PMP_OP_RESPONSE
+            {
+                return Err(NatError::InvalidResponse);
+            }
+
+            return match u16::from_be_bytes([buf[2], buf[3]]) {
+                0 => Ok(buf[..resp_len].to_vec()),
+                code => Err(NatError::NatPmp(code)),
+            };
+        }
+        wait *= 2;
+    }
+
+    Err(NatError::Timeout)
+}
+
+fn check_public(ip: IpAddr) -> Result<(), NatError> {
+    if is_reachable(ip) {
+        Ok(())
+    } else {
+        Err(NatError::NotPublic(ip))
+    }
+}
+
+/// The local address of the interface routing to the gateway
+fn local_ip_to(gateway: SocketAddr) -> io::Result<IpAddr> {
+    let socket = StdUdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))?;
+    socket.connect(gateway)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// The gateway of the default route
+#[cfg(target_os = "linux")]
+fn default_gateway() -> Option<Ipv4Addr> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            return None;
+        }
+
+        // The address is in hex of the host byte order
+        let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_gateway() -> Option<Ipv4Addr> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        time::Duration,
+    };
+
+    use protocol::tokio::{self, net::UdpSocket};
+
+    use super::{nat_pmp_map_port, renewal_interval, NatError, PortMapping, MIN_RENEWAL_INTERVAL};
+
+    /// A NAT-PMP gateway mapping every port to the next one
+    async fn mock_gateway(external_ip: [u8; 4], result_code: u16) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 12];
+            loop {
+                let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+                let mut resp = vec![0, buf[1] | 128];
+                resp.extend_from_slice(&result_code.to_be_bytes());
+                resp.extend_from_slice(&1u32.to_be_bytes());
+
+                match (len, buf[1]) {
+                    (2, 0) => resp.extend_from_slice(&external_ip),
+                    (12, 2) => {
+                        let port = u16::from_be_bytes([buf[4], buf[5]]);
+                        resp.extend_from_slice(&port.to_be_bytes());
+                        resp.extend_from_slice(&(port + 1).to_be_bytes());
+                        resp.extend_from_slice(&buf[8..12]);
+                    }
+                    _ => continue,
+                }
+                socket.send_to(&resp, from).await.unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_nat_pmp_map_port() {
+        let gateway = mock_gateway([1, 2, 3, 4], 0).await;
+        let mapping = nat_pmp_map_port(gateway, 8001, Duration::from_secs(7200))
+            .await
+            .unwrap();
+
+        assert_eq!(mapping, PortMapping {
+            external: SocketAddr::new(Ipv4Addr::new(1, 2, 3, 4).into(), 8002),
+            lifetime: Duration::from_secs(7200),
+        });
+    }
+
+    #[tokio::test]
+    async fn test_nat_pmp_errors() {
+        // Not authorized
+        let gateway = mock_gateway([1, 2, 3, 4], 2).await;
+        let res = nat_pmp_map_port(gateway, 8001, Duration::from_secs(7200)).await;
+        assert!(matches!(res, Err(NatError::NatPmp(2))));
+
+        // Behind another NAT
+        let gateway = mock_gateway([10, 0, 0, 1], 0).await;
+        let res = nat_pmp_map_port(gateway, 8001, Duration::from_secs(7200)).await;
+        assert!(matches!(res, Err(NatError::NotPublic(_))));
+    }
+
+    #[test]
+    fn test_renewal_interval() {
+        assert_eq!(
+            renewal_interval(Duration::from_secs(7200)),
+            Duration::from_secs(3600)
+        );
+        assert_eq!(
+            renewal_interval(Duration::from_secs(30)),
+            MIN_RENEWAL_INTERVAL
+        );
+        assert_eq!(renewal_interval(Duration::ZERO), MIN_RENEWAL_INTERVAL);
+    }
+}
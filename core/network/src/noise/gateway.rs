@@ -0,0 +1,284 @@
+//! Hand the connections secured by Noise to tentacle as raw sessions.
+//!
+//! Tentacle takes no other handshake than secio, so it keeps running secio
+//! inside the Noise stream, which leaves the peer ids and the remote
+//! addresses of the sessions as they are. The peer id authenticated by Noise
+//! of an inbound session is checked against the secio one when the session
+//! opens. The Noise listener takes the place of the tentacle one, and the
+//! Noise mode dials by itself.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use tentacle::{
+    multiaddr::Multiaddr,
+    service::{RawSessionInfo, ServiceAsyncControl, TargetProtocol},
+    utils::{extract_peer_id, is_reachable, multiaddr_to_socketaddr, socketaddr_to_multiaddr},
+};
+
+use protocol::tokio::{
+    self,
+    io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream},
+    net::{TcpListener, TcpSocket, TcpStream},
+    sync::Semaphore,
+    time::{sleep, timeout},
+};
+
+use super::{
+    handshake, NoiseError, NoiseIdentity, NoiseStream, NoiseTransport, Upgraded,
+    NOISE_MAX_PLAINTEXT_LEN,
+};
+use crate::{error::NetworkError, peer_manager::PeerManager};
+
+const DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// Longer than the secio handshake of tentacle
+const SESSION_OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PENDING_HANDSHAKES: usize = 256;
+const ACCEPT_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bridge the Noise stream to the byte stream of a tentacle session, until
+/// either side closes
+fn relay(mut noise: NoiseStream<TcpStream>) -> DuplexStream {
+    let (session_io, mut relay_io) = duplex(NOISE_MAX_PLAINTEXT_LEN);
+
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; NOISE_MAX_PLAINTEXT_LEN];
+        loop {
+            tokio::select! {
+                read = relay_io.read(&mut buf) => match read {
+                    Ok(len) if len > 0 => {
+                        if let Err(err) = noise.send(&buf[..len]).await {
+                            log::debug!("noise relay send failed: {}", err);
+                            break;
+                        }
+                    }
+                    _ => break,
+                },
+                recv = noise.recv() => match recv {
+                    Ok(Some(msg)) => {
+                        if relay_io.write_all(&msg).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        log::debug!("noise relay recv failed: {}", err);
+                        break;
+                    }
+                },
+            }
+        }
+    });
+
+    session_io
+}
+
+async fn open_raw_session(
+    control: &ServiceAsyncControl,
+    upgraded: Upgraded,
+    info: RawSessionInfo,
+) -> Result<(), NetworkError> {
+    let ret = match upgraded {
+        Upgraded::Noise(_, stream) => control.raw_session(relay(*stream), info).await,
+        Upgraded::Secio(stream) => control.raw_session(stream, info).await,
+    };
+    ret.map_err(|err| NetworkError::UnexpectedError(Box::new(err)))
+}
+
+/// Bind the listening address of the Noise or the mixed mode. The port is
+/// shared with the outbound sockets of tentacle, which bind it for the NAT.
+pub fn bind_listener(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+
+    #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+    socket.set_reuseport(true)?;
+
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+/// Accept the inbound connections by the transport security, and open the
+/// tentacle sessions on them
+pub struct NoiseGateway {
+    transport:    Arc<NoiseTransport>,
+    control:      ServiceAsyncControl,
+    peer_manager: Arc<PeerManager>,
+    listen_addr:  Multiaddr,
+    // The handshakes beyond the permits are refused
+    pending:      Arc<Semaphore>,
+}
+
+impl NoiseGateway {
+    pub fn new(
+        transport: Arc<NoiseTransport>,
+        control: ServiceAsyncControl,
+        peer_manager: Arc<PeerManager>,
+        listen_addr: Multiaddr,
+    ) -> Self {
+        NoiseGateway {
+            transport,
+            control,
+            peer_manager,
+            listen_addr,
+            pending: Arc::new(Semaphore::new(MAX_PENDING_HANDSHAKES)),
+        }
+    }
+
+    pub async fn run(self, listener: TcpListener) {
+        loop {
+            let (stream, remote_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    log::warn!("accept connection on {} failed: {}", self.listen_addr, err);
+                    sleep(ACCEPT_RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            let permit = match Arc::clone(&self.pending).try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    log::debug!("refuse {}, too many pending handshakes", remote_addr);
+                    continue;
+                }
+            };
+
+            let transport = Arc::clone(&self.transport);
+            let control = self.control.clone();
+            let peer_manager = Arc::clone(&self.peer_manager);
+            let listen_addr = self.listen_addr.clone();
+            tokio::spawn(async move {
+                let upgraded = timeout(HANDSHAKE_TIMEOUT, transport.upgrade_inbound(stream))
+                    .await
+                    .unwrap_or(Err(NoiseError::Timeout));
+                drop(permit);
+
+                let ret = match upgraded {
+                    Ok(upgraded) => {
+                        accept(&control, &peer_manager, upgraded, listen_addr, remote_addr).await
+                    }
+                    Err(err) => Err(err.into()),
+                };
+                if let Err(err) = ret {
+                    log::debug!("inbound connection from {} failed: {}", remote_addr, err);
+                }
+            });
+        }
+    }
+}
+
+/// Open the inbound session. The peer id authenticated by Noise is kept for
+/// the check on the session open, until the secio handshake must be over
+async fn accept(
+    control: &ServiceAsyncControl,
+    peer_manager: &PeerManager,
+    upgraded: Upgraded,
+    listen_addr: Multiaddr,
+    remote_addr: SocketAddr,
+) -> Result<(), NetworkError> {
+    let remote_addr = socketaddr_to_multiaddr(remote_addr);
+    let info = RawSessionInfo::inbound(listen_addr, remote_addr.clone());
+
+    let noise_peer_id = match &upgraded {
+        Upgraded::Noise(peer_id, _) => peer_id.clone(),
+        Upgraded::Secio(_) => return open_raw_session(control, upgraded, info).await,
+    };
+
+    peer_manager.expect_noise_peer(remote_addr.clone(), noise_peer_id);
+    let ret = open_raw_session(control, upgraded, info).await;
+    if ret.is_ok() {
+        sleep(SESSION_OPEN_TIMEOUT).await;
+    }
+    peer_manager.take_noise_peer(&remote_addr);
+    ret
+}
+
+/// Dial by tentacle, or by Noise in the Noise mode
+#[derive(Clone)]
+pub struct Dialer {
+    control:      ServiceAsyncControl,
+    peer_manager: Arc<PeerManager>,
+    // Only set in the Noise mode, the mixed one still dials by secio
+    identity:     Option<Arc<NoiseIdentity>>,
+}
+
+impl Dialer {
+    pub fn new(
+        control: ServiceAsyncControl,
+        peer_manager: Arc<PeerManager>,
+        identity: Option<Arc<NoiseIdentity>>,
+    ) -> Self {
+        Dialer {
+            control,
+            peer_manager,
+            identity,
+        }
+    }
+
+    /// The failures of the Noise dials are handled here, the same way as the
+    /// dialer errors of tentacle
+    pub async fn dial(&self, addr: Multiaddr, target: TargetProtocol) {
+        let identity = match self.identity.as_ref() {
+            Some(identity) => Arc::clone(identity),
+            None => {
+                let _ignore = self.control.dial(addr, target).await;
+                return;
+            }
+        };
+
+        let dialer = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = dialer.dial_noise(&identity, &addr, target).await {
+                log::debug!("noise dial {} failed: {}", addr, err);
+                dialer.peer_manager.with_registry_mut(|reg| {
+                    reg.remove_feeler(&addr);
+                    reg.dialing.remove(&addr)
+                });
+            }
+        });
+    }
+
+    async fn dial_noise(
+        &self,
+        identity: &NoiseIdentity,
+        addr: &Multiaddr,
+        target: TargetProtocol,
+    ) -> Result<(), NetworkError> {
+        let socket_addr = multiaddr_to_socketaddr(addr)
+            .ok_or_else(|| NetworkError::UnexpectedPeerAddr(addr.to_string()))?;
+        let (remote_peer_id, stream) = timeout(DIAL_TIMEOUT, async {
+            let stream = TcpStream::connect(socket_addr).await?;
+            handshake(stream, identity, true).await
+        })
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+
+        // Dialing an observed address of itself tells it is public
+        if remote_peer_id == identity.peer_id() {
+            self.peer_manager.with_registry_mut(|reg| {
+                reg.remove_feeler(addr);
+                reg.dialing.remove(addr)
+            });
+            if is_reachable(socket_addr.ip()) {
+                self.peer_manager.public_addrs.write().insert(addr.clone());
+            }
+            return Ok(());
+        }
+        if extract_peer_id(addr).as_ref() != Some(&remote_peer_id) {
+            return Err(NoiseError::UnexpectedPeer(remote_peer_id.to_base58()).into());
+        }
+
+        let upgraded = Upgraded::Noise(remote_peer_id, Box::new(stream));
+        open_raw_session(
+            &self.control,
+            upgraded,
+            RawSessionInfo::outbound(addr.clone(), target),
+        )
+        .await
+    }
+}
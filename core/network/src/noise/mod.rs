@@ -0,0 +1,465 @@
+//! Noise XX transport security, the replacement of the deprecated secio.
+//!
+//! The static Noise key is signed by the secp256k1 key of the node, so the
+//! `PeerId` of a node stays the same whichever security the connection uses.
+//! In the mixed mode the inbound connections of both are accepted, told apart
+//! by their first bytes, and the outbound ones keep secio until every node of
+//! the chain accepts Noise.
+
+mod gateway;
+
+pub use self::gateway::{bind_listener, Dialer, NoiseGateway};
+
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+
+use derive_more::Display;
+use futures::{SinkExt, StreamExt};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+use tentacle::secio::PeerId;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use protocol::tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    time::sleep,
+};
+use protocol::types::{Bytes, Hasher};
+
+use crate::PeerIdExt;
+
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
+const NOISE_MAX_MESSAGE_LEN: usize = 65535;
+const NOISE_TAG_LEN: usize = 16;
+/// The largest message sent at once on a Noise stream
+pub const NOISE_MAX_PLAINTEXT_LEN: usize = NOISE_MAX_MESSAGE_LEN - NOISE_TAG_LEN;
+
+/// The first message of the initiator is the 32 bytes ephemeral key, behind a
+/// 2 bytes length
+const NOISE_PREFIX: [u8; 2] = [0x00, 0x20];
+/// Secio opens with the propose message behind a 4 bytes length, far shorter
+/// than 64KiB
+const SECIO_PREFIX: [u8; 2] = [0x00, 0x00];
+const PEEK_INTERVAL: Duration = Duration::from_millis(10);
+
+const STATIC_KEY_SIG_PREFIX: &[u8] = b"axon-noise-static-key:";
+const IDENTITY_PAYLOAD_LEN: usize = 33 + 64;
+
+#[derive(Debug, Display)]
+pub enum NoiseError {
+    #[display(fmt = "io error: {}", _0)]
+    Io(std::io::Error),
+
+    #[display(fmt = "noise error: {}", _0)]
+    Snow(snow::Error),
+
+    #[display(fmt = "invalid identity of the remote static key")]
+    InvalidIdentity,
+
+    #[display(fmt = "message of {} bytes exceeds the maximum length", _0)]
+    TooLarge(usize),
+
+    #[display(fmt = "connection closed during handshake")]
+    UnexpectedEof,
+
+    #[display(fmt = "handshake timeout")]
+    Timeout,
+
+    #[display(fmt = "unknown transport security of prefix {:?}", _0)]
+    UnknownSecurity([u8; 2]),
+
+    #[display(fmt = "{} connection not accepted", _0)]
+    Rejected(TransportSecurity),
+
+    #[display(fmt = "unknown transport security {}, secio, noise or mixed", _0)]
+    UnexpectedSecurity(String),
+
+    #[display(fmt = "unexpected remote peer {}", _0)]
+    UnexpectedPeer(String),
+}
+
+impl std::error::Error for NoiseError {}
+
+impl From<std::io::Error> for NoiseError {
+    fn from(err: std::io::Error) -> NoiseError {
+        NoiseError::Io(err)
+    }
+}
+
+impl From<snow::Error> for NoiseError {
+    fn from(err: snow::Error) -> NoiseError {
+        NoiseError::Snow(err)
+    }
+}
+
+/// The security of the connections, the mixed one accepts both secio and
+/// Noise during the transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransportSecurity {
+    #[default]
+    Secio,
+    Noise,
+    Mixed,
+}
+
+impl fmt::Display for TransportSecurity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            TransportSecurity::Secio => "secio",
+            TransportSecurity::Noise => "noise",
+            TransportSecurity::Mixed => "mixed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for TransportSecurity {
+    type Err = NoiseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "secio" => Ok(TransportSecurity::Secio),
+            "noise" => Ok(TransportSecurity::Noise),
+            "mixed" => Ok(TransportSecurity::Mixed),
+            _ => Err(NoiseError::UnexpectedSecurity(s.to_owned())),
+        }
+    }
+}
+
+/// The static Noise key of a node, signed by its secp256k1 key
+pub struct NoiseIdentity {
+    pubkey:         PublicKey,
+    static_private: Vec<u8>,
+    signature:      [u8; 64],
+}
+
+impl NoiseIdentity {
+    pub fn new(secret: &SecretKey) -> Result<Self, NoiseError> {
+        let keypair = snow::Builder::new(noise_params()).generate_keypair()?;
+        let secp = Secp256k1::new();
+        let signature = secp
+            .sign_ecdsa(&static_key_message(&keypair.public), secret)
+            .serialize_compact();
+
+        Ok(NoiseIdentity {
+            pubkey: PublicKey::from_secret_key(&secp, secret),
+            static_private: keypair.private,
+            signature,
+        })
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        PeerId::from_pubkey_bytes(self.pubkey.serialize()).expect("valid secp256k1 public key")
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(IDENTITY_PAYLOAD_LEN);
+        payload.extend_from_slice(&self.pubkey.serialize());
+        payload.extend_from_slice(&self.signature);
+        payload
+    }
+}
+
+fn noise_params() -> snow::params::NoiseParams {
+    NOISE_PARAMS.parse().expect("valid noise params")
+}
+
+fn static_key_message(static_pubkey: &[u8]) -> Message {
+    let content = [STATIC_KEY_SIG_PREFIX, static_pubkey].concat();
+    Message::from_slice(Hasher::digest(content).as_bytes()).expect("32 bytes hash")
+}
+
+/// Check that the remote static key is signed by the secp256k1 key in the
+/// payload, returns the peer id of that key
+fn verify_identity(payload: &[u8], remote_static: &[u8]) -> Result<PeerId, NoiseError> {
+    if payload.len() != IDENTITY_PAYLOAD_LEN {
+        return Err(NoiseError::InvalidIdentity);
+    }
+
+    let (pubkey, signature) = payload.split_at(33);
+    let pubkey = PublicKey::from_slice(pubkey).map_err(|_| NoiseError::InvalidIdentity)?;
+    let signature = Signature::from_compact(signature).map_err(|_| NoiseError::InvalidIdentity)?;
+    Secp256k1::verification_only()
+        .verify_ecdsa(&static_key_message(remote_static), &signature, &pubkey)
+        .map_err(|_| NoiseError::InvalidIdentity)?;
+
+    PeerId::from_pubkey_bytes(pubkey.serialize()).map_err(|_| NoiseError::InvalidIdentity)
+}
+
+fn noise_framed<T: AsyncRead + AsyncWrite>(io: T) -> Framed<T, LengthDelimitedCodec> {
+    LengthDelimitedCodec::builder()
+        .length_field_length(2)
+        .max_frame_length(NOISE_MAX_MESSAGE_LEN)
+        .new_framed(io)
+}
+
+/// Run the Noise XX handshake, returns the peer id of the remote and the
+/// encrypted stream
+pub async fn handshake<T>(
+    io: T,
+    identity: &NoiseIdentity,
+    initiator: bool,
+) -> Result<(PeerId, NoiseStream<T>), NoiseError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let builder = snow::Builder::new(noise_params()).local_private_key(&identity.static_private);
+    let mut state = if initiator {
+        builder.build_initiator()?
+    } else {
+        builder.build_responder()?
+    };
+    let mut framed = noise_framed(io);
+    let mut buf = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+    let payload = identity.payload();
+
+    let remote_payload = if initiator {
+        // -> e
+        send_handshake(&mut framed, &mut state, &[], &mut buf).await?;
+        // <- e, ee, s, es
+        let remote_payload = recv_handshake(&mut framed, &mut state, &mut buf).await?;
+        // -> s, se
+        send_handshake(&mut framed, &mut state, &payload, &mut buf).await?;
+        remote_payload
+    } else {
+        recv_handshake(&mut framed, &mut state, &mut buf).await?;
+        send_handshake(&mut framed, &mut state, &payload, &mut buf).await?;
+        recv_handshake(&mut framed, &mut state, &mut buf).await?
+    };
+
+    let remote_static = state
+        .get_remote_static()
+        .ok_or(NoiseError::InvalidIdentity)?;
+    let peer_id = verify_identity(&remote_payload, remote_static)?;
+    let stream = NoiseStream {
+        framed,
+        state: state.into_transport_mode()?,
+        buf,
+    };
+
+    Ok((peer_id, stream))
+}
+
+async fn send_handshake<T>(
+    framed: &mut Framed<T, LengthDelimitedCodec>,
+    state: &mut snow::HandshakeState,
+    payload: &[u8],
+    buf: &mut [u8],
+) -> Result<(), NoiseError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let len = state.write_message(payload, buf)?;
+    framed.send(Bytes::copy_from_slice(&buf[..len])).await?;
+    Ok(())
+}
+
+async fn recv_handshake<T>(
+    framed: &mut Framed<T, LengthDelimitedCodec>,
+    state: &mut snow::HandshakeState,
+    buf: &mut [u8],
+) -> Result<Vec<u8>, NoiseError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let frame = framed.next().await.ok_or(NoiseError::UnexpectedEof)??;
+    let len = state.read_message(&frame, buf)?;
+    Ok(buf[..len].to_vec())
+}
+
+/// The messages encrypted after the handshake, each one in a frame
+pub struct NoiseStream<T> {
+    framed: Framed<T, LengthDelimitedCodec>,
+    state:  snow::TransportState,
+    buf:    Vec<u8>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> NoiseStream<T> {
+    pub async fn send(&mut self, msg: &[u8]) -> Result<(), NoiseError> {
+        if msg.len() > NOISE_MAX_PLAINTEXT_LEN {
+            return Err(NoiseError::TooLarge(msg.len()));
+        }
+
+        let len = self.state.write_message(msg, &mut self.buf)?;
+        self.framed
+            .send(Bytes::copy_from_slice(&self.buf[..len]))
+            .await?;
+        Ok(())
+    }
+
+    /// Receive the next message, returns none if the remote closes the stream
+    pub async fn recv(&mut self) -> Result<Option<Bytes>, NoiseError> {
+        let frame = match self.framed.next().await {
+            Some(frame) => frame?,
+            None => return Ok(None),
+        };
+
+        let len = self.state.read_message(&frame, &mut self.buf)?;
+        Ok(Some(Bytes::copy_from_slice(&self.buf[..len])))
+    }
+}
+
+/// Tell the security of an inbound connection by its first bytes, without
+/// consuming them
+pub async fn detect_security(stream: &TcpStream) -> Result<TransportSecurity, NoiseError> {
+    let mut prefix = [0u8; 2];
+    loop {
+        match stream.peek(&mut prefix).await? {
+            0 => return Err(NoiseError::UnexpectedEof),
+            1 => sleep(PEEK_INTERVAL).await,
+            _ => break,
+        }
+    }
+
+    match prefix {
+        NOISE_PREFIX => Ok(TransportSecurity::Noise),
+        SECIO_PREFIX => Ok(TransportSecurity::Secio),
+        _ => Err(NoiseError::UnknownSecurity(prefix)),
+    }
+}
+
+pub enum Upgraded {
+    Noise(PeerId, Box<NoiseStream<TcpStream>>),
+    /// The connection is left to the secio handshake
+    Secio(TcpStream),
+}
+
+/// Upgrade the inbound TCP connections of a node by the configured security,
+/// the outbound ones are left to the `Dialer`
+pub struct NoiseTransport {
+    identity: Arc<NoiseIdentity>,
+    security: TransportSecurity,
+}
+
+impl NoiseTransport {
+    pub fn new(identity: Arc<NoiseIdentity>, security: TransportSecurity) -> Self {
+        NoiseTransport { identity, security }
+    }
+
+    pub async fn upgrade_inbound(&self, stream: TcpStream) -> Result<Upgraded, NoiseError> {
+        let remote_security = match self.security {
+            TransportSecurity::Secio => return Ok(Upgraded::Secio(stream)),
+            TransportSecurity::Noise | TransportSecurity::Mixed => detect_security(&stream).await?,
+        };
+
+        match (remote_security, self.security) {
+            (TransportSecurity::Noise, _) => {
+                let (peer_id, stream) = handshake(stream, &self.identity, false).await?;
+                Ok(Upgraded::Noise(peer_id, Box::new(stream)))
+            }
+            (TransportSecurity::Secio, TransportSecurity::Mixed) => Ok(Upgraded::Secio(stream)),
+            (remote_security, _) => Err(NoiseError::Rejected(remote_security)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use secp256k1::SecretKey;
+    use tentacle::secio::SecioKeyPair;
+
+    use protocol::tokio::{self, io::AsyncReadExt, io::AsyncWriteExt, net::TcpStream};
+    use protocol::traits::{Context, Network};
+
+    use super::{noise_params, verify_identity, NoiseError, NoiseIdentity, TransportSecurity};
+    use crate::config::NetworkConfig;
+    use crate::service::tests::{
+        config, peer_addr, rejected_count, start_node, wait_peer_count, wait_rejected,
+    };
+
+    fn security_config(port: u16, security: &str) -> NetworkConfig {
+        config(port)
+            .transport_security(Some(security.to_owned()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_identity() {
+        let secret = SecretKey::from_slice(&[1; 32]).unwrap();
+        let identity = NoiseIdentity::new(&secret).unwrap();
+        let secio = SecioKeyPair::secp256k1_raw_key(secret.secret_bytes()).unwrap();
+        assert_eq!(identity.peer_id(), secio.peer_id());
+
+        // Signed by the secp256k1 key, but over another static key
+        let other = snow::Builder::new(noise_params())
+            .generate_keypair()
+            .unwrap();
+        assert!(matches!(
+            verify_identity(&identity.payload(), &other.public),
+            Err(NoiseError::InvalidIdentity)
+        ));
+
+        assert_eq!(
+            "Mixed".parse::<TransportSecurity>().unwrap(),
+            TransportSecurity::Mixed
+        );
+        assert!("tls".parse::<TransportSecurity>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_noise_nodes() {
+        let config_a = security_config(23381, "noise");
+        let addr_a = peer_addr(&config_a);
+
+        let (node_a, _) = start_node(config_a);
+        let (node_b, _) = start_node(security_config(23382, "noise").bootstraps(vec![addr_a]));
+
+        assert!(wait_peer_count(&node_a, 1).await);
+        assert!(wait_peer_count(&node_b, 1).await);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_nodes() {
+        let config_a = security_config(23383, "mixed");
+        let config_c = security_config(23385, "noise");
+        let (addr_a, addr_c) = (peer_addr(&config_a), peer_addr(&config_c));
+
+        // The mixed node accepts both secio and noise
+        let (node_a, _) = start_node(config_a);
+        let (node_b, _) =
+            start_node(security_config(23384, "secio").bootstraps(vec![addr_a.clone()]));
+        let (node_c, _) = start_node(config_c.bootstraps(vec![addr_a]));
+        assert!(wait_peer_count(&node_a, 2).await);
+        assert!(wait_peer_count(&node_b, 1).await);
+        assert!(wait_peer_count(&node_c, 1).await);
+
+        // The noise node refuses secio
+        let (node_d, _) = start_node(security_config(23386, "secio").bootstraps(vec![addr_c]));
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(node_d.peer_count(Context::new()).unwrap(), 0);
+        assert_eq!(node_c.peer_count(Context::new()).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_timeout() {
+        let _node = start_node(security_config(23387, "noise"));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // A peer which stops in the middle of the security prefix
+        let mut stream = TcpStream::connect("127.0.0.1:23387").await.unwrap();
+        stream.write_all(&[0]).await.unwrap();
+
+        let mut buf = [0u8; 8];
+        let read = tokio::time::timeout(Duration::from_secs(10), stream.read(&mut buf))
+            .await
+            .expect("connection closed by the gateway");
+        assert!(matches!(read, Ok(0) | Err(_)));
+    }
+
+    #[tokio::test]
+    async fn test_noise_peer_mismatch() {
+        let config_a = security_config(23388, "noise");
+        let addr_a = peer_addr(&config_a);
+        let (node_a, _) = start_node(config_a);
+
+        // Authenticated by another key in Noise than in secio
+        let mut config_b = security_config(23389, "noise").bootstraps(vec![addr_a]);
+        config_b.secio_keypair = SecioKeyPair::secp256k1_raw_key([7u8; 32]).unwrap();
+        let rejected = rejected_count("noise_peer_mismatch");
+        let _node_b = start_node(config_b);
+
+        assert!(wait_rejected("noise_peer_mismatch", rejected).await);
+        assert_eq!(node_a.peer_count(Context::new()).unwrap(), 0);
+    }
+}
@@ -8,13 +8,15 @@ use tentacle::{
 
 use common_apm::tracing::AxonTracer;
 
-use protocol::rand::{self, Rng};
+use protocol::rand::{self, seq::SliceRandom, Rng};
 use protocol::traits::{Context, Gossip, MessageCodec, Priority};
 use protocol::{async_trait, tokio, types::Bytes, ProtocolResult};
 
 use crate::endpoint::Endpoint;
 use crate::error::NetworkError;
 use crate::message::{Headers, NetworkMessage};
+use crate::noise::Dialer;
+use crate::outbound::ConsensusMesh;
 use crate::peer_manager::PeerManager;
 use crate::traits::NetworkContext;
 
@@ -22,13 +24,22 @@ use crate::traits::NetworkContext;
 pub struct NetworkGossip {
     pub(crate) transmitter:  ServiceAsyncControl,
     pub(crate) peer_manager: Arc<PeerManager>,
+    pub(crate) dialer:       Dialer,
+    mesh:                    ConsensusMesh,
 }
 
 impl NetworkGossip {
-    pub fn new(transmitter: ServiceAsyncControl, peer_manager: Arc<PeerManager>) -> Self {
+    pub fn new(
+        transmitter: ServiceAsyncControl,
+        peer_manager: Arc<PeerManager>,
+        dialer: Dialer,
+        mesh: ConsensusMesh,
+    ) -> Self {
         NetworkGossip {
             transmitter,
             peer_manager,
+            dialer,
+            mesh,
         }
     }
 
@@ -61,29 +72,65 @@ impl NetworkGossip {
         data: Bytes,
         priority: Priority,
     ) -> Result<(), NetworkError> {
-        match priority {
-            Priority::Normal => self
-                .transmitter
-                .filter_broadcast(
-                    target_session,
-                    crate::protocols::SupportProtocols::Transmitter.protocol_id(),
-                    data,
-                )
-                .await
-                .unwrap(),
-            Priority::High => self
-                .transmitter
-                .quick_filter_broadcast(
-                    target_session,
-                    crate::protocols::SupportProtocols::Transmitter.protocol_id(),
-                    data,
-                )
-                .await
-                .unwrap(),
-        }
+        // The validators on the consensus mesh receive the consensus messages
+        // from the mesh queue, and the others from the transmitter. The quick
+        // queue of the mesh sessions is left to the consensus messages, the
+        // other messages of high priority wait in the normal queue for them.
+        let (quick, normal) = match priority {
+            Priority::Consensus => {
+                let (mesh, mut others) = self.peer_manager.split_mesh_sessions(target_session);
+                if !mesh.is_empty() {
+                    if let Err(mesh) = self.mesh.try_send(mesh, data.clone()) {
+                        others.extend(mesh);
+                    }
+                }
+                (others, Vec::new())
+            }
+            Priority::High => {
+                let (mesh, others) = self.peer_manager.split_mesh_sessions(target_session);
+                (others, mesh)
+            }
+            Priority::Normal => (
+                Vec::new(),
+                self.peer_manager
+                    .with_registry(|online| online.target_sessions(target_session)),
+            ),
+        };
+
+        self.broadcast_to(quick, data.clone(), true).await;
+        self.broadcast_to(normal, data, false).await;
         Ok(())
     }
 
+    async fn broadcast_to(&self, sessions: Vec<SessionId>, data: Bytes, quick: bool) {
+        if sessions.is_empty() {
+            return;
+        }
+
+        let compressor = self.peer_manager.compressor();
+        let groups = self
+            .peer_manager
+            .sessions_by_compression(TargetSession::Multi(Box::new(sessions.into_iter())));
+
+        for (compression, sessions) in groups {
+            let target_session = TargetSession::Multi(Box::new(sessions.into_iter()));
+            let data = compressor.compress(data.clone(), compression);
+            let protocol_id = crate::protocols::SupportProtocols::Transmitter.protocol_id();
+
+            if quick {
+                self.transmitter
+                    .quick_filter_broadcast(target_session, protocol_id, data)
+                    .await
+                    .unwrap()
+            } else {
+                self.transmitter
+                    .filter_broadcast(target_session, protocol_id, data)
+                    .await
+                    .unwrap()
+            }
+        }
+    }
+
     async fn send_to_peers<'a, P: AsRef<[Bytes]> + 'a>(
         &self,
         ctx: Context,
@@ -101,10 +148,10 @@ impl NetworkGossip {
         };
         let (connected, unconnected) = self.peer_manager.peers(peer_ids);
         if !unconnected.is_empty() {
-            let control = self.transmitter.clone();
+            let dialer = self.dialer.clone();
             tokio::spawn(async move {
                 for addr in unconnected {
-                    control
+                    dialer
                         .dial(
                             addr,
                             TargetProtocol::Single(
@@ -112,7 +159,6 @@ impl NetworkGossip {
                             ),
                         )
                         .await
-                        .unwrap()
                 }
             });
         }
@@ -198,6 +244,78 @@ impl Gossip for NetworkGossip {
         );
         Ok(())
     }
+
+    async fn push_and_announce<M, A>(
+        &self,
+        mut cx: Context,
+        origin: Option<usize>,
+        endpoint: &str,
+        msg: M,
+        announce_endpoint: &str,
+        announce: A,
+        priority: Priority,
+    ) -> ProtocolResult<()>
+    where
+        M: MessageCodec,
+        A: MessageCodec,
+    {
+        let sessions = self.peer_manager.with_registry(|online| {
+            online
+                .peers
+                .values()
+                .map(|info| info.session_id)
+                .filter(|sid| origin.map(|id| SessionId::new(id) != *sid).unwrap_or(true))
+                .collect::<Vec<_>>()
+        });
+        let (push_sessions, announce_sessions) = split_sqrt_sessions(sessions);
+
+        if !push_sessions.is_empty() {
+            let push_count = push_sessions.len();
+            let msg = self.package_message(cx.clone(), endpoint, msg).await?;
+            let ctx = cx.set_url(endpoint.to_owned());
+            self.send_to_sessions(
+                ctx,
+                TargetSession::Multi(Box::new(push_sessions.into_iter())),
+                msg,
+                priority,
+            )
+            .await?;
+            common_apm::metrics::network::on_network_message_sent_multi_target(
+                endpoint,
+                push_count as f64,
+            );
+        }
+
+        if !announce_sessions.is_empty() {
+            let announce_count = announce_sessions.len();
+            let announce = self
+                .package_message(cx.clone(), announce_endpoint, announce)
+                .await?;
+            let ctx = cx.set_url(announce_endpoint.to_owned());
+            self.send_to_sessions(
+                ctx,
+                TargetSession::Multi(Box::new(announce_sessions.into_iter())),
+                announce,
+                priority,
+            )
+            .await?;
+            common_apm::metrics::network::on_network_message_sent_multi_target(
+                announce_endpoint,
+                announce_count as f64,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Split the sessions into a random subset of sqrt(n) sessions to push full
+/// messages and the rest to announce.
+fn split_sqrt_sessions(mut sessions: Vec<SessionId>) -> (Vec<SessionId>, Vec<SessionId>) {
+    sessions.shuffle(&mut rand::thread_rng());
+    let push_count = (sessions.len() as f64).sqrt().ceil() as usize;
+    let announce_sessions = sessions.split_off(push_count);
+    (sessions, announce_sessions)
 }
 
 struct RandomGossip {
@@ -237,7 +355,27 @@ impl Iterator for RandomGossip {
 
 #[cfg(test)]
 mod test {
-    use super::RandomGossip;
+    use tentacle::SessionId;
+
+    use super::{split_sqrt_sessions, RandomGossip};
+
+    #[test]
+    fn test_split_sqrt_sessions() {
+        let sessions = |n: usize| (0..n).map(SessionId::new).collect::<Vec<_>>();
+
+        let (push, announce) = split_sqrt_sessions(sessions(50));
+        assert_eq!((push.len(), announce.len()), (8, 42));
+        assert!(push.iter().all(|sid| !announce.contains(sid)));
+
+        let (push, announce) = split_sqrt_sessions(sessions(4));
+        assert_eq!((push.len(), announce.len()), (2, 2));
+
+        let (push, announce) = split_sqrt_sessions(sessions(1));
+        assert_eq!((push.len(), announce.len()), (1, 0));
+
+        let (push, announce) = split_sqrt_sessions(sessions(0));
+        assert!(push.is_empty() && announce.is_empty());
+    }
 
     #[test]
     fn test_random_gossip() {
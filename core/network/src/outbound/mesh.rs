@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use tentacle::{
+    service::{ServiceAsyncControl, TargetSession},
+    SessionId,
+};
+
+use protocol::tokio::sync::mpsc::{self, error::TrySendError};
+use protocol::types::Bytes;
+
+use crate::peer_manager::PeerManager;
+use crate::protocols::SupportProtocols;
+
+struct MeshMessage {
+    sessions: Vec<SessionId>,
+    data:     Bytes,
+}
+
+/// Sender of the consensus mesh
+///
+/// The consensus messages to the validators wait in a queue of their own and
+/// go through the consensus protocol, whose substream has its own flow control
+/// window, so they are never stuck behind the transactions gossiped over the
+/// transmitter protocol. They are sent by the quick queue of the sessions,
+/// which the gossip of high priority leaves to them on the mesh sessions, so
+/// they are written before any gossip or sync message waiting on the same
+/// connection.
+#[derive(Clone)]
+pub struct ConsensusMesh {
+    queue: mpsc::Sender<MeshMessage>,
+}
+
+impl ConsensusMesh {
+    pub fn new(
+        transmitter: ServiceAsyncControl,
+        peer_manager: Arc<PeerManager>,
+        queue_size: usize,
+    ) -> (Self, MeshWorker) {
+        let (tx, rx) = mpsc::channel(queue_size.max(1));
+        let worker = MeshWorker {
+            queue: rx,
+            transmitter,
+            peer_manager,
+        };
+
+        (ConsensusMesh { queue: tx }, worker)
+    }
+
+    /// Queue the message to the mesh sessions. The sessions are given back if
+    /// the queue is full or the worker is not running.
+    pub fn try_send(&self, sessions: Vec<SessionId>, data: Bytes) -> Result<(), Vec<SessionId>> {
+        match self.queue.try_send(MeshMessage { sessions, data }) {
+            Ok(()) => {
+                common_apm::metrics::network::NETWORK_CONSENSUS_MESH_QUEUE_LEN.inc();
+                Ok(())
+            }
+            Err(TrySendError::Full(msg)) | Err(TrySendError::Closed(msg)) => Err(msg.sessions),
+        }
+    }
+}
+
+pub struct MeshWorker {
+    queue:        mpsc::Receiver<MeshMessage>,
+    transmitter:  ServiceAsyncControl,
+    peer_manager: Arc<PeerManager>,
+}
+
+impl MeshWorker {
+    pub async fn run(mut self) {
+        while let Some(msg) = self.queue.recv().await {
+            common_apm::metrics::network::NETWORK_CONSENSUS_MESH_QUEUE_LEN.dec();
+
+            let compressor = self.peer_manager.compressor();
+            let groups = self
+                .peer_manager
+                .sessions_by_compression(TargetSession::Multi(Box::new(msg.sessions.into_iter())));
+
+            for (compression, sessions) in groups {
+                let data = compressor.compress(msg.data.clone(), compression);
+                if let Err(err) = self
+                    .transmitter
+                    .quick_filter_broadcast(
+                        TargetSession::Multi(Box::new(sessions.into_iter())),
+                        SupportProtocols::Consensus.protocol_id(),
+                        data,
+                    )
+                    .await
+                {
+                    log::warn!("send consensus mesh message failed: {:?}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use tentacle::{
+        builder::ServiceBuilder, multiaddr::Multiaddr, secio::PeerId, service::SessionType,
+        service::TargetSession, SessionId,
+    };
+
+    use protocol::{tokio, types::Bytes};
+
+    use super::ConsensusMesh;
+    use crate::config::NetworkConfig;
+    use crate::peer_manager::{PeerInfo, PeerManager};
+    use crate::protocols::SupportProtocols;
+
+    fn peer_manager() -> Arc<PeerManager> {
+        let dir = PathBuf::from("./free-space/network/mesh");
+        Arc::new(PeerManager::new(Arc::new(
+            NetworkConfig::new().peer_store_dir(dir),
+        )))
+    }
+
+    fn connect(peer_manager: &PeerManager, session_id: usize, consensus: bool) -> PeerId {
+        let peer_id = PeerId::random();
+        let addr = format!(
+            "/ip4/1.1.1.{}/tcp/2337/p2p/{}",
+            session_id,
+            peer_id.to_base58()
+        )
+        .parse::<Multiaddr>()
+        .unwrap();
+        let ty = SessionType::Outbound;
+        peer_manager.register(PeerInfo::with_session(addr, SessionId::new(session_id), ty));
+        if consensus {
+            peer_manager.open_protocol(&peer_id, SupportProtocols::Consensus.protocol_id());
+        }
+        peer_id
+    }
+
+    fn mesh_sessions(peer_manager: &PeerManager) -> Vec<SessionId> {
+        let (mut mesh, _) = peer_manager.split_mesh_sessions(TargetSession::All);
+        mesh.sort();
+        mesh
+    }
+
+    #[test]
+    fn test_mesh_membership() {
+        let peer_manager = peer_manager();
+        let validator_a = connect(&peer_manager, 1, true);
+        let validator_b = connect(&peer_manager, 2, true);
+        let _full_node = connect(&peer_manager, 3, false);
+        let validator_c = connect(&peer_manager, 4, false);
+
+        // The validators join the mesh when they are tagged
+        assert!(mesh_sessions(&peer_manager).is_empty());
+        peer_manager.consensus_list.write().extend([
+            validator_a,
+            validator_b.clone(),
+            validator_c.clone(),
+        ]);
+        assert_eq!(mesh_sessions(&peer_manager), vec![
+            SessionId::new(1),
+            SessionId::new(2)
+        ]);
+
+        // And after they open the consensus protocol
+        peer_manager.open_protocol(&validator_c, SupportProtocols::Consensus.protocol_id());
+        assert_eq!(mesh_sessions(&peer_manager).len(), 3);
+
+        // The validators of the last epoch and the closed links leave the mesh
+        peer_manager.consensus_list.write().remove(&validator_b);
+        peer_manager.close_protocol(&validator_c, &SupportProtocols::Consensus.protocol_id());
+        assert_eq!(mesh_sessions(&peer_manager), vec![SessionId::new(1)]);
+
+        // Only the target sessions are split
+        let target = TargetSession::Multi(Box::new(vec![SessionId::new(1)].into_iter()));
+        let (mesh, others) = peer_manager.split_mesh_sessions(target);
+        assert_eq!((mesh, others), (vec![SessionId::new(1)], vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_mesh_queue_overflow() {
+        let service = ServiceBuilder::new().build(());
+        let (mesh, worker) = ConsensusMesh::new(service.control().clone(), peer_manager(), 2);
+        let sessions = vec![SessionId::new(1), SessionId::new(2)];
+
+        // The worker is not running, so the queue is full after two messages
+        for _ in 0..2 {
+            assert!(mesh.try_send(sessions.clone(), Bytes::from("vote")).is_ok());
+        }
+        assert_eq!(
+            mesh.try_send(sessions.clone(), Bytes::from("vote")),
+            Err(sessions.clone())
+        );
+
+        // The sessions are given back when the worker is gone
+        drop(worker);
+        assert_eq!(
+            mesh.try_send(sessions.clone(), Bytes::from("vote")),
+            Err(sessions)
+        );
+    }
+}
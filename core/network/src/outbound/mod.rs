@@ -1,4 +1,6 @@
 mod gossip;
+mod mesh;
 mod rpc;
 pub use gossip::NetworkGossip;
+pub use mesh::{ConsensusMesh, MeshWorker};
 pub use rpc::NetworkRpc;
@@ -1,15 +1,16 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use tentacle::{bytes::Bytes, service::ServiceAsyncControl, SessionId};
 
 use common_apm::tracing::AxonTracer;
 use common_apm::Instant;
-use protocol::traits::{Context, MessageCodec, Priority, Rpc};
+use protocol::traits::{Context, MessageCodec, Priority, Rpc, TrustFeedback};
 use protocol::{async_trait, tokio, ProtocolResult};
 
 use crate::endpoint::Endpoint;
 use crate::error::{ErrorKind, NetworkError};
 use crate::message::{Headers, NetworkMessage};
+use crate::peer_manager::PeerManager;
 use crate::reactor::MessageRouter;
 use crate::rpc::RpcResponse;
 use crate::traits::NetworkContext;
@@ -18,13 +19,31 @@ use crate::traits::NetworkContext;
 pub struct NetworkRpc {
     transmitter:       ServiceAsyncControl,
     pub(crate) router: MessageRouter,
+    peer_manager:      Arc<PeerManager>,
 }
 
 impl NetworkRpc {
-    pub fn new(transmitter: ServiceAsyncControl, router: MessageRouter) -> Self {
+    pub fn new(
+        transmitter: ServiceAsyncControl,
+        router: MessageRouter,
+        peer_manager: Arc<PeerManager>,
+    ) -> Self {
         NetworkRpc {
             transmitter,
             router,
+            peer_manager,
+        }
+    }
+
+    /// Report the result of the rpc call to the trust metric of the callee.
+    fn report(&self, sid: SessionId, feedback: TrustFeedback) {
+        if let Some(peer_id) = self.peer_manager.peer_id_by_session(sid) {
+            if let Some(id) = self.peer_manager.report(&peer_id, feedback) {
+                let transmitter = self.transmitter.clone();
+                tokio::spawn(async move {
+                    let _ignore = transmitter.disconnect(id).await;
+                });
+            }
         }
     }
 
@@ -35,6 +54,9 @@ impl NetworkRpc {
         data: Bytes,
         priority: Priority,
     ) -> Result<(), NetworkError> {
+        let compression = self.peer_manager.session_compression(session_id);
+        let data = self.peer_manager.compressor().compress(data, compression);
+
         match priority {
             Priority::Normal => self
                 .transmitter
@@ -46,7 +68,7 @@ impl NetworkRpc {
                 )
                 .await
                 .unwrap(),
-            Priority::High => self
+            Priority::High | Priority::Consensus => self
                 .transmitter
                 .clone()
                 .send_message_to(
@@ -59,13 +81,11 @@ impl NetworkRpc {
         }
         Ok(())
     }
-}
 
-#[async_trait]
-impl Rpc for NetworkRpc {
-    async fn call<M, R>(
+    async fn call_session<M, R>(
         &self,
         mut cx: Context,
+        sid: SessionId,
         endpoint: &str,
         mut msg: M,
         priority: Priority,
@@ -75,7 +95,6 @@ impl Rpc for NetworkRpc {
         R: MessageCodec,
     {
         let endpoint = endpoint.parse::<Endpoint>()?;
-        let sid = cx.session_id()?;
         let rpc_map = &self.router.rpc_map;
         let rid = rpc_map.next_rpc_id();
         let connected_addr = cx.remote_connected_addr();
@@ -127,6 +146,7 @@ impl Rpc for NetworkRpc {
                     common_apm::metrics::network::NETWORK_PROTOCOL_TIME_HISTOGRAM_VEC_STATIC
                         .rpc
                         .observe(common_apm::metrics::duration_to_sec(inst.elapsed()));
+                    self.report(sid, TrustFeedback::Good);
 
                     Ok(R::decode_msg(v)?)
                 }
@@ -143,10 +163,47 @@ impl Rpc for NetworkRpc {
                 common_apm::metrics::network::NETWORK_RPC_RESULT_COUNT_VEC_STATIC
                     .timeout
                     .inc();
+                self.report(sid, TrustFeedback::Bad("rpc call timeout".to_owned()));
                 Err(NetworkError::from(ErrorKind::RpcTimeout(connected_addr)).into())
             }
         }
     }
+}
+
+#[async_trait]
+impl Rpc for NetworkRpc {
+    async fn call<M, R>(
+        &self,
+        cx: Context,
+        endpoint: &str,
+        msg: M,
+        priority: Priority,
+    ) -> ProtocolResult<R>
+    where
+        M: MessageCodec,
+        R: MessageCodec,
+    {
+        let sid = cx.session_id()?;
+        self.call_session(cx, sid, endpoint, msg, priority).await
+    }
+
+    async fn call_trusted<M, R>(
+        &self,
+        cx: Context,
+        endpoint: &str,
+        msg: M,
+        priority: Priority,
+    ) -> ProtocolResult<R>
+    where
+        M: MessageCodec,
+        R: MessageCodec,
+    {
+        let sid = match cx.session_id() {
+            Ok(sid) => sid,
+            Err(err) => self.peer_manager.trusted_session().ok_or(err)?,
+        };
+        self.call_session(cx, sid, endpoint, msg, priority).await
+    }
 
     async fn response<M>(
         &self,
@@ -1,21 +1,44 @@
 use parking_lot::{Mutex, RwLock};
 use std::{
     collections::{HashMap, HashSet},
+    net::SocketAddr,
     sync::Arc,
 };
 use tentacle::{
-    multiaddr::Multiaddr, secio::PeerId, utils::extract_peer_id, ProtocolId, SessionId,
+    multiaddr::{Multiaddr, Protocol},
+    secio::PeerId,
+    service::{SessionType, TargetSession},
+    utils::{extract_peer_id, multiaddr_to_socketaddr},
+    ProtocolId, SessionId,
 };
 
+use protocol::traits::TrustFeedback;
+
 pub use self::{
-    peer_store::{AddrInfo, PeerStore},
+    observed::ObservedAddrs,
+    peer_store::{AddrInfo, Group, OfflinePeerStore, PeerStore, Target},
     registry::{Online, PeerInfo},
+    trust_metric::TrustMetricConfig,
 };
+use crate::compress::{Compression, Compressor};
 use crate::config::NetworkConfig;
+use crate::protocols::SupportProtocols;
 use crate::PeerIdExt;
 
+mod observed;
 mod peer_store;
 mod registry;
+mod trust_metric;
+
+/// The decision on a new session under the connection limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionLimit {
+    Accept,
+    /// Accept the new session after disconnecting the given one
+    Evict(SessionId),
+    /// Reject the new session for the reason
+    Reject(&'static str),
+}
 
 pub struct PeerManager {
     online:           RwLock<Online>,
@@ -23,9 +46,15 @@ pub struct PeerManager {
     bootstraps:       HashMap<PeerId, Multiaddr>,
     chain_id:         Mutex<String>,
     pub public_addrs: RwLock<HashSet<Multiaddr>>,
+    observed_addrs:   RwLock<ObservedAddrs>,
+    mapped_addr:      RwLock<Option<Multiaddr>>,
+    gateway_addr:     RwLock<Option<Multiaddr>>,
+    noise_peers:      Mutex<HashMap<Multiaddr, PeerId>>,
     config:           Arc<NetworkConfig>,
+    compressor:       Arc<Compressor>,
 
     pub consensus_list: RwLock<HashSet<PeerId>>,
+    pub allowlist:      RwLock<HashSet<PeerId>>,
 }
 
 impl PeerManager {
@@ -41,14 +70,23 @@ impl PeerManager {
             }
             b
         };
+        let mut peer_store = PeerStore::load_from_dir_or_default(config.peer_store_path.clone());
+        peer_store.set_trust_config(TrustMetricConfig::new(
+            config.peer_trust_interval,
+            config.peer_trust_max_history,
+        ));
         PeerManager {
             online: RwLock::new(Online::default()),
-            peer_store: RwLock::new(PeerStore::load_from_dir_or_default(
-                config.peer_store_path.clone(),
-            )),
+            peer_store: RwLock::new(peer_store),
             chain_id: Mutex::new(String::new()),
             bootstraps,
             public_addrs: RwLock::new(HashSet::new()),
+            observed_addrs: RwLock::new(ObservedAddrs::default()),
+            mapped_addr: RwLock::new(None),
+            gateway_addr: RwLock::new(None),
+            noise_peers: Mutex::new(HashMap::new()),
+            allowlist: RwLock::new(config.allowlist.iter().cloned().collect()),
+            compressor: Arc::new(Compressor::new(config.zstd_dict.as_deref())),
             config,
             consensus_list: RwLock::new(HashSet::new()),
         }
@@ -152,6 +190,7 @@ impl PeerManager {
             self.with_peer_store_mut(|peer_store| peer_store.remove_disconnected_peer(&peer.addr));
 
             let peer_id = extract_peer_id(&peer.addr).unwrap();
+            self.observed_addrs.write().remove(&peer_id);
             common_apm::metrics::network::NETWORK_PEER_ID_DISCONNECTED_COUNT_VEC
                 .with_label_values(&[&peer_id.to_string()])
                 .inc();
@@ -236,11 +275,237 @@ impl PeerManager {
         let peer_id = extract_peer_id(addr).unwrap();
         self.bootstraps.contains_key(&peer_id)
             || self.consensus_list.read().contains(&peer_id)
+            || self.allowlist.read().contains(&peer_id)
             || self.with_peer_store(|peer_store| !peer_store.is_addr_banned(addr))
     }
 
+    /// Check the connection limits for a new session. The consensus peers and
+    /// the always allowed peers are exempt from the limits. When the inbound
+    /// slots are full, an inbound peer of the largest network group may be
+    /// evicted, so that a single host or network can't take up all the
+    /// slots.
+    pub fn check_session_limits(&self, addr: &Multiaddr, ty: SessionType) -> SessionLimit {
+        let peer_id = extract_peer_id(addr).unwrap();
+        if self.is_limit_exempt(&peer_id) {
+            return SessionLimit::Accept;
+        }
+
+        if self.with_peer_store(|peer_store| peer_store.is_addr_banned(addr)) {
+            return SessionLimit::Reject("banned");
+        }
+
+        let online = self.online.read();
+        // The loopback addresses are excluded for the local test networks
+        if let Some(ip) = multiaddr_to_socketaddr(addr)
+            .map(|socket_addr| socket_addr.ip())
+            .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+        {
+            let same_ip = online
+                .peers
+                .values()
+                .filter(|info| {
+                    multiaddr_to_socketaddr(&info.addr).map(|socket_addr| socket_addr.ip())
+                        == Some(ip)
+                })
+                .count();
+            if same_ip >= self.config.same_ip_conn_limit {
+                return SessionLimit::Reject("same_ip_limit");
+            }
+        }
+
+        let status = online.connection_status();
+        let max_outbound = self
+            .config
+            .max_connections
+            .saturating_sub(self.config.inbound_conn_limit);
+        let full = status.total >= self.config.max_connections;
+        match ty {
+            SessionType::Outbound if full || status.outbound >= max_outbound => {
+                SessionLimit::Reject("outbound_limit")
+            }
+            SessionType::Inbound if full || status.inbound >= self.config.inbound_conn_limit => {
+                self.evict_inbound(&online, addr)
+                    .map(SessionLimit::Evict)
+                    .unwrap_or(SessionLimit::Reject("inbound_limit"))
+            }
+            _ => SessionLimit::Accept,
+        }
+    }
+
+    fn is_limit_exempt(&self, peer_id: &PeerId) -> bool {
+        self.consensus_list.read().contains(peer_id) || self.allowlist.read().contains(peer_id)
+    }
+
+    /// Pick the inbound peer of the lowest trust score in the network group
+    /// with the most inbound peers. Nothing is evicted unless the group is
+    /// still no smaller than the group of the new peer after the eviction.
+    fn evict_inbound(&self, online: &Online, addr: &Multiaddr) -> Option<SessionId> {
+        let mut groups: HashMap<Group, Vec<(&PeerId, &PeerInfo)>> = HashMap::new();
+        for (peer_id, info) in online.peers.iter() {
+            if info.session_type.is_inbound() && !self.is_limit_exempt(peer_id) {
+                groups
+                    .entry(Group::from(&info.addr))
+                    .or_default()
+                    .push((peer_id, info));
+            }
+        }
+
+        let new_group_len = groups.get(&Group::from(addr)).map_or(0, Vec::len);
+        let (_, peers) = groups.into_iter().max_by_key(|(_, peers)| peers.len())?;
+        if peers.len() <= new_group_len + 1 {
+            return None;
+        }
+
+        let peer_store = self.peer_store.read();
+        peers
+            .into_iter()
+            .min_by_key(|(peer_id, _)| peer_store.trust_score(peer_id))
+            .map(|(_, info)| info.session_id)
+    }
+
+    /// Whether the peer may connect to us. In the allowlist only mode, only
+    /// the peers in the allowlist or the consensus list are allowed, so the
+    /// verifiers of the current epoch are always accepted.
+    pub fn is_allowed(&self, peer_id: &PeerId) -> bool {
+        !self.config.allowlist_only
+            || self.allowlist.read().contains(peer_id)
+            || self.consensus_list.read().contains(peer_id)
+    }
+
+    /// Replace the allowlist, and return the sessions of the connected peers
+    /// which are no longer allowed.
+    pub fn set_allowlist(&self, mut peer_ids: HashSet<PeerId>) -> Vec<SessionId> {
+        std::mem::swap(&mut *self.allowlist.write(), &mut peer_ids);
+        self.disallowed_sessions()
+    }
+
+    /// The sessions of the connected peers which are not allowed.
+    pub fn disallowed_sessions(&self) -> Vec<SessionId> {
+        if !self.config.allowlist_only {
+            return Vec::new();
+        }
+
+        self.online
+            .read()
+            .peers
+            .iter()
+            .filter(|(peer_id, _)| !self.is_allowed(peer_id))
+            .map(|(_, info)| info.session_id)
+            .collect()
+    }
+
+    /// The sessions of the connected peers which are banned.
+    pub fn banned_sessions(&self) -> Vec<SessionId> {
+        let sessions = self
+            .online
+            .read()
+            .peers
+            .values()
+            .map(|info| (info.session_id, info.addr.clone()))
+            .collect::<Vec<_>>();
+
+        self.with_peer_store(|peer_store| {
+            sessions
+                .into_iter()
+                .filter(|(_, addr)| peer_store.is_addr_banned(addr))
+                .map(|(session_id, _)| session_id)
+                .collect()
+        })
+    }
+
+    /// The addresses sent to the peers on identify, the announce addresses
+    /// from config replace all the others.
     pub fn local_listen_addrs(&self) -> Vec<Multiaddr> {
-        self.public_addrs.read().iter().cloned().collect()
+        if !self.config.announce_addrs.is_empty() {
+            return self.config.announce_addrs.clone();
+        }
+
+        let mut addrs = self.public_addrs.read().clone();
+        addrs.extend(self.announce_addrs());
+        addrs.into_iter().collect()
+    }
+
+    /// The public addresses announced through discovery: the ones from config,
+    /// or else the port mapped address and the observed address voted by the
+    /// majority of the peers.
+    pub fn announce_addrs(&self) -> Vec<Multiaddr> {
+        if !self.config.announce_addrs.is_empty() {
+            return self.config.announce_addrs.clone();
+        }
+
+        let mut addrs = Vec::new();
+        if let Some(addr) = self.mapped_addr.read().clone() {
+            addrs.push(addr);
+        }
+        if let Some(addr) = self.observed_addrs.read().majority() {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+
+        addrs
+    }
+
+    /// Count the address the peer sees us from, with the port replaced by
+    /// the listen port, as the outbound sessions come from random ports.
+    pub fn vote_observed_addr(&self, peer_id: PeerId, voter_addr: &Multiaddr, observed: Multiaddr) {
+        let listen_port = match multiaddr_to_socketaddr(&self.config.default_listen) {
+            Some(socket_addr) => socket_addr.port(),
+            None => return,
+        };
+
+        let observed = observed
+            .iter()
+            .filter(|proto| !matches!(proto, Protocol::P2P(_)))
+            .map(|proto| match proto {
+                Protocol::Tcp(_) => Protocol::Tcp(listen_port),
+                value => value,
+            })
+            .chain(std::iter::once(Protocol::P2P(
+                self.local_peer_id().as_bytes().to_vec().into(),
+            )))
+            .collect::<Multiaddr>();
+
+        self.observed_addrs
+            .write()
+            .vote(peer_id, voter_addr, observed);
+    }
+
+    pub fn set_mapped_addr(&self, addr: Option<SocketAddr>) {
+        *self.mapped_addr.write() = addr.map(|socket_addr| {
+            let mut addr = Multiaddr::from(socket_addr.ip());
+            addr.push(Protocol::Tcp(socket_addr.port()));
+            addr.push(Protocol::P2P(
+                self.local_peer_id().as_bytes().to_vec().into(),
+            ));
+            addr
+        });
+    }
+
+    /// The Noise gateway listens in place of tentacle, so its address is not
+    /// in the listens of the protocol contexts
+    pub fn set_gateway_addr(&self, addr: Multiaddr) {
+        *self.gateway_addr.write() = Some(addr);
+    }
+
+    pub fn gateway_addrs(&self) -> Vec<Multiaddr> {
+        self.gateway_addr.read().iter().cloned().collect()
+    }
+
+    /// Keep the peer id authenticated by Noise of an inbound connection, until
+    /// the session on it is opened and checked by `take_noise_peer`
+    pub fn expect_noise_peer(&self, remote_addr: Multiaddr, peer_id: PeerId) {
+        self.noise_peers.lock().insert(remote_addr, peer_id);
+    }
+
+    /// Take the peer id authenticated by Noise of the session address, whose
+    /// peer id is removed to match the remote address
+    pub fn take_noise_peer(&self, session_addr: &Multiaddr) -> Option<PeerId> {
+        let remote_addr = session_addr
+            .iter()
+            .filter(|proto| !matches!(proto, Protocol::P2P(_)))
+            .collect::<Multiaddr>();
+        self.noise_peers.lock().remove(&remote_addr)
     }
 
     pub(crate) fn public_addrs(&self, count: usize) -> Vec<Multiaddr> {
@@ -256,7 +521,8 @@ impl PeerManager {
         if let Some(info) = self.online.read().peers.get(peer_id) {
             self.peer_store
                 .write()
-                .ban_addr(&info.addr, timeout, ban_reason)
+                .ban_addr(&info.addr, timeout, ban_reason);
+            return Some(info.session_id);
         }
         None
     }
@@ -274,4 +540,383 @@ impl PeerManager {
             self.peer_store.write().ban_addr(&addr, timeout, ban_reason)
         }
     }
+
+    /// Record the feedback in the trust metric of the peer. The peer is
+    /// banned for the fatal ban duration on a fatal feedback, and for the
+    /// soft ban duration when its trust score falls below the ban score.
+    /// Consensus peers are never soft banned. Return the session to
+    /// disconnect if the peer is banned.
+    pub fn report(&self, peer_id: &PeerId, feedback: TrustFeedback) -> Option<SessionId> {
+        let result = self.with_peer_store_mut(|peer_store| peer_store.report(peer_id, &feedback));
+
+        match feedback {
+            TrustFeedback::Fatal(reason) => {
+                log::warn!("peer {:?} fatal ban: {}", peer_id, reason);
+                self.ban_id(
+                    peer_id,
+                    self.config.peer_fatal_ban.as_millis() as u64,
+                    reason,
+                )
+            }
+            TrustFeedback::Worse(reason) | TrustFeedback::Bad(reason)
+                if result.is_banned() && !self.consensus_list.read().contains(peer_id) =>
+            {
+                log::info!("peer {:?} soft ban: {}", peer_id, reason);
+                self.ban_id(
+                    peer_id,
+                    self.config.peer_soft_ban.as_millis() as u64,
+                    reason,
+                )
+            }
+            _ => None,
+        }
+    }
+
+    pub fn trust_score(&self, peer_id: &PeerId) -> u8 {
+        self.with_peer_store(|peer_store| peer_store.trust_score(peer_id)) as u8
+    }
+
+    /// The session of the connected peer with the highest trust score which
+    /// opens the transmitter protocol
+    pub fn trusted_session(&self) -> Option<SessionId> {
+        let online = self.online.read();
+        let peer_store = self.peer_store.read();
+        let transmitter = SupportProtocols::Transmitter.protocol_id();
+
+        online
+            .peers
+            .iter()
+            .filter(|(_, info)| info.is_protocol_opened(&transmitter))
+            .max_by_key(|(peer_id, _)| peer_store.trust_score(peer_id))
+            .map(|(_, info)| info.session_id)
+    }
+
+    pub fn peer_id_by_session(&self, session_id: SessionId) -> Option<PeerId> {
+        self.online
+            .read()
+            .peers
+            .iter()
+            .find(|(_, info)| info.session_id == session_id)
+            .map(|(peer_id, _)| peer_id.clone())
+    }
+
+    pub fn compressor(&self) -> Arc<Compressor> {
+        Arc::clone(&self.compressor)
+    }
+
+    pub fn set_compression(&self, peer_id: &PeerId, compression: Compression) {
+        if let Some(info) = self.online.write().peers.get_mut(peer_id) {
+            info.compression = compression;
+        }
+    }
+
+    pub fn session_compression(&self, session_id: SessionId) -> Compression {
+        self.online
+            .read()
+            .peers
+            .values()
+            .find(|info| info.session_id == session_id)
+            .map(|info| info.compression)
+            .unwrap_or_default()
+    }
+
+    /// Group the sessions of the target by the compression negotiated with
+    /// them, the unknown sessions are sent in snappy
+    pub fn sessions_by_compression(
+        &self,
+        target: TargetSession,
+    ) -> HashMap<Compression, Vec<SessionId>> {
+        let online = self.online.read();
+        let compressions = online
+            .peers
+            .values()
+            .map(|info| (info.session_id, info.compression))
+            .collect::<HashMap<_, _>>();
+
+        let mut groups: HashMap<Compression, Vec<SessionId>> = HashMap::new();
+        for id in online.target_sessions(target) {
+            let compression = compressions.get(&id).cloned().unwrap_or_default();
+            groups.entry(compression).or_default().push(id);
+        }
+        groups
+    }
+
+    /// Split the sessions of the target into the ones of the consensus mesh,
+    /// which are the validators opening the consensus protocol, and the others
+    pub fn split_mesh_sessions(&self, target: TargetSession) -> (Vec<SessionId>, Vec<SessionId>) {
+        let online = self.online.read();
+        let consensus_list = self.consensus_list.read();
+        let consensus = SupportProtocols::Consensus.protocol_id();
+        let mesh = online
+            .peers
+            .iter()
+            .filter(|(peer_id, info)| {
+                consensus_list.contains(peer_id) && info.is_protocol_opened(&consensus)
+            })
+            .map(|(_, info)| info.session_id)
+            .collect::<HashSet<_>>();
+
+        online
+            .target_sessions(target)
+            .into_iter()
+            .partition(|id| mesh.contains(id))
+    }
+
+    /// Export the health of the consensus mesh, the missing links and the
+    /// round trip time of the mesh sessions
+    pub fn observe_mesh_health(&self) {
+        let online = self.online.read();
+        let consensus_list = self.consensus_list.read();
+        let consensus = SupportProtocols::Consensus.protocol_id();
+        let local_peer_id = self.local_peer_id();
+
+        // The local node is not a validator of the current epoch
+        if !consensus_list.contains(&local_peer_id) {
+            common_apm::metrics::network::NETWORK_CONSENSUS_MESH_MISSING_LINKS.set(0);
+            common_apm::metrics::network::NETWORK_CONSENSUS_MESH_RTT_VEC.reset();
+            return;
+        }
+
+        let mut missing = 0;
+        common_apm::metrics::network::NETWORK_CONSENSUS_MESH_RTT_VEC.reset();
+        for peer_id in consensus_list.iter().filter(|id| **id != local_peer_id) {
+            match online.peers.get(peer_id) {
+                Some(info) if info.is_protocol_opened(&consensus) => {
+                    if let Some(rtt) = info.ping_rtt {
+                        common_apm::metrics::network::NETWORK_CONSENSUS_MESH_RTT_VEC
+                            .with_label_values(&[&peer_id.to_base58()])
+                            .set(rtt.as_millis() as i64);
+                    }
+                }
+                _ => missing += 1,
+            }
+        }
+        common_apm::metrics::network::NETWORK_CONSENSUS_MESH_MISSING_LINKS.set(missing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+    use tentacle::{multiaddr::Multiaddr, secio::PeerId, service::SessionType, SessionId};
+
+    use protocol::traits::TrustFeedback;
+
+    use super::{PeerInfo, PeerManager, SessionLimit};
+    use crate::config::NetworkConfig;
+
+    fn peer_manager(config: NetworkConfig) -> PeerManager {
+        let dir = PathBuf::from("./free-space/network/peer_manager");
+        PeerManager::new(Arc::new(config.peer_store_dir(dir)))
+    }
+
+    fn peer_addr(ip: &str, peer_id: &PeerId) -> Multiaddr {
+        format!("/ip4/{}/tcp/2337/p2p/{}", ip, peer_id.to_base58())
+            .parse()
+            .unwrap()
+    }
+
+    fn peer_info(ip: &str, peer_id: &PeerId, session_id: usize, ty: SessionType) -> PeerInfo {
+        PeerInfo::with_session(peer_addr(ip, peer_id), SessionId::new(session_id), ty)
+    }
+
+    /// A peer manager of 2 inbound slots and 2 outbound slots
+    fn limited_peer_manager() -> PeerManager {
+        let config = NetworkConfig::new()
+            .inbound_conn_limit(Some(2))
+            .unwrap()
+            .max_connections(Some(4))
+            .unwrap()
+            .same_ip_conn_limit(Some(2));
+        peer_manager(config)
+    }
+
+    fn check(peer_manager: &PeerManager, ip: &str, ty: SessionType) -> SessionLimit {
+        peer_manager.check_session_limits(&peer_addr(ip, &PeerId::random()), ty)
+    }
+
+    #[test]
+    fn test_allowlist_only() {
+        let (allowed, validator, stranger) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let config = NetworkConfig::new()
+            .allowlist(vec![allowed.to_base58()])
+            .unwrap()
+            .allowlist_only(Some(true));
+        let peer_manager = peer_manager(config);
+        peer_manager
+            .consensus_list
+            .write()
+            .insert(validator.clone());
+
+        assert!(peer_manager.is_allowed(&allowed));
+        assert!(peer_manager.is_allowed(&validator));
+        assert!(!peer_manager.is_allowed(&stranger));
+
+        // Out of the allowlist only mode, the allowlist only exempts the peers
+        // from the limits
+        let peer_manager = self::peer_manager(NetworkConfig::new());
+        assert!(peer_manager.is_allowed(&stranger));
+    }
+
+    #[test]
+    fn test_reload_allowlist() {
+        let (peer_a, peer_b, peer_c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let config = NetworkConfig::new()
+            .allowlist(vec![peer_a.to_base58(), peer_b.to_base58()])
+            .unwrap()
+            .allowlist_only(Some(true));
+        let peer_manager = peer_manager(config);
+        peer_manager.register(peer_info("1.1.1.1", &peer_a, 1, SessionType::Inbound));
+        peer_manager.register(peer_info("2.2.2.2", &peer_b, 2, SessionType::Outbound));
+        assert!(peer_manager.disallowed_sessions().is_empty());
+
+        let allowlist = vec![peer_b.clone(), peer_c.clone()];
+        let sessions = peer_manager.set_allowlist(allowlist.into_iter().collect::<HashSet<_>>());
+        assert_eq!(sessions, vec![SessionId::new(1)]);
+        assert!(!peer_manager.is_allowed(&peer_a));
+        assert!(peer_manager.is_allowed(&peer_c));
+
+        // The connected validators stay whatever the allowlist is
+        peer_manager.consensus_list.write().insert(peer_b);
+        assert_eq!(peer_manager.set_allowlist(HashSet::new()), vec![
+            SessionId::new(1)
+        ]);
+    }
+
+    #[test]
+    fn test_same_ip_limit() {
+        let config = NetworkConfig::new()
+            .inbound_conn_limit(Some(2))
+            .unwrap()
+            .max_connections(Some(4))
+            .unwrap()
+            .same_ip_conn_limit(Some(1));
+        let peer_manager = peer_manager(config);
+        peer_manager.register(peer_info(
+            "1.1.1.1",
+            &PeerId::random(),
+            1,
+            SessionType::Inbound,
+        ));
+
+        assert_eq!(
+            check(&peer_manager, "1.1.1.1", SessionType::Outbound),
+            SessionLimit::Reject("same_ip_limit")
+        );
+        assert_eq!(
+            check(&peer_manager, "1.1.1.2", SessionType::Outbound),
+            SessionLimit::Accept
+        );
+
+        // The loopback addresses are not limited for the local test networks
+        peer_manager.register(peer_info(
+            "127.0.0.1",
+            &PeerId::random(),
+            2,
+            SessionType::Inbound,
+        ));
+        assert_eq!(
+            check(&peer_manager, "127.0.0.1", SessionType::Outbound),
+            SessionLimit::Accept
+        );
+    }
+
+    #[test]
+    fn test_inbound_and_outbound_limits() {
+        let peer_manager = limited_peer_manager();
+        peer_manager.register(peer_info(
+            "1.1.1.1",
+            &PeerId::random(),
+            1,
+            SessionType::Inbound,
+        ));
+        peer_manager.register(peer_info(
+            "2.2.2.2",
+            &PeerId::random(),
+            2,
+            SessionType::Inbound,
+        ));
+        peer_manager.register(peer_info(
+            "3.3.3.3",
+            &PeerId::random(),
+            3,
+            SessionType::Outbound,
+        ));
+
+        // No network group has more inbound peers than the others to evict
+        assert_eq!(
+            check(&peer_manager, "4.4.4.4", SessionType::Inbound),
+            SessionLimit::Reject("inbound_limit")
+        );
+        assert_eq!(
+            check(&peer_manager, "4.4.4.4", SessionType::Outbound),
+            SessionLimit::Accept
+        );
+
+        peer_manager.register(peer_info(
+            "4.4.4.4",
+            &PeerId::random(),
+            4,
+            SessionType::Outbound,
+        ));
+        assert_eq!(
+            check(&peer_manager, "5.5.5.5", SessionType::Outbound),
+            SessionLimit::Reject("outbound_limit")
+        );
+
+        // The consensus peers and the allowlisted peers are exempt
+        let (validator, allowed) = (PeerId::random(), PeerId::random());
+        peer_manager
+            .consensus_list
+            .write()
+            .insert(validator.clone());
+        peer_manager.allowlist.write().insert(allowed.clone());
+        for peer_id in [validator, allowed] {
+            for ty in [SessionType::Inbound, SessionType::Outbound] {
+                assert_eq!(
+                    peer_manager.check_session_limits(&peer_addr("5.5.5.5", &peer_id), ty),
+                    SessionLimit::Accept
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_subnet_eviction() {
+        let (peer_a, peer_b) = (PeerId::random(), PeerId::random());
+
+        for (distrusted, session_id) in [(&peer_a, 1), (&peer_b, 2)] {
+            let peer_manager = limited_peer_manager();
+            peer_manager.register(peer_info("1.1.1.1", &peer_a, 1, SessionType::Inbound));
+            peer_manager.register(peer_info("1.1.2.2", &peer_b, 2, SessionType::Inbound));
+            peer_manager.register(peer_info(
+                "2.2.2.2",
+                &PeerId::random(),
+                3,
+                SessionType::Outbound,
+            ));
+            peer_manager.report(distrusted, TrustFeedback::Bad("timeout".to_owned()));
+
+            // A peer of the same /16 network can't take the slot of another
+            assert_eq!(
+                check(&peer_manager, "1.1.3.3", SessionType::Inbound),
+                SessionLimit::Reject("inbound_limit")
+            );
+
+            // The less trusted peer of the largest network group is evicted for
+            // a peer of another group
+            assert_eq!(
+                check(&peer_manager, "3.3.3.3", SessionType::Inbound),
+                SessionLimit::Evict(SessionId::new(session_id))
+            );
+
+            // The exempt peers are never evicted
+            peer_manager.consensus_list.write().insert(peer_a.clone());
+            assert_eq!(
+                check(&peer_manager, "3.3.3.3", SessionType::Inbound),
+                SessionLimit::Reject("inbound_limit")
+            );
+        }
+    }
 }
@@ -0,0 +1,110 @@
+use std::collections::{HashMap, HashSet};
+
+use tentacle::{multiaddr::Multiaddr, secio::PeerId};
+
+use crate::peer_manager::Group;
+
+/// The fewest peers from distinct groups to agree on an observed address
+pub const MIN_OBSERVED_VOTES: usize = 3;
+
+/// Votes of the connected peers on the address they see us from
+///
+/// Each peer has one vote, and the peers of the same group count once, so
+/// that a few peers in one subnet can't decide the address we advertise. An
+/// address wins when it has at least `MIN_OBSERVED_VOTES` groups and more
+/// than half of all the voting groups.
+#[derive(Default)]
+pub struct ObservedAddrs {
+    votes: HashMap<PeerId, (Group, Multiaddr)>,
+}
+
+impl ObservedAddrs {
+    pub fn vote(&mut self, peer_id: PeerId, voter_addr: &Multiaddr, observed: Multiaddr) {
+        self.votes
+            .insert(peer_id, (Group::from(voter_addr), observed));
+    }
+
+    pub fn remove(&mut self, peer_id: &PeerId) {
+        self.votes.remove(peer_id);
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    pub fn majority(&self) -> Option<Multiaddr> {
+        let mut groups_by_addr: HashMap<&Multiaddr, HashSet<&Group>> = HashMap::new();
+        for (group, addr) in self.votes.values() {
+            groups_by_addr.entry(addr).or_default().insert(group);
+        }
+
+        let all_groups = self
+            .votes
+            .values()
+            .map(|(group, _)| group)
+            .collect::<HashSet<_>>()
+            .len();
+
+        groups_by_addr
+            .into_iter()
+            .map(|(addr, groups)| (addr, groups.len()))
+            .filter(|(_, count)| *count >= MIN_OBSERVED_VOTES && *count * 2 > all_groups)
+            .max_by_key(|(_, count)| *count)
+            .map(|(addr, _)| addr.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tentacle::{multiaddr::Multiaddr, secio::PeerId};
+
+    use super::ObservedAddrs;
+
+    fn voter(ip: &str) -> (PeerId, Multiaddr) {
+        (
+            PeerId::random(),
+            format!("/ip4/{}/tcp/2337", ip).parse().unwrap(),
+        )
+    }
+
+    fn observed(ip: &str) -> Multiaddr {
+        format!("/ip4/{}/tcp/2337", ip).parse().unwrap()
+    }
+
+    #[test]
+    fn test_majority() {
+        let mut addrs = ObservedAddrs::default();
+        for ip in ["1.1.1.1", "2.2.2.2"] {
+            let (peer_id, addr) = voter(ip);
+            addrs.vote(peer_id, &addr, observed("8.8.8.8"));
+        }
+        assert_eq!(addrs.majority(), None);
+
+        let (peer_id, addr) = voter("3.3.3.3");
+        addrs.vote(peer_id.clone(), &addr, observed("8.8.8.8"));
+        assert_eq!(addrs.majority(), Some(observed("8.8.8.8")));
+
+        // Half of the groups is not a majority
+        for ip in ["4.4.4.4", "5.5.5.5", "6.6.6.6"] {
+            let (peer_id, addr) = voter(ip);
+            addrs.vote(peer_id, &addr, observed("9.9.9.9"));
+        }
+        assert_eq!(addrs.majority(), None);
+
+        addrs.remove(&peer_id);
+        assert_eq!(addrs.majority(), Some(observed("9.9.9.9")));
+    }
+
+    #[test]
+    fn test_same_group_votes_once() {
+        let mut addrs = ObservedAddrs::default();
+        for ip in ["1.1.1.1", "1.1.2.2", "1.1.3.3", "2.2.2.2"] {
+            let (peer_id, addr) = voter(ip);
+            addrs.vote(peer_id, &addr, observed("8.8.8.8"));
+        }
+        assert_eq!(addrs.majority(), None);
+
+        // A peer changes its vote
+        let (peer_id, addr) = voter("3.3.3.3");
+        addrs.vote(peer_id.clone(), &addr, observed("9.9.9.9"));
+        addrs.vote(peer_id, &addr, observed("8.8.8.8"));
+        assert_eq!(addrs.majority(), Some(observed("8.8.8.8")));
+    }
+}
@@ -0,0 +1,397 @@
+//! Peer store operations for the operators, on the running node or on the
+//! files of a stopped one
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ipnetwork::IpNetwork;
+use parking_lot::Mutex;
+use tentacle::{
+    multiaddr::Multiaddr,
+    secio::PeerId,
+    utils::{extract_peer_id, multiaddr_to_socketaddr},
+};
+
+use protocol::traits::PeerStoreAdmin;
+use protocol::types::{BannedRecord, PeerAddrRecord, PeerStoreSnapshot};
+use protocol::ProtocolResult;
+
+use crate::{
+    error::NetworkError,
+    peer_manager::peer_store::{types::ip_to_network, PeerStore},
+    PeerIdExt,
+};
+
+/// The peers to operate on, parsed from the target given by the operators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Peer(PeerId),
+    Addr(Multiaddr),
+    Network(IpNetwork),
+}
+
+impl FromStr for Target {
+    type Err = NetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('/') {
+            let addr = s
+                .parse::<Multiaddr>()
+                .map_err(|_| NetworkError::InvalidTarget(s.to_owned()))?;
+            return Ok(Target::Addr(addr));
+        }
+
+        if let Ok(network) = s.parse::<IpNetwork>() {
+            return Ok(Target::Network(network));
+        }
+
+        PeerId::from_str_ext(s)
+            .map(Target::Peer)
+            .map_err(|_| NetworkError::InvalidTarget(s.to_owned()))
+    }
+}
+
+impl Target {
+    /// Whether the saved address is one of the target
+    pub fn matches(&self, addr: &Multiaddr) -> bool {
+        match self {
+            Target::Peer(peer_id) => extract_peer_id(addr).as_ref() == Some(peer_id),
+            Target::Addr(target) => match extract_peer_id(target) {
+                Some(_) => addr == target,
+                // Match all the peers on the address without peer id
+                None => addr.iter().zip(target.iter()).all(|(a, b)| a == b),
+            },
+            Target::Network(network) => multiaddr_to_socketaddr(addr)
+                .map(|socket_addr| network.contains(socket_addr.ip()))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl PeerStore {
+    pub fn snapshot(&self) -> PeerStoreSnapshot {
+        let mut addrs = self
+            .addr_manager()
+            .addrs_iter()
+            .map(|info| {
+                let peer_id = extract_peer_id(&info.addr);
+                let trust_score = peer_id
+                    .as_ref()
+                    .filter(|peer_id| self.trust_metrics().any(|(id, _)| id == *peer_id))
+                    .map(|peer_id| self.trust_score(peer_id));
+
+                PeerAddrRecord {
+                    addr: info.addr.to_string(),
+                    peer_id: peer_id.as_ref().map(PeerIdExt::to_string),
+                    score: info.score,
+                    trust_score,
+                    last_connected_at_ms: info.last_connected_at_ms,
+                    last_tried_at_ms: info.last_tried_at_ms,
+                    attempts_count: info.attempts_count,
+                }
+            })
+            .collect::<Vec<_>>();
+        addrs.sort_by(|a, b| {
+            b.last_connected_at_ms
+                .cmp(&a.last_connected_at_ms)
+                .then_with(|| a.addr.cmp(&b.addr))
+        });
+
+        let mut banned = self
+            .ban_list()
+            .get_banned_addrs()
+            .into_iter()
+            .map(|banned| BannedRecord {
+                address:       banned.address.to_string(),
+                ban_until_ms:  banned.ban_until,
+                created_at_ms: banned.created_at,
+                ban_reason:    banned.ban_reason,
+            })
+            .collect::<Vec<_>>();
+        banned.sort_by(|a, b| b.created_at_ms.cmp(&a.created_at_ms));
+
+        PeerStoreSnapshot { addrs, banned }
+    }
+
+    /// Ban the networks of the target, returns the count of the banned
+    /// networks
+    pub fn ban_target(&mut self, target: &Target, timeout_ms: u64, ban_reason: String) -> usize {
+        let networks = match target {
+            Target::Network(network) => vec![*network],
+            _ => {
+                let mut addrs = self.matched_addrs(target);
+                match target {
+                    Target::Addr(addr) => addrs.push(addr.clone()),
+                    Target::Peer(peer_id) => addrs.extend(self.connected_addr(peer_id).cloned()),
+                    Target::Network(_) => (),
+                }
+
+                let mut networks = addrs
+                    .iter()
+                    .filter_map(multiaddr_to_socketaddr)
+                    .map(|socket_addr| ip_to_network(socket_addr.ip()))
+                    .collect::<Vec<_>>();
+                networks.sort_unstable();
+                networks.dedup();
+                networks
+            }
+        };
+
+        for network in networks.iter() {
+            self.ban_network(*network, timeout_ms, ban_reason.clone());
+        }
+        let banned_addrs = self
+            .addr_manager()
+            .addrs_iter()
+            .filter(|info| self.is_addr_banned(&info.addr))
+            .map(|info| info.addr.clone())
+            .collect::<Vec<_>>();
+        for addr in banned_addrs.iter() {
+            self.mut_addr_manager().remove(addr);
+        }
+
+        networks.len()
+    }
+
+    /// Lift the bans inside the target network, returns the count of them
+    pub fn unban_target(&mut self, target: &Target) -> Result<usize, NetworkError> {
+        let network = match target {
+            Target::Network(network) => *network,
+            Target::Addr(addr) => multiaddr_to_socketaddr(addr)
+                .map(|socket_addr| ip_to_network(socket_addr.ip()))
+                .ok_or_else(|| NetworkError::InvalidTarget(addr.to_string()))?,
+            // The addresses of the banned peers are forgotten
+            Target::Peer(peer_id) => {
+                return Err(NetworkError::InvalidTarget(format!(
+                    "{}, unban by the address instead",
+                    peer_id.to_base58()
+                )))
+            }
+        };
+
+        let unbanned = self
+            .ban_list()
+            .get_banned_addrs()
+            .into_iter()
+            .filter(|banned| {
+                banned.address.prefix() >= network.prefix() && network.contains(banned.address.ip())
+            })
+            .collect::<Vec<_>>();
+        for banned in unbanned.iter() {
+            self.mut_ban_list().unban_network(&banned.address);
+        }
+
+        Ok(unbanned.len())
+    }
+
+    /// Remove the saved addresses of the target, and the trust metric of the
+    /// target peer, returns the count of the removed addresses
+    pub fn forget_target(&mut self, target: &Target) -> usize {
+        let addrs = self.matched_addrs(target);
+        for addr in addrs.iter() {
+            self.mut_addr_manager().remove(addr);
+        }
+        if let Target::Peer(peer_id) = target {
+            self.remove_trust_metric(peer_id);
+        }
+
+        addrs.len()
+    }
+
+    /// Save the addresses with peer id, returns the count of the saved ones
+    pub fn import_addrs(&mut self, addrs: Vec<String>) -> Result<usize, NetworkError> {
+        let mut count = 0;
+        for addr in addrs {
+            let multiaddr = addr
+                .parse::<Multiaddr>()
+                .map_err(|_| NetworkError::UnexpectedPeerAddr(addr.clone()))?;
+            if extract_peer_id(&multiaddr).is_none() {
+                return Err(NetworkError::UnexpectedPeerAddr(addr));
+            }
+            if self.is_addr_banned(&multiaddr) {
+                continue;
+            }
+
+            self.add_addr(multiaddr)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn matched_addrs(&self, target: &Target) -> Vec<Multiaddr> {
+        self.addr_manager()
+            .addrs_iter()
+            .filter(|info| target.matches(&info.addr))
+            .map(|info| info.addr.clone())
+            .collect()
+    }
+}
+
+/// The peer store of a stopped node, the changes are written back to the
+/// files right away
+pub struct OfflinePeerStore {
+    dir:   PathBuf,
+    store: Mutex<PeerStore>,
+}
+
+impl OfflinePeerStore {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Self {
+        OfflinePeerStore {
+            dir:   dir.as_ref().to_path_buf(),
+            store: Mutex::new(PeerStore::load_from_dir_or_default(dir)),
+        }
+    }
+
+    fn update<F, T>(&self, f: F) -> ProtocolResult<T>
+    where
+        F: FnOnce(&mut PeerStore) -> Result<T, NetworkError>,
+    {
+        let mut store = self.store.lock();
+        let ret = f(&mut *store)?;
+        store.dump_to_dir(&self.dir)?;
+        Ok(ret)
+    }
+}
+
+impl PeerStoreAdmin for OfflinePeerStore {
+    fn peer_store_snapshot(&self) -> ProtocolResult<PeerStoreSnapshot> {
+        Ok(self.store.lock().snapshot())
+    }
+
+    fn ban_peer(&self, target: &str, duration_secs: u64, reason: String) -> ProtocolResult<usize> {
+        let target = target.parse::<Target>()?;
+        self.update(|store| Ok(store.ban_target(&target, duration_secs * 1000, reason)))
+    }
+
+    fn unban_peer(&self, target: &str) -> ProtocolResult<usize> {
+        let target = target.parse::<Target>()?;
+        self.update(|store| store.unban_target(&target))
+    }
+
+    fn forget_peer(&self, target: &str) -> ProtocolResult<usize> {
+        let target = target.parse::<Target>()?;
+        self.update(|store| Ok(store.forget_target(&target)))
+    }
+
+    fn import_peers(&self, addrs: Vec<String>) -> ProtocolResult<usize> {
+        self.update(|store| store.import_addrs(addrs))
+    }
+
+    fn set_allowlist(&self, _peer_ids: Vec<String>) -> ProtocolResult<usize> {
+        Err(NetworkError::OfflineAllowlist.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use tentacle::{multiaddr::Multiaddr, secio::PeerId};
+
+    use protocol::{traits::PeerStoreAdmin, types::PeerStoreSnapshot};
+
+    use super::{OfflinePeerStore, Target};
+    use crate::peer_manager::peer_store::PeerStore;
+
+    fn peer_addr(ip: &str, peer_id: &PeerId) -> Multiaddr {
+        format!("/ip4/{}/tcp/2337/p2p/{}", ip, peer_id.to_base58())
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let peer_id = PeerId::random();
+
+        assert_eq!(
+            peer_id.to_base58().parse::<Target>().unwrap(),
+            Target::Peer(peer_id.clone())
+        );
+        assert_eq!(
+            "1.1.1.1".parse::<Target>().unwrap(),
+            Target::Network("1.1.1.1/32".parse().unwrap())
+        );
+        assert_eq!(
+            "10.0.0.0/8".parse::<Target>().unwrap(),
+            Target::Network("10.0.0.0/8".parse().unwrap())
+        );
+        assert_eq!(
+            "/ip4/1.1.1.1/tcp/2337".parse::<Target>().unwrap(),
+            Target::Addr("/ip4/1.1.1.1/tcp/2337".parse().unwrap())
+        );
+        assert!("axon".parse::<Target>().is_err());
+        assert!("/ip4/axon".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn test_ban_unban_and_forget() {
+        let (peer_a, peer_b) = (PeerId::random(), PeerId::random());
+        let mut store = PeerStore::default();
+        store.add_addr(peer_addr("1.1.1.1", &peer_a)).unwrap();
+        store.add_addr(peer_addr("1.1.2.2", &peer_a)).unwrap();
+        store.add_addr(peer_addr("2.2.2.2", &peer_b)).unwrap();
+
+        let banned = store.ban_target(&Target::Peer(peer_a), 60_000, "spam".to_owned());
+        assert_eq!(banned, 2);
+        let snapshot = store.snapshot();
+        assert_eq!(snapshot.addrs.len(), 1);
+        assert_eq!(snapshot.banned.len(), 2);
+        assert!(snapshot.banned.iter().all(|ban| ban.ban_reason == "spam"));
+
+        // Banned addresses are not imported again
+        let imported = store
+            .import_addrs(vec![peer_addr("1.1.1.1", &PeerId::random()).to_string()])
+            .unwrap();
+        assert_eq!(imported, 0);
+        assert!(store
+            .import_addrs(vec!["/ip4/3.3.3.3/tcp/2337".to_owned()])
+            .is_err());
+
+        let unbanned = store.unban_target(&"1.1.0.0/16".parse().unwrap()).unwrap();
+        assert_eq!(unbanned, 2);
+        assert!(store.snapshot().banned.is_empty());
+
+        let forgotten = store.forget_target(&"/ip4/2.2.2.2/tcp/2337".parse().unwrap());
+        assert_eq!(forgotten, 1);
+        assert!(store.snapshot().addrs.is_empty());
+    }
+
+    fn sorted(mut snapshot: PeerStoreSnapshot) -> PeerStoreSnapshot {
+        snapshot.addrs.sort_by_key(|record| record.addr.clone());
+        snapshot.banned.sort_by_key(|record| record.address.clone());
+        snapshot
+    }
+
+    #[test]
+    fn test_offline_dump_and_reload() {
+        let dir = PathBuf::from("./free-space/network/offline_peer_store");
+        let _ = fs::remove_dir_all(&dir);
+        let (peer_a, peer_b) = (PeerId::random(), PeerId::random());
+
+        let store = OfflinePeerStore::open(&dir);
+        let imported = store
+            .import_peers(vec![
+                peer_addr("1.1.1.1", &peer_a).to_string(),
+                peer_addr("2.2.2.2", &peer_b).to_string(),
+                peer_addr("3.3.3.3", &peer_b).to_string(),
+            ])
+            .unwrap();
+        assert_eq!(imported, 3);
+        assert_eq!(store.ban_peer("3.3.3.3", 60, "spam".to_owned()).unwrap(), 1);
+        let snapshot = sorted(store.peer_store_snapshot().unwrap());
+        assert_eq!(snapshot.addrs.len(), 2);
+        assert_eq!(snapshot.banned.len(), 1);
+
+        // Every change is written back to the files
+        let reloaded = OfflinePeerStore::open(&dir);
+        assert_eq!(sorted(reloaded.peer_store_snapshot().unwrap()), snapshot);
+
+        assert_eq!(reloaded.forget_peer(&peer_a.to_base58()).unwrap(), 1);
+        let reloaded = OfflinePeerStore::open(&dir);
+        let snapshot = reloaded.peer_store_snapshot().unwrap();
+        assert_eq!(snapshot.addrs.len(), 1);
+        assert_eq!(snapshot.banned.len(), 1);
+    }
+}
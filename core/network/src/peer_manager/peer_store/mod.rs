@@ -5,6 +5,7 @@ use std::net::IpAddr;
 use tentacle::{multiaddr::Multiaddr, utils::multiaddr_to_socketaddr};
 
 mod addr_manager;
+mod admin;
 mod ban_list;
 mod peer_store_db;
 mod peer_store_impl;
@@ -23,7 +24,11 @@ pub(crate) const DIAL_INTERVAL: u64 = 15 * 1000;
 const ADDR_MAX_RETRIES: u32 = 3;
 const ADDR_MAX_FAILURES: u32 = 10;
 
-pub use self::{peer_store_impl::PeerStore, types::AddrInfo};
+pub use self::{
+    admin::{OfflinePeerStore, Target},
+    peer_store_impl::PeerStore,
+    types::AddrInfo,
+};
 
 /// Alias score
 pub type Score = i32;
@@ -6,16 +6,21 @@ use crate::{
         types::{AddrInfo, BannedAddr},
         PeerStore,
     },
+    peer_manager::trust_metric::TrustMetric,
+    PeerIdExt,
 };
 use log::{debug, error};
+use std::collections::HashMap;
 use std::path::Path;
 use std::{
     fs::{copy, create_dir_all, remove_file, rename, File, OpenOptions},
     io::{Read, Write},
 };
+use tentacle::secio::PeerId;
 
 const DEFAULT_ADDR_MANAGER_DB: &str = "addr_manager.db";
 const DEFAULT_BAN_LIST_DB: &str = "ban_list.db";
+const DEFAULT_TRUST_METRIC_DB: &str = "trust_metric.db";
 
 impl Manager {
     /// Load address list from disk
@@ -65,11 +70,37 @@ impl BanList {
     }
 }
 
+/// Load trust metrics from disk, the peer ids are encoded in base58
+fn load_trust_metrics<R: Read>(r: R) -> Result<HashMap<PeerId, TrustMetric>, NetworkError> {
+    let metrics: HashMap<String, TrustMetric> =
+        serde_json::from_reader(r).map_err(|e| NetworkError::SerdeError(Box::new(e)))?;
+    metrics
+        .into_iter()
+        .map(|(peer_id, metric)| Ok((PeerId::from_str_ext(peer_id)?, metric)))
+        .collect()
+}
+
 impl PeerStore {
+    /// Dump trust metrics to disk
+    fn dump_trust_metrics(&self, mut file: File) -> Result<(), NetworkError> {
+        let metrics: HashMap<_, _> = self
+            .trust_metrics()
+            .map(|(peer_id, metric)| (PeerIdExt::to_string(peer_id), metric))
+            .collect();
+        debug!("dump {} trust metrics", metrics.len());
+        // empty file and dump the json string to it
+        file.set_len(0)
+            .and_then(|_| serde_json::to_string(&metrics).map_err(Into::into))
+            .and_then(|json_string| file.write_all(json_string.as_bytes()))
+            .and_then(|_| file.sync_all())
+            .map_err(Into::into)
+    }
+
     /// Init peer store from disk
     pub fn load_from_dir_or_default<P: AsRef<Path>>(path: P) -> Self {
         let addr_manager_path = path.as_ref().join(DEFAULT_ADDR_MANAGER_DB);
         let ban_list_path = path.as_ref().join(DEFAULT_BAN_LIST_DB);
+        let trust_metric_path = path.as_ref().join(DEFAULT_TRUST_METRIC_DB);
 
         let addr_manager = File::open(&addr_manager_path)
             .map_err(|err| {
@@ -105,7 +136,24 @@ impl PeerStore {
             })
             .unwrap_or_default();
 
-        PeerStore::new(addr_manager, ban_list)
+        let trust_metrics = File::open(&trust_metric_path)
+            .map_err(|err| {
+                debug!(
+                    "Failed to open TrustMetric db, file: {:?}, error: {:?}",
+                    trust_metric_path, err
+                )
+            })
+            .and_then(|file| {
+                load_trust_metrics(file).map_err(|err| {
+                    error!(
+                        "Failed to load TrustMetric db, file: {:?}, error: {:?}",
+                        trust_metric_path, err
+                    )
+                })
+            })
+            .unwrap_or_default();
+
+        PeerStore::new(addr_manager, ban_list, trust_metrics)
     }
 
     /// Dump all info to disk
@@ -117,6 +165,7 @@ impl PeerStore {
         create_dir_all(&tmp_dir)?;
         let tmp_addr_manager = tmp_dir.join(DEFAULT_ADDR_MANAGER_DB);
         let tmp_ban_list = tmp_dir.join(DEFAULT_BAN_LIST_DB);
+        let tmp_trust_metric = tmp_dir.join(DEFAULT_TRUST_METRIC_DB);
         self.addr_manager().dump(
             OpenOptions::new()
                 .write(true)
@@ -136,6 +185,17 @@ impl PeerStore {
                 .open(&tmp_ban_list)?,
         )?;
         move_file(tmp_ban_list, path.as_ref().join(DEFAULT_BAN_LIST_DB))?;
+        self.dump_trust_metrics(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(false)
+                .open(&tmp_trust_metric)?,
+        )?;
+        move_file(
+            tmp_trust_metric,
+            path.as_ref().join(DEFAULT_TRUST_METRIC_DB),
+        )?;
         Ok(())
     }
 }
@@ -8,6 +8,7 @@ use tentacle::{
 };
 
 use protocol::rand::{self, prelude::IteratorRandom};
+use protocol::traits::TrustFeedback;
 
 use crate::{
     error::ErrorKind,
@@ -15,9 +16,10 @@ use crate::{
         addr_manager::Manager,
         ban_list::BanList,
         types::{ip_to_network, AddrInfo, BannedAddr, PeerInfo},
-        Group, Multiaddr, PeerScoreConfig, ReportResult, Status, ADDR_COUNT_LIMIT, ADDR_TIMEOUT_MS,
-        ADDR_TRY_TIMEOUT_MS, DIAL_INTERVAL,
+        Group, Multiaddr, PeerScoreConfig, ReportResult, Score, Status, ADDR_COUNT_LIMIT,
+        ADDR_TIMEOUT_MS, ADDR_TRY_TIMEOUT_MS, DIAL_INTERVAL,
     },
+    peer_manager::trust_metric::{TrustMetric, TrustMetricConfig, WORSE_BAD_EVENTS},
 };
 
 /// Peer store
@@ -27,23 +29,36 @@ use crate::{
 /// days  |
 #[derive(Default)]
 pub struct PeerStore {
-    addr_manager: Manager,
-    ban_list:     BanList,
-    peers:        HashMap<PeerId, PeerInfo>,
-    score_config: PeerScoreConfig,
+    addr_manager:  Manager,
+    ban_list:      BanList,
+    peers:         HashMap<PeerId, PeerInfo>,
+    score_config:  PeerScoreConfig,
+    trust_metrics: HashMap<PeerId, TrustMetric>,
+    trust_config:  TrustMetricConfig,
 }
 
 impl PeerStore {
-    /// New with address list and ban list
-    pub fn new(addr_manager: Manager, ban_list: BanList) -> Self {
+    /// New with address list, ban list and trust metrics
+    pub fn new(
+        addr_manager: Manager,
+        ban_list: BanList,
+        trust_metrics: HashMap<PeerId, TrustMetric>,
+    ) -> Self {
         PeerStore {
             addr_manager,
             ban_list,
             peers: Default::default(),
             score_config: Default::default(),
+            trust_metrics,
+            trust_config: Default::default(),
         }
     }
 
+    /// Set the interval and the max history of the trust metrics
+    pub fn set_trust_config(&mut self, config: TrustMetricConfig) {
+        self.trust_config = config;
+    }
+
     /// this method will assume peer is connected, which implies address is
     /// "verified".
     pub fn add_connected_peer(&mut self, addr: Multiaddr, session_type: SessionType) {
@@ -73,7 +88,7 @@ impl PeerStore {
             return Ok(());
         }
         self.check_purge()?;
-        let score = self.score_config.default_score;
+        let score = self.addr_score(&addr);
         self.addr_manager.add(AddrInfo::new(addr, 0, score));
         Ok(())
     }
@@ -83,7 +98,7 @@ impl PeerStore {
         if self.ban_list.is_addr_banned(&addr) {
             return;
         }
-        let score = self.score_config.default_score;
+        let score = self.addr_score(&addr);
         self.addr_manager
             .add(AddrInfo::new(addr, faketime::unix_time_as_millis(), score));
     }
@@ -98,21 +113,68 @@ impl PeerStore {
         &mut self.addr_manager
     }
 
-    /// Report peer behaviours
-    pub fn report(&mut self, addr: &Multiaddr) -> ReportResult {
-        if let Some(peer_addr) = self.addr_manager.get_mut(addr) {
-            let score = peer_addr.score.saturating_add(0);
-            peer_addr.score = score;
-            if score < self.score_config.ban_score {
-                self.ban_addr(
-                    addr,
-                    self.score_config.ban_timeout_ms,
-                    "report behaviour".to_string(),
-                );
-                return ReportResult::Banned;
+    /// Report peer behaviours, record them in the trust metric of the peer
+    /// and return whether the trust score falls below the ban score. The
+    /// ban itself is left to the caller.
+    #[allow(clippy::mutable_key_type)]
+    pub fn report(&mut self, peer_id: &PeerId, feedback: &TrustFeedback) -> ReportResult {
+        let now_ms = faketime::unix_time_as_millis();
+        let config = self.trust_config;
+        if self.trust_metrics.len() >= ADDR_COUNT_LIMIT {
+            self.trust_metrics
+                .retain(|_, metric| !metric.is_stale(&config, now_ms));
+        }
+        let metric = self.trust_metrics.entry(peer_id.clone()).or_default();
+        match feedback {
+            TrustFeedback::Fatal(_) | TrustFeedback::Worse(_) => {
+                metric.bad_events(&config, WORSE_BAD_EVENTS, now_ms)
             }
+            TrustFeedback::Bad(_) => metric.bad_events(&config, 1, now_ms),
+            TrustFeedback::Good => metric.good_events(&config, 1, now_ms),
+            TrustFeedback::Neutral => return ReportResult::Ok,
         }
-        ReportResult::Ok
+
+        let score = metric.score(&config, now_ms);
+        let addrs = self.addr_manager.get_by_peer_id(peer_id, |_| true);
+        for addr in addrs.iter() {
+            if let Some(peer_addr) = self.addr_manager.get_mut(addr) {
+                peer_addr.score = score;
+            }
+        }
+
+        if score < self.score_config.ban_score {
+            ReportResult::Banned
+        } else {
+            ReportResult::Ok
+        }
+    }
+
+    /// Get the trust score of a peer, the peer without any report has the
+    /// default score
+    pub fn trust_score(&self, peer_id: &PeerId) -> Score {
+        self.trust_metrics
+            .get(peer_id)
+            .map(|metric| metric.score(&self.trust_config, faketime::unix_time_as_millis()))
+            .unwrap_or(self.score_config.default_score)
+    }
+
+    /// Get trust metrics which are not decayed away
+    pub fn trust_metrics(&self) -> impl Iterator<Item = (&PeerId, &TrustMetric)> {
+        let now_ms = faketime::unix_time_as_millis();
+        self.trust_metrics
+            .iter()
+            .filter(move |(_, metric)| !metric.is_stale(&self.trust_config, now_ms))
+    }
+
+    /// Remove the trust metric of a peer
+    pub fn remove_trust_metric(&mut self, peer_id: &PeerId) -> Option<TrustMetric> {
+        self.trust_metrics.remove(peer_id)
+    }
+
+    fn addr_score(&self, addr: &Multiaddr) -> Score {
+        extract_peer_id(addr)
+            .map(|peer_id| self.trust_score(&peer_id))
+            .unwrap_or(self.score_config.default_score)
     }
 
     /// Remove peer id
@@ -129,8 +191,13 @@ impl PeerStore {
         }
     }
 
+    /// Get the address of a connected peer
+    pub fn connected_addr(&self, peer_id: &PeerId) -> Option<&Multiaddr> {
+        self.peers.get(peer_id).map(|peer| &peer.connected_addr)
+    }
+
     /// Get peers for outbound connection, this method randomly return recently
-    /// connected peer addrs
+    /// connected peer addrs, the addrs of high-trust peers come first
     pub fn fetch_addrs_to_attempt(&mut self, count: usize) -> Vec<AddrInfo> {
         // Get info:
         // 1. Not already connected
@@ -140,15 +207,19 @@ impl PeerStore {
         let peers = &self.peers;
         let addr_expired_ms = now_ms.saturating_sub(ADDR_TRY_TIMEOUT_MS);
         // get addrs that can attempt.
-        self.addr_manager
-            .fetch_random(count, |peer_addr: &AddrInfo| {
+        let mut addrs = self
+            .addr_manager
+            .fetch_random(count * 2, |peer_addr: &AddrInfo| {
                 extract_peer_id(&peer_addr.addr)
                     .map(|peer_id| !peers.contains_key(&peer_id))
                     .unwrap_or_default()
                     && peer_addr.connected(|t| {
                         t > addr_expired_ms && t <= now_ms.saturating_sub(DIAL_INTERVAL)
                     })
-            })
+            });
+        addrs.sort_by_key(|peer_addr| std::cmp::Reverse(peer_addr.score));
+        addrs.truncate(count);
+        addrs
     }
 
     /// Get peers for feeler connection, this method randomly return peer addrs
@@ -1,12 +1,18 @@
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
+    time::Duration,
 };
 use tentacle::{
-    context::SessionContext, multiaddr::Multiaddr, secio::PeerId, service::SessionType, ProtocolId,
-    SessionId,
+    context::SessionContext,
+    multiaddr::Multiaddr,
+    secio::PeerId,
+    service::{SessionType, TargetSession},
+    ProtocolId, SessionId,
 };
 
+use crate::compress::Compression;
+
 pub struct PeerInfo {
     pub addr:         Multiaddr,
     pub session_id:   SessionId,
@@ -14,17 +20,27 @@ pub struct PeerInfo {
     pub session_type: SessionType,
     pub listens:      Vec<Multiaddr>,
     pub reuse:        bool,
+    /// The compression negotiated during identify
+    pub compression:  Compression,
+    /// The round trip time of the last ping
+    pub ping_rtt:     Option<Duration>,
 }
 
 impl PeerInfo {
     pub fn new(ctx: Arc<SessionContext>) -> Self {
+        PeerInfo::with_session(ctx.address.clone(), ctx.id, ctx.ty)
+    }
+
+    pub fn with_session(addr: Multiaddr, session_id: SessionId, session_type: SessionType) -> Self {
         PeerInfo {
-            addr:             ctx.address.clone(),
-            session_id:       ctx.id,
+            addr,
+            session_id,
             opened_protocols: Default::default(),
-            session_type:     ctx.ty,
-            listens:          Vec::new(),
-            reuse:            false,
+            session_type,
+            listens: Vec::new(),
+            reuse: false,
+            compression: Compression::default(),
+            ping_rtt: None,
         }
     }
 
@@ -35,6 +51,10 @@ impl PeerInfo {
     pub fn remove_protocol(&mut self, id: &ProtocolId) {
         self.opened_protocols.remove(id);
     }
+
+    pub fn is_protocol_opened(&self, id: &ProtocolId) -> bool {
+        self.opened_protocols.contains(id)
+    }
 }
 
 #[derive(Default)]
@@ -56,6 +76,17 @@ impl Online {
     pub fn is_feeler(&self, addr: &Multiaddr) -> bool {
         self.feeler.contains(addr)
     }
+
+    /// The online sessions of the target
+    pub fn target_sessions(&self, target: TargetSession) -> Vec<SessionId> {
+        let sessions = self.peers.values().map(|info| info.session_id);
+        match target {
+            TargetSession::All => sessions.collect(),
+            TargetSession::Single(id) => vec![id],
+            TargetSession::Multi(ids) => ids.collect(),
+            TargetSession::Filter(mut filter) => sessions.filter(|id| filter(id)).collect(),
+        }
+    }
 }
 
 pub struct ConnectionStatus {
@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    DEFAULT_PEER_TRUST_INTERVAL_DURATION, DEFAULT_PEER_TRUST_MAX_HISTORY_DURATION,
+};
+use crate::peer_manager::peer_store::Score;
+
+/// The highest trust score, which is given to the peers without bad events
+pub const MAX_TRUST_SCORE: Score = 100;
+/// The count of bad events recorded for a worse or fatal feedback
+pub const WORSE_BAD_EVENTS: u32 = 10;
+/// The weight left of the events as old as the max history
+const HISTORY_WEIGHT_FLOOR: f64 = 0.01;
+/// The good events assumed for every peer, so that a single bad event does
+/// not ruin the score of a peer without history
+const PRIOR_GOOD_EVENTS: f64 = 5.0;
+/// The metric whose decayed events are fewer than this is forgotten
+const STALE_EVENTS: f64 = 0.01;
+
+#[derive(Debug, Copy, Clone)]
+pub struct TrustMetricConfig {
+    interval_ms:    u64,
+    max_history_ms: u64,
+}
+
+impl TrustMetricConfig {
+    pub fn new(interval: Duration, max_history: Duration) -> Self {
+        TrustMetricConfig {
+            interval_ms:    (interval.as_millis() as u64).max(1),
+            max_history_ms: (max_history.as_millis() as u64).max(1),
+        }
+    }
+
+    /// The factor of the weight left after the given intervals
+    fn decay_factor(&self, intervals: u64) -> f64 {
+        let elapsed_ms = intervals.saturating_mul(self.interval_ms) as f64;
+        HISTORY_WEIGHT_FLOOR.powf(elapsed_ms / self.max_history_ms as f64)
+    }
+}
+
+impl Default for TrustMetricConfig {
+    fn default() -> Self {
+        TrustMetricConfig::new(
+            DEFAULT_PEER_TRUST_INTERVAL_DURATION,
+            DEFAULT_PEER_TRUST_MAX_HISTORY_DURATION,
+        )
+    }
+}
+
+/// Trust metric of a peer
+///
+/// The good and bad events reported for the peer are decayed at the end of
+/// every interval, so that the events as old as the max history keep only
+/// 1% of their weight. The score is the percentage of the good events, with
+/// a few good events assumed for everyone.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrustMetric {
+    good:          f64,
+    bad:           f64,
+    updated_at_ms: u64,
+}
+
+impl TrustMetric {
+    pub fn good_events(&mut self, config: &TrustMetricConfig, count: u32, now_ms: u64) {
+        self.decay(config, now_ms);
+        self.good += f64::from(count);
+    }
+
+    pub fn bad_events(&mut self, config: &TrustMetricConfig, count: u32, now_ms: u64) {
+        self.decay(config, now_ms);
+        self.bad += f64::from(count);
+    }
+
+    /// Trust score between 0 and `MAX_TRUST_SCORE` at the given time
+    pub fn score(&self, config: &TrustMetricConfig, now_ms: u64) -> Score {
+        let mut metric = *self;
+        metric.decay(config, now_ms);
+
+        let good = metric.good + PRIOR_GOOD_EVENTS;
+        let ratio = good / (good + metric.bad);
+        (ratio * f64::from(MAX_TRUST_SCORE)).round() as Score
+    }
+
+    /// Whether the events are decayed so much that the metric is no
+    /// different from a new one
+    pub fn is_stale(&self, config: &TrustMetricConfig, now_ms: u64) -> bool {
+        let mut metric = *self;
+        metric.decay(config, now_ms);
+        metric.good + metric.bad < STALE_EVENTS
+    }
+
+    fn decay(&mut self, config: &TrustMetricConfig, now_ms: u64) {
+        // The intervals begin with the first event
+        if self.good == 0.0 && self.bad == 0.0 {
+            self.updated_at_ms = now_ms;
+            return;
+        }
+
+        let intervals = now_ms.saturating_sub(self.updated_at_ms) / config.interval_ms;
+        if intervals == 0 {
+            return;
+        }
+
+        let factor = config.decay_factor(intervals);
+        self.good *= factor;
+        self.bad *= factor;
+        self.updated_at_ms += intervals * config.interval_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{TrustMetric, TrustMetricConfig, MAX_TRUST_SCORE, WORSE_BAD_EVENTS};
+
+    const NOW_MS: u64 = 1_000_000_000;
+
+    fn config() -> TrustMetricConfig {
+        TrustMetricConfig::new(Duration::from_secs(60), Duration::from_secs(600))
+    }
+
+    #[test]
+    fn test_score() {
+        let config = config();
+        let mut metric = TrustMetric::default();
+        assert_eq!(metric.score(&config, NOW_MS), MAX_TRUST_SCORE);
+
+        // A single bad event does not ruin a new peer, but a worse one does.
+        metric.bad_events(&config, 1, NOW_MS);
+        assert_eq!(metric.score(&config, NOW_MS), 83);
+        metric.bad_events(&config, WORSE_BAD_EVENTS, NOW_MS);
+        assert_eq!(metric.score(&config, NOW_MS), 31);
+
+        // The good events make up for the bad ones.
+        metric.good_events(&config, 50, NOW_MS);
+        assert_eq!(metric.score(&config, NOW_MS), 83);
+    }
+
+    #[test]
+    fn test_decay() {
+        let config = config();
+        let mut metric = TrustMetric::default();
+        metric.bad_events(&config, 100, NOW_MS);
+        assert_eq!(metric.score(&config, NOW_MS), 5);
+
+        // The events are decayed at the end of the intervals.
+        assert_eq!(metric.score(&config, NOW_MS + 59_999), 5);
+        assert!(metric.score(&config, NOW_MS + 60_000) > 5);
+
+        // The events as old as the max history keep 1% of their weight.
+        assert_eq!(metric.score(&config, NOW_MS + 600_000), 83);
+        assert!(!metric.is_stale(&config, NOW_MS + 600_000));
+        assert!(metric.is_stale(&config, NOW_MS + 3_000_000));
+
+        // The partial interval is kept for the next decay.
+        let mut decayed = metric;
+        decayed.good_events(&config, 0, NOW_MS + 90_000);
+        assert_eq!(
+            decayed.score(&config, NOW_MS + 120_000),
+            metric.score(&config, NOW_MS + 120_000)
+        );
+    }
+}
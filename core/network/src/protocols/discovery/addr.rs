@@ -45,6 +45,9 @@ pub trait AddressManager {
     fn misbehave(&mut self, session_id: SessionId, kind: Misbehavior) -> MisbehaveResult;
     fn get_random(&mut self, n: usize) -> Vec<Multiaddr>;
     fn consensus_list(&self) -> Vec<Multiaddr>;
+    fn announce_addrs(&self) -> Vec<Multiaddr>;
+    /// The listen addresses which are not in the listens of the contexts
+    fn gateway_addrs(&self) -> Vec<Multiaddr>;
     fn add_reuse_port_addr(&mut self, addr: Multiaddr);
 }
 
@@ -226,6 +226,7 @@ impl<M: AddressManager + Send + Sync> ServiceProtocol for DiscoveryProtocol<M> {
 
         // get announce list
         let mut announce_list = addr_mgr.consensus_list();
+        announce_list.extend(addr_mgr.announce_addrs());
         for (id, state) in self.sessions.iter_mut() {
             state.send_messages(context, *id).await;
 
@@ -387,6 +388,18 @@ impl AddressManager for DiscoveryAddressManager {
         self.peer_manager.connected_consensus_peer()
     }
 
+    fn announce_addrs(&self) -> Vec<Multiaddr> {
+        self.peer_manager
+            .announce_addrs()
+            .into_iter()
+            .filter(|addr| self.is_valid_addr(addr))
+            .collect()
+    }
+
+    fn gateway_addrs(&self) -> Vec<Multiaddr> {
+        self.peer_manager.gateway_addrs()
+    }
+
     fn add_reuse_port_addr(&mut self, addr: Multiaddr) {
         let peer_id = extract_peer_id(&addr).unwrap();
         self.peer_manager.with_registry_mut(|reg| {
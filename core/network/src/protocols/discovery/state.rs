@@ -44,9 +44,12 @@ impl SessionState {
     ) -> SessionState {
         let mut addr_known = AddrKnown::default();
         let remote_addr = if context.session.ty.is_outbound() {
+            // The Noise gateway listens in place of tentacle
+            let gateway_addrs = addr_manager.gateway_addrs();
             let port = context
                 .listens()
                 .iter()
+                .chain(gateway_addrs.iter())
                 .flat_map(|address| {
                     // Verify self is a public node first
                     // if not, try to make public network nodes broadcast hole punching information
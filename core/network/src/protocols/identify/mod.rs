@@ -150,6 +150,27 @@ impl IdentifyProtocol {
         }
     }
 
+    fn process_compressions(
+        &mut self,
+        context: &mut ProtocolContextMutRef,
+        compressions: &[u32],
+        zstd_dict_id: u32,
+    ) -> MisbehaveResult {
+        let compression = self
+            .peer_manager
+            .compressor()
+            .negotiate(compressions, zstd_dict_id);
+        trace!(
+            "negotiated compression {} with {}",
+            compression,
+            context.session.address
+        );
+
+        let peer_id = extract_peer_id(&context.session.address).unwrap();
+        self.peer_manager.set_compression(&peer_id, compression);
+        MisbehaveResult::Continue
+    }
+
     fn process_listens(
         &mut self,
         context: &mut ProtocolContextMutRef,
@@ -212,6 +233,12 @@ impl IdentifyProtocol {
             return MisbehaveResult::Continue;
         }
 
+        self.peer_manager.vote_observed_addr(
+            extract_peer_id(&session.address).unwrap(),
+            &session.address,
+            observed.clone(),
+        );
+
         if session.ty.is_inbound() {
             // The address already been discovered by other peer
             return MisbehaveResult::Continue;
@@ -303,9 +330,12 @@ impl ServiceProtocol for IdentifyProtocol {
             .cloned()
             .collect();
 
+        let compressor = self.peer_manager.compressor();
         let data = Identity::new(
             self.peer_manager.chain_id(),
             AddressInfo::new(listen_addrs, session.address.clone()),
+            compressor.supported(),
+            compressor.dict_id(),
         )
         .into_bytes();
 
@@ -342,6 +372,13 @@ impl ServiceProtocol for IdentifyProtocol {
             Some(message) => match message.addr_info {
                 Some(addr_info) => {
                     if self.check_duplicate(&mut context).is_disconnect()
+                        || self
+                            .process_compressions(
+                                &mut context,
+                                &message.compressions,
+                                message.zstd_dict_id,
+                            )
+                            .is_disconnect()
                         || self
                             .received_identify(&mut context, &message.chain_id)
                             .await
@@ -5,6 +5,8 @@ use tentacle::{
     multiaddr::Multiaddr,
 };
 
+use crate::compress::Compression;
+
 #[derive(Message)]
 pub struct AddressInfo {
     #[prost(bytes, repeated, tag = "1")]
@@ -35,16 +37,30 @@ impl AddressInfo {
 #[derive(Message)]
 pub struct Identity {
     #[prost(string, tag = "1")]
-    pub chain_id:  String,
+    pub chain_id:     String,
     #[prost(message, tag = "2")]
-    pub addr_info: Option<AddressInfo>,
+    pub addr_info:    Option<AddressInfo>,
+    /// Supported compression formats, absent for the peers only knowing
+    /// snappy
+    #[prost(uint32, repeated, tag = "3")]
+    pub compressions: Vec<u32>,
+    /// Id of the shared zstd dictionary, 0 for no dictionary
+    #[prost(uint32, tag = "4")]
+    pub zstd_dict_id: u32,
 }
 
 impl Identity {
-    pub fn new(chain_id: String, addr_info: AddressInfo) -> Self {
+    pub fn new(
+        chain_id: String,
+        addr_info: AddressInfo,
+        compressions: Vec<Compression>,
+        zstd_dict_id: u32,
+    ) -> Self {
         Identity {
             chain_id,
             addr_info: Some(addr_info),
+            compressions: compressions.into_iter().map(|c| c as u32).collect(),
+            zstd_dict_id,
         }
     }
 
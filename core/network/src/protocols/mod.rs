@@ -11,9 +11,12 @@ pub use self::{
     ping::PingHandler,
     transmitter::{protocol::ReceivedMessage, TransmitterProtocol},
 };
-use crate::compress::{compress, decompress};
+use std::sync::Arc;
+
+use crate::compress::{compress, Compressor};
 use tentacle::{
     builder::MetaBuilder,
+    bytes::BytesMut,
     service::{ProtocolHandle, ProtocolMeta},
     traits::ServiceProtocol,
     ProtocolId,
@@ -26,6 +29,7 @@ pub enum SupportProtocols {
     Discovery,
     Transmitter,
     Feeler,
+    Consensus,
 }
 
 impl SupportProtocols {
@@ -36,6 +40,7 @@ impl SupportProtocols {
             SupportProtocols::Discovery => 3,
             SupportProtocols::Transmitter => 4,
             SupportProtocols::Feeler => 5,
+            SupportProtocols::Consensus => 6,
         }
         .into()
     }
@@ -47,6 +52,7 @@ impl SupportProtocols {
             SupportProtocols::Discovery => "/axon/discovery",
             SupportProtocols::Transmitter => "/axon/transmitter",
             SupportProtocols::Feeler => "/axon/feeler",
+            SupportProtocols::Consensus => "/axon/consensus",
         }
         .to_owned()
     }
@@ -58,6 +64,7 @@ impl SupportProtocols {
             SupportProtocols::Discovery => vec!["1".to_owned()],
             SupportProtocols::Transmitter => vec!["1".to_owned()],
             SupportProtocols::Feeler => vec!["1".to_owned()],
+            SupportProtocols::Consensus => vec!["1".to_owned()],
         }
     }
 
@@ -68,6 +75,7 @@ impl SupportProtocols {
             SupportProtocols::Discovery => 512 * 1024,
             SupportProtocols::Transmitter => 4 * 1024 * 1024,
             SupportProtocols::Feeler => 1024,
+            SupportProtocols::Consensus => 4 * 1024 * 1024,
         }
     }
 
@@ -75,28 +83,37 @@ impl SupportProtocols {
         SH: FnOnce() -> ProtocolHandle<Box<dyn ServiceProtocol + Send + 'static + Unpin>>,
     >(
         self,
+        compressor: Arc<Compressor>,
         service_handle: SH,
     ) -> ProtocolMeta {
-        let meta_builder: MetaBuilder = self.into();
-        meta_builder.service_handle(service_handle).build()
+        self.meta_builder(compressor)
+            .service_handle(service_handle)
+            .build()
     }
-}
 
-impl From<SupportProtocols> for MetaBuilder {
-    fn from(p: SupportProtocols) -> Self {
-        let max_frame_length = p.max_frame_length();
-        MetaBuilder::default()
-            .id(p.protocol_id())
-            .support_versions(p.support_versions())
-            .name(move |_| p.name())
-            .before_send(compress)
-            .before_receive(|| Some(Box::new(decompress)))
+    fn meta_builder(self, compressor: Arc<Compressor>) -> MetaBuilder {
+        let max_frame_length = self.max_frame_length();
+        let builder = MetaBuilder::default()
+            .id(self.protocol_id())
+            .support_versions(self.support_versions())
+            .name(move |_| self.name())
+            .before_receive(move || {
+                let compressor = Arc::clone(&compressor);
+                Some(Box::new(move |data: BytesMut| compressor.decompress(data)))
+            })
             .codec(move || {
                 Box::new(
                     tokio_util::codec::length_delimited::Builder::new()
                         .max_frame_length(max_frame_length)
                         .new_codec(),
                 )
-            })
+            });
+
+        match self {
+            // The transmitter messages are compressed in the format negotiated
+            // with each peer before sending
+            SupportProtocols::Transmitter | SupportProtocols::Consensus => builder,
+            _ => builder.before_send(compress),
+        }
     }
 }
@@ -10,6 +10,7 @@ use tentacle::{
     async_trait,
     bytes::{Bytes, BytesMut},
     context::{ProtocolContext, ProtocolContextMutRef},
+    secio::PeerId,
     service::TargetSession,
     traits::ServiceProtocol,
     utils::extract_peer_id,
@@ -52,7 +53,14 @@ impl PingHandler {
 
     fn ping_received(&mut self, _id: SessionId) {}
 
-    fn pong_received(&mut self, _id: SessionId, _last_ping: Instant) {}
+    fn pong_received(&mut self, peer_id: &PeerId, last_ping: Instant) {
+        let rtt = last_ping.elapsed();
+        self.peer_manager.with_registry_mut(|reg| {
+            if let Some(info) = reg.peers.get_mut(peer_id) {
+                info.ping_rtt = Some(rtt);
+            }
+        });
+    }
 
     async fn ping_peers(&mut self, context: &ProtocolContext) {
         let now = Instant::now();
@@ -191,7 +199,8 @@ impl ServiceProtocol for PingHandler {
                         if (true, nonce) == (status.processing, status.nonce()) {
                             status.processing = false;
                             let last_ping_sent_at = status.last_ping_sent_at;
-                            self.pong_received(session.id, last_ping_sent_at);
+                            let peer_id = extract_peer_id(&session.address).unwrap();
+                            self.pong_received(&peer_id, last_ping_sent_at);
                             return;
                         }
                         if let Err(err) = context.disconnect(session.id).await {
@@ -12,20 +12,29 @@ use tentacle::{
 use self::protocol::ReceivedMessage;
 use crate::{
     peer_manager::PeerManager,
-    reactor::{MessageRouter, RemotePeer},
+    protocols::SupportProtocols,
+    reactor::{MessageRouter, RemotePeer, Routed},
     PeerIdExt,
 };
 
 pub mod protocol;
 
+/// Handler of the transmitter protocol, which also serves the consensus
+/// protocol, the direct mesh between the validators
 pub struct TransmitterProtocol {
+    protocol:     SupportProtocols,
     router:       MessageRouter,
     peer_manager: Arc<PeerManager>,
 }
 
 impl TransmitterProtocol {
-    pub fn new(router: MessageRouter, peer_manager: Arc<PeerManager>) -> Self {
+    pub fn new(
+        protocol: SupportProtocols,
+        router: MessageRouter,
+        peer_manager: Arc<PeerManager>,
+    ) -> Self {
         TransmitterProtocol {
+            protocol,
             router,
             peer_manager,
         }
@@ -45,16 +54,18 @@ impl ServiceProtocol for TransmitterProtocol {
         );
         self.peer_manager.open_protocol(
             &extract_peer_id(&context.session.address).unwrap(),
-            crate::protocols::SupportProtocols::Transmitter.protocol_id(),
+            self.protocol.protocol_id(),
         )
     }
 
     async fn disconnected(&mut self, context: ProtocolContextMutRef<'_>) {
         log::info!("{} close on {}", context.proto_id, context.session.id);
-        self.peer_manager.close_protocol(
-            &extract_peer_id(&context.session.address).unwrap(),
-            &crate::protocols::SupportProtocols::Transmitter.protocol_id(),
-        )
+        let peer_id = extract_peer_id(&context.session.address).unwrap();
+        if matches!(self.protocol, SupportProtocols::Transmitter) {
+            self.router.rate_limiter.remove_peer(&peer_id);
+        }
+        self.peer_manager
+            .close_protocol(&peer_id, &self.protocol.protocol_id())
     }
 
     async fn received(&mut self, context: ProtocolContextMutRef<'_>, data: Bytes) {
@@ -66,7 +77,23 @@ impl ServiceProtocol for TransmitterProtocol {
         };
 
         let remote_peer = RemotePeer::from_proto_context(&context);
+        if matches!(self.protocol, SupportProtocols::Consensus)
+            && !self
+                .peer_manager
+                .consensus_list
+                .read()
+                .contains(&remote_peer.peer_id)
+        {
+            log::debug!(
+                "drop consensus message from non-validator {:?}",
+                remote_peer
+            );
+            return;
+        }
+
         let peer_id = remote_peer.peer_id.to_string();
+        let peer_manager = Arc::clone(&self.peer_manager);
+        let control = context.control().clone();
 
         // let host = remote_peer.connected_addr.host.to_owned();
         let route_fut = self.router.route_message(remote_peer.clone(), recv_msg);
@@ -76,8 +103,26 @@ impl ServiceProtocol for TransmitterProtocol {
                 .with_label_values(&[&peer_id])
                 .inc();
 
-            if let Err(err) = route_fut.await {
-                log::warn!("route message from {:?} failed: {:?}", remote_peer, err);
+            match route_fut.await {
+                Ok(Routed { feedback, reply }) => {
+                    if let Some(data) = reply {
+                        let compression = peer_manager.session_compression(remote_peer.session_id);
+                        let data = peer_manager.compressor().compress(data, compression);
+                        let proto_id = SupportProtocols::Transmitter.protocol_id();
+                        if let Err(err) = control
+                            .send_message_to(remote_peer.session_id, proto_id, data)
+                            .await
+                        {
+                            log::debug!("reply to {:?} failed: {:?}", remote_peer, err);
+                        }
+                    }
+                    if let Some(id) = peer_manager.report(&remote_peer.peer_id, feedback) {
+                        let _ignore = control.disconnect(id).await;
+                    }
+                }
+                Err(err) => {
+                    log::warn!("route message from {:?} failed: {:?}", remote_peer, err);
+                }
             }
 
             common_apm::metrics::network::NETWORK_RECEIVED_MESSAGE_IN_PROCESSING_GUAGE.dec();
@@ -1,3 +1,4 @@
+mod rate_limit;
 mod router;
 mod rpc_map;
 
@@ -13,16 +14,18 @@ use crate::message::NetworkMessage;
 use crate::rpc::RpcResponse;
 use crate::traits::NetworkContext;
 
-pub(crate) use router::{MessageRouter, RemotePeer, RouterContext};
+pub use rate_limit::{RateLimit, RateLimiter};
+pub(crate) use router::{MessageRouter, RemotePeer, Routed, RouterContext};
 
 #[async_trait]
 pub trait Reactor: Send + Sync {
+    /// React to the message and return the feedback on the remote peer.
     async fn react(
         &self,
         context: RouterContext,
         endpoint: Endpoint,
         network_message: NetworkMessage,
-    ) -> ProtocolResult<()>;
+    ) -> ProtocolResult<TrustFeedback>;
 }
 
 pub struct MessageReactor<M: MessageCodec, H: MessageHandler<Message = M>> {
@@ -46,7 +49,7 @@ impl<M: MessageCodec, H: MessageHandler<Message = M>> Reactor for MessageReactor
         context: RouterContext,
         endpoint: Endpoint,
         mut network_message: NetworkMessage,
-    ) -> ProtocolResult<()> {
+    ) -> ProtocolResult<TrustFeedback> {
         let ctx = Context::new()
             .set_session_id(context.remote_peer.session_id)
             .set_remote_peer_id(context.remote_peer.peer_id.clone())
@@ -61,7 +64,7 @@ impl<M: MessageCodec, H: MessageHandler<Message = M>> Reactor for MessageReactor
         };
 
         let session_id = context.remote_peer.session_id;
-        let _feedback = match endpoint.scheme() {
+        let feedback = match endpoint.scheme() {
             EndpointScheme::Gossip => {
                 let raw_context = Bytes::from(network_message.content);
                 let content = M::decode_msg(raw_context)?;
@@ -100,7 +103,7 @@ impl<M: MessageCodec, H: MessageHandler<Message = M>> Reactor for MessageReactor
                         full_url,
                         context.remote_peer
                     );
-                    return Ok(());
+                    return Ok(TrustFeedback::Neutral);
                 }
 
                 let rpc_id = rpc_endpoint.rpc_id().value();
@@ -110,12 +113,11 @@ impl<M: MessageCodec, H: MessageHandler<Message = M>> Reactor for MessageReactor
                     log::warn!("network: reactor: {} rpc dropped on {}", session_id, end);
                 }
 
-                return Ok(());
+                return Ok(TrustFeedback::Neutral);
             }
         };
 
-        // context.report_feedback(feedback);
-        Ok(())
+        Ok(feedback)
     }
 }
 
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use tentacle::secio::PeerId;
+
+use crate::endpoint::Endpoint;
+
+/// The messages a peer may send to an endpoint, `rate` per second on average
+/// and `burst` at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub rate:  u32,
+    pub burst: u32,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens:     f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        TokenBucket {
+            tokens:     f64::from(limit.burst),
+            updated_at: now,
+        }
+    }
+
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(limit.rate)).min(f64::from(limit.burst));
+        self.updated_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Token buckets of every peer on the endpoints with limits, the endpoints
+/// without a limit are not limited
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    limits:  HashMap<Endpoint, RateLimit>,
+    buckets: Mutex<HashMap<(PeerId, Endpoint), TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: HashMap<Endpoint, RateLimit>) -> Self {
+        RateLimiter {
+            limits,
+            buckets: Default::default(),
+        }
+    }
+
+    /// Take a token for the message, returns false if the peer runs out of
+    /// tokens on the endpoint
+    pub fn check(&self, peer_id: &PeerId, endpoint: &Endpoint) -> bool {
+        self.check_at(peer_id, endpoint, Instant::now())
+    }
+
+    fn check_at(&self, peer_id: &PeerId, endpoint: &Endpoint, now: Instant) -> bool {
+        let limit = match self.limits.get(endpoint) {
+            Some(limit) => limit,
+            None => return true,
+        };
+
+        self.buckets
+            .lock()
+            .entry((peer_id.clone(), endpoint.clone()))
+            .or_insert_with(|| TokenBucket::new(limit, now))
+            .take(limit, now)
+    }
+
+    pub fn remove_peer(&self, peer_id: &PeerId) {
+        self.buckets.lock().retain(|(id, _), _| id != peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use tentacle::secio::PeerId;
+
+    use super::{RateLimit, RateLimiter};
+    use crate::endpoint::Endpoint;
+
+    #[test]
+    fn test_token_bucket() {
+        let limited = "/gossip/mempool/new_txs".parse::<Endpoint>().unwrap();
+        let unlimited = "/gossip/consensus/signed_vote".parse::<Endpoint>().unwrap();
+        let limiter = RateLimiter::new(HashMap::from([(limited.clone(), RateLimit {
+            rate:  2,
+            burst: 3,
+        })]));
+        let (peer_a, peer_b) = (PeerId::random(), PeerId::random());
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check_at(&peer_a, &limited, now));
+        }
+        assert!(!limiter.check_at(&peer_a, &limited, now));
+        assert!(limiter.check_at(&peer_b, &limited, now));
+        assert!(limiter.check_at(&peer_a, &unlimited, now));
+
+        // Refilled 2 tokens in a second, but no more than the burst later
+        let now = now + Duration::from_secs(1);
+        assert!(limiter.check_at(&peer_a, &limited, now));
+        assert!(limiter.check_at(&peer_a, &limited, now));
+        assert!(!limiter.check_at(&peer_a, &limited, now));
+
+        let now = now + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.check_at(&peer_a, &limited, now));
+        }
+        assert!(!limiter.check_at(&peer_a, &limited, now));
+
+        limiter.remove_peer(&peer_a);
+        assert!(limiter.check_at(&peer_a, &limited, now));
+    }
+}
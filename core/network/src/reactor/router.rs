@@ -4,18 +4,20 @@ use std::sync::Arc;
 
 use derive_more::Display;
 use parking_lot::RwLock;
-use protocol::traits::{MessageCodec, MessageHandler};
+use protocol::traits::{MessageCodec, MessageHandler, TrustFeedback};
 use protocol::{types::Bytes, ProtocolResult};
 use tentacle::context::ProtocolContextMutRef;
 use tentacle::secio::PeerId;
 use tentacle::SessionId;
 
 use crate::common::ConnectedAddr;
-use crate::endpoint::Endpoint;
+use crate::endpoint::{Endpoint, EndpointScheme, RPC_CALL_SCHEME, RPC_RESPONSE_SCHEME};
 use crate::error::{ErrorKind, NetworkError};
-use crate::message::NetworkMessage;
+use crate::message::{Headers, NetworkMessage};
 use crate::protocols::ReceivedMessage;
+use crate::rpc::RpcResponse;
 
+use super::rate_limit::RateLimiter;
 use super::rpc_map::RpcMap;
 use super::Reactor;
 
@@ -58,6 +60,23 @@ impl RouterContext {
     }
 }
 
+/// The feedback on the remote peer, with the message to reply if the router
+/// refuses an rpc call, so that the caller fails without waiting for timeout
+#[derive(Debug)]
+pub struct Routed {
+    pub feedback: TrustFeedback,
+    pub reply:    Option<Bytes>,
+}
+
+impl From<TrustFeedback> for Routed {
+    fn from(feedback: TrustFeedback) -> Self {
+        Routed {
+            feedback,
+            reply: None,
+        }
+    }
+}
+
 type ReactorMap = HashMap<Endpoint, Arc<Box<dyn Reactor>>>;
 
 #[derive(Clone)]
@@ -67,13 +86,17 @@ pub struct MessageRouter {
 
     // Rpc map
     pub(crate) rpc_map: Arc<RpcMap>,
+
+    // Per peer and endpoint message limits
+    pub(crate) rate_limiter: Arc<RateLimiter>,
 }
 
 impl MessageRouter {
-    pub fn new() -> Self {
+    pub fn new(rate_limiter: RateLimiter) -> Self {
         MessageRouter {
-            reactor_map: Default::default(),
-            rpc_map:     Arc::new(RpcMap::new()),
+            reactor_map:  Default::default(),
+            rpc_map:      Arc::new(RpcMap::new()),
+            rate_limiter: Arc::new(rate_limiter),
         }
     }
 
@@ -99,8 +122,9 @@ impl MessageRouter {
         &self,
         remote_peer: RemotePeer,
         recv_msg: ReceivedMessage,
-    ) -> impl Future<Output = ProtocolResult<()>> {
+    ) -> impl Future<Output = ProtocolResult<Routed>> {
         let reactor_map = Arc::clone(&self.reactor_map);
+        let rate_limiter = Arc::clone(&self.rate_limiter);
         let router_context = RouterContext::new(remote_peer, Arc::clone(&self.rpc_map));
         // let raw_data_size = recv_msg.data.len();
 
@@ -113,6 +137,19 @@ impl MessageRouter {
             //     .with_label_values(&["received", &endpoint.root()])
             //     .inc_by(raw_data_size as f64);
 
+            if !rate_limiter.check(&router_context.remote_peer.peer_id, &endpoint) {
+                log::debug!(
+                    "drop {} message from {}, rate limit exceeded",
+                    endpoint.root(),
+                    router_context.remote_peer
+                );
+                let reason = format!("rate limit exceeded on {}", endpoint.root());
+                return Ok(Routed {
+                    reply:    error_reply(&endpoint, reason.clone())?,
+                    feedback: TrustFeedback::Bad(reason),
+                });
+            }
+
             let reactor = {
                 let opt_reactor = reactor_map.read().get(&endpoint).cloned();
                 opt_reactor
@@ -125,7 +162,115 @@ impl MessageRouter {
             if let Err(err) = ret.as_ref() {
                 log::error!("process {:?} message failed: {:?}", endpoint, err);
             }
-            ret
+            ret.map(Routed::from)
         }
     }
 }
+
+/// The error response to an rpc call, sent to the response endpoint of the
+/// same path and rpc id
+fn error_reply(endpoint: &Endpoint, reason: String) -> Result<Option<Bytes>, NetworkError> {
+    if endpoint.scheme() != EndpointScheme::RpcCall {
+        return Ok(None);
+    }
+
+    let resp_endpoint = endpoint
+        .full_url()
+        .replacen(RPC_CALL_SCHEME, RPC_RESPONSE_SCHEME, 1)
+        .parse::<Endpoint>()?;
+    let content = RpcResponse::Error(reason).encode();
+    let msg = NetworkMessage::new(resp_endpoint, content, Headers::default()).encode()?;
+    Ok(Some(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tentacle::{multiaddr::Multiaddr, secio::PeerId, SessionId};
+
+    use protocol::traits::{Context, MessageHandler, TrustFeedback};
+    use protocol::{async_trait, tokio, types::Bytes};
+
+    use super::{MessageRouter, RemotePeer, Routed};
+    use crate::common::ConnectedAddr;
+    use crate::endpoint::Endpoint;
+    use crate::message::{Headers, NetworkMessage};
+    use crate::protocols::ReceivedMessage;
+    use crate::reactor::{RateLimit, RateLimiter};
+
+    const RPC_CALL: &str = "/rpc_call/test/ping";
+    const GOSSIP: &str = "/gossip/test/ping";
+
+    #[derive(Clone, Default)]
+    struct CountHandler(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl MessageHandler for CountHandler {
+        type Message = Bytes;
+
+        async fn process(&self, _: Context, _: Bytes) -> TrustFeedback {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            TrustFeedback::Neutral
+        }
+    }
+
+    fn router(handler: &CountHandler) -> MessageRouter {
+        let limit = RateLimit { rate: 1, burst: 1 };
+        let router = MessageRouter::new(RateLimiter::new(HashMap::from([
+            (RPC_CALL.parse::<Endpoint>().unwrap(), limit),
+            (GOSSIP.parse::<Endpoint>().unwrap(), limit),
+        ])));
+        router.register_reactor(RPC_CALL.parse().unwrap(), handler.clone());
+        router.register_reactor(GOSSIP.parse().unwrap(), handler.clone());
+        router
+    }
+
+    async fn route(router: &MessageRouter, peer: &RemotePeer, url: &str) -> Routed {
+        let endpoint = url.parse::<Endpoint>().unwrap();
+        let data = NetworkMessage::new(endpoint, Bytes::from("ping"), Headers::default())
+            .encode()
+            .unwrap();
+        let recv_msg = ReceivedMessage {
+            session_id: peer.session_id,
+            peer_id: peer.peer_id.clone(),
+            data,
+        };
+        router.route_message(peer.clone(), recv_msg).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_messages() {
+        let handler = CountHandler::default();
+        let router = router(&handler);
+        let addr = "/ip4/1.1.1.1/tcp/2337".parse::<Multiaddr>().unwrap();
+        let peer = RemotePeer {
+            session_id:     SessionId::new(1),
+            peer_id:        PeerId::random(),
+            connected_addr: ConnectedAddr::from(&addr),
+        };
+
+        let routed = route(&router, &peer, &format!("{}/7", RPC_CALL)).await;
+        assert!(matches!(routed.feedback, TrustFeedback::Neutral));
+        assert!(routed.reply.is_none());
+        let routed = route(&router, &peer, GOSSIP).await;
+        assert!(routed.reply.is_none());
+        assert_eq!(handler.0.load(Ordering::SeqCst), 2);
+
+        // The gossip over the limit is dropped
+        let routed = route(&router, &peer, GOSSIP).await;
+        assert!(matches!(routed.feedback, TrustFeedback::Bad(_)));
+        assert!(routed.reply.is_none());
+
+        // The rpc call over the limit is answered with an error
+        let routed = route(&router, &peer, &format!("{}/8", RPC_CALL)).await;
+        assert!(matches!(routed.feedback, TrustFeedback::Bad(_)));
+        let reply = NetworkMessage::decode(routed.reply.unwrap()).unwrap();
+        assert_eq!(reply.url, "/rpc_resp/test/ping/8");
+        assert_eq!(reply.content[0], 1);
+        assert!(String::from_utf8_lossy(&reply.content[1..]).contains("rate limit exceeded"));
+        assert_eq!(handler.0.load(Ordering::SeqCst), 2);
+    }
+}
@@ -7,12 +7,13 @@ use tentacle::{
     multiaddr::Multiaddr,
     secio::{error::SecioError, PeerId},
     service::{
-        ProtocolHandle, Service, ServiceAsyncControl, ServiceError, ServiceEvent, SessionType,
-        TargetProtocol, TcpSocket,
+        ProtocolHandle, Service, ServiceAsyncControl, ServiceError, ServiceEvent, TargetProtocol,
+        TcpSocket,
     },
     traits::ServiceHandle,
     utils::{extract_peer_id, is_reachable, multiaddr_to_socketaddr},
     yamux::Config as YamuxConfig,
+    SessionId,
 };
 
 use protocol::rand::{self, prelude::IteratorRandom};
@@ -20,24 +21,31 @@ use protocol::tokio::time::{Instant, MissedTickBehavior};
 use protocol::{
     async_trait, tokio,
     traits::{
-        Context, Gossip, MessageCodec, MessageHandler, Network, PeerTag, PeerTrust, Priority, Rpc,
-        TrustFeedback,
+        Context, Gossip, MessageCodec, MessageHandler, Network, PeerStoreAdmin, PeerTag, PeerTrust,
+        Priority, Rpc, TrustFeedback,
     },
-    types::Bytes,
+    types::{Bytes, PeerStoreSnapshot},
     ProtocolResult,
 };
 
 use crate::{
     config::NetworkConfig,
+    dns::{is_dns_addr, DnsSeeder, SystemResolver},
     endpoint::{Endpoint, EndpointScheme},
     error::NetworkError,
-    outbound::{NetworkGossip, NetworkRpc},
-    peer_manager::{AddrInfo, PeerInfo, PeerManager, PeerStore},
+    nat,
+    noise::{
+        bind_listener, Dialer, NoiseGateway, NoiseIdentity, NoiseTransport, TransportSecurity,
+    },
+    outbound::{ConsensusMesh, MeshWorker, NetworkGossip, NetworkRpc},
+    peer_manager::{AddrInfo, PeerInfo, PeerManager, PeerStore, SessionLimit, Target},
     protocols::{
         DiscoveryAddressManager, DiscoveryProtocol, Feeler, IdentifyProtocol, PingHandler,
         SupportProtocols, TransmitterProtocol,
     },
-    reactor::MessageRouter,
+    reactor::{MessageRouter, RateLimiter},
+    traits::NetworkContext,
+    PeerIdExt,
 };
 
 #[derive(Clone)]
@@ -83,6 +91,25 @@ impl Gossip for NetworkServiceHandle {
     {
         self.gossip.multicast(cx, end, peer_ids, msg, p).await
     }
+
+    async fn push_and_announce<M, A>(
+        &self,
+        cx: Context,
+        origin: Option<usize>,
+        end: &str,
+        msg: M,
+        announce_end: &str,
+        announce: A,
+        p: Priority,
+    ) -> ProtocolResult<()>
+    where
+        M: MessageCodec,
+        A: MessageCodec,
+    {
+        self.gossip
+            .push_and_announce(cx, origin, end, msg, announce_end, announce, p)
+            .await
+    }
 }
 
 #[async_trait]
@@ -95,6 +122,20 @@ impl Rpc for NetworkServiceHandle {
         self.rpc.call(cx, end, msg, p).await
     }
 
+    async fn call_trusted<M, R>(
+        &self,
+        cx: Context,
+        end: &str,
+        msg: M,
+        p: Priority,
+    ) -> ProtocolResult<R>
+    where
+        M: MessageCodec,
+        R: MessageCodec,
+    {
+        self.rpc.call_trusted(cx, end, msg, p).await
+    }
+
     async fn response<M>(
         &self,
         cx: Context,
@@ -109,8 +150,77 @@ impl Rpc for NetworkServiceHandle {
     }
 }
 
+impl NetworkServiceHandle {
+    /// Dial the validators out of the consensus mesh at once instead of
+    /// waiting for the next heartbeat
+    fn dial_consensus(&self) {
+        let peer_manager = &self.gossip.peer_manager;
+        let addrs = peer_manager
+            .unconnected_consensus_peer()
+            .into_iter()
+            .filter(|addr| peer_manager.with_registry_mut(|reg| reg.dialing.insert(addr.clone())))
+            .collect::<Vec<_>>();
+        if addrs.is_empty() {
+            return;
+        }
+
+        let dialer = self.gossip.dialer.clone();
+        tokio::spawn(async move {
+            for addr in addrs {
+                dialer
+                    .dial(
+                        addr,
+                        TargetProtocol::Single(SupportProtocols::Identify.protocol_id()),
+                    )
+                    .await;
+            }
+        });
+    }
+
+    fn disconnect(&self, sessions: Vec<SessionId>) {
+        if sessions.is_empty() {
+            return;
+        }
+
+        let sender = self.gossip.transmitter.clone();
+        tokio::spawn(async move {
+            for id in sessions {
+                let _ignore = sender.disconnect(id).await;
+            }
+        });
+    }
+
+    fn remote_peer_id(&self, ctx: &Context) -> Option<PeerId> {
+        ctx.remote_peer_id().ok().or_else(|| {
+            ctx.session_id()
+                .ok()
+                .and_then(|sid| self.gossip.peer_manager.peer_id_by_session(sid))
+        })
+    }
+}
+
 impl PeerTrust for NetworkServiceHandle {
-    fn report(&self, _ctx: Context, _feedback: TrustFeedback) {}
+    fn report(&self, ctx: Context, feedback: TrustFeedback) {
+        let peer_id = match self.remote_peer_id(&ctx) {
+            Some(peer_id) => peer_id,
+            None => {
+                log::debug!("no remote peer to report {:?}", feedback);
+                return;
+            }
+        };
+
+        if let Some(id) = self.gossip.peer_manager.report(&peer_id, feedback) {
+            let sender = self.gossip.transmitter.clone();
+            tokio::spawn(async move {
+                let _ignore = sender.disconnect(id).await;
+            });
+        }
+    }
+
+    fn trust_score(&self, ctx: &Context) -> Option<u8> {
+        self.remote_peer_id(ctx)
+            .map(|peer_id| self.gossip.peer_manager.trust_score(&peer_id))
+    }
 }
 
 impl Network for NetworkServiceHandle {
@@ -126,6 +236,9 @@ impl Network for NetworkServiceHandle {
                     .write()
                     .insert(peer_id);
             }
+            PeerTag::AlwaysAllow => {
+                self.gossip.peer_manager.allowlist.write().insert(peer_id);
+            }
             PeerTag::Ban { until } => {
                 if let Some(id) = self.gossip.peer_manager.ban_id(
                     &peer_id,
@@ -147,13 +260,20 @@ impl Network for NetworkServiceHandle {
         let peer_id =
             PeerId::from_bytes(peer_id.to_vec()).map_err(|_| NetworkError::InvalidPeerId)?;
 
-        if let PeerTag::Consensus = tag {
-            self.gossip
-                .peer_manager
-                .consensus_list
-                .write()
-                .remove(&peer_id);
+        match tag {
+            PeerTag::Consensus => {
+                self.gossip
+                    .peer_manager
+                    .consensus_list
+                    .write()
+                    .remove(&peer_id);
+            }
+            PeerTag::AlwaysAllow => {
+                self.gossip.peer_manager.allowlist.write().remove(&peer_id);
+            }
+            _ => return Ok(()),
         }
+        self.disconnect(self.gossip.peer_manager.disallowed_sessions());
 
         Ok(())
     }
@@ -172,6 +292,9 @@ impl Network for NetworkServiceHandle {
             &mut *self.gossip.peer_manager.consensus_list.write(),
             &mut peer_ids,
         );
+        // The verifiers of the previous epoch may be no longer allowed
+        self.disconnect(self.gossip.peer_manager.disallowed_sessions());
+        self.dial_consensus();
 
         Ok(())
     }
@@ -184,6 +307,64 @@ impl Network for NetworkServiceHandle {
     }
 }
 
+impl PeerStoreAdmin for NetworkServiceHandle {
+    fn peer_store_snapshot(&self) -> ProtocolResult<PeerStoreSnapshot> {
+        Ok(self
+            .gossip
+            .peer_manager
+            .with_peer_store(|peer_store| peer_store.snapshot()))
+    }
+
+    fn ban_peer(&self, target: &str, duration_secs: u64, reason: String) -> ProtocolResult<usize> {
+        let target = target.parse::<Target>()?;
+        let count = self.gossip.peer_manager.with_peer_store_mut(|peer_store| {
+            peer_store.ban_target(&target, duration_secs * 1000, reason)
+        });
+        self.disconnect(self.gossip.peer_manager.banned_sessions());
+
+        Ok(count)
+    }
+
+    fn unban_peer(&self, target: &str) -> ProtocolResult<usize> {
+        let target = target.parse::<Target>()?;
+        let count = self
+            .gossip
+            .peer_manager
+            .with_peer_store_mut(|peer_store| peer_store.unban_target(&target))?;
+
+        Ok(count)
+    }
+
+    fn forget_peer(&self, target: &str) -> ProtocolResult<usize> {
+        let target = target.parse::<Target>()?;
+        Ok(self
+            .gossip
+            .peer_manager
+            .with_peer_store_mut(|peer_store| peer_store.forget_target(&target)))
+    }
+
+    fn import_peers(&self, addrs: Vec<String>) -> ProtocolResult<usize> {
+        let count = self
+            .gossip
+            .peer_manager
+            .with_peer_store_mut(|peer_store| peer_store.import_addrs(addrs))?;
+
+        Ok(count)
+    }
+
+    fn set_allowlist(&self, peer_ids: Vec<String>) -> ProtocolResult<usize> {
+        let peer_ids = peer_ids
+            .iter()
+            .map(PeerId::from_str_ext)
+            .collect::<Result<HashSet<_>, _>>()?;
+        let sessions = self.gossip.peer_manager.set_allowlist(peer_ids);
+        let count = sessions.len();
+        self.disconnect(sessions);
+
+        Ok(count)
+    }
+}
+
 pub struct NetworkService {
     // Config backup
     config: Arc<NetworkConfig>,
@@ -195,8 +376,12 @@ pub struct NetworkService {
     // Core service
     peer_mgr_handle: Arc<PeerManager>,
     net:             Option<Service<ServiceHandler>>,
+    mesh_worker:     Option<MeshWorker>,
 
     control:            ServiceAsyncControl,
+    dialer:             Dialer,
+    // The listener of the Noise and the mixed modes
+    noise:              Option<Arc<NoiseTransport>>,
     try_identify_count: u8,
 }
 
@@ -208,7 +393,7 @@ impl NetworkService {
             peer_store: Arc::clone(&peer_manager),
             config:     Arc::clone(&config),
         };
-        let message_router = MessageRouter::new();
+        let message_router = MessageRouter::new(RateLimiter::new(config.rate_limits.clone()));
 
         let mut protocol_meta = Vec::new();
 
@@ -216,44 +401,73 @@ impl NetworkService {
         let ping_handle =
             PingHandler::new(config.ping_interval, config.ping_timeout, ping_peer_manager);
         let ping = SupportProtocols::Ping
-            .build_meta_with_service_handle(|| ProtocolHandle::Callback(Box::new(ping_handle)));
+            .build_meta_with_service_handle(peer_manager.compressor(), || {
+                ProtocolHandle::Callback(Box::new(ping_handle))
+            });
         protocol_meta.push(ping);
 
         let identify_peer_manager = Arc::clone(&peer_manager);
-        let identify = SupportProtocols::Identify.build_meta_with_service_handle(move || {
-            ProtocolHandle::Callback(Box::new(IdentifyProtocol::new(identify_peer_manager)))
-        });
+        let identify = SupportProtocols::Identify.build_meta_with_service_handle(
+            peer_manager.compressor(),
+            move || {
+                ProtocolHandle::Callback(Box::new(IdentifyProtocol::new(identify_peer_manager)))
+            },
+        );
         protocol_meta.push(identify);
 
         let discovery_peer_manager = DiscoveryAddressManager::new(Arc::clone(&peer_manager));
-        let discovery = SupportProtocols::Discovery.build_meta_with_service_handle(move || {
-            ProtocolHandle::Callback(Box::new(DiscoveryProtocol::new(
-                discovery_peer_manager,
-                None,
-            )))
-        });
+        let discovery = SupportProtocols::Discovery.build_meta_with_service_handle(
+            peer_manager.compressor(),
+            move || {
+                ProtocolHandle::Callback(Box::new(DiscoveryProtocol::new(
+                    discovery_peer_manager,
+                    None,
+                )))
+            },
+        );
         protocol_meta.push(discovery);
 
         let transmitter_peer_manager = Arc::clone(&peer_manager);
         let transmitter_router = message_router.clone();
-        let transmitter = SupportProtocols::Transmitter.build_meta_with_service_handle(move || {
-            ProtocolHandle::Callback(Box::new(TransmitterProtocol::new(
-                transmitter_router,
-                transmitter_peer_manager,
-            )))
-        });
+        let transmitter = SupportProtocols::Transmitter.build_meta_with_service_handle(
+            peer_manager.compressor(),
+            move || {
+                ProtocolHandle::Callback(Box::new(TransmitterProtocol::new(
+                    SupportProtocols::Transmitter,
+                    transmitter_router,
+                    transmitter_peer_manager,
+                )))
+            },
+        );
         protocol_meta.push(transmitter);
 
+        let consensus_peer_manager = Arc::clone(&peer_manager);
+        let consensus_router = message_router.clone();
+        let consensus = SupportProtocols::Consensus.build_meta_with_service_handle(
+            peer_manager.compressor(),
+            move || {
+                ProtocolHandle::Callback(Box::new(TransmitterProtocol::new(
+                    SupportProtocols::Consensus,
+                    consensus_router,
+                    consensus_peer_manager,
+                )))
+            },
+        );
+        protocol_meta.push(consensus);
+
         let feeler_peer_manager = Arc::clone(&peer_manager);
-        let feeler = SupportProtocols::Feeler.build_meta_with_service_handle(move || {
-            ProtocolHandle::Callback(Box::new(Feeler::new(feeler_peer_manager)))
-        });
+        let feeler = SupportProtocols::Feeler
+            .build_meta_with_service_handle(peer_manager.compressor(), move || {
+                ProtocolHandle::Callback(Box::new(Feeler::new(feeler_peer_manager)))
+            });
         protocol_meta.push(feeler);
 
         let mut service_builder = ServiceBuilder::new();
         let yamux_config = YamuxConfig {
             max_stream_count: protocol_meta.len(),
             max_stream_window_size: 1024 * 1024,
+            accept_backlog: config.max_wait_streams,
+            connection_write_timeout: Duration::from_secs(config.write_timeout),
             ..Default::default()
         };
 
@@ -289,8 +503,32 @@ impl NetworkService {
 
         let control: ServiceAsyncControl = service.control().clone();
 
-        let gossip = NetworkGossip::new(control.clone(), Arc::clone(&peer_manager));
-        let rpc = NetworkRpc::new(control, message_router);
+        let security = config.transport_security;
+        let identity = (security != TransportSecurity::Secio).then(|| {
+            let identity = NoiseIdentity::new(&config.secret_key).expect("noise identity");
+            Arc::new(identity)
+        });
+        let noise = identity
+            .clone()
+            .map(|identity| Arc::new(NoiseTransport::new(identity, security)));
+        let dialer = Dialer::new(
+            control.clone(),
+            Arc::clone(&peer_manager),
+            identity.filter(|_| security == TransportSecurity::Noise),
+        );
+
+        let (mesh, mesh_worker) = ConsensusMesh::new(
+            control.clone(),
+            Arc::clone(&peer_manager),
+            config.consensus_queue_size,
+        );
+        let gossip = NetworkGossip::new(
+            control.clone(),
+            Arc::clone(&peer_manager),
+            dialer.clone(),
+            mesh,
+        );
+        let rpc = NetworkRpc::new(control, message_router, Arc::clone(&peer_manager));
 
         NetworkService {
             config,
@@ -298,7 +536,10 @@ impl NetworkService {
             rpc,
             peer_mgr_handle: peer_manager,
             control: service.control().clone(),
+            dialer,
+            noise,
             net: Some(service),
+            mesh_worker: Some(mesh_worker),
             try_identify_count: 0,
         }
     }
@@ -352,14 +593,16 @@ impl NetworkService {
     /// Dial just feeler protocol
     pub async fn dial_feeler(&mut self, addr: Multiaddr) {
         let peer_id = extract_peer_id(&addr).unwrap();
+        if !self.peer_mgr_handle.is_allowed(&peer_id) {
+            return;
+        }
         let can_dial = self.peer_mgr_handle.with_registry_mut(|reg| {
             !reg.peers.contains_key(&peer_id)
                 && !reg.dialing.contains(&addr)
                 && reg.add_feeler(addr.clone())
         });
         if can_dial {
-            let _ignore = self
-                .control
+            self.dialer
                 .dial(
                     addr,
                     TargetProtocol::Single(SupportProtocols::Identify.protocol_id()),
@@ -371,14 +614,16 @@ impl NetworkService {
     /// Dial just identify protocol
     pub async fn dial_identify(&mut self, addr: Multiaddr) {
         let peer_id = extract_peer_id(&addr).unwrap();
+        if !self.peer_mgr_handle.is_allowed(&peer_id) {
+            return;
+        }
         let can_dial = self.peer_mgr_handle.with_registry_mut(|reg| {
             !reg.peers.contains_key(&peer_id)
                 && !reg.is_feeler(&addr)
                 && reg.dialing.insert(addr.clone())
         });
         if can_dial {
-            let _ignore = self
-                .control
+            self.dialer
                 .dial(
                     addr,
                     TargetProtocol::Single(SupportProtocols::Identify.protocol_id()),
@@ -431,7 +676,7 @@ impl NetworkService {
             .peer_mgr_handle
             .with_registry(|reg| reg.connection_status());
         let count = (self.config.max_connections - self.config.inbound_conn_limit)
-            .saturating_sub(status.inbound);
+            .saturating_sub(status.outbound);
         if count == 0 {
             self.try_identify_count = 0;
             return;
@@ -501,12 +746,48 @@ impl NetworkService {
         }
     }
 
+    fn spawn_dns_seeder(&self) {
+        if self.config.dns_trees.is_empty() && !self.config.bootstraps.iter().any(is_dns_addr) {
+            return;
+        }
+
+        let resolver = match SystemResolver::new() {
+            Ok(resolver) => resolver,
+            Err(err) => {
+                log::warn!("create dns resolver failed: {}", err);
+                return;
+            }
+        };
+
+        let seeder = DnsSeeder::new(
+            resolver,
+            self.config.bootstraps.clone(),
+            self.config.dns_trees.clone(),
+            self.config.dns_seed_interval,
+            Arc::clone(&self.peer_mgr_handle),
+        );
+        tokio::spawn(seeder.run());
+    }
+
     #[allow(clippy::unnecessary_to_owned)]
     pub async fn run(mut self) {
         if let Some(mut net) = self.net.take() {
-            net.listen(self.config.default_listen.clone())
-                .await
-                .unwrap();
+            let listen_addr = self.config.default_listen.clone();
+            match self.noise.as_ref() {
+                Some(transport) => {
+                    let socket_addr = multiaddr_to_socketaddr(&listen_addr).unwrap();
+                    let listener = bind_listener(socket_addr).unwrap();
+                    self.peer_mgr_handle.set_gateway_addr(listen_addr.clone());
+                    let gateway = NoiseGateway::new(
+                        Arc::clone(transport),
+                        self.control.clone(),
+                        Arc::clone(&self.peer_mgr_handle),
+                        listen_addr,
+                    );
+                    tokio::spawn(gateway.run(listener));
+                }
+                None => net.listen(listen_addr).await.unwrap(),
+            }
 
             for addr in self.config.bootstraps.to_vec() {
                 self.dial_identify(addr).await;
@@ -515,7 +796,23 @@ impl NetworkService {
             tokio::spawn(async move { net.run().await });
         }
 
-        let mut interval = tokio::time::interval_at(Instant::now(), Duration::from_secs(10));
+        if let Some(mesh_worker) = self.mesh_worker.take() {
+            tokio::spawn(mesh_worker.run());
+        }
+
+        self.spawn_dns_seeder();
+
+        if self.config.nat_port_mapping {
+            if let Some(socket_addr) = multiaddr_to_socketaddr(&self.config.default_listen) {
+                tokio::spawn(nat::run_port_mapping(
+                    Arc::clone(&self.peer_mgr_handle),
+                    socket_addr.port(),
+                ));
+            }
+        }
+
+        let mut interval =
+            tokio::time::interval_at(Instant::now(), self.config.heart_beat_interval);
         let mut dump_interval =
             tokio::time::interval_at(Instant::now(), Duration::from_secs(3600 * 24));
 
@@ -523,6 +820,7 @@ impl NetworkService {
         loop {
             tokio::select! {
                 _ = interval.tick() => {
+                    self.peer_mgr_handle.observe_mesh_health();
                     self.try_dial_consensus().await;
                     self.try_dial_peers().await;
                     self.try_dial_feeler().await;
@@ -648,29 +946,66 @@ impl ServiceHandle for ServiceHandler {
     async fn handle_event(&mut self, control: &mut ServiceContext, event: ServiceEvent) {
         match event {
             ServiceEvent::SessionOpen { session_context } => {
-                let (feeler, status) = self.peer_store.with_registry_mut(|reg| {
+                let feeler = self.peer_store.with_registry_mut(|reg| {
                     reg.dialing.remove(&session_context.address);
-                    (
-                        reg.is_feeler(&session_context.address),
-                        reg.connection_status(),
-                    )
+                    reg.is_feeler(&session_context.address)
                 });
-                if feeler {
+
+                let peer_id = extract_peer_id(&session_context.address).unwrap();
+                let noise_peer_id = self.peer_store.take_noise_peer(&session_context.address);
+                if noise_peer_id.map_or(false, |noise_peer_id| noise_peer_id != peer_id) {
+                    log::debug!(
+                        "reject session {} of {:?}, not the peer authenticated by noise",
+                        session_context.id,
+                        peer_id
+                    );
+                    common_apm::metrics::network::NETWORK_REJECTED_SESSION_COUNT_VEC
+                        .with_label_values(&["noise_peer_mismatch"])
+                        .inc();
+                    let _ignore = control.disconnect(session_context.id).await;
                     return;
                 }
-                let disable = status.total + 1 > self.config.max_connections
-                    || match session_context.ty {
-                        SessionType::Inbound => status.inbound + 1 > self.config.inbound_conn_limit,
-                        SessionType::Outbound => {
-                            status.outbound + 1
-                                > self.config.max_connections - self.config.inbound_conn_limit
-                        }
-                    };
 
-                if disable && !self.peer_store.always_allow(&session_context.address) {
+                if !self.peer_store.is_allowed(&peer_id) {
+                    log::debug!(
+                        "reject session {} of {:?} out of the allowlist",
+                        session_context.id,
+                        peer_id
+                    );
+                    common_apm::metrics::network::NETWORK_REJECTED_SESSION_COUNT_VEC
+                        .with_label_values(&["not_allowlisted"])
+                        .inc();
                     let _ignore = control.disconnect(session_context.id).await;
-                } else {
-                    self.peer_store.register(PeerInfo::new(session_context))
+                    return;
+                }
+
+                if feeler {
+                    return;
+                }
+
+                match self
+                    .peer_store
+                    .check_session_limits(&session_context.address, session_context.ty)
+                {
+                    SessionLimit::Accept => {
+                        self.peer_store.register(PeerInfo::new(session_context))
+                    }
+                    SessionLimit::Evict(id) => {
+                        log::debug!(
+                            "evict session {} for session {} of another network group",
+                            id,
+                            session_context.id
+                        );
+                        let _ignore = control.disconnect(id).await;
+                        self.peer_store.register(PeerInfo::new(session_context))
+                    }
+                    SessionLimit::Reject(reason) => {
+                        log::debug!("reject session {}: {}", session_context.id, reason);
+                        common_apm::metrics::network::NETWORK_REJECTED_SESSION_COUNT_VEC
+                            .with_label_values(&[reason])
+                            .inc();
+                        let _ignore = control.disconnect(session_context.id).await;
+                    }
                 }
             }
             ServiceEvent::SessionClose { session_context } => {
@@ -685,3 +1020,100 @@ impl ServiceHandle for ServiceHandler {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use tentacle::{multiaddr::Multiaddr, secio::PeerId};
+
+    use protocol::tokio;
+    use protocol::traits::{Context, Network, PeerStoreAdmin};
+
+    use super::{NetworkService, NetworkServiceHandle};
+    use crate::config::NetworkConfig;
+
+    const CHAIN_ID: &str = "0x01";
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub(crate) fn config(port: u16) -> NetworkConfig {
+        let listen = format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap();
+        let dir = PathBuf::from(format!("./free-space/network/service_{}", port));
+        NetworkConfig::new().listen_addr(listen).peer_store_dir(dir)
+    }
+
+    pub(crate) fn peer_addr(config: &NetworkConfig) -> Multiaddr {
+        format!(
+            "{}/p2p/{}",
+            config.default_listen,
+            config.secio_keypair.peer_id().to_base58()
+        )
+        .parse()
+        .unwrap()
+    }
+
+    pub(crate) fn start_node(config: NetworkConfig) -> (NetworkServiceHandle, PeerId) {
+        let service = NetworkService::new(config);
+        service.set_chain_id(CHAIN_ID.to_owned());
+        let (handle, peer_id) = (service.handle(), service.peer_id());
+        tokio::spawn(service.run());
+        (handle, peer_id)
+    }
+
+    /// Wait until the node has the count of peers, returns false on timeout
+    pub(crate) async fn wait_peer_count(handle: &NetworkServiceHandle, count: usize) -> bool {
+        let wait = async {
+            while handle.peer_count(Context::new()).unwrap() != count {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(WAIT_TIMEOUT, wait).await.is_ok()
+    }
+
+    /// The count of the sessions rejected for the reason, by all the nodes of
+    /// the tests
+    pub(crate) fn rejected_count(reason: &str) -> f64 {
+        common_apm::metrics::network::NETWORK_REJECTED_SESSION_COUNT_VEC
+            .with_label_values(&[reason])
+            .get()
+    }
+
+    /// Wait until the count of the sessions rejected for the reason is above
+    /// the given one, returns false on timeout
+    pub(crate) async fn wait_rejected(reason: &str, count: f64) -> bool {
+        let wait = async {
+            while rejected_count(reason) <= count {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(WAIT_TIMEOUT, wait).await.is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_only_rejects_peers() {
+        let (config_a, config_c) = (config(23371), config(23373));
+        let config_b = config(23372)
+            .allowlist(vec![config_c.secio_keypair.peer_id().to_base58()])
+            .unwrap()
+            .allowlist_only(Some(true));
+        let addr_b = peer_addr(&config_b);
+        let rejected = rejected_count("not_allowlisted");
+
+        let (node_b, _) = start_node(config_b);
+        let (node_a, _) = start_node(config_a.bootstraps(vec![addr_b.clone()]));
+        let (node_c, _) = start_node(config_c.bootstraps(vec![addr_b]));
+
+        // Only the allowlisted peer is accepted
+        assert!(wait_peer_count(&node_c, 1).await);
+        assert!(wait_peer_count(&node_b, 1).await);
+        assert!(wait_rejected("not_allowlisted", rejected).await);
+        assert!(wait_peer_count(&node_a, 0).await);
+        assert_eq!(node_b.peer_count(Context::new()).unwrap(), 1);
+
+        // The peer out of the reloaded allowlist is disconnected
+        assert_eq!(node_b.set_allowlist(vec![]).unwrap(), 1);
+        assert!(wait_peer_count(&node_b, 0).await);
+        assert!(wait_peer_count(&node_c, 0).await);
+        assert!(node_b.set_allowlist(vec!["axon".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,860 @@
+//! The offline archive of a chain at a certain height. It contains the blocks
+//! with their transactions and receipts, the proof of the last block, the
+//! state trie with the contract codes and the system contract tries of the
+//! last block.
+//!
+//! An archive is a sequence of records, each of them is framed as
+//! `tag (u8) | payload length (u32, big endian) | RLP payload |
+//! keccak(payload)`. The end record carries the number of records and the
+//! checksum chained over all the record hashes, so that a truncated or modified
+//! archive is rejected before anything is imported.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use rlp::Encodable;
+use rlp_derive::{RlpDecodable, RlpEncodable};
+
+use core_consensus::snapshot::TrieSync;
+use core_executor::{system_contract, AxonExecutorAdapter};
+use protocol::traits::{Context, Storage};
+use protocol::types::{
+    Block, Bytes, Hash, Hasher, MerkleRoot, Proof, Proposal, Receipt, SignedTransaction, TrieKind,
+};
+use protocol::{trie, Display, ProtocolError, ProtocolErrorKind, ProtocolResult};
+
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const TRIE_NODES_CHUNK_SIZE: usize = 1024;
+const MAX_RECORD_SIZE: usize = 256 * 1024 * 1024;
+
+const TAG_HEADER: u8 = 0;
+const TAG_BLOCK: u8 = 1;
+const TAG_PROOF: u8 = 2;
+const TAG_TRIE_NODES: u8 = 3;
+const TAG_END: u8 = 0xff;
+
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveHeader {
+    pub version:          u32,
+    pub chain_id:         u64,
+    pub number:           u64,
+    pub state_root:       MerkleRoot,
+    pub metadata_root:    Hash,
+    pub header_cell_root: Hash,
+}
+
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug)]
+struct ArchiveBlock {
+    block:    Block,
+    txs:      Vec<SignedTransaction>,
+    receipts: Vec<Receipt>,
+}
+
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug)]
+struct ArchiveTrieNodes {
+    kind:  TrieKind,
+    nodes: Vec<Bytes>,
+}
+
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug)]
+struct ArchiveEnd {
+    records:  u64,
+    checksum: Hash,
+}
+
+struct ArchiveWriter<W> {
+    inner:    W,
+    records:  u64,
+    checksum: Hash,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    fn new(inner: W) -> Self {
+        ArchiveWriter {
+            inner,
+            records: 0,
+            checksum: Hash::default(),
+        }
+    }
+
+    fn write<T: Encodable>(&mut self, tag: u8, item: &T) -> ProtocolResult<()> {
+        let hash = self.write_raw(tag, item)?;
+        self.records += 1;
+        self.checksum = chain_checksum(&self.checksum, &hash);
+        Ok(())
+    }
+
+    fn write_raw<T: Encodable>(&mut self, tag: u8, item: &T) -> ProtocolResult<Hash> {
+        let payload = rlp::encode(item);
+        let hash = Hasher::digest(&payload);
+
+        self.inner.write_all(&[tag]).map_err(ArchiveError::Io)?;
+        self.inner
+            .write_all(&(payload.len() as u32).to_be_bytes())
+            .map_err(ArchiveError::Io)?;
+        self.inner.write_all(&payload).map_err(ArchiveError::Io)?;
+        self.inner
+            .write_all(hash.as_bytes())
+            .map_err(ArchiveError::Io)?;
+
+        Ok(hash)
+    }
+
+    fn finish(mut self) -> ProtocolResult<W> {
+        let end = ArchiveEnd {
+            records:  self.records,
+            checksum: self.checksum,
+        };
+        self.write_raw(TAG_END, &end)?;
+        self.inner.flush().map_err(ArchiveError::Io)?;
+        Ok(self.inner)
+    }
+}
+
+struct ArchiveReader<R> {
+    inner:    R,
+    records:  u64,
+    checksum: Hash,
+    finished: bool,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    fn new(inner: R) -> Self {
+        ArchiveReader {
+            inner,
+            records: 0,
+            checksum: Hash::default(),
+            finished: false,
+        }
+    }
+
+    /// Read the next record, return `None` after the end record is verified.
+    fn next_record(&mut self) -> ProtocolResult<Option<(u8, Vec<u8>)>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let mut tag = [0u8; 1];
+        self.read_exact(&mut tag)?;
+        let mut len = [0u8; 4];
+        self.read_exact(&mut len)?;
+
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_RECORD_SIZE {
+            return Err(ArchiveError::CorruptedRecord(self.records).into());
+        }
+
+        let mut payload = vec![0u8; len];
+        self.read_exact(&mut payload)?;
+        let mut hash = [0u8; 32];
+        self.read_exact(&mut hash)?;
+
+        let hash = Hash::from(hash);
+        if Hasher::digest(&payload) != hash {
+            return Err(ArchiveError::CorruptedRecord(self.records).into());
+        }
+
+        if tag[0] == TAG_END {
+            let end: ArchiveEnd = decode(&payload)?;
+            if end.records != self.records || end.checksum != self.checksum {
+                return Err(ArchiveError::ChecksumMismatch.into());
+            }
+
+            self.finished = true;
+            return Ok(None);
+        }
+
+        self.records += 1;
+        self.checksum = chain_checksum(&self.checksum, &hash);
+        Ok(Some((tag[0], payload)))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> ProtocolResult<()> {
+        self.inner.read_exact(buf).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                ArchiveError::Truncated
+            } else {
+                ArchiveError::Io(e)
+            }
+        })?;
+        Ok(())
+    }
+}
+
+/// Export the chain from genesis to the given block.
+pub async fn export<S, DB>(
+    storage: Arc<S>,
+    trie_db: Arc<DB>,
+    number: u64,
+    path: &Path,
+) -> ProtocolResult<ArchiveHeader>
+where
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+{
+    let ctx = Context::new();
+    let latest_proof = storage.get_latest_proof(ctx.clone()).await?;
+    let latest_number = storage.get_latest_block_header(ctx.clone()).await?.number;
+    if number > latest_number {
+        return Err(ArchiveError::FutureBlock(number, latest_number).into());
+    }
+
+    let proof = if number == latest_number {
+        latest_proof
+    } else {
+        storage
+            .get_block_header(ctx.clone(), number + 1)
+            .await?
+            .ok_or(ArchiveError::MissingBlock(number + 1))?
+            .proof
+    };
+
+    let block = storage
+        .get_block(ctx.clone(), number)
+        .await?
+        .ok_or(ArchiveError::MissingBlock(number))?;
+    let backend = AxonExecutorAdapter::from_root(
+        block.header.state_root,
+        Arc::clone(&trie_db),
+        Arc::clone(&storage),
+        Default::default(),
+    )?;
+    let (metadata_root, header_cell_root) = system_contract::system_contract_roots(&backend);
+
+    let header = ArchiveHeader {
+        version: ARCHIVE_VERSION,
+        chain_id: block.header.chain_id,
+        number,
+        state_root: block.header.state_root,
+        metadata_root,
+        header_cell_root,
+    };
+
+    let file = File::create(path).map_err(ArchiveError::Io)?;
+    let mut writer = ArchiveWriter::new(BufWriter::new(file));
+    writer.write(TAG_HEADER, &header)?;
+
+    for n in 0..=number {
+        let block = storage
+            .get_block(ctx.clone(), n)
+            .await?
+            .ok_or(ArchiveError::MissingBlock(n))?;
+        let txs = storage
+            .get_transactions(ctx.clone(), n, &block.tx_hashes)
+            .await?
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ArchiveError::MissingBlock(n))?;
+        let receipts = storage
+            .get_receipts(ctx.clone(), n, &block.tx_hashes)
+            .await?
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ArchiveError::MissingBlock(n))?;
+
+        writer.write(TAG_BLOCK, &ArchiveBlock {
+            block,
+            txs,
+            receipts,
+        })?;
+    }
+
+    writer.write(TAG_PROOF, &proof)?;
+
+    let mut state_sync = TrieSync::new(TrieKind::State, header.state_root);
+    export_trie(&storage, &trie_db, &mut state_sync, &mut writer).await?;
+    let mut code_sync = TrieSync::new_codes(state_sync.take_codes());
+    export_trie(&storage, &trie_db, &mut code_sync, &mut writer).await?;
+    let mut metadata_sync = TrieSync::new(TrieKind::Metadata, metadata_root);
+    export_trie(&storage, &trie_db, &mut metadata_sync, &mut writer).await?;
+    let mut header_cell_sync = TrieSync::new(TrieKind::HeaderCell, header_cell_root);
+    export_trie(&storage, &trie_db, &mut header_cell_sync, &mut writer).await?;
+
+    writer.finish()?;
+    Ok(header)
+}
+
+/// Write the nodes of the trie from the root to the leaves, so that they can
+/// be verified in order when imported.
+async fn export_trie<S, DB, W>(
+    storage: &Arc<S>,
+    trie_db: &Arc<DB>,
+    trie_sync: &mut TrieSync,
+    writer: &mut ArchiveWriter<W>,
+) -> ProtocolResult<()>
+where
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+    W: Write,
+{
+    let kind = trie_sync.kind();
+
+    while !trie_sync.is_finished() {
+        let mut nodes = Vec::new();
+        for hash in trie_sync.pending_hashes(TRIE_NODES_CHUNK_SIZE) {
+            let node = match kind {
+                TrieKind::State => trie_db
+                    .get(hash.as_bytes())
+                    .map_err(|e| ArchiveError::TrieDB(e.to_string()))?
+                    .map(Bytes::from),
+                TrieKind::Metadata | TrieKind::HeaderCell => {
+                    system_contract::get_trie_node(kind, &hash)?.map(Bytes::from)
+                }
+                TrieKind::Code => storage.get_code_by_hash(Context::new(), &hash).await?,
+            };
+
+            nodes.push(node.ok_or(ArchiveError::MissingTrieNode(kind, hash))?);
+        }
+
+        trie_sync.process(nodes.clone())?;
+        writer.write(TAG_TRIE_NODES, &ArchiveTrieNodes { kind, nodes })?;
+    }
+
+    Ok(())
+}
+
+/// Check the framing and the checksum of the archive without importing it.
+pub fn verify(path: &Path) -> ProtocolResult<u64> {
+    let file = File::open(path).map_err(ArchiveError::Io)?;
+    let mut reader = ArchiveReader::new(BufReader::new(file));
+    while reader.next_record()?.is_some() {}
+    Ok(reader.records)
+}
+
+/// Import the archive into empty databases. Every block is linked to the
+/// previous one and every trie node is verified by the roots in the archive
+/// header, the latest block is set after all the records are imported.
+pub async fn import<S, DB>(
+    storage: Arc<S>,
+    trie_db: Arc<DB>,
+    chain_id: u64,
+    path: &Path,
+) -> ProtocolResult<ArchiveHeader>
+where
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+{
+    verify(path)?;
+
+    let ctx = Context::new();
+    if storage.get_latest_block(ctx.clone()).await.is_ok() {
+        return Err(ArchiveError::NotEmpty.into());
+    }
+
+    let file = File::open(path).map_err(ArchiveError::Io)?;
+    let mut reader = ArchiveReader::new(BufReader::new(file));
+
+    let header: ArchiveHeader = match reader.next_record()? {
+        Some((TAG_HEADER, payload)) => decode(&payload)?,
+        _ => return Err(ArchiveError::UnexpectedRecord(0).into()),
+    };
+    if header.version != ARCHIVE_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(header.version).into());
+    }
+    if header.chain_id != chain_id {
+        return Err(ArchiveError::ChainIdMismatch(chain_id, header.chain_id).into());
+    }
+
+    let mut last_block: Option<Block> = None;
+    let mut proof: Option<Proof> = None;
+    let mut state_sync = TrieSync::new(TrieKind::State, header.state_root);
+    let mut code_sync: Option<TrieSync> = None;
+    let mut metadata_sync = TrieSync::new(TrieKind::Metadata, header.metadata_root);
+    let mut header_cell_sync = TrieSync::new(TrieKind::HeaderCell, header.header_cell_root);
+
+    while let Some((tag, payload)) = reader.next_record()? {
+        match tag {
+            TAG_BLOCK => {
+                let ArchiveBlock {
+                    block,
+                    txs,
+                    receipts,
+                } = decode(&payload)?;
+                let number = block.header.number;
+                let linked = match last_block.as_ref() {
+                    Some(prev) => {
+                        number == prev.header.number + 1 && block.header.prev_hash == prev.hash()
+                    }
+                    None => number == 0,
+                };
+                if !linked || number > header.number {
+                    return Err(ArchiveError::InvalidBlock(number).into());
+                }
+
+                storage
+                    .insert_transactions(ctx.clone(), number, txs)
+                    .await?;
+                storage
+                    .insert_receipts(ctx.clone(), number, receipts)
+                    .await?;
+                storage.set_block(ctx.clone(), block.clone()).await?;
+                last_block = Some(block);
+            }
+            TAG_PROOF => proof = Some(decode(&payload)?),
+            TAG_TRIE_NODES => {
+                let ArchiveTrieNodes { kind, nodes } = decode(&payload)?;
+                let trie_sync = match kind {
+                    TrieKind::State => &mut state_sync,
+                    TrieKind::Metadata => &mut metadata_sync,
+                    TrieKind::HeaderCell => &mut header_cell_sync,
+                    TrieKind::Code => {
+                        if !state_sync.is_finished() {
+                            return Err(ArchiveError::UnexpectedRecord(reader.records).into());
+                        }
+                        code_sync
+                            .get_or_insert_with(|| TrieSync::new_codes(state_sync.take_codes()))
+                    }
+                };
+
+                let verified = trie_sync.process(nodes)?;
+                import_trie_nodes(&storage, &trie_db, trie_sync, verified).await?;
+            }
+            _ => return Err(ArchiveError::UnexpectedRecord(reader.records).into()),
+        }
+    }
+
+    let last_block = last_block
+        .filter(|block| block.header.number == header.number)
+        .ok_or(ArchiveError::MissingBlock(header.number))?;
+    if last_block.header.state_root != header.state_root {
+        return Err(ArchiveError::InvalidBlock(header.number).into());
+    }
+
+    let proof = proof.ok_or(ArchiveError::MissingProof(header.number))?;
+    if header.number != 0
+        && (proof.number != header.number || proof.block_hash != Proposal::from(&last_block).hash())
+    {
+        return Err(ArchiveError::MissingProof(header.number).into());
+    }
+
+    if code_sync.is_none() {
+        code_sync = Some(TrieSync::new_codes(state_sync.take_codes()));
+    }
+    for trie_sync in [
+        &state_sync,
+        code_sync.as_ref().unwrap(),
+        &metadata_sync,
+        &header_cell_sync,
+    ] {
+        if !trie_sync.is_finished() {
+            return Err(ArchiveError::IncompleteTrie(trie_sync.kind()).into());
+        }
+    }
+
+    storage.update_latest_proof(ctx.clone(), proof).await?;
+    storage.set_latest_block(ctx, last_block).await?;
+
+    Ok(header)
+}
+
+async fn import_trie_nodes<S, DB>(
+    storage: &Arc<S>,
+    trie_db: &Arc<DB>,
+    trie_sync: &TrieSync,
+    nodes: Vec<(Hash, Bytes)>,
+) -> ProtocolResult<()>
+where
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+{
+    match trie_sync.kind() {
+        TrieKind::State => {
+            let (keys, values): (Vec<_>, Vec<_>) = nodes
+                .into_iter()
+                .map(|(hash, node)| (hash.as_bytes().to_vec(), node.to_vec()))
+                .unzip();
+            trie_db
+                .insert_batch(keys, values)
+                .map_err(|e| ArchiveError::TrieDB(e.to_string()))?;
+        }
+        TrieKind::Metadata | TrieKind::HeaderCell => {
+            system_contract::insert_trie_nodes(trie_sync.kind(), nodes)?
+        }
+        TrieKind::Code => {
+            for (code_hash, code) in nodes.into_iter() {
+                for address in trie_sync.code_addresses(&code_hash) {
+                    storage
+                        .insert_code(Context::new(), (*address).into(), code_hash, code.clone())
+                        .await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn chain_checksum(checksum: &Hash, hash: &Hash) -> Hash {
+    let mut buf = checksum.as_bytes().to_vec();
+    buf.extend_from_slice(hash.as_bytes());
+    Hasher::digest(buf)
+}
+
+fn decode<T: rlp::Decodable>(payload: &[u8]) -> ProtocolResult<T> {
+    Ok(rlp::decode(payload).map_err(ArchiveError::Rlp)?)
+}
+
+#[derive(Debug, Display)]
+pub enum ArchiveError {
+    #[display(fmt = "Archive io error {:?}", _0)]
+    Io(io::Error),
+
+    #[display(fmt = "Archive decode error {:?}", _0)]
+    Rlp(rlp::DecoderError),
+
+    #[display(fmt = "Archive trie db error {}", _0)]
+    TrieDB(String),
+
+    #[display(fmt = "Archive record {} is corrupted", _0)]
+    CorruptedRecord(u64),
+
+    #[display(fmt = "Archive record {} is unexpected", _0)]
+    UnexpectedRecord(u64),
+
+    #[display(fmt = "Archive is truncated")]
+    Truncated,
+
+    #[display(fmt = "Archive checksum mismatch")]
+    ChecksumMismatch,
+
+    #[display(fmt = "Unsupported archive version {}", _0)]
+    UnsupportedVersion(u32),
+
+    #[display(fmt = "Chain id mismatch, expect {}, archive {}", _0, _1)]
+    ChainIdMismatch(u64, u64),
+
+    #[display(fmt = "Block {} is higher than the latest block {}", _0, _1)]
+    FutureBlock(u64, u64),
+
+    #[display(fmt = "Block {} is missing or invalid", _0)]
+    MissingBlock(u64),
+
+    #[display(fmt = "Block {} is not linked to the previous one", _0)]
+    InvalidBlock(u64),
+
+    #[display(fmt = "Proof of block {} is missing or invalid", _0)]
+    MissingProof(u64),
+
+    #[display(fmt = "{:?} trie node {:?} is missing", _0, _1)]
+    MissingTrieNode(TrieKind, Hash),
+
+    #[display(fmt = "{:?} trie is incomplete", _0)]
+    IncompleteTrie(TrieKind),
+
+    #[display(fmt = "The chain data is not empty")]
+    NotEmpty,
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<ArchiveError> for ProtocolError {
+    fn from(error: ArchiveError) -> ProtocolError {
+        ProtocolError::new(ProtocolErrorKind::Main, Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_crypto::{
+        Crypto, PrivateKey, Secp256k1Recoverable, Secp256k1RecoverablePrivateKey, Signature,
+    };
+    use core_executor::MPTTrie;
+    use core_storage::{adapter::memory::MemoryAdapter, ImplStorage};
+    use protocol::codec::ProtocolCodec;
+    use protocol::rand::{random, rngs::OsRng};
+    use protocol::tokio;
+    use protocol::trie::MemoryDB;
+    use protocol::types::{
+        Account, Eip1559Transaction, Header, SignatureComponents, TransactionAction,
+        UnsignedTransaction, UnverifiedTransaction, H160, NIL_DATA, RLP_NULL, U256,
+    };
+
+    use super::*;
+
+    const CHAIN_ID: u64 = 5;
+
+    fn write_archive() -> Vec<u8> {
+        let mut writer = ArchiveWriter::new(Vec::new());
+        writer
+            .write(TAG_HEADER, &ArchiveHeader {
+                version:          ARCHIVE_VERSION,
+                chain_id:         CHAIN_ID,
+                number:           1,
+                state_root:       Hasher::digest("state"),
+                metadata_root:    Hasher::digest("metadata"),
+                header_cell_root: Hash::default(),
+            })
+            .unwrap();
+        writer.write(TAG_PROOF, &Proof::default()).unwrap();
+        writer
+            .write(TAG_TRIE_NODES, &ArchiveTrieNodes {
+                kind:  TrieKind::Code,
+                nodes: vec![Bytes::from_static(b"code")],
+            })
+            .unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn read_all(data: &[u8]) -> ProtocolResult<Vec<(u8, Vec<u8>)>> {
+        let mut reader = ArchiveReader::new(data);
+        let mut records = Vec::new();
+        while let Some(record) = reader.next_record()? {
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let data = write_archive();
+        let records = read_all(&data).unwrap();
+        assert_eq!(
+            records.iter().map(|(tag, _)| *tag).collect::<Vec<_>>(),
+            vec![TAG_HEADER, TAG_PROOF, TAG_TRIE_NODES]
+        );
+
+        let header: ArchiveHeader = decode(&records[0].1).unwrap();
+        assert_eq!(header.chain_id, 5);
+        assert_eq!(header.number, 1);
+    }
+
+    fn mock_signed_tx(nonce: u64) -> SignedTransaction {
+        let mut utx = UnverifiedTransaction {
+            unsigned:  UnsignedTransaction::Eip1559(Eip1559Transaction {
+                nonce:                    nonce.into(),
+                max_priority_fee_per_gas: Default::default(),
+                gas_price:                Default::default(),
+                gas_limit:                Default::default(),
+                action:                   TransactionAction::Create,
+                value:                    Default::default(),
+                data:                     Bytes::new(),
+                access_list:              vec![],
+            }),
+            signature: Some(SignatureComponents {
+                standard_v: 4,
+                r:          Default::default(),
+                s:          Default::default(),
+            }),
+            chain_id:  CHAIN_ID,
+            hash:      Default::default(),
+        };
+
+        let priv_key = Secp256k1RecoverablePrivateKey::generate(&mut OsRng);
+        let signature = Secp256k1Recoverable::sign_message(
+            utx.signature_hash(true).as_bytes(),
+            &priv_key.to_bytes(),
+        )
+        .unwrap()
+        .to_bytes();
+        utx.signature = Some(signature.into());
+
+        utx.calc_hash().try_into().unwrap()
+    }
+
+    /// Build a chain of the given height on the state of some accounts and a
+    /// contract, every block carries two transactions with their receipts.
+    async fn mock_chain(
+        storage: &Arc<ImplStorage<MemoryAdapter>>,
+        trie_db: &Arc<MemoryDB>,
+        number: u64,
+    ) -> Proof {
+        let ctx = Context::new();
+        let code = Bytes::from_static(b"contract code");
+        let contract = H160::from_low_u64_be(7);
+        storage
+            .insert_code(
+                ctx.clone(),
+                contract.into(),
+                Hasher::digest(&code),
+                code.clone(),
+            )
+            .await
+            .unwrap();
+
+        let mut state_trie = MPTTrie::new(Arc::clone(trie_db));
+        for i in 0..100u64 {
+            let address = H160::from_low_u64_be(i);
+            let account = Account {
+                nonce:        U256::zero(),
+                balance:      U256::from(i),
+                storage_root: RLP_NULL,
+                code_hash:    if address == contract {
+                    Hasher::digest(&code)
+                } else {
+                    NIL_DATA
+                },
+            };
+            state_trie
+                .insert(address.as_bytes(), &account.encode().unwrap())
+                .unwrap();
+        }
+        let state_root = state_trie.commit().unwrap();
+
+        let mut prev_hash = Hash::default();
+        for n in 0..=number {
+            let txs = (0..2)
+                .map(|i| mock_signed_tx(n * 2 + i))
+                .collect::<Vec<_>>();
+            let block = Block {
+                tx_hashes: txs.iter().map(|tx| tx.transaction.hash).collect(),
+                header:    Header {
+                    prev_hash,
+                    state_root,
+                    number: n,
+                    chain_id: CHAIN_ID,
+                    ..Default::default()
+                },
+            };
+            let receipts = txs
+                .iter()
+                .enumerate()
+                .map(|(i, tx)| Receipt {
+                    tx_hash: tx.transaction.hash,
+                    block_number: n,
+                    block_hash: block.hash(),
+                    tx_index: i as u32,
+                    state_root,
+                    used_gas: U256::from(random::<u32>()),
+                    sender: tx.sender,
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+
+            prev_hash = block.hash();
+            storage
+                .insert_transactions(ctx.clone(), n, txs)
+                .await
+                .unwrap();
+            storage
+                .insert_receipts(ctx.clone(), n, receipts)
+                .await
+                .unwrap();
+            storage.insert_block(ctx.clone(), block).await.unwrap();
+        }
+
+        let proof = Proof {
+            number,
+            block_hash: prev_hash,
+            ..Default::default()
+        };
+        storage
+            .update_latest_proof(ctx, proof.clone())
+            .await
+            .unwrap();
+        proof
+    }
+
+    #[tokio::test]
+    async fn test_export_import() {
+        let ctx = Context::new();
+        let number = 10;
+        let path = std::env::temp_dir().join(format!("axon-archive-{}", random::<u64>()));
+
+        let storage = Arc::new(ImplStorage::new(Arc::new(MemoryAdapter::new()), 10));
+        let trie_db = Arc::new(MemoryDB::new(false));
+        let proof = mock_chain(&storage, &trie_db, number).await;
+        let header = export(Arc::clone(&storage), Arc::clone(&trie_db), number, &path)
+            .await
+            .unwrap();
+        assert!(verify(&path).is_ok());
+
+        let new_storage = Arc::new(ImplStorage::new(Arc::new(MemoryAdapter::new()), 10));
+        let new_trie_db = Arc::new(MemoryDB::new(false));
+        assert!(import(
+            Arc::clone(&new_storage),
+            Arc::clone(&new_trie_db),
+            CHAIN_ID + 1,
+            &path
+        )
+        .await
+        .is_err());
+        let imported = import(
+            Arc::clone(&new_storage),
+            Arc::clone(&new_trie_db),
+            CHAIN_ID,
+            &path,
+        )
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(imported, header);
+
+        for n in 0..=number {
+            let block = storage.get_block(ctx.clone(), n).await.unwrap().unwrap();
+            let new_block = new_storage.get_block(ctx.clone(), n).await.unwrap();
+            assert_eq!(new_block.as_ref(), Some(&block));
+            assert_eq!(
+                new_storage
+                    .get_receipts(ctx.clone(), n, &block.tx_hashes)
+                    .await
+                    .unwrap(),
+                storage
+                    .get_receipts(ctx.clone(), n, &block.tx_hashes)
+                    .await
+                    .unwrap()
+            );
+            assert_eq!(
+                new_storage
+                    .get_transactions(ctx.clone(), n, &block.tx_hashes)
+                    .await
+                    .unwrap(),
+                storage
+                    .get_transactions(ctx.clone(), n, &block.tx_hashes)
+                    .await
+                    .unwrap()
+            );
+        }
+        assert_eq!(
+            new_storage.get_latest_proof(ctx.clone()).await.unwrap(),
+            proof
+        );
+        assert_eq!(
+            new_storage
+                .get_latest_block_header(ctx.clone())
+                .await
+                .unwrap()
+                .number,
+            number
+        );
+
+        // The state is complete, every account and the contract code are there.
+        let state = MPTTrie::from_root(header.state_root, new_trie_db).unwrap();
+        for i in 0..100u64 {
+            assert!(state
+                .get(H160::from_low_u64_be(i).as_bytes())
+                .unwrap()
+                .is_some());
+        }
+        assert_eq!(
+            new_storage
+                .get_code_by_address(ctx, &H160::from_low_u64_be(7).into())
+                .await
+                .unwrap(),
+            Some(Bytes::from_static(b"contract code"))
+        );
+    }
+
+    #[test]
+    fn test_archive_corrupted() {
+        let data = write_archive();
+
+        // Truncated in the middle of a record.
+        assert!(read_all(&data[..data.len() / 2]).is_err());
+        // Without the end record.
+        let mut writer = ArchiveWriter::new(Vec::new());
+        writer.write(TAG_PROOF, &Proof::default()).unwrap();
+        assert!(read_all(&writer.inner).is_err());
+
+        // A flipped byte in a payload.
+        let mut corrupted = data.clone();
+        corrupted[10] ^= 0xff;
+        assert!(read_all(&corrupted).is_err());
+
+        // A record is dropped with its hash.
+        let first_len = 1 + 4 + u32::from_be_bytes(data[1..5].try_into().unwrap()) as usize + 32;
+        assert!(read_all(&data[first_len..]).is_err());
+    }
+}
@@ -1,6 +1,19 @@
 #![allow(clippy::uninlined_format_args, clippy::mutable_key_type)]
 
-use std::{collections::HashMap, panic::PanicInfo, str::FromStr, sync::Arc, time::Duration};
+mod archive;
+mod peers;
+mod replay;
+
+pub use peers::PeersCommand;
+
+use std::{
+    collections::HashMap,
+    panic::PanicInfo,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use backtrace::Backtrace;
 use ethers_signers::{coins_bip39::English, MnemonicBuilder, Signer};
@@ -25,11 +38,11 @@ use common_crypto::{
 use core_api::{jsonrpc::run_jsonrpc_server, DefaultAPIAdapter};
 use core_consensus::message::{
     ChokeMessageHandler, ProposalMessageHandler, PullBlockRpcHandler, PullProofRpcHandler,
-    PullTxsRpcHandler, QCMessageHandler, RemoteHeightMessageHandler, VoteMessageHandler,
-    BROADCAST_HEIGHT, END_GOSSIP_AGGREGATED_VOTE, END_GOSSIP_SIGNED_CHOKE,
+    PullTrieNodesRpcHandler, PullTxsRpcHandler, QCMessageHandler, RemoteHeightMessageHandler,
+    VoteMessageHandler, BROADCAST_HEIGHT, END_GOSSIP_AGGREGATED_VOTE, END_GOSSIP_SIGNED_CHOKE,
     END_GOSSIP_SIGNED_PROPOSAL, END_GOSSIP_SIGNED_VOTE, RPC_RESP_SYNC_PULL_BLOCK,
-    RPC_RESP_SYNC_PULL_PROOF, RPC_RESP_SYNC_PULL_TXS, RPC_SYNC_PULL_BLOCK, RPC_SYNC_PULL_PROOF,
-    RPC_SYNC_PULL_TXS,
+    RPC_RESP_SYNC_PULL_PROOF, RPC_RESP_SYNC_PULL_TRIE_NODES, RPC_RESP_SYNC_PULL_TXS,
+    RPC_SYNC_PULL_BLOCK, RPC_SYNC_PULL_PROOF, RPC_SYNC_PULL_TRIE_NODES, RPC_SYNC_PULL_TXS,
 };
 use core_consensus::status::{CurrentStatus, StatusAgent};
 use core_consensus::{
@@ -39,8 +52,10 @@ use core_consensus::{
 use core_executor::{system_contract, AxonExecutor, AxonExecutorAdapter, MPTTrie, RocksTrieDB};
 use core_interoperation::InteroperationImpl;
 use core_mempool::{
-    DefaultMemPoolAdapter, MemPoolImpl, NewTxsHandler, PullTxsHandler, END_GOSSIP_NEW_TXS,
-    RPC_PULL_TXS, RPC_RESP_PULL_TXS, RPC_RESP_PULL_TXS_SYNC,
+    AnnounceTxsHandler, CompactTxsHandler, DefaultMemPoolAdapter, MemPoolImpl, NewTxsHandler,
+    PrivateTxsHandler, PullTxsHandler, END_GOSSIP_ANNOUNCE_TXS, END_GOSSIP_COMPACT_TXS,
+    END_GOSSIP_NEW_TXS, END_GOSSIP_PRIVATE_TXS, RPC_PULL_TXS, RPC_RESP_PULL_TXS,
+    RPC_RESP_PULL_TXS_SYNC,
 };
 use core_metadata::{MetadataAdapterImpl, MetadataController};
 use core_network::{
@@ -48,12 +63,13 @@ use core_network::{
 };
 use core_storage::{adapter::rocks::RocksAdapter, ImplStorage};
 
-use protocol::lazy::{CHAIN_ID, CURRENT_STATE_ROOT};
+use protocol::lazy::{CHAIN_ID, CURRENT_STATE_ROOT, SPECULATIVE_EXECUTION_FROM};
 #[cfg(unix)]
 use protocol::tokio::signal::unix as os_impl;
 use protocol::tokio::{runtime::Builder as RuntimeBuilder, sync::Mutex as AsyncMutex, time::sleep};
 use protocol::traits::{
-    CommonStorage, Context, Executor, MemPool, MetadataControl, Network, NodeInfo, Storage,
+    CommonStorage, Context, Executor, MaintenanceStorage, MemPool, MetadataControl, Network,
+    NodeInfo, Storage,
 };
 use protocol::types::{
     Account, Address, MerkleRoot, Proposal, RichBlock, Validator, NIL_DATA, RLP_NULL,
@@ -72,6 +88,17 @@ use protocol::{tokio, Display, From, ProtocolError, ProtocolErrorKind, ProtocolR
 #[global_allocator]
 pub static JEMALLOC: Jemalloc = Jemalloc;
 
+/// The message limits of every peer, the endpoint, the messages per second
+/// and the messages at once.
+const DEFAULT_RATE_LIMITS: &[(&str, u32, u32)] = &[
+    (END_GOSSIP_NEW_TXS, 100, 500),
+    (BROADCAST_HEIGHT, 2, 10),
+    (RPC_SYNC_PULL_BLOCK, 50, 200),
+    (RPC_SYNC_PULL_PROOF, 50, 200),
+    (RPC_SYNC_PULL_TXS, 50, 200),
+    (RPC_SYNC_PULL_TRIE_NODES, 20, 100),
+];
+
 #[derive(Debug)]
 pub struct Axon {
     config:     Config,
@@ -81,6 +108,15 @@ pub struct Axon {
 
 impl Axon {
     pub fn new(config: Config, genesis: RichBlock) -> Axon {
+        // The hash of a block depends on the mode, so it is set before any
+        // block is read.
+        SPECULATIVE_EXECUTION_FROM.swap(Arc::new(
+            config
+                .consensus
+                .speculative_execution_from
+                .unwrap_or(u64::MAX),
+        ));
+
         Axon {
             config,
             genesis,
@@ -109,6 +145,266 @@ impl Axon {
         Ok(())
     }
 
+    /// Export the chain from genesis to the given block into an archive.
+    pub fn export(self, number: u64, path: &Path) -> ProtocolResult<()> {
+        let rt = RuntimeBuilder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("new tokio runtime");
+
+        let header = rt.block_on(async move {
+            let (storage, trie_db) = self.open_databases()?;
+            archive::export(storage, trie_db, number, path).await
+        })?;
+
+        log::info!(
+            "Export block {} with state root {:?} to {:?}",
+            header.number,
+            header.state_root,
+            path
+        );
+        Ok(())
+    }
+
+    /// Import an archive into the empty databases, the node starts from the
+    /// last block of the archive after that.
+    pub fn import(self, path: &Path) -> ProtocolResult<()> {
+        let rt = RuntimeBuilder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("new tokio runtime");
+
+        let chain_id = self.genesis.block.header.chain_id;
+        let header = rt.block_on(async move {
+            let (storage, trie_db) = self.open_databases()?;
+            archive::import(storage, trie_db, chain_id, path).await
+        })?;
+
+        log::info!(
+            "Import block {} with state root {:?} from {:?}",
+            header.number,
+            header.state_root,
+            path
+        );
+        Ok(())
+    }
+
+    /// Export the blocks in `[from, to]` as a length-prefixed RLP stream.
+    pub fn export_blocks(self, from: u64, to: u64, path: &Path) -> ProtocolResult<()> {
+        let rt = RuntimeBuilder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("new tokio runtime");
+
+        let count = rt.block_on(async move {
+            let (storage, _) = self.open_databases()?;
+            replay::export_blocks(storage, from, to, path).await
+        })?;
+
+        log::info!("Export {} blocks to {:?}", count, path);
+        Ok(())
+    }
+
+    /// Replay the blocks of the stream on top of the local chain, stop at the
+    /// first block whose roots are different from the re-executed ones.
+    pub fn import_blocks(self, path: &Path) -> ProtocolResult<()> {
+        let rt = RuntimeBuilder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("new tokio runtime");
+
+        let report = rt.block_on(async move {
+            let (storage, trie_db) = self.open_databases()?;
+            let current_block = storage.get_latest_block(Context::new()).await?;
+            let backend = AxonExecutorAdapter::from_root(
+                current_block.header.state_root,
+                Arc::clone(&trie_db),
+                Arc::clone(&storage),
+                Proposal::from(current_block.header.clone()).into(),
+            )?;
+            system_contract::init(
+                self.config.data_path_for_system_contract(),
+                self.config.rocksdb.clone(),
+                backend,
+            );
+
+            let metadata_adapter =
+                MetadataAdapterImpl::new(Arc::clone(&storage), Arc::clone(&trie_db));
+            let metadata_controller = Arc::new(MetadataController::new(
+                Arc::new(metadata_adapter),
+                self.config.epoch_len,
+                self.config.metadata_contract_address,
+            ));
+
+            replay::import_blocks(
+                storage,
+                trie_db,
+                metadata_controller,
+                self.config.metadata_contract_address,
+                path,
+            )
+            .await
+        })?;
+
+        log::info!(
+            "Replay {} blocks and skip {} blocks, the latest block is {}",
+            report.imported,
+            report.skipped,
+            report.latest
+        );
+        Ok(())
+    }
+
+    /// Roll back the chain to the given block offline. The blocks above it
+    /// are removed with their transactions and receipts, and the WALs of the
+    /// removed blocks are cleared.
+    pub fn rollback(self, number: u64) -> ProtocolResult<()> {
+        let rt = RuntimeBuilder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("new tokio runtime");
+
+        let removed = rt.block_on(async move {
+            let (storage, _) = self.open_databases()?;
+            storage
+                .get_block(Context::new(), number)
+                .await?
+                .ok_or_else(|| MainError::Other(format!("block {} is not found", number)))?;
+
+            // The state of the block is kept in the trie db, the node switches
+            // the system contracts to it when it starts from the latest block.
+            let removed = storage.rollback_to(Context::new(), number).await?;
+
+            SignedTxsWAL::new(self.config.data_path_for_txs_wal()).remove_all()?;
+            ConsensusWal::new(self.config.data_path_for_consensus_wal()).clear()?;
+
+            ProtocolResult::Ok(removed)
+        })?;
+
+        log::info!(
+            "Roll back to block {}, {} blocks are removed",
+            number,
+            removed
+        );
+        Ok(())
+    }
+
+    /// List the heights and the transaction counts of the signed
+    /// transactions WAL and the files of the consensus WAL.
+    pub fn inspect_wal(self) -> ProtocolResult<()> {
+        let txs_wal = SignedTxsWAL::new(self.config.data_path_for_txs_wal());
+        println!("Signed transactions WAL:");
+        for (number, files) in txs_wal.inspect()? {
+            let txs = files.iter().map(|f| f.records).sum::<usize>();
+            let corrupt = files.iter().filter(|f| f.corrupt_bytes > 0).count();
+            println!(
+                "  height {}: {} txs in {} files, {} corrupt files",
+                number,
+                txs,
+                files.len(),
+                corrupt
+            );
+        }
+
+        let consensus_wal = ConsensusWal::new(self.config.data_path_for_consensus_wal());
+        println!("Consensus WAL:");
+        for file in consensus_wal.inspect()? {
+            println!(
+                "  {:?}: {} records, {} corrupt bytes",
+                file.path, file.records, file.corrupt_bytes
+            );
+        }
+        Ok(())
+    }
+
+    /// Remove the torn signed transactions WAL files, cut the corrupt tails of
+    /// the consensus WAL files and remove the invalid ones.
+    pub fn repair_wal(self) -> ProtocolResult<()> {
+        let txs = SignedTxsWAL::new(self.config.data_path_for_txs_wal()).repair()?;
+        let consensus = ConsensusWal::new(self.config.data_path_for_consensus_wal()).repair()?;
+
+        log::info!(
+            "Repair {} signed transactions WAL files and {} consensus WAL files",
+            txs,
+            consensus
+        );
+        Ok(())
+    }
+
+    /// Remove the signed transactions WAL of the committed heights, keep the
+    /// latest consensus WAL state only, and rewrite the files of the older
+    /// versions in the current format.
+    pub fn compact_wal(self) -> ProtocolResult<()> {
+        let rt = RuntimeBuilder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("new tokio runtime");
+
+        let latest = rt.block_on(async {
+            let (storage, _) = self.open_databases()?;
+            let block = storage.get_latest_block(Context::new()).await?;
+            ProtocolResult::Ok(block.header.number)
+        })?;
+
+        let txs = SignedTxsWAL::new(self.config.data_path_for_txs_wal()).compact(latest)?;
+        ConsensusWal::new(self.config.data_path_for_consensus_wal()).compact()?;
+
+        log::info!(
+            "Compact {} signed transactions WAL files at block {}",
+            txs,
+            latest
+        );
+        Ok(())
+    }
+
+    /// List or change the peer store, through the admin RPC if the node is
+    /// running.
+    pub fn peers(self, cmd: PeersCommand) -> ProtocolResult<()> {
+        let rt = RuntimeBuilder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("new tokio runtime");
+
+        let output = rt.block_on(peers::execute(
+            self.config.rpc.admin_listening_address,
+            &self.config.data_path,
+            &self.config.data_path_for_peer_store(),
+            cmd,
+        ))?;
+
+        match output {
+            peers::PeersOutput::Snapshot(snapshot) => {
+                let now_ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("system time")
+                    .as_millis() as u64;
+                peers::print_snapshot(&snapshot, now_ms);
+            }
+            peers::PeersOutput::Count(count) => println!("{} changed", count),
+        }
+        Ok(())
+    }
+
+    fn open_databases(&self) -> ProtocolResult<(Arc<ImplStorage<RocksAdapter>>, Arc<RocksTrieDB>)> {
+        let config = &self.config;
+        let rocks_adapter = Arc::new(RocksAdapter::new(
+            config.data_path_for_block(),
+            config.rocksdb.clone(),
+        )?);
+        let storage = Arc::new(ImplStorage::new(rocks_adapter, config.rocksdb.cache_size));
+        let trie_db = Arc::new(RocksTrieDB::new(
+            config.data_path_for_state(),
+            config.rocksdb.clone(),
+            config.executor.triedb_cache_size,
+        )?);
+        system_contract::init_db(
+            config.data_path_for_system_contract(),
+            config.rocksdb.clone(),
+        );
+
+        Ok((storage, trie_db))
+    }
+
     pub async fn create_genesis(&mut self) -> ProtocolResult<()> {
         // Init Block db
         let path_block = self.config.data_path_for_block();
@@ -219,6 +515,7 @@ impl Axon {
 
         log::info!("node starts");
         observe_listen_port_occupancy(&[self.config.network.listening_address.clone()]).await?;
+        let _pid_file = peers::PidFile::create(&self.config.data_path)?;
         let config = self.config.clone();
         // Init Block db
         let path_block = config.data_path_for_block();
@@ -247,20 +544,48 @@ impl Axon {
         // Init network
         let network_config = NetworkConfig::new()
             .max_connections(config.network.max_connected_peers)?
-            .peer_store_dir(config.data_path.clone().join("peer_store"))
+            .inbound_conn_limit(config.network.inbound_conn_limit)?
+            .same_ip_conn_limit(config.network.same_ip_conn_limit)
+            .max_wait_streams(config.network.max_wait_streams)
+            .write_timeout(config.network.write_timeout)
+            .selfcheck_interval(config.network.selfcheck_interval)
+            .peer_store_dir(config.data_path_for_peer_store())
             .ping_interval(config.network.ping_interval)
             .max_frame_length(config.network.max_frame_length)
             .send_buffer_size(config.network.send_buffer_size)
-            .recv_buffer_size(config.network.recv_buffer_size);
+            .recv_buffer_size(config.network.recv_buffer_size)
+            .zstd_dict(config.network.zstd_dict_path.clone())?
+            .peer_trust_metric(
+                config.network.trust_interval_duration,
+                config.network.trust_max_history_duration,
+            )?
+            .peer_fatal_ban(config.network.fatal_ban_duration)
+            .peer_soft_ban(config.network.soft_ban_duration)
+            .consensus_queue_size(config.network.consensus_queue_size);
 
         let network_privkey = config.privkey.as_string_trim0x();
 
-        // let allowlist = config.network.allowlist.clone().unwrap_or_default();
-        let network_config = network_config
+        let allowlist = config.network.allowlist.clone().unwrap_or_default();
+        let mut network_config = network_config
             .bootstraps(self.config.network.bootstraps.clone().unwrap_or_default().iter().map(|addr| addr.multi_address.clone()).collect())
-            // .allowlist(allowlist)?
+            .dns_trees(config.network.dns_trees.clone().unwrap_or_default())?
+            .dns_seed_interval(config.network.dns_seed_interval)
+            .allowlist(allowlist)?
+            .allowlist_only(config.network.allowlist_only)
             .listen_addr(self.config.network.listening_address.clone())
-            .secio_keypair(&network_privkey)?;
+            .announce_addrs(config.network.announce_addresses.clone().unwrap_or_default())
+            .nat_port_mapping(config.network.nat_port_mapping)
+            .secio_keypair(&network_privkey)?
+            .transport_security(config.network.transport_security.clone())?;
+
+        let rate_limits = config.network.rate_limits.clone().unwrap_or_default();
+        for (endpoint, rate, burst) in DEFAULT_RATE_LIMITS.iter().copied().chain(
+            rate_limits
+                .iter()
+                .map(|limit| (limit.endpoint.as_str(), limit.rate, limit.burst)),
+        ) {
+            network_config = network_config.rate_limit(endpoint, rate, burst)?;
+        }
 
         let mut network_service = NetworkService::new(network_config);
         network_service.set_chain_id(self.genesis.block.header.chain_id.to_string());
@@ -335,11 +660,15 @@ impl Axon {
                 config.mempool.pool_size as usize,
                 config.mempool.broadcast_txs_size,
                 config.mempool.broadcast_txs_interval,
+                config.mempool.verify_pool_size,
             );
         let mempool = Arc::new(
             MemPoolImpl::new(
                 config.mempool.pool_size as usize,
                 config.mempool.timeout_gap,
+                config.mempool.tx_ttl,
+                config.mempool.private_tx_timeout_gap,
+                config.mempool.dropped_tx_cache_size,
                 mempool_adapter,
                 current_stxs.clone(),
                 config.crosschain_contract_address,
@@ -370,6 +699,24 @@ impl Axon {
             NewTxsHandler::new(Arc::clone(&mempool)),
         )?;
 
+        // register announced transaction hashes
+        network_service.register_endpoint_handler(
+            END_GOSSIP_ANNOUNCE_TXS,
+            AnnounceTxsHandler::new(Arc::clone(&mempool)),
+        )?;
+
+        // register private txs forwarded to validators
+        network_service.register_endpoint_handler(
+            END_GOSSIP_PRIVATE_TXS,
+            PrivateTxsHandler::new(Arc::clone(&mempool)),
+        )?;
+
+        // register compact txs relayed by the proposer
+        network_service.register_endpoint_handler(
+            END_GOSSIP_COMPACT_TXS,
+            CompactTxsHandler::new(Arc::clone(&mempool)),
+        )?;
+
         // register pull txs from other node
         network_service.register_endpoint_handler(
             RPC_PULL_TXS,
@@ -472,6 +819,11 @@ impl Axon {
 
         let synchronization = Arc::new(OverlordSynchronization::<_>::new(
             config.consensus.sync_txs_chunk_size,
+            config
+                .consensus
+                .snapshot_sync
+                .as_ref()
+                .map(|checkpoint| (checkpoint.number, checkpoint.hash)),
             consensus_adapter,
             status_agent.clone(),
             lock,
@@ -522,9 +874,19 @@ impl Axon {
             RPC_SYNC_PULL_TXS,
             PullTxsRpcHandler::new(Arc::new(network_service.handle()), Arc::clone(&storage)),
         )?;
+
+        network_service.register_endpoint_handler(
+            RPC_SYNC_PULL_TRIE_NODES,
+            PullTrieNodesRpcHandler::new(
+                Arc::new(network_service.handle()),
+                Arc::clone(&storage),
+                Arc::clone(&trie_db),
+            ),
+        )?;
         network_service.register_rpc_response(RPC_RESP_SYNC_PULL_BLOCK)?;
         network_service.register_rpc_response(RPC_RESP_SYNC_PULL_PROOF)?;
         network_service.register_rpc_response(RPC_RESP_SYNC_PULL_TXS)?;
+        network_service.register_rpc_response(RPC_RESP_SYNC_PULL_TRIE_NODES)?;
 
         let network_handle = network_service.handle();
 
@@ -536,9 +898,10 @@ impl Axon {
             Arc::clone(&mempool),
             Arc::clone(&storage),
             Arc::clone(&trie_db),
-            Arc::new(network_handle),
+            Arc::new(network_handle.clone()),
         ));
-        let _handles = run_jsonrpc_server(self.config.clone(), api_adapter).await?;
+        let _handles =
+            run_jsonrpc_server(self.config.clone(), api_adapter, Arc::new(network_handle)).await?;
 
         // Run sync
         tokio::spawn(async move {
@@ -0,0 +1,206 @@
+//! Inspect and change the peer store. The admin RPC of the running node is
+//! used when it answers, otherwise the files of the stopped node are changed
+//! in place.
+
+use std::fs;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use core_api::jsonrpc::{admin_rpc_client, AxonAdminRpcClient};
+use core_api::APIError;
+use core_network::OfflinePeerStore;
+use protocol::traits::PeerStoreAdmin;
+use protocol::types::PeerStoreSnapshot;
+use protocol::ProtocolResult;
+
+use crate::MainError;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const PID_FILE: &str = "axon.pid";
+
+#[derive(Clone, Debug)]
+pub enum PeersCommand {
+    List,
+    Ban {
+        target:   String,
+        duration: u64,
+        reason:   String,
+    },
+    Unban(String),
+    Forget(String),
+    Import(Vec<String>),
+    Allowlist(Vec<String>),
+}
+
+pub enum PeersOutput {
+    Snapshot(PeerStoreSnapshot),
+    Count(usize),
+}
+
+/// The pid file of the running node in the data path, removed when the node
+/// stops.
+pub struct PidFile(PathBuf);
+
+impl PidFile {
+    pub fn create(data_path: &Path) -> ProtocolResult<Self> {
+        fs::create_dir_all(data_path).map_err(MainError::Io)?;
+        let path = data_path.join(PID_FILE);
+        fs::write(&path, std::process::id().to_string()).map_err(MainError::Io)?;
+        Ok(PidFile(path))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            log::warn!("Remove pid file {:?} failed: {}", self.0, e);
+        }
+    }
+}
+
+/// The pid of the node running on the data path. A pid file left by a killed
+/// node is ignored.
+fn running_pid(data_path: &Path) -> Option<u32> {
+    let pid = fs::read_to_string(data_path.join(PID_FILE))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    is_alive(pid).then_some(pid)
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    // The signal 0 checks the process only, the process of another user fails
+    // with EPERM
+    let ret = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    ret == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Run the command on the running node if the admin address answers, or else
+/// on the peer store files. The files are not changed while the node runs,
+/// since it overwrites them.
+pub async fn execute(
+    admin_addr: Option<SocketAddr>,
+    data_path: &Path,
+    peer_store_dir: &Path,
+    cmd: PeersCommand,
+) -> ProtocolResult<PeersOutput> {
+    if let Some(addr) = admin_addr.filter(|addr| is_listening(*addr)) {
+        log::info!("Peer store of the running node at {}", addr);
+        return execute_online(addr, cmd).await;
+    }
+
+    if !matches!(cmd, PeersCommand::List) {
+        if let Some(pid) = running_pid(data_path) {
+            return Err(MainError::Other(format!(
+                "The node {} is running, change its peer store through the admin rpc",
+                pid
+            ))
+            .into());
+        }
+    }
+    execute_offline(&OfflinePeerStore::open(peer_store_dir), cmd)
+}
+
+async fn execute_online(addr: SocketAddr, cmd: PeersCommand) -> ProtocolResult<PeersOutput> {
+    let client = admin_rpc_client(addr)?;
+    let output = match cmd {
+        PeersCommand::List => client.peers().await.map(PeersOutput::Snapshot),
+        PeersCommand::Ban {
+            target,
+            duration,
+            reason,
+        } => client
+            .ban_peer(target, duration, reason)
+            .await
+            .map(PeersOutput::Count),
+        PeersCommand::Unban(target) => client.unban_peer(target).await.map(PeersOutput::Count),
+        PeersCommand::Forget(target) => client.forget_peer(target).await.map(PeersOutput::Count),
+        PeersCommand::Import(addrs) => client.import_peers(addrs).await.map(PeersOutput::Count),
+        PeersCommand::Allowlist(peer_ids) => {
+            client.set_allowlist(peer_ids).await.map(PeersOutput::Count)
+        }
+    };
+
+    output.map_err(|e| APIError::AdminClient(e.to_string()).into())
+}
+
+fn execute_offline(store: &dyn PeerStoreAdmin, cmd: PeersCommand) -> ProtocolResult<PeersOutput> {
+    let output = match cmd {
+        PeersCommand::List => PeersOutput::Snapshot(store.peer_store_snapshot()?),
+        PeersCommand::Ban {
+            target,
+            duration,
+            reason,
+        } => PeersOutput::Count(store.ban_peer(&target, duration, reason)?),
+        PeersCommand::Unban(target) => PeersOutput::Count(store.unban_peer(&target)?),
+        PeersCommand::Forget(target) => PeersOutput::Count(store.forget_peer(&target)?),
+        PeersCommand::Import(addrs) => PeersOutput::Count(store.import_peers(addrs)?),
+        PeersCommand::Allowlist(peer_ids) => PeersOutput::Count(store.set_allowlist(peer_ids)?),
+    };
+
+    Ok(output)
+}
+
+fn is_listening(addr: SocketAddr) -> bool {
+    TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok()
+}
+
+/// Print the saved addresses and the banned networks, the times are relative
+/// to now.
+pub fn print_snapshot(snapshot: &PeerStoreSnapshot, now_ms: u64) {
+    println!("Addresses:");
+    for record in snapshot.addrs.iter() {
+        let last_connected = if record.last_connected_at_ms == 0 {
+            "never".to_owned()
+        } else {
+            format!(
+                "{} ago",
+                humanize(now_ms.saturating_sub(record.last_connected_at_ms))
+            )
+        };
+        let trust_score = record
+            .trust_score
+            .map(|score| score.to_string())
+            .unwrap_or_else(|| "-".to_owned());
+
+        println!(
+            "  {}: last connected {}, {} attempts, score {}, trust score {}",
+            record.addr, last_connected, record.attempts_count, record.score, trust_score
+        );
+    }
+
+    println!("Banned:");
+    for record in snapshot.banned.iter() {
+        let until = if record.ban_until_ms > now_ms {
+            format!("for {}", humanize(record.ban_until_ms - now_ms))
+        } else {
+            "expired".to_owned()
+        };
+
+        println!(
+            "  {}: {}, banned {} ago, reason {:?}",
+            record.address,
+            until,
+            humanize(now_ms.saturating_sub(record.created_at_ms)),
+            record.ban_reason
+        );
+    }
+}
+
+fn humanize(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
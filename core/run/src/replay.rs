@@ -0,0 +1,712 @@
+//! Export the blocks in a length-prefixed RLP stream and replay them against
+//! the local chain. Every block is re-executed and the roots are compared
+//! with the header, the replay stops at the first mismatch with a diff report.
+//! Every block is exported with its own proof, which is carried by the next
+//! block in the chain, so that the latest proof is right after the replay.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use rlp_derive::{RlpDecodable, RlpEncodable};
+
+use core_consensus::engine::{contains_change_metadata, generate_receipts_and_logs};
+use core_executor::{AxonExecutor, AxonExecutorAdapter};
+use protocol::traits::{Context, Executor, MetadataControl, Storage};
+use protocol::types::{
+    Block, ExecResp, Hash, MerkleRoot, Proof, Proposal, SignedTransaction, H160, U256,
+};
+use protocol::{trie, Display, ProtocolError, ProtocolErrorKind, ProtocolResult};
+
+const MAX_BLOCK_SIZE: usize = 64 * 1024 * 1024;
+
+/// A block of the stream with its signed transactions and its proof.
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug, PartialEq, Eq)]
+pub struct ReplayBlock {
+    pub block: Block,
+    pub txs:   Vec<SignedTransaction>,
+    pub proof: Proof,
+}
+
+pub struct BlockWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> BlockWriter<W> {
+    pub fn new(inner: W) -> Self {
+        BlockWriter { inner }
+    }
+
+    pub fn write(&mut self, block: &ReplayBlock) -> ProtocolResult<()> {
+        let payload = rlp::encode(block);
+        self.inner
+            .write_all(&(payload.len() as u32).to_be_bytes())
+            .map_err(ReplayError::Io)?;
+        self.inner.write_all(&payload).map_err(ReplayError::Io)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> ProtocolResult<W> {
+        self.inner.flush().map_err(ReplayError::Io)?;
+        Ok(self.inner)
+    }
+}
+
+pub struct BlockReader<R> {
+    inner: R,
+}
+
+impl<R: Read> BlockReader<R> {
+    pub fn new(inner: R) -> Self {
+        BlockReader { inner }
+    }
+
+    /// Read the next block, return `None` at the end of the stream.
+    pub fn next_block(&mut self) -> ProtocolResult<Option<ReplayBlock>> {
+        let mut len = [0u8; 4];
+        match self.inner.read(&mut len[..1]).map_err(ReplayError::Io)? {
+            0 => return Ok(None),
+            _ => self.read_exact(&mut len[1..])?,
+        }
+
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_BLOCK_SIZE {
+            return Err(ReplayError::OversizedBlock(len).into());
+        }
+
+        let mut payload = vec![0u8; len];
+        self.read_exact(&mut payload)?;
+        Ok(Some(rlp::decode(&payload).map_err(ReplayError::Rlp)?))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> ProtocolResult<()> {
+        self.inner.read_exact(buf).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                ReplayError::Truncated
+            } else {
+                ReplayError::Io(e)
+            }
+        })?;
+        Ok(())
+    }
+}
+
+/// Export the blocks in `[from, to]` with their signed transactions and
+/// proofs.
+pub async fn export_blocks<S: Storage>(
+    storage: Arc<S>,
+    from: u64,
+    to: u64,
+    path: &Path,
+) -> ProtocolResult<u64> {
+    let ctx = Context::new();
+    let latest_number = storage.get_latest_block_header(ctx.clone()).await?.number;
+    if from > to || to > latest_number {
+        return Err(ReplayError::InvalidRange(from, to, latest_number).into());
+    }
+
+    let file = File::create(path).map_err(ReplayError::Io)?;
+    let mut writer = BlockWriter::new(BufWriter::new(file));
+
+    for number in from..=to {
+        let block = storage
+            .get_block(ctx.clone(), number)
+            .await?
+            .ok_or(ReplayError::MissingBlock(number))?;
+        let txs = storage
+            .get_transactions(ctx.clone(), number, &block.tx_hashes)
+            .await?
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ReplayError::MissingBlock(number))?;
+        let proof = if number == latest_number {
+            storage.get_latest_proof(ctx.clone()).await?
+        } else {
+            storage
+                .get_block_header(ctx.clone(), number + 1)
+                .await?
+                .ok_or(ReplayError::MissingBlock(number + 1))?
+                .proof
+        };
+
+        writer.write(&ReplayBlock { block, txs, proof })?;
+    }
+
+    writer.finish()?;
+    Ok(to - from + 1)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub imported: u64,
+    pub skipped:  u64,
+    pub latest:   u64,
+}
+
+/// The difference between the header of a block and the result of
+/// re-executing it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockDiff {
+    pub number:        u64,
+    pub hash:          Hash,
+    pub state_root:    (MerkleRoot, MerkleRoot),
+    pub receipts_root: (MerkleRoot, MerkleRoot),
+    pub gas_used:      (U256, U256),
+    pub txs:           Vec<(Hash, String, u64)>,
+}
+
+impl BlockDiff {
+    fn new(block: &Block, txs: &[SignedTransaction], resp: &ExecResp) -> Option<Self> {
+        let header = &block.header;
+        let diff = BlockDiff {
+            number:        header.number,
+            hash:          block.hash(),
+            state_root:    (header.state_root, resp.state_root),
+            receipts_root: (header.receipts_root, resp.receipt_root),
+            gas_used:      (header.gas_used, resp.gas_used.into()),
+            txs:           txs
+                .iter()
+                .zip(resp.tx_resp.iter())
+                .map(|(tx, res)| {
+                    (
+                        tx.transaction.hash,
+                        format!("{:?}", res.exit_reason),
+                        res.gas_used,
+                    )
+                })
+                .collect(),
+        };
+
+        if diff.state_root.0 == diff.state_root.1
+            && diff.receipts_root.0 == diff.receipts_root.1
+            && diff.gas_used.0 == diff.gas_used.1
+        {
+            return None;
+        }
+
+        Some(diff)
+    }
+}
+
+impl fmt::Display for BlockDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "block {} {:?} mismatch", self.number, self.hash)?;
+        for (name, expect, actual) in [
+            ("state root", self.state_root.0, self.state_root.1),
+            ("receipts root", self.receipts_root.0, self.receipts_root.1),
+        ] {
+            let mark = if expect == actual { " " } else { "*" };
+            writeln!(
+                f,
+                "{} {:<13} expect {:?}, actual {:?}",
+                mark, name, expect, actual
+            )?;
+        }
+
+        let mark = if self.gas_used.0 == self.gas_used.1 {
+            " "
+        } else {
+            "*"
+        };
+        write!(
+            f,
+            "{} {:<13} expect {}, actual {}",
+            mark, "gas used", self.gas_used.0, self.gas_used.1
+        )?;
+
+        for (hash, exit_reason, gas_used) in self.txs.iter() {
+            write!(
+                f,
+                "\n  tx {:?} gas used {}, {}",
+                hash, gas_used, exit_reason
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Replay the blocks of the stream on top of the latest block. The blocks
+/// which are already in the chain are skipped if their hashes match. The
+/// metadata is updated after the blocks which call the metadata contract, the
+/// same as the consensus engine does.
+pub async fn import_blocks<S, DB, M>(
+    storage: Arc<S>,
+    trie_db: Arc<DB>,
+    metadata: Arc<M>,
+    metadata_address: H160,
+    path: &Path,
+) -> ProtocolResult<ReplayReport>
+where
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+    M: MetadataControl + 'static,
+{
+    let ctx = Context::new();
+    let mut latest = storage.get_latest_block(ctx.clone()).await?;
+    metadata.get_metadata(ctx.clone(), &latest.header)?;
+
+    let file = File::open(path).map_err(ReplayError::Io)?;
+    let mut reader = BlockReader::new(BufReader::new(file));
+    let mut report = ReplayReport::default();
+
+    while let Some(ReplayBlock { block, txs, proof }) = reader.next_block()? {
+        let number = block.header.number;
+        if number <= latest.header.number {
+            let stored = storage
+                .get_block_header(ctx.clone(), number)
+                .await?
+                .ok_or(ReplayError::MissingBlock(number))?;
+            if stored != block.header {
+                return Err(ReplayError::Fork(number).into());
+            }
+
+            report.skipped += 1;
+            continue;
+        }
+
+        if number != latest.header.number + 1 || block.header.prev_hash != latest.hash() {
+            return Err(ReplayError::Discontinuous(number, latest.header.number).into());
+        }
+
+        let tx_hashes = txs.iter().map(|tx| tx.transaction.hash).collect::<Vec<_>>();
+        if tx_hashes != block.tx_hashes {
+            return Err(ReplayError::InvalidTxs(number).into());
+        }
+        if proof.number != number || proof.block_hash != block.hash() {
+            return Err(ReplayError::InvalidProof(number).into());
+        }
+
+        let resp = exec(&storage, &trie_db, &metadata, &latest, &block, &txs)?;
+        if let Some(diff) = BlockDiff::new(&block, &txs, &resp) {
+            return Err(ReplayError::Mismatch(Box::new(diff)).into());
+        }
+
+        let is_change_metadata = contains_change_metadata(metadata_address, &txs);
+        let (receipts, _logs) =
+            generate_receipts_and_logs(number, block.hash(), block.header.state_root, &txs, &resp);
+
+        storage
+            .insert_transactions(ctx.clone(), number, txs)
+            .await?;
+        storage
+            .insert_receipts(ctx.clone(), number, receipts)
+            .await?;
+        storage.update_latest_proof(ctx.clone(), proof).await?;
+        storage.insert_block(ctx.clone(), block.clone()).await?;
+
+        if is_change_metadata {
+            metadata.update_metadata(ctx.clone(), &block.header)?;
+        }
+
+        report.imported += 1;
+        latest = block;
+    }
+
+    report.latest = latest.header.number;
+    Ok(report)
+}
+
+fn exec<S, DB, M>(
+    storage: &Arc<S>,
+    trie_db: &Arc<DB>,
+    metadata: &Arc<M>,
+    latest: &Block,
+    block: &Block,
+    txs: &[SignedTransaction],
+) -> ProtocolResult<ExecResp>
+where
+    S: Storage + 'static,
+    DB: trie::DB + 'static,
+    M: MetadataControl + 'static,
+{
+    let proposal = Proposal::from(block);
+    let number = proposal.number;
+    let mut backend = AxonExecutorAdapter::from_root(
+        latest.header.state_root,
+        Arc::clone(trie_db),
+        Arc::clone(storage),
+        proposal.into(),
+    )?;
+
+    Ok(AxonExecutor::default().exec(
+        &mut backend,
+        txs,
+        &metadata
+            .get_metadata_unchecked(Context::new(), number)
+            .verifier_list,
+    ))
+}
+
+#[derive(Debug, Display)]
+pub enum ReplayError {
+    #[display(fmt = "Block stream io error {:?}", _0)]
+    Io(io::Error),
+
+    #[display(fmt = "Block stream decode error {:?}", _0)]
+    Rlp(rlp::DecoderError),
+
+    #[display(fmt = "Block stream is truncated")]
+    Truncated,
+
+    #[display(fmt = "Block size {} is too large", _0)]
+    OversizedBlock(usize),
+
+    #[display(fmt = "Invalid range [{}, {}], latest block {}", _0, _1, _2)]
+    InvalidRange(u64, u64, u64),
+
+    #[display(fmt = "Block {} is missing", _0)]
+    MissingBlock(u64),
+
+    #[display(fmt = "Block {} is different from the local one", _0)]
+    Fork(u64),
+
+    #[display(fmt = "Block {} does not follow the latest block {}", _0, _1)]
+    Discontinuous(u64, u64),
+
+    #[display(fmt = "Transactions of block {} do not match the tx hashes", _0)]
+    InvalidTxs(u64),
+
+    #[display(fmt = "Proof of block {} does not match the block", _0)]
+    InvalidProof(u64),
+
+    #[display(fmt = "Replay mismatch, {}", _0)]
+    Mismatch(Box<BlockDiff>),
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<ReplayError> for ProtocolError {
+    fn from(error: ReplayError) -> ProtocolError {
+        ProtocolError::new(ProtocolErrorKind::Main, Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use common_crypto::{
+        Crypto, PrivateKey, Secp256k1Recoverable, Secp256k1RecoverablePrivateKey, Signature,
+    };
+    use core_executor::MPTTrie;
+    use core_storage::{adapter::memory::MemoryAdapter, ImplStorage};
+    use protocol::codec::ProtocolCodec;
+    use protocol::rand::{random, rngs::OsRng};
+    use protocol::tokio;
+    use protocol::trie::MemoryDB;
+    use protocol::types::{
+        Account, Bytes, Eip1559Transaction, ExitReason, ExitSucceed, Header, Metadata,
+        SignatureComponents, TransactionAction, TxResp, UnsignedTransaction, UnverifiedTransaction,
+        MAX_BLOCK_GAS_LIMIT, NIL_DATA, RLP_NULL,
+    };
+
+    use super::*;
+
+    const CHAIN_ID: u64 = 5;
+
+    #[derive(Default)]
+    struct MockMetadata {
+        updated: AtomicU64,
+    }
+
+    impl MetadataControl for MockMetadata {
+        fn calc_epoch(&self, _block_number: u64) -> u64 {
+            0
+        }
+
+        fn need_change_metadata(&self, _block_number: u64) -> bool {
+            false
+        }
+
+        fn update_metadata(&self, _ctx: Context, _header: &Header) -> ProtocolResult<()> {
+            self.updated.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn get_metadata(&self, _ctx: Context, _header: &Header) -> ProtocolResult<Metadata> {
+            Ok(Metadata::default())
+        }
+
+        fn get_metadata_unchecked(&self, _ctx: Context, _block_number: u64) -> Metadata {
+            Metadata::default()
+        }
+    }
+
+    fn mock_block(number: u64) -> ReplayBlock {
+        ReplayBlock {
+            block: Block {
+                header:    Header {
+                    number,
+                    ..Default::default()
+                },
+                tx_hashes: vec![],
+            },
+            txs:   vec![],
+            proof: Proof::default(),
+        }
+    }
+
+    fn mock_transfer(
+        priv_key: &Secp256k1RecoverablePrivateKey,
+        nonce: u64,
+        to: H160,
+    ) -> SignedTransaction {
+        let mut utx = UnverifiedTransaction {
+            unsigned:  UnsignedTransaction::Eip1559(Eip1559Transaction {
+                nonce:                    nonce.into(),
+                max_priority_fee_per_gas: U256::one(),
+                gas_price:                U256::one(),
+                gas_limit:                U256::from(21000u64),
+                action:                   TransactionAction::Call(to),
+                value:                    U256::one(),
+                data:                     Bytes::new(),
+                access_list:              vec![],
+            }),
+            signature: Some(SignatureComponents {
+                standard_v: 4,
+                r:          Default::default(),
+                s:          Default::default(),
+            }),
+            chain_id:  CHAIN_ID,
+            hash:      Default::default(),
+        };
+
+        let signature = Secp256k1Recoverable::sign_message(
+            utx.signature_hash(true).as_bytes(),
+            &priv_key.to_bytes(),
+        )
+        .unwrap()
+        .to_bytes();
+        utx.signature = Some(signature.into());
+
+        utx.calc_hash().try_into().unwrap()
+    }
+
+    /// A chain with only the genesis block, the sender is funded in the state.
+    async fn mock_genesis(sender: H160) -> (Arc<ImplStorage<MemoryAdapter>>, Arc<MemoryDB>) {
+        let ctx = Context::new();
+        let storage = Arc::new(ImplStorage::new(Arc::new(MemoryAdapter::new()), 10));
+        let trie_db = Arc::new(MemoryDB::new(false));
+
+        let mut state = MPTTrie::new(Arc::clone(&trie_db));
+        let account = Account {
+            nonce:        U256::zero(),
+            balance:      U256::from(u64::MAX),
+            storage_root: RLP_NULL,
+            code_hash:    NIL_DATA,
+        };
+        state
+            .insert(sender.as_bytes(), &account.encode().unwrap())
+            .unwrap();
+
+        let genesis = Block {
+            header:    Header {
+                state_root: state.commit().unwrap(),
+                chain_id: CHAIN_ID,
+                ..Default::default()
+            },
+            tx_hashes: vec![],
+        };
+        storage
+            .update_latest_proof(ctx.clone(), Proof {
+                block_hash: genesis.hash(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        storage.insert_block(ctx, genesis).await.unwrap();
+
+        (storage, trie_db)
+    }
+
+    /// Execute the blocks on top of the latest block and commit them the same
+    /// as the consensus engine, every block carries the proof of the previous
+    /// one.
+    async fn mock_chain(
+        storage: &Arc<ImplStorage<MemoryAdapter>>,
+        trie_db: &Arc<MemoryDB>,
+        blocks: Vec<Vec<SignedTransaction>>,
+    ) {
+        let ctx = Context::new();
+        let metadata = Arc::new(MockMetadata::default());
+        let mut latest = storage.get_latest_block(ctx.clone()).await.unwrap();
+        let mut proof = storage.get_latest_proof(ctx.clone()).await.unwrap();
+
+        for txs in blocks.into_iter() {
+            let number = latest.header.number + 1;
+            let proposal = Block {
+                header:    Header {
+                    prev_hash: latest.hash(),
+                    timestamp: number,
+                    number,
+                    gas_limit: MAX_BLOCK_GAS_LIMIT.into(),
+                    proof: proof.clone(),
+                    chain_id: CHAIN_ID,
+                    ..Default::default()
+                },
+                tx_hashes: txs.iter().map(|tx| tx.transaction.hash).collect(),
+            };
+            let resp = exec(storage, trie_db, &metadata, &latest, &proposal, &txs).unwrap();
+            let block = Block::new(Proposal::from(&proposal), resp.clone());
+            let (receipts, _logs) = generate_receipts_and_logs(
+                number,
+                block.hash(),
+                block.header.state_root,
+                &txs,
+                &resp,
+            );
+
+            proof = Proof {
+                number,
+                block_hash: block.hash(),
+                ..Default::default()
+            };
+            storage
+                .insert_transactions(ctx.clone(), number, txs)
+                .await
+                .unwrap();
+            storage
+                .insert_receipts(ctx.clone(), number, receipts)
+                .await
+                .unwrap();
+            storage
+                .update_latest_proof(ctx.clone(), proof.clone())
+                .await
+                .unwrap();
+            storage
+                .insert_block(ctx.clone(), block.clone())
+                .await
+                .unwrap();
+            latest = block;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_exported_chain() {
+        let ctx = Context::new();
+        let metadata_address = H160::from_low_u64_be(0xff);
+        let priv_key = Secp256k1RecoverablePrivateKey::generate(&mut OsRng);
+        let blocks = (0..10u64)
+            .map(|n| {
+                (0..3u64)
+                    .map(|i| {
+                        // The only call to the metadata contract is in block 5.
+                        let to = if n == 4 && i == 0 {
+                            metadata_address
+                        } else {
+                            H160::from_low_u64_be(n * 3 + i + 1)
+                        };
+                        mock_transfer(&priv_key, n * 3 + i, to)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let sender = blocks[0][0].sender;
+
+        let (storage, trie_db) = mock_genesis(sender).await;
+        mock_chain(&storage, &trie_db, blocks).await;
+        let path = std::env::temp_dir().join(format!("axon-blocks-{}", random::<u64>()));
+        assert_eq!(
+            export_blocks(Arc::clone(&storage), 1, 10, &path)
+                .await
+                .unwrap(),
+            10
+        );
+
+        let (new_storage, new_trie_db) = mock_genesis(sender).await;
+        let metadata = Arc::new(MockMetadata::default());
+        let report = import_blocks(
+            Arc::clone(&new_storage),
+            Arc::clone(&new_trie_db),
+            Arc::clone(&metadata),
+            metadata_address,
+            &path,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report, ReplayReport {
+            imported: 10,
+            skipped:  0,
+            latest:   10,
+        });
+        assert_eq!(metadata.updated.load(Ordering::SeqCst), 1);
+
+        for number in 1..=10 {
+            let header = storage
+                .get_block_header(ctx.clone(), number)
+                .await
+                .unwrap()
+                .unwrap();
+            let new_header = new_storage
+                .get_block_header(ctx.clone(), number)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(new_header.state_root, header.state_root);
+            assert_eq!(new_header, header);
+        }
+        assert_eq!(
+            new_storage.get_latest_proof(ctx.clone()).await.unwrap(),
+            storage.get_latest_proof(ctx).await.unwrap()
+        );
+
+        // The blocks already in the chain are skipped.
+        let report = import_blocks(new_storage, new_trie_db, metadata, metadata_address, &path)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.skipped, 10);
+        assert_eq!(report.imported, 0);
+    }
+
+    #[test]
+    fn test_block_stream() {
+        let mut writer = BlockWriter::new(Vec::new());
+        for i in 0..3 {
+            writer.write(&mock_block(i)).unwrap();
+        }
+        let data = writer.finish().unwrap();
+
+        let mut reader = BlockReader::new(data.as_slice());
+        for i in 0..3 {
+            assert_eq!(reader.next_block().unwrap(), Some(mock_block(i)));
+        }
+        assert_eq!(reader.next_block().unwrap(), None);
+
+        let mut reader = BlockReader::new(&data[..data.len() - 1]);
+        assert!(reader.next_block().unwrap().is_some());
+        assert!(reader.next_block().unwrap().is_some());
+        assert!(reader.next_block().is_err());
+    }
+
+    #[test]
+    fn test_block_diff() {
+        let ReplayBlock { block, txs, .. } = mock_block(1);
+        let mut resp = ExecResp {
+            state_root:   Default::default(),
+            receipt_root: Default::default(),
+            gas_used:     0,
+            tx_resp:      vec![],
+        };
+        assert!(BlockDiff::new(&block, &txs, &resp).is_none());
+
+        resp.gas_used = 21000;
+        resp.tx_resp.push(TxResp {
+            exit_reason:  ExitReason::Succeed(ExitSucceed::Stopped),
+            ret:          vec![],
+            gas_used:     21000,
+            remain_gas:   0,
+            fee_cost:     U256::zero(),
+            logs:         vec![],
+            code_address: None,
+            removed:      false,
+        });
+        let diff = BlockDiff::new(&block, &txs, &resp).unwrap();
+        assert_eq!(diff.gas_used, (U256::zero(), U256::from(21000)));
+        assert!(diff.to_string().contains("* gas used"));
+        assert!(diff.to_string().contains("  state root"));
+    }
+}
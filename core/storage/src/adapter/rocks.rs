@@ -39,6 +39,7 @@ impl RocksAdapter {
             map_category(StorageCategory::Code),
             map_category(StorageCategory::CkbCrossChain),
             map_category(StorageCategory::IbcCrossChain),
+            map_category(StorageCategory::Evidence),
         ];
 
         let (mut opts, cf_descriptors) = if let Some(ref file) = config.options_file {
@@ -288,6 +289,7 @@ const C_HASH_HEIGHT_MAP: &str = "c6";
 const C_EVM_CODE_MAP: &str = "c7";
 const C_CKB_CROSSCHAIN_MAP: &str = "c8";
 const C_IBC_CROSSCHAIN_MAP: &str = "c9";
+const C_EVIDENCES: &str = "c10";
 
 fn map_category(c: StorageCategory) -> &'static str {
     match c {
@@ -300,6 +302,7 @@ fn map_category(c: StorageCategory) -> &'static str {
         StorageCategory::Code => C_EVM_CODE_MAP,
         StorageCategory::CkbCrossChain => C_CKB_CROSSCHAIN_MAP,
         StorageCategory::IbcCrossChain => C_IBC_CROSSCHAIN_MAP,
+        StorageCategory::Evidence => C_EVIDENCES,
     }
 }
 
@@ -19,12 +19,12 @@ use common_apm::Instant;
 use common_apm_derive::trace_span;
 use protocol::codec::ProtocolCodec;
 use protocol::traits::{
-    CommonStorage, Context, Storage, StorageAdapter, StorageBatchModify, StorageCategory,
-    StorageSchema,
+    CommonStorage, Context, MaintenanceStorage, Storage, StorageAdapter, StorageBatchModify,
+    StorageCategory, StorageSchema,
 };
 use protocol::types::{
-    Block, BlockNumber, Bytes, DBBytes, Hash, Hasher, Header, Proof, Receipt, SignedTransaction,
-    H256,
+    Block, BlockNumber, Bytes, DBBytes, DoubleSignEvidence, Hash, Hasher, Header, Proof, Receipt,
+    SignedTransaction, H256,
 };
 use protocol::{
     async_trait, tokio, Display, From, ProtocolError, ProtocolErrorKind, ProtocolResult,
@@ -33,9 +33,9 @@ use protocol::{
 use crate::cache::StorageCache;
 use crate::hash_key::{BlockKey, CommonHashKey, CommonPrefix};
 use crate::schema::{
-    BlockHashNumberSchema, BlockHeaderSchema, BlockSchema, EvmCodeAddressSchema, EvmCodeSchema,
-    LatestBlockSchema, LatestProofSchema, ReceiptBytesSchema, ReceiptSchema,
-    TransactionBytesSchema, TransactionSchema, TxHashNumberSchema,
+    BlockHashNumberSchema, BlockHeaderSchema, BlockSchema, EvidenceSchema, EvmCodeAddressSchema,
+    EvmCodeSchema, LatestBlockSchema, LatestProofSchema, ReceiptBytesSchema, ReceiptSchema,
+    SnapshotNumberSchema, TransactionBytesSchema, TransactionSchema, TxHashNumberSchema,
 };
 
 const BATCH_VALUE_DECODE_NUMBER: usize = 1000;
@@ -43,6 +43,7 @@ const BATCH_VALUE_DECODE_NUMBER: usize = 1000;
 lazy_static::lazy_static! {
     pub static ref LATEST_BLOCK_KEY: Hash = Hasher::digest(Bytes::from("latest_hash"));
     pub static ref LATEST_PROOF_KEY: Hash = Hasher::digest(Bytes::from("latest_proof"));
+    pub static ref SNAPSHOT_NUMBER_KEY: Hash = Hasher::digest(Bytes::from("snapshot_number"));
     pub static ref OVERLORD_WAL_KEY: Hash = Hasher::digest(Bytes::from("overlord_wal"));
     pub static ref MONITOR_CKB_NUMBER_KEY: Hash = Hasher::digest(Bytes::from("monitor_ckb_number"));
 }
@@ -221,8 +222,20 @@ impl<Adapter: StorageAdapter> CommonStorage for ImplStorage<Adapter> {
         Ok(())
     }
 
-    async fn remove_block(&self, _ctx: Context, height: u64) -> ProtocolResult<()> {
-        self.adapter.remove::<BlockSchema>(BlockKey::new(height))
+    async fn remove_block(&self, ctx: Context, height: u64) -> ProtocolResult<()> {
+        if let Some(block) = self.get_block(ctx, height).await? {
+            let hash = block.hash();
+            self.adapter.remove::<BlockHashNumberSchema>(hash)?;
+            self.cache.block_numbers.lock().pop(&hash);
+        }
+
+        self.adapter
+            .remove::<BlockHeaderSchema>(BlockKey::new(height))?;
+        self.adapter.remove::<BlockSchema>(BlockKey::new(height))?;
+        self.cache.headers.lock().pop(&height);
+        self.cache.blocks.lock().pop(&height);
+
+        Ok(())
     }
 
     async fn get_latest_block(&self, _ctx: Context) -> ProtocolResult<Block> {
@@ -549,6 +562,118 @@ impl<Adapter: StorageAdapter> Storage for ImplStorage<Adapter> {
             Ok(proof)
         }
     }
+
+    async fn update_snapshot_number(&self, _ctx: Context, number: u64) -> ProtocolResult<()> {
+        self.adapter
+            .insert::<SnapshotNumberSchema>(*SNAPSHOT_NUMBER_KEY, number)
+    }
+
+    async fn get_snapshot_number(&self, _ctx: Context) -> ProtocolResult<Option<u64>> {
+        get!(self, *SNAPSHOT_NUMBER_KEY, SnapshotNumberSchema)
+    }
+
+    #[trace_span(kind = "storage")]
+    async fn insert_evidence(
+        &self,
+        ctx: Context,
+        evidence: DoubleSignEvidence,
+    ) -> ProtocolResult<()> {
+        let key = CommonHashKey::new(evidence.height, evidence.hash());
+        if self.adapter.contains::<EvidenceSchema>(key.clone())? {
+            return Ok(());
+        }
+
+        self.adapter.insert::<EvidenceSchema>(key, evidence)
+    }
+
+    #[trace_span(kind = "storage")]
+    async fn get_evidences(
+        &self,
+        ctx: Context,
+        height: u64,
+    ) -> ProtocolResult<Vec<DoubleSignEvidence>> {
+        let key_prefix = CommonPrefix::new(height);
+        let prepare_iter = self
+            .adapter
+            .prepare_iter::<EvidenceSchema, _>(&key_prefix)?;
+
+        // The evidences are rare, so the ones of other heights are skipped
+        // instead of relying on the order of the keys.
+        let mut evidences = Vec::new();
+        for item in prepare_iter.ref_to_iter() {
+            let (key, evidence) = item?;
+            if key.height() == height {
+                evidences.push(evidence);
+            }
+        }
+
+        Ok(evidences)
+    }
+}
+
+#[async_trait]
+impl<Adapter: StorageAdapter> MaintenanceStorage for ImplStorage<Adapter> {
+    async fn remove_block_data(&self, ctx: Context, height: u64) -> ProtocolResult<()> {
+        let block = match self.get_block(ctx.clone(), height).await? {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+
+        let len = block.tx_hashes.len();
+        let keys = block
+            .tx_hashes
+            .iter()
+            .map(|hash| CommonHashKey::new(height, *hash))
+            .collect::<Vec<_>>();
+
+        self.adapter.batch_modify::<TransactionSchema>(
+            keys.clone(),
+            (0..len).map(|_| StorageBatchModify::Remove).collect(),
+        )?;
+        self.adapter.batch_modify::<ReceiptSchema>(
+            keys,
+            (0..len).map(|_| StorageBatchModify::Remove).collect(),
+        )?;
+        self.adapter.batch_modify::<TxHashNumberSchema>(
+            block.tx_hashes.clone(),
+            (0..len).map(|_| StorageBatchModify::Remove).collect(),
+        )?;
+
+        {
+            let mut transactions = self.cache.transactions.lock();
+            let mut receipts = self.cache.receipts.lock();
+            for hash in block.tx_hashes.iter() {
+                transactions.pop(hash);
+                receipts.pop(hash);
+            }
+        }
+
+        self.remove_block(ctx, height).await
+    }
+
+    async fn rollback_to(&self, ctx: Context, number: u64) -> ProtocolResult<u64> {
+        let latest_number = self.get_latest_block_header(ctx.clone()).await?.number;
+        let mut removed = 0;
+
+        for height in ((number + 1)..=latest_number).rev() {
+            let header = self
+                .get_block_header(ctx.clone(), height)
+                .await?
+                .ok_or_else(|| StorageError::GetNone(format!("block {}", height)))?;
+            let prev_block = self
+                .get_block(ctx.clone(), height - 1)
+                .await?
+                .ok_or_else(|| StorageError::GetNone(format!("block {}", height - 1)))?;
+
+            // The proof of a block is carried by the header of the next one.
+            self.set_latest_block(ctx.clone(), prev_block).await?;
+            self.update_latest_proof(ctx.clone(), header.proof).await?;
+            self.remove_block_data(ctx.clone(), height).await?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
 }
 
 #[derive(Debug, Display, From)]
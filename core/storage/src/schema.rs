@@ -1,5 +1,7 @@
 use protocol::traits::{StorageCategory, StorageSchema};
-use protocol::types::{Block, Bytes, DBBytes, Hash, Header, Proof, Receipt, SignedTransaction};
+use protocol::types::{
+    Block, Bytes, DBBytes, DoubleSignEvidence, Hash, Header, Proof, Receipt, SignedTransaction,
+};
 
 use crate::hash_key::{BlockKey, CommonHashKey};
 
@@ -38,6 +40,8 @@ impl_storage_schema_for!(ReceiptBytesSchema, CommonHashKey, DBBytes, Receipt);
 impl_storage_schema_for!(TxHashNumberSchema, Hash, u64, HashHeight);
 impl_storage_schema_for!(LatestBlockSchema, Hash, Block, Block);
 impl_storage_schema_for!(LatestProofSchema, Hash, Proof, Block);
+impl_storage_schema_for!(SnapshotNumberSchema, Hash, u64, Block);
 impl_storage_schema_for!(OverlordWalSchema, Hash, Bytes, Wal);
 impl_storage_schema_for!(EvmCodeSchema, Hash, Bytes, Code);
 impl_storage_schema_for!(EvmCodeAddressSchema, Hash, Hash, Code);
+impl_storage_schema_for!(EvidenceSchema, CommonHashKey, DoubleSignEvidence, Evidence);
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
-use protocol::traits::{CommonStorage, Context, Storage};
-use protocol::types::Hasher;
+use protocol::traits::{CommonStorage, Context, MaintenanceStorage, Storage};
+use protocol::types::{DoubleSignEvidence, EvidenceKind, Hasher, SignedMessage};
 
 use crate::adapter::memory::MemoryAdapter;
 use crate::tests::{get_random_bytes, mock_block, mock_proof, mock_receipt, mock_signed_tx};
@@ -97,6 +97,18 @@ fn test_storage_latest_proof_insert() {
     assert_eq!(proof.block_hash, proof_2.block_hash);
 }
 
+#[test]
+fn test_storage_snapshot_number() {
+    let storage = ImplStorage::new(Arc::new(MemoryAdapter::new()), 10);
+    assert_eq!(exec!(storage.get_snapshot_number(Context::new())), None);
+
+    exec!(storage.update_snapshot_number(Context::new(), 1000));
+    assert_eq!(
+        exec!(storage.get_snapshot_number(Context::new())),
+        Some(1000)
+    );
+}
+
 #[test]
 fn test_storage_evm_code_insert() {
     let storage = ImplStorage::new(Arc::new(MemoryAdapter::new()), 10);
@@ -113,3 +125,90 @@ fn test_storage_evm_code_insert() {
     let code_3 = exec!(storage.get_code_by_address(Context::new(), &address));
     assert_eq!(code, code_3.unwrap());
 }
+
+#[test]
+fn test_storage_rollback() {
+    let storage = ImplStorage::new(Arc::new(MemoryAdapter::new()), 10);
+
+    let mut blocks = Vec::new();
+    for height in 0..5 {
+        let tx = mock_signed_tx();
+        let tx_hash = tx.transaction.hash;
+        let mut block = mock_block(height, Default::default());
+        block.header.proof = mock_proof(Hasher::digest(height.to_be_bytes()));
+        block.tx_hashes = vec![tx_hash];
+
+        exec!(storage.insert_transactions(Context::new(), height, vec![tx]));
+        exec!(storage.insert_receipts(Context::new(), height, vec![mock_receipt(tx_hash)]));
+        exec!(storage.insert_block(Context::new(), block.clone()));
+        blocks.push(block);
+    }
+
+    assert_eq!(exec!(storage.rollback_to(Context::new(), 2)), 2);
+
+    let latest = exec!(storage.get_latest_block(Context::new()));
+    assert_eq!(latest, blocks[2]);
+    let proof = exec!(storage.get_latest_proof(Context::new()));
+    assert_eq!(proof, blocks[3].header.proof);
+
+    for block in blocks.iter() {
+        let height = block.header.number;
+        let tx_hash = block.tx_hashes[0];
+        let kept = height <= 2;
+
+        assert_eq!(
+            exec!(storage.get_block(Context::new(), height)).is_some(),
+            kept
+        );
+        assert_eq!(
+            exec!(storage.get_block_header(Context::new(), height)).is_some(),
+            kept
+        );
+        assert_eq!(
+            exec!(storage.get_block_by_hash(Context::new(), &block.hash())).is_some(),
+            kept
+        );
+        assert_eq!(
+            exec!(storage.get_transaction_by_hash(Context::new(), &tx_hash)).is_some(),
+            kept
+        );
+        assert_eq!(
+            exec!(storage.get_receipt_by_hash(Context::new(), &tx_hash)).is_some(),
+            kept
+        );
+    }
+
+    // Nothing is removed if the chain is not higher than the given number.
+    assert_eq!(exec!(storage.rollback_to(Context::new(), 3)), 0);
+}
+
+#[test]
+fn test_storage_evidence() {
+    let storage = ImplStorage::new(Arc::new(MemoryAdapter::new()), 10);
+
+    let mock_evidence = |height: u64| {
+        let message = |len| SignedMessage {
+            message:   get_random_bytes(len),
+            signature: get_random_bytes(96),
+        };
+        DoubleSignEvidence::new(
+            EvidenceKind::Prevote,
+            height,
+            0,
+            get_random_bytes(33),
+            message(32),
+            message(32),
+        )
+    };
+
+    let evidence = mock_evidence(10);
+    exec!(storage.insert_evidence(Context::new(), evidence.clone()));
+    exec!(storage.insert_evidence(Context::new(), evidence.clone()));
+    exec!(storage.insert_evidence(Context::new(), mock_evidence(11)));
+
+    assert_eq!(exec!(storage.get_evidences(Context::new(), 10)), vec![
+        evidence
+    ]);
+    assert_eq!(exec!(storage.get_evidences(Context::new(), 11)).len(), 1);
+    assert!(exec!(storage.get_evidences(Context::new(), 12)).is_empty());
+}
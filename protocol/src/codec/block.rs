@@ -3,12 +3,14 @@ use std::error::Error;
 use overlord::Codec;
 use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
 
-use crate::types::{Bytes, Proposal, BASE_FEE_PER_GAS, MAX_BLOCK_GAS_LIMIT};
+use crate::types::{Bytes, Proposal, TrieKind, BASE_FEE_PER_GAS, MAX_BLOCK_GAS_LIMIT};
 use crate::{codec::error::CodecError, lazy::CHAIN_ID, ProtocolError};
 
 impl Encodable for Proposal {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(10)
+        let post_state = self.state_root.zip(self.receipts_root);
+
+        s.begin_list(if post_state.is_some() { 12 } else { 10 })
             .append(&self.prev_hash)
             .append(&self.proposer)
             .append(&self.transactions_root)
@@ -19,30 +21,63 @@ impl Encodable for Proposal {
             .append(&self.last_checkpoint_block_hash)
             .append(&self.call_system_script_count)
             .append_list(&self.tx_hashes);
+
+        if let Some((state_root, receipts_root)) = post_state {
+            s.append(&state_root).append(&receipts_root);
+        }
     }
 }
 
 impl Decodable for Proposal {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
-        match r.prototype()? {
-            Prototype::List(10) => Ok(Proposal {
-                prev_hash:                  r.val_at(0)?,
-                proposer:                   r.val_at(1)?,
-                transactions_root:          r.val_at(2)?,
-                signed_txs_hash:            r.val_at(3)?,
-                timestamp:                  r.val_at(4)?,
-                number:                     r.val_at(5)?,
-                gas_limit:                  MAX_BLOCK_GAS_LIMIT.into(),
-                extra_data:                 Default::default(),
-                mixed_hash:                 None,
-                base_fee_per_gas:           BASE_FEE_PER_GAS.into(),
-                proof:                      r.val_at(6)?,
-                last_checkpoint_block_hash: r.val_at(7)?,
-                chain_id:                   **CHAIN_ID.load(),
-                call_system_script_count:   r.val_at(8)?,
-                tx_hashes:                  r.list_at(9)?,
-            }),
-            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        let (post_state_root, post_receipts_root) = match r.prototype()? {
+            Prototype::List(10) => (None, None),
+            Prototype::List(12) => (Some(r.val_at(10)?), Some(r.val_at(11)?)),
+            _ => return Err(DecoderError::RlpInconsistentLengthAndData),
+        };
+
+        Ok(Proposal {
+            prev_hash:                  r.val_at(0)?,
+            proposer:                   r.val_at(1)?,
+            transactions_root:          r.val_at(2)?,
+            signed_txs_hash:            r.val_at(3)?,
+            timestamp:                  r.val_at(4)?,
+            number:                     r.val_at(5)?,
+            gas_limit:                  MAX_BLOCK_GAS_LIMIT.into(),
+            extra_data:                 Default::default(),
+            mixed_hash:                 None,
+            base_fee_per_gas:           BASE_FEE_PER_GAS.into(),
+            proof:                      r.val_at(6)?,
+            last_checkpoint_block_hash: r.val_at(7)?,
+            chain_id:                   **CHAIN_ID.load(),
+            call_system_script_count:   r.val_at(8)?,
+            tx_hashes:                  r.list_at(9)?,
+            state_root:                 post_state_root,
+            receipts_root:              post_receipts_root,
+        })
+    }
+}
+
+impl Encodable for TrieKind {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let kind: u8 = match self {
+            TrieKind::State => 0,
+            TrieKind::Metadata => 1,
+            TrieKind::HeaderCell => 2,
+            TrieKind::Code => 3,
+        };
+        s.append(&kind);
+    }
+}
+
+impl Decodable for TrieKind {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.as_val::<u8>()? {
+            0 => Ok(TrieKind::State),
+            1 => Ok(TrieKind::Metadata),
+            2 => Ok(TrieKind::HeaderCell),
+            3 => Ok(TrieKind::Code),
+            _ => Err(DecoderError::Custom("Unknown trie kind")),
         }
     }
 }
@@ -62,7 +97,7 @@ impl Codec for Proposal {
 #[cfg(test)]
 mod tests {
     use crate::traits::MessageCodec;
-    use crate::types::{Block, Header, Proof};
+    use crate::types::{Block, Hasher, Header, Proof};
 
     use super::*;
 
@@ -92,6 +127,21 @@ mod tests {
         assert_eq!(proof, decode);
     }
 
+    #[test]
+    fn test_trie_kind_codec() {
+        for kind in [
+            TrieKind::State,
+            TrieKind::Metadata,
+            TrieKind::HeaderCell,
+            TrieKind::Code,
+        ] {
+            let bytes = rlp::encode(&kind);
+            assert_eq!(kind, rlp::decode::<TrieKind>(bytes.as_ref()).unwrap());
+        }
+
+        assert!(rlp::decode::<TrieKind>(rlp::encode(&4u8).as_ref()).is_err());
+    }
+
     #[test]
     fn test_proposal_codec() {
         let mut proposal = Proposal {
@@ -102,5 +152,12 @@ mod tests {
         let bytes = proposal.encode_msg().unwrap();
         let decode: Proposal = Proposal::decode_msg(bytes).unwrap();
         assert_eq!(proposal, decode);
+
+        proposal.state_root = Some(Hasher::digest("state"));
+        proposal.receipts_root = Some(Hasher::digest("receipts"));
+        let bytes = proposal.encode_msg().unwrap();
+        assert_eq!(Rlp::new(&bytes).item_count().unwrap(), 12);
+        let decode: Proposal = Proposal::decode_msg(bytes).unwrap();
+        assert_eq!(proposal, decode);
     }
 }
@@ -1,6 +1,7 @@
 use arc_swap::ArcSwap;
 use ckb_always_success_script::ALWAYS_SUCCESS;
 use ckb_types::{core::ScriptHashType, packed, prelude::*};
+use tokio::sync::Mutex;
 
 use crate::ckb_blake2b_256;
 use crate::types::{Hasher, Hex, MerkleRoot};
@@ -9,6 +10,10 @@ lazy_static::lazy_static! {
     pub static ref CURRENT_STATE_ROOT: ArcSwap<MerkleRoot> = ArcSwap::from_pointee(Default::default());
     pub static ref CHAIN_ID: ArcSwap<u64> = ArcSwap::from_pointee(Default::default());
     pub static ref PROTOCOL_VERSION: ArcSwap<Hex> = ArcSwap::from_pointee(Default::default());
+    pub static ref SPECULATIVE_EXECUTION_FROM: ArcSwap<u64> = ArcSwap::from_pointee(u64::MAX);
+    // Held while the process-global roots of system contracts are switched by
+    // an execution, until they are switched to the committed state.
+    pub static ref SYSTEM_CONTRACT_ROOTS_LOCK: Mutex<()> = Mutex::new(());
     pub static ref ALWAYS_SUCCESS_DEPLOY_TX_HASH: [u8; 32] = Hasher::digest("AlwaysSuccessDeployTx").0;
     pub static ref ALWAYS_SUCCESS_TYPE_SCRIPT: packed::Script
         = packed::ScriptBuilder::default()
@@ -1,6 +1,6 @@
 use crate::types::{
-    Account, Block, BlockNumber, Bytes, Hash, Header, Proposal, Receipt, SignedTransaction, TxResp,
-    H160, U256,
+    Account, Block, BlockNumber, Bytes, DoubleSignEvidence, DroppedTx, Hash, Header, Proposal,
+    Receipt, SignedTransaction, TxResp, H160, U256,
 };
 use crate::{async_trait, traits::Context, ProtocolResult};
 
@@ -12,6 +12,14 @@ pub trait APIAdapter: Send + Sync {
         signed_tx: SignedTransaction,
     ) -> ProtocolResult<()>;
 
+    /// Insert a transaction which is forwarded to the validators only instead
+    /// of being gossiped.
+    async fn insert_private_signed_txs(
+        &self,
+        ctx: Context,
+        signed_tx: SignedTransaction,
+    ) -> ProtocolResult<()>;
+
     async fn get_block_by_number(
         &self,
         ctx: Context,
@@ -45,6 +53,18 @@ pub trait APIAdapter: Send + Sync {
         tx_hash: Hash,
     ) -> ProtocolResult<Option<SignedTransaction>>;
 
+    async fn get_pending_tx(
+        &self,
+        ctx: Context,
+        tx_hash: Hash,
+    ) -> ProtocolResult<Option<SignedTransaction>>;
+
+    async fn get_dropped_tx(
+        &self,
+        ctx: Context,
+        tx_hash: Hash,
+    ) -> ProtocolResult<Option<DroppedTx>>;
+
     async fn get_transactions_by_hashes(
         &self,
         ctx: Context,
@@ -85,4 +105,10 @@ pub trait APIAdapter: Send + Sync {
         position: U256,
         state_root: Hash,
     ) -> ProtocolResult<Bytes>;
+
+    async fn get_evidences(
+        &self,
+        ctx: Context,
+        number: BlockNumber,
+    ) -> ProtocolResult<Vec<DoubleSignEvidence>>;
 }
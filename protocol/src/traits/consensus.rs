@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
 use crate::types::{
-    Address, Block, BlockNumber, Bytes, ExecResp, Hash, Header, Hex, MerkleRoot, Metadata,
-    PackedTxHashes, Proof, Proposal, Receipt, SignedTransaction, Validator, U256,
+    Address, Block, BlockNumber, Bytes, DoubleSignEvidence, ExecResp, Hash, Header, Hex,
+    MerkleRoot, Metadata, PackedTxHashes, Proof, Proposal, Receipt, SignedTransaction, TrieKind,
+    Validator, H160, U256,
 };
 use crate::{async_trait, traits::Context, ProtocolResult};
 
@@ -76,6 +77,43 @@ pub trait SynchronizationAdapter: CommonConsensusAdapter + Send + Sync {
     ) -> ProtocolResult<Proof>;
 
     fn get_tx_from_mem(&self, ctx: Context, tx_hash: &Hash) -> Option<SignedTransaction>;
+
+    /// Penalise the remote peer of the context which served invalid sync
+    /// data.
+    fn report_bad_peer(&self, ctx: Context, reason: String);
+
+    /// The trust score between 0 and 100 of the remote peer of the context.
+    fn peer_trust_score(&self, ctx: &Context) -> Option<u8>;
+
+    /// Pull the trie nodes or the contract codes corresponding to the given
+    /// hashes from other nodes. The nodes which the remote node does not have
+    /// are omitted in the response.
+    async fn get_trie_nodes_from_remote(
+        &self,
+        ctx: Context,
+        kind: TrieKind,
+        hashes: &[Hash],
+    ) -> ProtocolResult<Vec<Bytes>>;
+
+    /// Save some verified trie nodes to the database of the trie kind.
+    async fn save_trie_nodes(
+        &self,
+        ctx: Context,
+        kind: TrieKind,
+        nodes: Vec<(Hash, Bytes)>,
+    ) -> ProtocolResult<()>;
+
+    async fn save_code(
+        &self,
+        ctx: Context,
+        address: H160,
+        code_hash: Hash,
+        code: Bytes,
+    ) -> ProtocolResult<()>;
+
+    /// Mark the block whose state is synced by snapshot, it is saved without
+    /// receipts since it is not executed.
+    async fn save_snapshot_number(&self, ctx: Context, number: u64) -> ProtocolResult<()>;
 }
 
 #[async_trait]
@@ -166,6 +204,21 @@ pub trait CommonConsensusAdapter: Send + Sync {
         weight_map: HashMap<Bytes, u32>,
         signed_voters: Vec<Bytes>,
     ) -> ProtocolResult<()>;
+
+    /// Get the metadata root and the header cell root saved in the given
+    /// state.
+    fn get_system_contract_roots(
+        &self,
+        ctx: Context,
+        state_root: MerkleRoot,
+    ) -> ProtocolResult<(Hash, Hash)>;
+
+    fn update_system_contract_roots(
+        &self,
+        ctx: Context,
+        metadata_root: Hash,
+        header_cell_root: Hash,
+    );
 }
 
 #[async_trait]
@@ -204,4 +257,19 @@ pub trait ConsensusAdapter: CommonConsensusAdapter + Send + Sync {
     async fn pull_block(&self, ctx: Context, number: u64, end: &str) -> ProtocolResult<Block>;
 
     async fn verify_txs(&self, ctx: Context, number: u64, txs: &[Hash]) -> ProtocolResult<()>;
+
+    /// Push the transactions of a new proposal to the other validators given
+    /// by their public keys.
+    async fn relay_proposal_txs(
+        &self,
+        ctx: Context,
+        number: u64,
+        txs: Vec<SignedTransaction>,
+        pub_keys: Vec<Bytes>,
+    ) -> ProtocolResult<()>;
+
+    /// Save the evidence of a validator which signs two different consensus
+    /// messages at the same height and round.
+    async fn save_evidence(&self, ctx: Context, evidence: DoubleSignEvidence)
+        -> ProtocolResult<()>;
 }
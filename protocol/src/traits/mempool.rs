@@ -1,10 +1,25 @@
-use crate::types::{BlockNumber, Hash, MerkleRoot, PackedTxHashes, SignedTransaction, H160, U256};
+use crate::types::{
+    BlockNumber, Bytes, DroppedTx, Hash, MerkleRoot, PackedTxHashes, ShortTxId, SignedTransaction,
+    H160, U256,
+};
 use crate::{async_trait, traits::Context, ProtocolResult};
 
 #[async_trait]
 pub trait MemPool: Send + Sync {
     async fn insert(&self, ctx: Context, tx: SignedTransaction) -> ProtocolResult<()>;
 
+    /// Insert a private transaction. It is forwarded to the validators only
+    /// rather than gossiped, and dropped if not committed in a few blocks.
+    async fn insert_private(&self, ctx: Context, tx: SignedTransaction) -> ProtocolResult<()>;
+
+    /// Insert a batch of transactions and return the result of each one in
+    /// the same order.
+    async fn insert_batch(
+        &self,
+        ctx: Context,
+        txs: Vec<SignedTransaction>,
+    ) -> Vec<ProtocolResult<()>>;
+
     async fn package(
         &self,
         ctx: Context,
@@ -33,9 +48,40 @@ pub trait MemPool: Send + Sync {
         order_tx_hashes: &[Hash],
     ) -> ProtocolResult<()>;
 
+    /// Relay the transactions of a proposal to the given peers. Each peer
+    /// receives the short ids of the transactions which it holds, and the
+    /// rest in full.
+    async fn relay_proposal_txs(
+        &self,
+        ctx: Context,
+        height: u64,
+        txs: Vec<SignedTransaction>,
+        peer_ids: Vec<Bytes>,
+    ) -> ProtocolResult<()>;
+
+    /// Insert the transactions relayed by the proposer of the given height.
+    /// The relay of a stale height and the transactions which don't fit the
+    /// latest state are skipped, an error means invalid transactions.
+    async fn insert_relayed_txs(
+        &self,
+        ctx: Context,
+        height: u64,
+        short_ids: Vec<ShortTxId>,
+        txs: Vec<SignedTransaction>,
+    ) -> ProtocolResult<()>;
+
+    /// Pull the unknown ones of the announced transactions from the remote
+    /// peer of the context and insert them.
+    async fn fetch_announced_txs(&self, ctx: Context, tx_hashes: Vec<Hash>) -> ProtocolResult<()>;
+
     async fn get_tx_count_by_address(&self, ctx: Context, address: H160) -> ProtocolResult<usize>;
 
     fn get_tx_from_mem(&self, ctx: Context, tx_hash: &Hash) -> Option<SignedTransaction>;
+
+    /// Get the drop record of a tx which was removed from the mempool without
+    /// being committed.
+    fn get_dropped_tx(&self, ctx: Context, tx_hash: &Hash) -> Option<DroppedTx>;
+
     fn set_args(&self, context: Context, state_root: MerkleRoot, gas_limit: u64, max_tx_size: u64);
 }
 
@@ -55,14 +101,45 @@ pub trait MemPoolAdapter: Send + Sync {
         tx: SignedTransaction,
     ) -> ProtocolResult<()>;
 
+    async fn push_compact_txs(
+        &self,
+        ctx: Context,
+        height: u64,
+        txs: Vec<SignedTransaction>,
+        peer_ids: Vec<Bytes>,
+    ) -> ProtocolResult<()>;
+
+    /// Forward a private transaction to the validators of the next block.
+    async fn forward_private_tx(&self, ctx: Context, tx: SignedTransaction) -> ProtocolResult<()>;
+
+    /// Record that the remote peer of the context holds the given
+    /// transactions.
+    fn mark_known_txs(&self, ctx: Context, short_ids: &[ShortTxId]);
+
     async fn check_authorization(
         &self,
         ctx: Context,
         tx: &SignedTransaction,
     ) -> ProtocolResult<U256>;
 
+    /// The batch version of `check_authorization`, the accounts of all the
+    /// senders are read from the same state.
+    async fn check_authorizations(
+        &self,
+        ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<U256>>;
+
     async fn check_transaction(&self, ctx: Context, tx: &SignedTransaction) -> ProtocolResult<()>;
 
+    /// The batch version of `check_transaction`, the signatures are verified
+    /// in parallel.
+    async fn check_transactions(
+        &self,
+        ctx: Context,
+        txs: &[SignedTransaction],
+    ) -> Vec<ProtocolResult<()>>;
+
     async fn check_storage_exist(&self, ctx: Context, tx_hash: &Hash) -> ProtocolResult<()>;
 
     async fn get_latest_height(&self, ctx: Context) -> ProtocolResult<u64>;
@@ -20,7 +20,8 @@ pub use interoperation::Interoperation;
 pub use mempool::{MemPool, MemPoolAdapter};
 pub use metadata::{MetadataControl, MetadataControlAdapter};
 pub use network::{
-    Gossip, MessageCodec, MessageHandler, Network, PeerTag, PeerTrust, Priority, Rpc, TrustFeedback,
+    Gossip, MessageCodec, MessageHandler, Network, PeerStoreAdmin, PeerTag, PeerTrust, Priority,
+    Rpc, TrustFeedback,
 };
 pub use storage::{
     CommonStorage, IntoIteratorByRef, Storage, StorageAdapter, StorageBatchModify, StorageCategory,
@@ -3,13 +3,15 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-use crate::types::Bytes;
+use crate::types::{Bytes, PeerStoreSnapshot};
 use crate::{async_trait, codec::ProtocolCodec, traits::Context, Display, ProtocolResult};
 
 #[derive(Clone, Debug, Copy)]
 pub enum Priority {
     High,
     Normal,
+    /// Sent ahead of the others over the direct mesh between the validators
+    Consensus,
 }
 
 #[derive(Debug, Display, Clone)]
@@ -123,6 +125,22 @@ pub trait Gossip: Send + Sync {
     where
         M: MessageCodec,
         P: AsRef<[Bytes]> + Send + 'a;
+
+    /// Push the message to a random subset of sqrt(n) connected peers and
+    /// send the announcement to the rest. The origin peer is skipped.
+    async fn push_and_announce<M, A>(
+        &self,
+        cx: Context,
+        origin: Option<usize>,
+        end: &str,
+        msg: M,
+        announce_end: &str,
+        announce: A,
+        p: Priority,
+    ) -> ProtocolResult<()>
+    where
+        M: MessageCodec,
+        A: MessageCodec;
 }
 
 #[async_trait]
@@ -132,6 +150,19 @@ pub trait Rpc: Send + Sync {
         M: MessageCodec,
         R: MessageCodec;
 
+    /// Call the peer of the session in the context, or the connected peer
+    /// with the highest trust score if the context has no session.
+    async fn call_trusted<M, R>(
+        &self,
+        ctx: Context,
+        end: &str,
+        msg: M,
+        pri: Priority,
+    ) -> ProtocolResult<R>
+    where
+        M: MessageCodec,
+        R: MessageCodec;
+
     async fn response<M>(
         &self,
         cx: Context,
@@ -150,8 +181,34 @@ pub trait Network: Send + Sync {
     fn peer_count(&self, ctx: Context) -> ProtocolResult<usize>;
 }
 
+/// Inspect and change the peer store for the operators. A target is a peer
+/// id, a multiaddr, an IP address or an IP network in CIDR notation.
+pub trait PeerStoreAdmin: Send + Sync {
+    fn peer_store_snapshot(&self) -> ProtocolResult<PeerStoreSnapshot>;
+
+    /// Ban the networks of the target, and disconnect the banned peers.
+    fn ban_peer(&self, target: &str, duration_secs: u64, reason: String) -> ProtocolResult<usize>;
+
+    fn unban_peer(&self, target: &str) -> ProtocolResult<usize>;
+
+    /// Remove the saved addresses of the target.
+    fn forget_peer(&self, target: &str) -> ProtocolResult<usize>;
+
+    /// Save the multiaddrs with peer id, returns the count of the saved ones.
+    fn import_peers(&self, addrs: Vec<String>) -> ProtocolResult<usize>;
+
+    /// Replace the allowlist of the running node. In the allowlist only mode,
+    /// the connected peers out of it and the consensus list are disconnected,
+    /// returns the count of them.
+    fn set_allowlist(&self, peer_ids: Vec<String>) -> ProtocolResult<usize>;
+}
+
 pub trait PeerTrust: Send + Sync {
     fn report(&self, ctx: Context, feedback: TrustFeedback);
+
+    /// The trust score between 0 and 100 of the remote peer of the context,
+    /// or `None` if the context has no remote peer.
+    fn trust_score(&self, ctx: &Context) -> Option<u8>;
 }
 
 #[async_trait]
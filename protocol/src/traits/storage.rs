@@ -1,4 +1,6 @@
-use crate::types::{Block, Bytes, Hash, Header, Proof, Receipt, SignedTransaction, H256};
+use crate::types::{
+    Block, Bytes, DoubleSignEvidence, Hash, Header, Proof, Receipt, SignedTransaction, H256,
+};
 use crate::{async_trait, codec::ProtocolCodec, traits::Context, Display, ProtocolResult};
 
 #[derive(Debug, Copy, Clone, Display)]
@@ -12,6 +14,7 @@ pub enum StorageCategory {
     Code,
     CkbCrossChain,
     IbcCrossChain,
+    Evidence,
 }
 
 pub type StorageIterator<'a, S> = Box<
@@ -116,10 +119,39 @@ pub trait Storage: CommonStorage {
     async fn update_latest_proof(&self, ctx: Context, proof: Proof) -> ProtocolResult<()>;
 
     async fn get_latest_proof(&self, ctx: Context) -> ProtocolResult<Proof>;
+
+    /// Record the block whose state is synced by snapshot, the receipts of
+    /// the blocks up to it are unknown.
+    async fn update_snapshot_number(&self, ctx: Context, number: u64) -> ProtocolResult<()>;
+
+    async fn get_snapshot_number(&self, ctx: Context) -> ProtocolResult<Option<u64>>;
+
+    /// Save the double-sign evidence of a validator, the same evidence is
+    /// saved only once.
+    async fn insert_evidence(
+        &self,
+        ctx: Context,
+        evidence: DoubleSignEvidence,
+    ) -> ProtocolResult<()>;
+
+    async fn get_evidences(
+        &self,
+        ctx: Context,
+        height: u64,
+    ) -> ProtocolResult<Vec<DoubleSignEvidence>>;
 }
 
 #[async_trait]
-pub trait MaintenanceStorage: CommonStorage {}
+pub trait MaintenanceStorage: Storage {
+    /// Remove the block with its transactions, receipts and the indexes of
+    /// them.
+    async fn remove_block_data(&self, ctx: Context, height: u64) -> ProtocolResult<()>;
+
+    /// Remove the blocks above the given number from the highest one, the
+    /// latest block and proof are moved back with every removed block.
+    /// Return the number of the removed blocks.
+    async fn rollback_to(&self, ctx: Context, number: u64) -> ProtocolResult<u64>;
+}
 
 pub enum StorageBatchModify<S: StorageSchema> {
     Remove,
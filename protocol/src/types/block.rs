@@ -2,6 +2,7 @@ use rlp_derive::{RlpDecodable, RlpEncodable};
 use serde::{Deserialize, Serialize};
 
 use crate::codec::ProtocolCodec;
+use crate::lazy::SPECULATIVE_EXECUTION_FROM;
 use crate::types::{
     Bloom, BloomInput, Bytes, ExecResp, Hash, Hasher, MerkleRoot, SignedTransaction, H160, H64,
     U256,
@@ -29,6 +30,10 @@ pub struct Proposal {
     pub chain_id:                   u64,
     pub call_system_script_count:   u32,
     pub tx_hashes:                  Vec<Hash>,
+    /// The post-execution roots which the proposer commits to in speculative
+    /// execution mode.
+    pub state_root:                 Option<MerkleRoot>,
+    pub receipts_root:              Option<MerkleRoot>,
 }
 
 impl From<&Block> for Proposal {
@@ -49,6 +54,10 @@ impl From<&Block> for Proposal {
             chain_id:                   b.header.chain_id,
             call_system_script_count:   b.header.call_system_script_count,
             tx_hashes:                  b.tx_hashes.clone(),
+            state_root:                 is_speculative_execution(b.header.number)
+                .then_some(b.header.state_root),
+            receipts_root:              is_speculative_execution(b.header.number)
+                .then_some(b.header.receipts_root),
         }
     }
 }
@@ -71,6 +80,9 @@ impl From<Header> for Proposal {
             chain_id:                   h.chain_id,
             call_system_script_count:   h.call_system_script_count,
             tx_hashes:                  vec![],
+            state_root:                 is_speculative_execution(h.number).then_some(h.state_root),
+            receipts_root:              is_speculative_execution(h.number)
+                .then_some(h.receipts_root),
         }
     }
 }
@@ -81,6 +93,13 @@ impl Proposal {
     }
 }
 
+/// Whether the proposal of the given block is executed before voting and
+/// carries the post-execution roots. It is a chain parameter, so the hash of
+/// a block depends on it.
+pub fn is_speculative_execution(number: BlockNumber) -> bool {
+    number >= **SPECULATIVE_EXECUTION_FROM.load()
+}
+
 pub struct PackedTxHashes {
     pub hashes:                   Vec<Hash>,
     pub call_system_script_count: u32,
@@ -180,7 +199,17 @@ pub struct Proof {
     pub bitmap:     Bytes,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+/// The tries which a new node downloads from its peers in snapshot sync.
+/// `Code` is not a trie, the contract code is pulled by code hash.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TrieKind {
+    State,
+    Metadata,
+    HeaderCell,
+    Code,
+}
+
+#[derive(RlpEncodable, RlpDecodable, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct RichBlock {
     pub block: Block,
     pub txs:   Vec<SignedTransaction>,
@@ -0,0 +1,153 @@
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use rlp_derive::{RlpDecodable, RlpEncodable};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Bytes, Hash, Hasher};
+
+/// The kind of the consensus message which is signed twice by a validator.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EvidenceKind {
+    Proposal,
+    Prevote,
+    Precommit,
+    Choke,
+}
+
+impl EvidenceKind {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            EvidenceKind::Proposal => 0,
+            EvidenceKind::Prevote => 1,
+            EvidenceKind::Precommit => 2,
+            EvidenceKind::Choke => 3,
+        }
+    }
+}
+
+impl TryFrom<u8> for EvidenceKind {
+    type Error = DecoderError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(EvidenceKind::Proposal),
+            1 => Ok(EvidenceKind::Prevote),
+            2 => Ok(EvidenceKind::Precommit),
+            3 => Ok(EvidenceKind::Choke),
+            _ => Err(DecoderError::Custom("Invalid evidence kind")),
+        }
+    }
+}
+
+impl Encodable for EvidenceKind {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.append(&self.as_u8());
+    }
+}
+
+impl Decodable for EvidenceKind {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        EvidenceKind::try_from(r.as_val::<u8>()?)
+    }
+}
+
+/// A consensus message with the signature of the signer. The `message` is
+/// the RLP encoded message and the signature is made on its keccak hash.
+#[derive(RlpEncodable, RlpDecodable, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SignedMessage {
+    pub message:   Bytes,
+    pub signature: Bytes,
+}
+
+impl SignedMessage {
+    pub fn hash(&self) -> Hash {
+        Hasher::digest(&self.message)
+    }
+}
+
+/// The evidence that a validator signs two different messages of the same
+/// kind at the same height and round. It is self-contained so that a
+/// slashing transaction can carry its RLP encoding to the metadata system
+/// contract, where the signatures are checked against the BLS public key of
+/// the `signer` in the verifier list of the height.
+#[derive(RlpEncodable, RlpDecodable, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DoubleSignEvidence {
+    pub kind:   EvidenceKind,
+    pub height: u64,
+    pub round:  u64,
+    /// The public key of the validator, which is the address in consensus.
+    pub signer: Bytes,
+    pub first:  SignedMessage,
+    pub second: SignedMessage,
+}
+
+impl DoubleSignEvidence {
+    /// The two messages are sorted by their hashes, so that the same evidence
+    /// is built whichever message is received first.
+    pub fn new(
+        kind: EvidenceKind,
+        height: u64,
+        round: u64,
+        signer: Bytes,
+        first: SignedMessage,
+        second: SignedMessage,
+    ) -> Self {
+        let (first, second) = if first.hash() <= second.hash() {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        DoubleSignEvidence {
+            kind,
+            height,
+            round,
+            signer,
+            first,
+            second,
+        }
+    }
+
+    pub fn hash(&self) -> Hash {
+        Hasher::digest(rlp::encode(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::ProtocolCodec;
+
+    fn signed_message(message: &'static [u8]) -> SignedMessage {
+        SignedMessage {
+            message:   Bytes::from_static(message),
+            signature: Bytes::from_static(b"signature"),
+        }
+    }
+
+    #[test]
+    fn test_evidence_codec() {
+        let evidence = DoubleSignEvidence::new(
+            EvidenceKind::Precommit,
+            10,
+            1,
+            Bytes::from_static(b"signer"),
+            signed_message(b"first"),
+            signed_message(b"second"),
+        );
+        let swapped = DoubleSignEvidence::new(
+            EvidenceKind::Precommit,
+            10,
+            1,
+            Bytes::from_static(b"signer"),
+            signed_message(b"second"),
+            signed_message(b"first"),
+        );
+        assert_eq!(evidence, swapped);
+        assert_eq!(evidence.hash(), swapped.hash());
+
+        let decoded =
+            <DoubleSignEvidence as ProtocolCodec>::decode(evidence.encode().unwrap()).unwrap();
+        assert_eq!(decoded, evidence);
+        assert!(rlp::decode::<EvidenceKind>(&rlp::encode(&4u8)).is_err());
+    }
+}
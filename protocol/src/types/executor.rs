@@ -1,10 +1,13 @@
 pub use ethereum::{AccessList, AccessListItem, Account};
 pub use evm::{backend::Log, Config, ExitError, ExitFatal, ExitReason, ExitRevert, ExitSucceed};
 
+use rlp::RlpStream;
 use rlp_derive::{RlpDecodable, RlpEncodable};
 
 use crate::codec::ProtocolCodec;
-use crate::types::{Hash, Hasher, Header, MerkleRoot, Proposal, H160, U256};
+use crate::types::{
+    is_speculative_execution, BlockNumber, Hash, Hasher, Header, MerkleRoot, Proposal, H160, U256,
+};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ExecResp {
@@ -41,6 +44,20 @@ impl Default for TxResp {
     }
 }
 
+/// The leaf of a transaction in the receipts root. From the speculative
+/// execution height, which changes the block hashes already, it commits to
+/// the success of the transaction too, so that a light client can prove it.
+pub fn receipt_leaf(number: BlockNumber, resp: &TxResp) -> Hash {
+    if !is_speculative_execution(number) {
+        return Hasher::digest(&resp.ret);
+    }
+
+    let mut stream = RlpStream::new_list(2);
+    stream.append(&resp.exit_reason.is_succeed());
+    stream.append(&resp.ret);
+    Hasher::digest(stream.out())
+}
+
 #[derive(RlpEncodable, RlpDecodable, Default, Clone, Debug, PartialEq, Eq)]
 pub struct ExecutorContext {
     pub block_number:           U256,
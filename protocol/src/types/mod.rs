@@ -4,11 +4,14 @@ pub use batch::*;
 pub use block::*;
 pub use bytes::{Buf, BufMut, Bytes, BytesMut};
 pub use ckb_client::*;
+pub use evidence::*;
 pub use evm::{backend::*, ExitError, ExitRevert, ExitSucceed};
 pub use executor::{
-    AccessList, AccessListItem, Account, Config, ExecResp, ExecutorContext, ExitReason, TxResp,
+    receipt_leaf, AccessList, AccessListItem, Account, Config, ExecResp, ExecutorContext,
+    ExitReason, TxResp,
 };
 pub use interoperation::*;
+pub use peer::*;
 pub use primitive::*;
 pub use receipt::*;
 pub use transaction::*;
@@ -16,8 +19,10 @@ pub use transaction::*;
 pub mod batch;
 pub mod block;
 pub mod ckb_client;
+pub mod evidence;
 pub mod executor;
 pub mod interoperation;
+pub mod peer;
 pub mod primitive;
 pub mod receipt;
 pub mod transaction;
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// An address saved in the peer store.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PeerAddrRecord {
+    pub addr:                 String,
+    pub peer_id:              Option<String>,
+    pub score:                i32,
+    /// The trust score between 0 and 100 of the peer.
+    pub trust_score:          Option<i32>,
+    /// Zero if never connected.
+    pub last_connected_at_ms: u64,
+    pub last_tried_at_ms:     u64,
+    /// The failed attempts since the last connection.
+    pub attempts_count:       u32,
+}
+
+/// A network banned in the peer store.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BannedRecord {
+    /// The IP network in CIDR notation.
+    pub address:       String,
+    pub ban_until_ms:  u64,
+    pub created_at_ms: u64,
+    pub ban_reason:    String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerStoreSnapshot {
+    pub addrs:  Vec<PeerAddrRecord>,
+    pub banned: Vec<BannedRecord>,
+}
@@ -457,6 +457,48 @@ impl SignedTransaction {
     }
 }
 
+/// The reason why a transaction was removed from the mempool without being
+/// committed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TxDropReason {
+    /// The transaction stayed in the mempool longer than the configured block
+    /// gap or wall-clock TTL.
+    Timeout,
+    /// A transaction with the same sender and a greater or equal nonce has
+    /// been committed.
+    NonceTooLow,
+    /// Another transaction with the same sender and nonce and a higher gas
+    /// price took the slot.
+    Replaced,
+    /// A transaction with the same sender and nonce and a higher or equal gas
+    /// price already holds the slot.
+    Underpriced,
+    /// The pending queue of the sender is full.
+    Evicted,
+    /// The transaction no longer passes the authorization check against the
+    /// latest state.
+    InvalidAfterStateChange,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DroppedTx {
+    pub hash:       Hash,
+    pub reason:     TxDropReason,
+    /// Unix timestamp in milliseconds.
+    pub dropped_at: u64,
+}
+
+/// A compact identifier of a transaction, used to tell which transactions a
+/// peer already holds without sending the full hash.
+pub type ShortTxId = u64;
+
+pub fn short_tx_id(hash: &Hash) -> ShortTxId {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&hash.as_bytes()[..8]);
+    u64::from_le_bytes(buf)
+}
+
 pub fn public_to_address(public: &Public) -> H160 {
     let hash = Hasher::digest(public);
     let mut ret = H160::zero();